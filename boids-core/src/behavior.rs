@@ -0,0 +1,43 @@
+use crate::data::BehaviorState;
+
+// Highest speed multiplier of any state
+pub const MAX_SPEED_FACTOR: f32 = 2.0;
+
+// Multipliers applied on top of the global rule weights and speed.
+#[derive(Clone, Copy, Debug)]
+pub struct StateWeights {
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub separation: f32,
+    pub speed: f32,
+}
+
+pub fn state_weights(state: BehaviorState) -> StateWeights {
+    match state {
+        BehaviorState::Flocking => StateWeights {
+            alignment: 1.0,
+            cohesion: 1.0,
+            separation: 1.0,
+            speed: 1.0,
+        },
+        // Slow down and spread out a bit while feeding
+        BehaviorState::Feeding => StateWeights {
+            alignment: 0.2,
+            cohesion: 0.5,
+            separation: 1.0,
+            speed: 0.3,
+        },
+        BehaviorState::Fleeing => StateWeights {
+            alignment: 0.5,
+            cohesion: 0.2,
+            separation: 2.0,
+            speed: 2.0,
+        },
+        BehaviorState::Perching | BehaviorState::Dead => StateWeights {
+            alignment: 0.0,
+            cohesion: 0.0,
+            separation: 0.0,
+            speed: 0.0,
+        },
+    }
+}
@@ -0,0 +1,125 @@
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+
+use crate::components::Components;
+use crate::data::*;
+use crate::day_cycle::DayCycle;
+use crate::events::Events;
+use crate::grid::{CellOrder, Grid};
+use crate::kdtree::KdTree;
+use crate::neighbor_list::NeighborList;
+use crate::quadtree::Quadtree;
+use crate::scenario::Timeline;
+use crate::schedule::Schedule;
+use crate::systems::SteeringScratch;
+use crate::simulation::Simulation;
+use crate::spatial::{Neighborhood, SpatialBackend};
+use crate::species::InteractionPreset;
+use crate::{AGENT_SPEED, CELL_SIZE, GUST_INTERVAL, PERCEPTION_RADIUS, SPECIES_COUNT};
+
+// Puts together a simulation, everything left out keeps the default of `Simulation::new`.
+//
+//     let simulation = SimulationBuilder::new(500, [800.0, 600.0])
+//         .seed(7)
+//         .preset(InteractionPreset::PredatorPrey)
+//         .boundary(Boundary::Bounce)
+//         .build();
+pub struct SimulationBuilder {
+    agent_count: usize,
+    world_size: [f32; 2],
+    seed: Option<u64>,
+    preset: InteractionPreset,
+    boundary: Boundary,
+    species: Vec<usize>,
+}
+
+impl SimulationBuilder {
+    pub fn new(agent_count: usize, world_size: [f32; 2]) -> SimulationBuilder {
+        SimulationBuilder {
+            agent_count,
+            world_size,
+            seed: None,
+            preset: InteractionPreset::Segregated,
+            boundary: Boundary::Wrap,
+            species: (0..SPECIES_COUNT).collect(),
+        }
+    }
+
+    // Random without one
+    pub fn seed(mut self, seed: u64) -> SimulationBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    pub fn preset(mut self, preset: InteractionPreset) -> SimulationBuilder {
+        self.preset = preset;
+        self
+    }
+
+    pub fn boundary(mut self, boundary: Boundary) -> SimulationBuilder {
+        self.boundary = boundary;
+        self
+    }
+
+    // Species the boids are picked from, the first ones and every one spawned later.
+    // Ids go up to SPECIES_COUNT, the last one is the predator.
+    pub fn species(mut self, species: &[usize]) -> SimulationBuilder {
+        assert!(!species.is_empty(), "Boids need at least one species to pick from");
+        assert!(species.iter().all(|id| *id < SPECIES_COUNT), "Species ids go up to {}", SPECIES_COUNT);
+
+        self.species = species.to_vec();
+        self
+    }
+
+    pub fn build(self) -> Simulation {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let world_size = self.world_size;
+
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let components = Components::new(self.agent_count, world_size, &self.species, &mut rng);
+
+        Simulation {
+            components,
+            grid: Grid::new(world_size[0], world_size[1], CELL_SIZE, CellOrder::RowMajor),
+            quadtree: Quadtree::default(),
+            kdtree: KdTree::default(),
+            spatial_backend: SpatialBackend::Grid,
+            neighborhood: Neighborhood::Metric,
+            cell_pairs: true,
+            neighbor_list: NeighborList::default(),
+            steering_scratch: SteeringScratch::default(),
+
+            interactions: self.preset.matrix(SPECIES_COUNT),
+            interaction_preset: self.preset,
+            weights: FlockWeights::default(),
+            speed: AGENT_SPEED,
+            perception_radius: PERCEPTION_RADIUS,
+            boundary: self.boundary,
+            spawn_species: self.species,
+
+            trails: false,
+            since_trail: 0.0,
+
+            gusts: Vec::new(),
+            next_gust: GUST_INTERVAL,
+
+            obstacles: Vec::new(),
+            walls: Vec::new(),
+            timeline: Timeline::default(),
+
+            day_cycle: DayCycle::new(),
+
+            flow_field: FlowField::default(),
+
+            rng,
+            seed,
+
+            world_size,
+
+            schedule: Schedule::standard(),
+            events: Events::default(),
+            script: None,
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use std::f32::consts::PI;
+
+use rand::Rng;
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::data::*;
+use crate::diagnostics::vec_bytes;
+use crate::grid::Grid;
+use crate::query::{Column, Query};
+use crate::AGENT_SCALE_RANGE;
+
+// Handle of one boid that stays valid while the boid moves around in the component
+// arrays. The generation makes handles of despawned boids invalid, even once their
+// slot is reused by a new boid.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct BoidHandle {
+    slot: u32,
+    generation: u32,
+}
+
+impl BoidHandle {
+    // Number of the boid that no other boid of the simulation gets, for trajectories and other files
+    pub fn id(self) -> u64 {
+        (self.generation as u64) << 32 | self.slot as u64
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Slot {
+    generation: u32,
+    // Index of the boid in the component arrays, none while the slot is free
+    index: Option<usize>,
+}
+
+// Declares every component column once. Each one is a Vec in `Components`, where index `i`
+// belongs to the same boid, and gets a marker type in `column` to name it in queries.
+// A new component is one more line in the list below. Its type has to be Copy and Default,
+// new boids get the default unless `spawn` gives them something else.
+macro_rules! declare_columns {
+    ($($name:ident: $marker:ident<$item:ty>,)*) => {
+        // Structure of arrays store of all boids, index `i` of every array belongs to the same boid.
+        // Indices change when boids are sorted or despawned, handles are used to keep track of one.
+        #[derive(Clone, Serialize, Deserialize)]
+        pub struct Components {
+            $(pub $name: Vec<$item>,)*
+            // Handle of the boid at each index
+            pub handles: Vec<BoidHandle>,
+
+            slots: Vec<Slot>,
+            free_slots: Vec<u32>,
+
+            #[serde(skip)]
+            scratch: ReorderScratch,
+        }
+
+        // Buffers `sort_by_cell` permutes into, swapped with the columns afterwards
+        // so sorting keeps reusing the same allocations from frame to frame
+        #[derive(Clone, Default)]
+        struct ReorderScratch {
+            order: Vec<usize>,
+            $($name: Vec<$item>,)*
+            handles: Vec<BoidHandle>,
+        }
+
+        impl ReorderScratch {
+            fn allocated_bytes(&self) -> usize {
+                vec_bytes(&self.order) $(+ vec_bytes(&self.$name))* + vec_bytes(&self.handles)
+            }
+        }
+
+        // Every column borrowed at once, a query takes out the ones it names
+        pub struct Columns<'a> {
+            $($name: Option<&'a mut Vec<$item>>,)*
+        }
+
+        // Marker types naming the columns in queries, like `Read<column::Positions>`
+        pub mod column {
+            $(pub struct $marker;)*
+        }
+
+        $(
+            impl Column for column::$marker {
+                type Item = $item;
+                const NAME: &'static str = stringify!($name);
+
+                fn take<'a>(columns: &mut Columns<'a>) -> Option<&'a mut Vec<$item>> {
+                    columns.$name.take()
+                }
+            }
+        )*
+
+        const COLUMN_COUNT: usize = [$(stringify!($name)),*].len();
+
+        impl Components {
+            fn empty() -> Components {
+                Components {
+                    $($name: Vec::new(),)*
+                    handles: Vec::new(),
+                    slots: Vec::new(),
+                    free_slots: Vec::new(),
+                    scratch: ReorderScratch::default(),
+                }
+            }
+
+            fn reorder_columns(&mut self, order: &[usize]) {
+                $(reorder(&mut self.$name, &mut self.scratch.$name, order);)*
+                reorder(&mut self.handles, &mut self.scratch.handles, order);
+            }
+
+            fn columns_mut(&mut self) -> [&mut dyn AnyColumn; COLUMN_COUNT] {
+                [$(&mut self.$name as &mut dyn AnyColumn),*]
+            }
+
+            fn columns(&self) -> [&dyn AnyColumn; COLUMN_COUNT] {
+                [$(&self.$name as &dyn AnyColumn),*]
+            }
+
+            // Typed access to several columns at once, see `query::Query`
+            pub fn query<'a, Q: Query<'a>>(&'a mut self) -> Q::Output {
+                let mut columns = Columns {
+                    $($name: Some(&mut self.$name),)*
+                };
+
+                Q::fetch(&mut columns)
+            }
+        }
+    };
+}
+
+declare_columns! {
+    directions: Directions<Forward>,
+    // Snapshot of directions taken before steering
+    previous_directions: PreviousDirections<Forward>,
+    positions: Positions<Position>,
+    scales: Scales<Scale>,
+    flaps: Flaps<Flap>,
+    species: Species<Species>,
+    behaviors: Behaviors<Behavior>,
+    trails: Trails<Trail>,
+    crowding: Crowding<Crowding>,
+    forces: Forces<SteeringForces>,
+    leaders: Leaders<Leader>,
+}
+
+// What moving whole boids around does to a column, whatever its type
+trait AnyColumn {
+    fn swap_remove_boid(&mut self, index: usize);
+    // Fills up the column with defaults until it holds `len` boids
+    fn pad(&mut self, len: usize);
+    fn allocated_bytes(&self) -> usize;
+}
+
+impl<T: Copy + Default> AnyColumn for Vec<T> {
+    fn swap_remove_boid(&mut self, index: usize) {
+        self.swap_remove(index);
+    }
+
+    fn pad(&mut self, len: usize) {
+        if self.len() < len {
+            self.resize(len, T::default());
+        }
+    }
+
+    fn allocated_bytes(&self) -> usize {
+        vec_bytes(self)
+    }
+}
+
+impl Components {
+    pub fn new(count: usize, world_size: [f32; 2], species: &[usize], rng: &mut StdRng) -> Components {
+        let mut components = Components::empty();
+
+        components.spawn(count, world_size, species, rng);
+
+        components
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    // Current index of a boid, none if it was despawned
+    pub fn index(&self, handle: BoidHandle) -> Option<usize> {
+        let slot = self.slots.get(handle.slot as usize)?;
+
+        if slot.generation != handle.generation {
+            return None;
+        }
+
+        slot.index
+    }
+
+    // Adds boids at random places in the world, each of a random one of `species`
+    pub fn spawn(&mut self, count: usize, world_size: [f32; 2], species: &[usize], rng: &mut StdRng) {
+        self.spawn_in(count, [0.0, 0.0, world_size[0], world_size[1]], species, rng);
+    }
+
+    // Like `spawn`, but in the rectangle from the corner x, y to the corner x, y of `area`
+    pub fn spawn_in(&mut self, count: usize, area: [f32; 4], species: &[usize], rng: &mut StdRng) {
+        let directions = get_random_directions(count, rng);
+        let species = get_random_species(count, species, rng);
+
+        self.previous_directions.extend_from_slice(&directions);
+        self.directions.extend(directions);
+        self.positions.extend(get_random_positions_in(count, area, rng));
+        self.scales.extend(get_random_scales(count, rng));
+        self.flaps.extend(get_random_flaps(count, rng));
+        self.species.extend(species);
+
+        let len = self.positions.len();
+
+        for column in self.columns_mut() {
+            column.pad(len);
+        }
+
+        for index in self.handles.len()..self.positions.len() {
+            let handle = self.allocate(index);
+            self.handles.push(handle);
+        }
+    }
+
+    fn allocate(&mut self, index: usize) -> BoidHandle {
+        match self.free_slots.pop() {
+            Some(slot) => {
+                let entry = &mut self.slots[slot as usize];
+                entry.index = Some(index);
+
+                BoidHandle { slot, generation: entry.generation }
+            }
+            None => {
+                self.slots.push(Slot { generation: 0, index: Some(index) });
+
+                BoidHandle { slot: self.slots.len() as u32 - 1, generation: 0 }
+            }
+        }
+    }
+
+    // Removes a boid by moving the last one into its place.
+    // Returns false if the boid was already despawned.
+    pub fn despawn(&mut self, handle: BoidHandle) -> bool {
+        let index = match self.index(handle) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        for column in self.columns_mut() {
+            column.swap_remove_boid(index);
+        }
+
+        self.handles.swap_remove(index);
+
+        let slot = &mut self.slots[handle.slot as usize];
+        slot.generation += 1;
+        slot.index = None;
+        self.free_slots.push(handle.slot);
+
+        if let Some(moved) = self.handles.get(index) {
+            self.slots[moved.slot as usize].index = Some(index);
+        }
+
+        true
+    }
+
+    // Removes random boids, data is sorted by cell so removing from the end
+    // would clear out one part of the world.
+    pub fn despawn_random(&mut self, count: usize, rng: &mut StdRng) {
+        for _ in 0..count.min(self.len()) {
+            let handle = self.handles[rng.gen_range(0..self.len())];
+            self.despawn(handle);
+        }
+    }
+
+    // Reorders all boid data by grid cell, so boids of one cell are next to each other in memory.
+    pub fn sort_by_cell(&mut self, grid: &mut Grid) {
+        let mut order = std::mem::take(&mut self.scratch.order);
+        grid.sorted_order(&mut order);
+
+        self.reorder_columns(&order);
+        self.scratch.order = order;
+
+        for (index, handle) in self.handles.iter().enumerate() {
+            self.slots[handle.slot as usize].index = Some(index);
+        }
+
+        grid.renumber();
+    }
+
+    pub fn snapshot_directions(&mut self) {
+        self.previous_directions.copy_from_slice(&self.directions);
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        let columns: usize = self.columns().iter().map(|column| column.allocated_bytes()).sum();
+
+        columns
+            + vec_bytes(&self.handles)
+            + vec_bytes(&self.slots)
+            + vec_bytes(&self.free_slots)
+            + self.scratch.allocated_bytes()
+    }
+}
+
+fn reorder<T: Copy>(values: &mut Vec<T>, scratch: &mut Vec<T>, order: &[usize]) {
+    scratch.clear();
+    scratch.extend(order.iter().map(|i| values[*i]));
+    std::mem::swap(values, scratch);
+}
+
+pub fn get_random_positions(count: usize, world_size: [f32; 2], rng: &mut StdRng) -> Vec<Position> {
+    get_random_positions_in(count, [0.0, 0.0, world_size[0], world_size[1]], rng)
+}
+
+// Corners x, y and x, y of `area`, the second one is left out
+pub fn get_random_positions_in(count: usize, area: [f32; 4], rng: &mut StdRng) -> Vec<Position> {
+    let mut positions = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        positions.push(Position {
+            value: [
+                rng.gen_range(area[0]..area[2]),
+                rng.gen_range(area[1]..area[3]),
+            ]
+        });
+    }
+
+    positions
+}
+
+pub fn get_random_directions(count: usize, rng: &mut StdRng) -> Vec<Forward> {
+    let mut forwards = Vec::with_capacity(count);
+
+    const TWO_PI: f32 = PI * 2.0;
+    let mut angle: f32;
+
+    for _ in 0..count {
+        angle = rng.gen_range(0.0..TWO_PI);
+
+        forwards.push(Forward {
+            direction: [angle.cos(), angle.sin()]
+        });
+    }
+
+    forwards
+}
+
+fn get_random_scales(count: usize, rng: &mut StdRng) -> Vec<Scale> {
+    let [min, max] = AGENT_SCALE_RANGE;
+    let mut scales = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        scales.push(Scale {
+            factor: rng.gen_range(min..=max)
+        });
+    }
+
+    scales
+}
+
+// Boids start at random points of their wing beat, so they don't flap in sync
+fn get_random_flaps(count: usize, rng: &mut StdRng) -> Vec<Flap> {
+    let mut flaps = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        flaps.push(Flap {
+            phase: rng.gen_range(0.0..1.0)
+        });
+    }
+
+    flaps
+}
+
+fn get_random_species(count: usize, choices: &[usize], rng: &mut StdRng) -> Vec<Species> {
+    let mut species = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        species.push(Species {
+            id: choices[rng.gen_range(0..choices.len())]
+        });
+    }
+
+    species
+}
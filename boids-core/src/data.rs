@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use vecmath::Vector2;
+
+use crate::{ALIGNMENT_WEIGHT, COHESION_WEIGHT, SEPARATION_WEIGHT, TRAIL_LENGTH};
+
+// Render size of a boid relative to the renderer's AGENT_SIZE, only changes how it is drawn
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Scale {
+    pub factor: f32,
+}
+
+// Wing beat of a boid, the phase goes from 0 to 1 once per beat
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Flap {
+    pub phase: f32,
+}
+
+// Positions and forwards are packed into a `BoidInstance` for drawing,
+// the vertex shader builds the boid transform from them. Both are laid out like two f32s,
+// so the C API in boids-ffi can hand out their arrays as they are.
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[repr(transparent)]
+pub struct Forward {
+    pub direction: Vector2<f32>
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[repr(transparent)]
+pub struct Position {
+    pub value: Vector2<f32>
+}
+
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Species {
+    pub id: usize
+}
+
+// How strongly boids follow each flocking rule, on top of the weights of their behavior state
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct FlockWeights {
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub separation: f32,
+}
+
+impl Default for FlockWeights {
+    fn default() -> FlockWeights {
+        FlockWeights {
+            alignment: ALIGNMENT_WEIGHT,
+            cohesion: COHESION_WEIGHT,
+            separation: SEPARATION_WEIGHT,
+        }
+    }
+}
+
+// Average velocity of the living boids in every grid cell, indexed like the grid's cells
+#[derive(Default)]
+pub struct FlowField {
+    pub enabled: bool,
+    // World units per second
+    pub velocities: Vec<Vector2<f32>>,
+    pub counts: Vec<u32>,
+}
+
+// Pinned leaders keep their heading instead of steering, the flock around them follows
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Leader {
+    pub pinned: bool,
+}
+
+// Weighted rule vectors of the last steering pass, kept for debug drawing
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SteeringForces {
+    pub alignment: Vector2<f32>,
+    pub cohesion: Vector2<f32>,
+    pub separation: Vector2<f32>,
+    // Resulting heading
+    pub heading: Vector2<f32>,
+}
+
+// Neighbors counted by the last steering pass
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+pub struct Crowding {
+    pub neighbors: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum BehaviorState {
+    Flocking,
+    Feeding,
+    Fleeing,
+    Perching,
+    Dead,
+}
+
+impl BehaviorState {
+    // In the order of their discriminants, so `state as usize` indexes it
+    pub const ALL: [BehaviorState; 5] = [
+        BehaviorState::Flocking,
+        BehaviorState::Feeding,
+        BehaviorState::Fleeing,
+        BehaviorState::Perching,
+        BehaviorState::Dead,
+    ];
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Behavior {
+    pub state: BehaviorState,
+    // Seconds spent in the current state
+    pub time: f32,
+    // Set by the steering pass when a repelling species shares the boid's cell
+    pub threatened: bool,
+    pub since_threat: f32,
+}
+
+impl Default for Behavior {
+    fn default() -> Behavior {
+        Behavior {
+            state: BehaviorState::Flocking,
+            time: 0.0,
+            threatened: false,
+            since_threat: 0.0,
+        }
+    }
+}
+
+// Last TRAIL_LENGTH sampled positions of a boid in a ring buffer
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Trail {
+    pub points: [Vector2<f32>; TRAIL_LENGTH],
+    // Where the next point is written
+    pub head: usize,
+    pub len: usize,
+}
+
+impl Default for Trail {
+    fn default() -> Trail {
+        Trail {
+            points: [[0.0, 0.0]; TRAIL_LENGTH],
+            head: 0,
+            len: 0,
+        }
+    }
+}
+
+impl Trail {
+    pub fn push(&mut self, point: Vector2<f32>) {
+        self.points[self.head] = point;
+        self.head = (self.head + 1) % TRAIL_LENGTH;
+        self.len = (self.len + 1).min(TRAIL_LENGTH);
+    }
+
+    pub fn last(&self) -> Option<Vector2<f32>> {
+        if self.len == 0 {
+            return None;
+        }
+
+        Some(self.points[(self.head + TRAIL_LENGTH - 1) % TRAIL_LENGTH])
+    }
+
+    // Point `i` counted from the oldest one
+    pub fn point(&self, i: usize) -> Vector2<f32> {
+        self.points[(self.head + TRAIL_LENGTH - self.len + i) % TRAIL_LENGTH]
+    }
+}
+
+// Short-lived localized wind pushing boids in one direction.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Gust {
+    pub center: Vector2<f32>,
+    pub direction: Vector2<f32>,
+    pub radius: f32,
+    pub strength: f32,
+    pub age: f32,
+    pub duration: f32,
+}
+
+// Round obstacle boids steer around and can't fly into
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Obstacle {
+    pub center: Vector2<f32>,
+    pub radius: f32,
+}
+
+// Straight wall from `start` to `end`, boids can't fly through it from either side
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Wall {
+    pub start: Vector2<f32>,
+    pub end: Vector2<f32>,
+}
+
+// What happens to boids that fly out of the world
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Boundary {
+    // They come back in on the other side
+    Wrap,
+    // They turn back at the edge
+    Bounce,
+}
+
+impl Boundary {
+    // Case doesn't matter
+    pub fn from_name(name: &str) -> Option<Boundary> {
+        match name.to_ascii_lowercase().as_str() {
+            "wrap" => Some(Boundary::Wrap),
+            "bounce" => Some(Boundary::Bounce),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> Boundary {
+        match self {
+            Boundary::Wrap => Boundary::Bounce,
+            Boundary::Bounce => Boundary::Wrap,
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{DAY_CYCLE, DAY_LENGTH, NIGHT_ROOSTING, NIGHT_TINT, ROOST_DARKNESS};
+
+// Slow ambient cycle from day to night and back. It runs on simulation time,
+// so it stands still whenever the simulation does.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DayCycle {
+    pub enabled: bool,
+    // Seconds from one noon to the next
+    pub length: f32,
+    // Part of the day gone since noon, from 0 to 1
+    pub phase: f32,
+    // Boids roost at the bottom edge through the night
+    pub roosting: bool,
+}
+
+impl Default for DayCycle {
+    fn default() -> DayCycle {
+        DayCycle::new()
+    }
+}
+
+impl DayCycle {
+    pub fn new() -> DayCycle {
+        DayCycle {
+            enabled: DAY_CYCLE,
+            length: DAY_LENGTH,
+            phase: 0.0,
+            roosting: NIGHT_ROOSTING,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if self.enabled {
+            self.phase = (self.phase + dt / self.length).fract();
+        }
+    }
+
+    // 0 at noon, 1 at midnight
+    pub fn darkness(&self) -> f32 {
+        if !self.enabled {
+            return 0.0;
+        }
+
+        (1.0 - (self.phase * 2.0 * PI).cos()) / 2.0
+    }
+
+    pub fn is_night(&self) -> bool {
+        self.darkness() > ROOST_DARKNESS
+    }
+
+    // Whether boids should roost right now
+    pub fn roosting(&self) -> bool {
+        self.roosting && self.is_night()
+    }
+
+    // Color as the ambient light shows it, multiplied towards NIGHT_TINT as it gets dark
+    pub fn shade(&self, color: [f32; 3]) -> [f32; 3] {
+        let darkness = self.darkness();
+        let mut shaded = color;
+
+        for (channel, tint) in shaded.iter_mut().zip(&NIGHT_TINT) {
+            *channel *= 1.0 + (tint - 1.0) * darkness;
+        }
+
+        shaded
+    }
+
+    pub fn shade_rgba(&self, color: [f32; 4]) -> [f32; 4] {
+        let [r, g, b] = self.shade([color[0], color[1], color[2]]);
+        [r, g, b, color[3]]
+    }
+}
@@ -0,0 +1,140 @@
+use std::mem::size_of;
+
+use tracing::{debug, warn, Level};
+use vecmath::Vector2;
+
+use crate::components::column;
+use crate::grid::Grid;
+use crate::query::Read;
+use crate::simulation::Simulation;
+use crate::MEMORY_LOG_INTERVAL;
+
+// Bytes reserved by a vector, including unused capacity
+pub fn vec_bytes<T>(values: &Vec<T>) -> usize {
+    values.capacity() * size_of::<T>()
+}
+
+// Tracks how much memory the big allocations hold. Sizes are reported every frame
+// by name, a change in a size means the allocation grew or shrank.
+// Sizes are always tracked, `enabled` only turns the log on.
+pub struct MemoryDiagnostics {
+    pub enabled: bool,
+    // Sizes of the current frame in bytes
+    current: Vec<(&'static str, usize)>,
+    previous: Vec<(&'static str, usize)>,
+    since_log: f32,
+}
+
+impl Default for MemoryDiagnostics {
+    fn default() -> MemoryDiagnostics {
+        MemoryDiagnostics::new()
+    }
+}
+
+impl MemoryDiagnostics {
+    pub fn new() -> MemoryDiagnostics {
+        MemoryDiagnostics {
+            enabled: false,
+            current: Vec::new(),
+            previous: Vec::new(),
+            since_log: 0.0,
+        }
+    }
+
+    pub fn record(&mut self, name: &'static str, bytes: usize) {
+        self.current.push((name, bytes));
+    }
+
+    pub fn end_frame(&mut self, delta_time: f32) {
+        if self.enabled {
+            self.log_reallocations();
+
+            self.since_log += delta_time;
+
+            if self.since_log >= MEMORY_LOG_INTERVAL {
+                self.since_log = 0.0;
+                self.log();
+            }
+        }
+
+        std::mem::swap(&mut self.current, &mut self.previous);
+        self.current.clear();
+    }
+
+    // Prints allocations that changed size since the last frame
+    fn log_reallocations(&self) {
+        for (name, bytes) in &self.current {
+            let before = self.previous.iter().find(|(other, _)| other == name);
+
+            if let Some((_, before)) = before {
+                if before != bytes {
+                    println!("Reallocated {}: {} -> {}", name, format_bytes(*before), format_bytes(*bytes));
+                }
+            }
+        }
+    }
+
+    fn log(&self) {
+        let total: usize = self.current.iter().map(|(_, bytes)| bytes).sum();
+
+        println!("Memory usage:");
+
+        for (name, bytes) in &self.current {
+            println!("  {:<18} {:>10}", name, format_bytes(*bytes));
+        }
+
+        println!("  {:<18} {:>10}", "Total", format_bytes(total));
+    }
+}
+
+fn format_bytes(bytes: usize) -> String {
+    const KIB: f32 = 1024.0;
+    const MIB: f32 = 1024.0 * 1024.0;
+
+    let bytes_f = bytes as f32;
+
+    if bytes_f >= MIB {
+        format!("{:.2} MiB", bytes_f / MIB)
+    }
+    else if bytes_f >= KIB {
+        format!("{:.1} KiB", bytes_f / KIB)
+    }
+    else {
+        format!("{} B", bytes)
+    }
+}
+
+// Warns about boids whose position or heading isn't finite anymore. Runs after every
+// system at the debug level, so the warning names the system that broke them.
+pub fn check_boids(simulation: &mut Simulation, system: &'static str) {
+    let (positions, directions) = simulation.components
+        .query::<(Read<column::Positions>, Read<column::Directions>)>();
+
+    let mut broken = positions.iter().zip(directions.iter())
+        .enumerate()
+        .filter(|(_, (position, forward))| !is_finite(position.value) || !is_finite(forward.direction))
+        .map(|(index, _)| index);
+
+    if let Some(first) = broken.next() {
+        let count = broken.count() + 1;
+
+        warn!(system, count, first, "Boids with a position or heading that isn't finite");
+    }
+}
+
+// How the boids spread over the cells of a rebuilt grid, only counted at the debug level
+pub fn log_grid(grid: &Grid) {
+    if !tracing::enabled!(Level::DEBUG) {
+        return;
+    }
+
+    let cells = grid.cell_count();
+    let empty = (0..cells).filter(|cell| grid.cell(*cell).is_empty()).count();
+    let fullest = (0..cells).map(|cell| grid.cell(cell).len()).max().unwrap_or(0);
+
+    debug!(cells, empty, fullest, "Grid rebuilt");
+}
+
+fn is_finite(vector: Vector2<f32>) -> bool {
+    vector.iter().all(|value| value.is_finite())
+}
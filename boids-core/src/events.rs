@@ -0,0 +1,61 @@
+use std::slice::Iter;
+
+use crate::components::BoidHandle;
+
+// Settings of a simulation that can change while it runs
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Parameter {
+    Alignment,
+    Cohesion,
+    Separation,
+    Speed,
+    PerceptionRadius,
+    CellSize,
+    InteractionPreset,
+    // One entry of the interaction matrix
+    Interaction,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Event {
+    // Boid added to a running simulation, the first boids don't count
+    BoidSpawned(BoidHandle),
+    // Boid that was hunted until it died of exhaustion
+    BoidEaten(BoidHandle),
+    // Boid that spawned as a predator or was turned into one
+    PredatorSpawned(BoidHandle),
+    ParameterChanged(Parameter),
+    // Boid that left the world and wrapped around to the other side
+    BoundaryHit(BoidHandle),
+}
+
+// Events sent during one frame, read by everyone during the next one.
+// The owner of the frame loop calls `next_frame` once at the start of every frame.
+#[derive(Default)]
+pub struct Events {
+    sent: Vec<Event>,
+    received: Vec<Event>,
+}
+
+impl Events {
+    pub fn send(&mut self, event: Event) {
+        self.sent.push(event);
+    }
+
+    // Events sent during the last frame
+    pub fn iter(&self) -> Iter<'_, Event> {
+        self.received.iter()
+    }
+
+    // Events of the last frame are dropped, the ones sent since become readable
+    pub fn next_frame(&mut self) {
+        std::mem::swap(&mut self.sent, &mut self.received);
+        self.sent.clear();
+    }
+}
+
+impl Extend<Event> for Events {
+    fn extend<I: IntoIterator<Item = Event>>(&mut self, events: I) {
+        self.sent.extend(events);
+    }
+}
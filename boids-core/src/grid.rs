@@ -0,0 +1,588 @@
+use vecmath::{Vector2, vec2_square_len, vec2_sub};
+
+use crate::data::Position;
+use crate::diagnostics::vec_bytes;
+use crate::spatial::{SpatialIndex, knn_insert, knn_worst};
+use crate::INCREMENTAL_SORT_INTERVAL;
+
+// Order in which cells are numbered, boid data sorted by cell follows it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CellOrder {
+    RowMajor,
+    // Z-curve, keeps cells that are close in 2D close in memory too
+    Morton,
+}
+
+// How the grid follows agents from one frame to the next
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GridUpdate {
+    // Counting sort of all agents every frame
+    Rebuild,
+    // Only agents that crossed into another cell are moved
+    Incremental,
+}
+
+impl GridUpdate {
+    pub fn next(self) -> GridUpdate {
+        match self {
+            GridUpdate::Rebuild => GridUpdate::Incremental,
+            GridUpdate::Incremental => GridUpdate::Rebuild,
+        }
+    }
+}
+
+// Interleaves bits of both coordinates, x takes the even bits.
+pub fn morton_code(x: u32, y: u32) -> u64 {
+    fn spread(v: u32) -> u64 {
+        let mut v = v as u64;
+
+        v = (v | (v << 16)) & 0x0000_FFFF_0000_FFFF;
+        v = (v | (v << 8)) & 0x00FF_00FF_00FF_00FF;
+        v = (v | (v << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+        v = (v | (v << 2)) & 0x3333_3333_3333_3333;
+        v = (v | (v << 1)) & 0x5555_5555_5555_5555;
+
+        v
+    }
+
+    spread(x) | (spread(y) << 1)
+}
+
+// Dense uniform grid covering the whole world.
+// Agents are counting sorted by their cell, so every cell is a contiguous
+// slice of `agents` and nothing is allocated after the first frame.
+pub struct Grid {
+    pub cell_size: f32,
+    pub columns: usize,
+    pub rows: usize,
+    pub order: CellOrder,
+    pub update_mode: GridUpdate,
+    // Size of the world the cells cover, the last row and column can be cut off by its edges
+    size: Vector2<f32>,
+    // Cells at opposite edges touch, set with `set_wrap`
+    wrap: bool,
+    // Frames since `sort_due` last asked for a sort
+    frames_since_sort: usize,
+    // Index of each row major cell in the Morton order
+    morton_ranks: Vec<usize>,
+
+    // Cells touching cell `c`, itself first, are `adjacency[adjacency_starts[c]..adjacency_starts[c + 1]]`.
+    // The ones after it up to `forward_ends[c]` are the half that comes later in row major order,
+    // every pair of touching cells is in exactly one of these halves.
+    // Rebuilt on resize, order and wrap changes. While the grid wraps border cells touch the ones
+    // at the opposite edge, otherwise they have fewer.
+    adjacency_starts: Vec<usize>,
+    forward_ends: Vec<usize>,
+    adjacency: Vec<usize>,
+
+    // Agents of cell `c` are `agents[cell_starts[c]..cell_starts[c + 1]]`
+    cell_starts: Vec<usize>,
+    agents: Vec<usize>,
+    // Cell of each agent, `usize::MAX` for agents left out of the grid
+    agent_cells: Vec<usize>,
+    // Index of each agent in `agents`, only meaningful for agents in the grid
+    agent_slots: Vec<usize>,
+}
+
+impl Grid {
+    pub fn new(width: f32, height: f32, cell_size: f32, order: CellOrder) -> Grid {
+        let mut grid = Grid {
+            cell_size,
+            columns: 0,
+            rows: 0,
+            order,
+            update_mode: GridUpdate::Rebuild,
+            size: [width, height],
+            wrap: false,
+            frames_since_sort: 0,
+            morton_ranks: Vec::new(),
+            adjacency_starts: Vec::new(),
+            forward_ends: Vec::new(),
+            adjacency: Vec::new(),
+            cell_starts: Vec::new(),
+            agents: Vec::new(),
+            agent_cells: Vec::new(),
+            agent_slots: Vec::new(),
+        };
+
+        grid.resize(width, height);
+
+        grid
+    }
+
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.columns = ((width / self.cell_size).ceil() as usize).max(1);
+        self.rows = ((height / self.cell_size).ceil() as usize).max(1);
+        self.size = [width, height];
+
+        self.cell_starts.clear();
+        self.cell_starts.resize(self.cell_count() + 1, 0);
+        // Old cells are meaningless now, next update has to rebuild
+        self.agent_cells.clear();
+
+        self.update_morton_ranks();
+        self.update_adjacency();
+    }
+
+    pub fn set_order(&mut self, order: CellOrder) {
+        self.order = order;
+        self.agent_cells.clear();
+        self.update_morton_ranks();
+        self.update_adjacency();
+    }
+
+    // Neighborhoods reach across the edges of a world that wraps around
+    pub fn set_wrap(&mut self, wrap: bool) {
+        if self.wrap != wrap {
+            self.wrap = wrap;
+            self.update_adjacency();
+        }
+    }
+
+    // Columns or rows within a cell of `i`, itself included, out of `count` covering `extent`.
+    // While wrapping the first and the last touch, and when the last is cut off by the edge
+    // of the world the one before it is less than a cell from the first as well.
+    fn axis_neighbors(&self, i: usize, count: usize, extent: f32) -> Vec<usize> {
+        let mut neighbors = vec![i];
+        let mut add = |n: usize| {
+            if !neighbors.contains(&n) {
+                neighbors.push(n);
+            }
+        };
+
+        if i > 0 {
+            add(i - 1);
+        }
+        else if self.wrap {
+            add(count - 1);
+        }
+
+        if i + 1 < count {
+            add(i + 1);
+        }
+        else if self.wrap {
+            add(0);
+        }
+
+        if self.wrap && count > 2 && extent < count as f32 * self.cell_size {
+            if i == 0 {
+                add(count - 2);
+            }
+            if i == count - 2 {
+                add(0);
+            }
+        }
+
+        neighbors
+    }
+
+    fn update_adjacency(&mut self) {
+        let cell_count = self.cell_count();
+        let columns = self.columns;
+
+        self.adjacency_starts.clear();
+        self.adjacency_starts.resize(cell_count + 1, 0);
+        self.forward_ends.clear();
+        self.forward_ends.resize(cell_count, 0);
+        self.adjacency.clear();
+
+        // Filled in the order of cell indices, which depends on the cell order
+        let mut coords = vec![(0, 0); cell_count];
+
+        for y in 0..self.rows {
+            for x in 0..self.columns {
+                coords[self.cell_at(x, y)] = (x, y);
+            }
+        }
+
+        let column_neighbors: Vec<Vec<usize>> = (0..self.columns)
+            .map(|x| self.axis_neighbors(x, self.columns, self.size[0]))
+            .collect();
+        let row_neighbors: Vec<Vec<usize>> = (0..self.rows)
+            .map(|y| self.axis_neighbors(y, self.rows, self.size[1]))
+            .collect();
+
+        // Row major numbers of the later half of the touching cells, then of the earlier half
+        let mut forward = Vec::new();
+        let mut backward = Vec::new();
+
+        for (cell, (x, y)) in coords.into_iter().enumerate() {
+            let own = y * columns + x;
+
+            forward.clear();
+            backward.clear();
+
+            for ny in &row_neighbors[y] {
+                for nx in &column_neighbors[x] {
+                    let other = ny * columns + nx;
+
+                    if other > own {
+                        forward.push(other);
+                    }
+                    else if other < own {
+                        backward.push(other);
+                    }
+                }
+            }
+
+            // Going away from the cell in row major order on both sides
+            forward.sort_unstable();
+            backward.sort_unstable_by(|a, b| b.cmp(a));
+
+            self.adjacency_starts[cell] = self.adjacency.len();
+            self.adjacency.push(cell);
+
+            for other in &forward {
+                self.adjacency.push(self.cell_at(other % columns, other / columns));
+            }
+
+            self.forward_ends[cell] = self.adjacency.len();
+
+            for other in &backward {
+                self.adjacency.push(self.cell_at(other % columns, other / columns));
+            }
+        }
+
+        self.adjacency_starts[cell_count] = self.adjacency.len();
+    }
+
+    // The cell and all cells touching it
+    pub fn adjacent_cells(&self, cell: usize) -> &[usize] {
+        &self.adjacency[self.adjacency_starts[cell]..self.adjacency_starts[cell + 1]]
+    }
+
+    // Half of the cells touching the cell, so visiting these from every cell covers each pair once
+    pub fn forward_cells(&self, cell: usize) -> &[usize] {
+        &self.adjacency[self.adjacency_starts[cell] + 1..self.forward_ends[cell]]
+    }
+
+    // Ranks keep the Morton order dense even when the grid isn't a power of two square
+    fn update_morton_ranks(&mut self) {
+        self.morton_ranks.clear();
+
+        if self.order != CellOrder::Morton {
+            return;
+        }
+
+        let columns = self.columns;
+
+        let mut cells: Vec<usize> = (0..self.cell_count()).collect();
+        cells.sort_by_key(|cell| morton_code((cell % columns) as u32, (cell / columns) as u32));
+
+        self.morton_ranks.resize(cells.len(), 0);
+
+        for (rank, cell) in cells.iter().enumerate() {
+            self.morton_ranks[*cell] = rank;
+        }
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    // Positions outside of the world are clamped to the closest border cell
+    pub fn cell_coords(&self, position: Vector2<f32>) -> (usize, usize) {
+        let x = (position[0] / self.cell_size).floor().max(0.0) as usize;
+        let y = (position[1] / self.cell_size).floor().max(0.0) as usize;
+
+        (x.min(self.columns - 1), y.min(self.rows - 1))
+    }
+
+    pub fn cell_index(&self, position: Vector2<f32>) -> usize {
+        let (x, y) = self.cell_coords(position);
+
+        self.cell_at(x, y)
+    }
+
+    pub fn cell_at(&self, x: usize, y: usize) -> usize {
+        match self.order {
+            CellOrder::RowMajor => y * self.columns + x,
+            CellOrder::Morton => self.morton_ranks[y * self.columns + x],
+        }
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        vec_bytes(&self.morton_ranks)
+            + vec_bytes(&self.adjacency_starts)
+            + vec_bytes(&self.forward_ends)
+            + vec_bytes(&self.adjacency)
+            + vec_bytes(&self.cell_starts)
+            + vec_bytes(&self.agents)
+            + vec_bytes(&self.agent_cells)
+            + vec_bytes(&self.agent_slots)
+    }
+
+    // Calls `f` with every cell a radius query checks. These are the cells touching
+    // the center cell when the circle fits into them, otherwise every cell
+    // overlapping the bounding box of the circle.
+    pub fn for_each_query_cell<F>(&self, center: Vector2<f32>, radius: f32, mut f: F)
+    where
+        F: FnMut(usize)
+    {
+        if radius <= self.cell_size {
+            for cell in self.adjacent_cells(self.cell_index(center)) {
+                f(*cell);
+            }
+
+            return;
+        }
+
+        let (min_x, min_y) = self.cell_coords([center[0] - radius, center[1] - radius]);
+        let (max_x, max_y) = self.cell_coords([center[0] + radius, center[1] + radius]);
+
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                f(self.cell_at(x, y));
+            }
+        }
+    }
+
+    pub fn cell(&self, cell: usize) -> &[usize] {
+        &self.agents[self.cell_starts[cell]..self.cell_starts[cell + 1]]
+    }
+
+    // Counting sort of all agents for which `include` returns true.
+    pub fn build<F>(&mut self, positions: &[Position], include: F)
+    where
+        F: Fn(usize) -> bool
+    {
+        let cell_count = self.cell_count();
+
+        for start in self.cell_starts.iter_mut() {
+            *start = 0;
+        }
+
+        self.agent_cells.clear();
+        self.agent_cells.resize(positions.len(), usize::MAX);
+
+        // Count agents in each cell, shifted by one so the prefix sum gives starts
+        for (i, position) in positions.iter().enumerate() {
+            if !include(i) {
+                continue;
+            }
+
+            let cell = self.cell_index(position.value);
+
+            self.agent_cells[i] = cell;
+            self.cell_starts[cell + 1] += 1;
+        }
+
+        for cell in 0..cell_count {
+            self.cell_starts[cell + 1] += self.cell_starts[cell];
+        }
+
+        self.agents.clear();
+        self.agents.resize(self.cell_starts[cell_count], 0);
+        self.agent_slots.resize(positions.len(), 0);
+
+        // Fill cells back to front, each cell end is decremented until it becomes the start
+        for i in (0..positions.len()).rev() {
+            let cell = self.agent_cells[i];
+
+            if cell == usize::MAX {
+                continue;
+            }
+
+            self.cell_starts[cell + 1] -= 1;
+            self.agents[self.cell_starts[cell + 1]] = i;
+            self.agent_slots[i] = self.cell_starts[cell + 1];
+        }
+
+        // Starts are still shifted by one cell, move them back
+        self.cell_starts.rotate_left(1);
+        self.cell_starts[cell_count] = self.agents.len();
+    }
+
+    // Moves only the agents whose cell changed since the last update, everything else
+    // keeps its place. Falls back to a full build when agents join or leave the grid.
+    pub fn update<F>(&mut self, positions: &[Position], include: F)
+    where
+        F: Fn(usize) -> bool
+    {
+        let membership_changed = self.agent_cells.len() != positions.len()
+            || (0..positions.len()).any(|i| include(i) != (self.agent_cells[i] != usize::MAX));
+
+        if membership_changed {
+            self.build(positions, include);
+            return;
+        }
+
+        for (i, position) in positions.iter().enumerate() {
+            let from = self.agent_cells[i];
+
+            if from == usize::MAX {
+                continue;
+            }
+
+            let to = self.cell_index(position.value);
+
+            if to != from {
+                self.move_agent(i, from, to);
+            }
+        }
+    }
+
+    // Cells are contiguous, so the agent is carried over every cell boundary between
+    // `from` and `to`. Each step swaps it with the edge agent of the cell it enters
+    // and shifts that boundary by one slot, which costs one swap per crossed cell.
+    fn move_agent(&mut self, agent: usize, from: usize, to: usize) {
+        let mut slot = self.agent_slots[agent];
+
+        if from < to {
+            for cell in from..to {
+                let last = self.cell_starts[cell + 1] - 1;
+
+                self.swap_slots(slot, last);
+                self.cell_starts[cell + 1] -= 1;
+                slot = last;
+            }
+        }
+        else {
+            for cell in (to + 1..=from).rev() {
+                let first = self.cell_starts[cell];
+
+                self.swap_slots(slot, first);
+                self.cell_starts[cell] += 1;
+                slot = first;
+            }
+        }
+
+        self.agent_cells[agent] = to;
+    }
+
+    fn swap_slots(&mut self, a: usize, b: usize) {
+        self.agents.swap(a, b);
+        self.agent_slots[self.agents[a]] = a;
+        self.agent_slots[self.agents[b]] = b;
+    }
+
+    // Whether the boid data should be sorted by cell this frame. A rebuild touches every
+    // agent anyway, so it's sorted every frame. Sorting renumbers every agent, which would
+    // undo the savings of incremental updates, so then it only happens once in a while
+    // to keep neighbors close in memory.
+    pub fn sort_due(&mut self) -> bool {
+        match self.update_mode {
+            GridUpdate::Rebuild => true,
+            GridUpdate::Incremental => {
+                self.frames_since_sort += 1;
+
+                if self.frames_since_sort < INCREMENTAL_SORT_INTERVAL {
+                    return false;
+                }
+
+                self.frames_since_sort = 0;
+                true
+            }
+        }
+    }
+
+    // Agents in the order of their cells, followed by the agents left out of the grid.
+    pub fn sorted_order(&self, order: &mut Vec<usize>) {
+        order.clear();
+        order.extend_from_slice(&self.agents);
+        order.extend((0..self.agent_cells.len()).filter(|i| self.agent_cells[*i] == usize::MAX));
+    }
+
+    // Updates agent indices after the agent data was reordered by `sorted_order`.
+    pub fn renumber(&mut self) {
+        for (i, agent) in self.agents.iter_mut().enumerate() {
+            *agent = i;
+            self.agent_slots[i] = i;
+        }
+
+        for cell in self.agent_cells.iter_mut() {
+            *cell = usize::MAX;
+        }
+
+        for cell in 0..self.cell_count() {
+            for i in self.cell_starts[cell]..self.cell_starts[cell + 1] {
+                self.agent_cells[i] = cell;
+            }
+        }
+    }
+}
+
+impl Grid {
+    // Closest distance from `center` to anything outside of the square of cells
+    // within `ring` cells of (x, y). Sides at the world border don't limit it.
+    fn ring_reach(&self, center: Vector2<f32>, x: usize, y: usize, ring: usize) -> f32 {
+        let mut reach = f32::INFINITY;
+
+        if x > ring {
+            reach = reach.min(center[0] - (x - ring) as f32 * self.cell_size);
+        }
+        if x + ring + 1 < self.columns {
+            reach = reach.min((x + ring + 1) as f32 * self.cell_size - center[0]);
+        }
+        if y > ring {
+            reach = reach.min(center[1] - (y - ring) as f32 * self.cell_size);
+        }
+        if y + ring + 1 < self.rows {
+            reach = reach.min((y + ring + 1) as f32 * self.cell_size - center[1]);
+        }
+
+        reach.max(0.0)
+    }
+}
+
+impl SpatialIndex for Grid {
+    fn insert_all(&mut self, positions: &[Position], include: &dyn Fn(usize) -> bool) {
+        match self.update_mode {
+            GridUpdate::Rebuild => self.build(positions, include),
+            GridUpdate::Incremental => self.update(positions, include),
+        }
+    }
+
+    fn query_radius(&self, positions: &[Position], center: Vector2<f32>, radius: f32, out: &mut Vec<usize>) {
+        let radius_squared = radius * radius;
+
+        self.for_each_query_cell(center, radius, |cell| {
+            for agent in self.cell(cell) {
+                if vec2_square_len(vec2_sub(positions[*agent].value, center)) <= radius_squared {
+                    out.push(*agent);
+                }
+            }
+        });
+    }
+
+    // Searches rings of cells around the center cell until no unvisited cell
+    // can contain anything closer than the k-th nearest agent found so far.
+    fn query_knn(&self, positions: &[Position], center: Vector2<f32>, k: usize, out: &mut Vec<usize>) {
+        out.clear();
+
+        if k == 0 {
+            return;
+        }
+
+        let (cx, cy) = self.cell_coords(center);
+        let max_ring = self.columns.max(self.rows);
+
+        for ring in 0..max_ring {
+            let min_x = cx.saturating_sub(ring);
+            let min_y = cy.saturating_sub(ring);
+            let max_x = (cx + ring).min(self.columns - 1);
+            let max_y = (cy + ring).min(self.rows - 1);
+
+            for y in min_y..=max_y {
+                for x in min_x..=max_x {
+                    // Only the outline of the square is new in this ring
+                    let on_ring = x + ring == cx || x == cx + ring || y + ring == cy || y == cy + ring;
+
+                    if !on_ring {
+                        continue;
+                    }
+
+                    for agent in self.cell(self.cell_at(x, y)) {
+                        knn_insert(positions, center, None, k, out, *agent);
+                    }
+                }
+            }
+
+            let reach = self.ring_reach(center, cx, cy, ring);
+
+            if knn_worst(positions, center, None, k, out) <= reach * reach {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use vecmath::{Vector2, vec2_square_len, vec2_sub};
+
+use crate::data::Position;
+use crate::diagnostics::vec_bytes;
+use crate::spatial::{SpatialIndex, knn_insert, knn_worst};
+
+// Balanced 2D k-d tree stored implicitly in one array.
+// Node of range `lo..hi` is the median at `(lo + hi) / 2`, its children are
+// the ranges on either side of it and the split axis alternates with depth.
+// Queries are exact, which makes it a good baseline for the other indices.
+#[derive(Default)]
+pub struct KdTree {
+    agents: Vec<usize>,
+}
+
+fn distance_squared(positions: &[Position], agent: usize, center: Vector2<f32>) -> f32 {
+    vec2_square_len(vec2_sub(positions[agent].value, center))
+}
+
+impl KdTree {
+    pub fn allocated_bytes(&self) -> usize {
+        vec_bytes(&self.agents)
+    }
+
+    fn build(&mut self, positions: &[Position], lo: usize, hi: usize, depth: usize) {
+        if hi - lo <= 1 {
+            return;
+        }
+
+        let axis = depth % 2;
+        let mid = (lo + hi) / 2;
+
+        self.agents[lo..hi].select_nth_unstable_by(mid - lo, |a, b| {
+            positions[*a].value[axis].total_cmp(&positions[*b].value[axis])
+        });
+
+        self.build(positions, lo, mid, depth + 1);
+        self.build(positions, mid + 1, hi, depth + 1);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn radius(
+        &self,
+        positions: &[Position],
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        center: Vector2<f32>,
+        radius_squared: f32,
+        out: &mut Vec<usize>
+    ) {
+        if lo >= hi {
+            return;
+        }
+
+        let axis = depth % 2;
+        let mid = (lo + hi) / 2;
+        let agent = self.agents[mid];
+
+        if distance_squared(positions, agent, center) <= radius_squared {
+            out.push(agent);
+        }
+
+        let diff = center[axis] - positions[agent].value[axis];
+
+        if diff <= 0.0 || diff * diff <= radius_squared {
+            self.radius(positions, lo, mid, depth + 1, center, radius_squared, out);
+        }
+
+        if diff >= 0.0 || diff * diff <= radius_squared {
+            self.radius(positions, mid + 1, hi, depth + 1, center, radius_squared, out);
+        }
+    }
+
+    // `out` is kept sorted by distance and never grows over `k`
+    #[allow(clippy::too_many_arguments)]
+    fn nearest(
+        &self,
+        positions: &[Position],
+        lo: usize,
+        hi: usize,
+        depth: usize,
+        center: Vector2<f32>,
+        k: usize,
+        out: &mut Vec<usize>
+    ) {
+        if lo >= hi {
+            return;
+        }
+
+        let axis = depth % 2;
+        let mid = (lo + hi) / 2;
+        let agent = self.agents[mid];
+
+        knn_insert(positions, center, None, k, out, agent);
+
+        let diff = center[axis] - positions[agent].value[axis];
+
+        let (near, far) = if diff < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        }
+        else {
+            ((mid + 1, hi), (lo, mid))
+        };
+
+        self.nearest(positions, near.0, near.1, depth + 1, center, k, out);
+
+        if diff * diff < knn_worst(positions, center, None, k, out) {
+            self.nearest(positions, far.0, far.1, depth + 1, center, k, out);
+        }
+    }
+}
+
+impl SpatialIndex for KdTree {
+    fn insert_all(&mut self, positions: &[Position], include: &dyn Fn(usize) -> bool) {
+        self.agents.clear();
+        self.agents.extend((0..positions.len()).filter(|i| include(*i)));
+
+        self.build(positions, 0, self.agents.len(), 0);
+    }
+
+    fn query_radius(&self, positions: &[Position], center: Vector2<f32>, radius: f32, out: &mut Vec<usize>) {
+        self.radius(positions, 0, self.agents.len(), 0, center, radius * radius, out);
+    }
+
+    fn query_knn(&self, positions: &[Position], center: Vector2<f32>, k: usize, out: &mut Vec<usize>) {
+        out.clear();
+
+        if k == 0 {
+            return;
+        }
+
+        self.nearest(positions, 0, self.agents.len(), 0, center, k, out);
+    }
+}
@@ -0,0 +1,105 @@
+// Boid simulation without a window or any rendering: the boid components, the systems
+// stepping them, the spatial indices and the parameters below. The `flocking` binary
+// draws it, anything else can step a `simulation::Simulation` on its own.
+
+pub mod behavior;
+pub mod builder;
+pub mod components;
+pub mod data;
+pub mod day_cycle;
+pub mod diagnostics;
+pub mod events;
+pub mod grid;
+pub mod kdtree;
+pub mod metrics;
+pub mod neighbor_list;
+pub mod plugin;
+pub mod profiler;
+pub mod quadtree;
+pub mod query;
+pub mod save;
+pub mod schedule;
+pub mod scenario;
+pub mod script;
+pub mod simulation;
+pub mod spatial;
+pub mod species;
+pub mod systems;
+
+pub const AGENT_SPEED: f32 = 50.0;
+// Every boid is drawn this many times its size, picked at random when it spawns
+pub const AGENT_SCALE_RANGE: [f32; 2] = [0.7, 1.3];
+// Wing beats per second at AGENT_SPEED, the beat speeds up and slows down with the boid
+pub const FLAP_FREQUENCY: f32 = 3.0;
+
+// Colors of the species are set by the theme
+pub const SPECIES_COUNT: usize = 3;
+
+pub const CELL_SIZE: f32 = 100.0;
+// Incremental grid updates sort the boid data by cell only once in this many frames
+pub const INCREMENTAL_SORT_INTERVAL: usize = 30;
+// Boids steer by neighbors closer than this at startup
+pub const PERCEPTION_RADIUS: f32 = 50.0;
+// Extra distance covered by cached neighbor lists, they last until a boid moves half of it
+pub const NEIGHBOR_SKIN: f32 = 15.0;
+// Boids steer by this many nearest neighbors in the topological mode
+pub const TOPOLOGICAL_NEIGHBORS: usize = 7;
+
+pub const ALIGNMENT_WEIGHT: f32 = 0.95;
+pub const COHESION_WEIGHT: f32 = 0.2;
+pub const SEPARATION_WEIGHT: f32 = 8.0;
+
+// Positions kept in the trail of every boid, sampled every TRAIL_INTERVAL seconds
+pub const TRAIL_LENGTH: usize = 16;
+pub const TRAIL_INTERVAL: f32 = 0.05;
+// Longer steps are wraps around the screen and break the trail
+pub const TRAIL_BREAK_DISTANCE: f32 = 100.0;
+
+// Average time between two gusts in seconds
+pub const GUST_INTERVAL: f32 = 4.0;
+pub const GUST_DURATION: f32 = 3.0;
+pub const GUST_FADE_TIME: f32 = 0.75;
+pub const GUST_STRENGTH: f32 = 2.0;
+pub const GUST_RADIUS: [f32; 2] = [100.0, 300.0];
+
+// Boids turn away from obstacles and walls closer than this, harder the closer they get
+pub const OBSTACLE_AVOID_DISTANCE: f32 = 30.0;
+pub const OBSTACLE_AVOIDANCE: f32 = 0.5;
+// Boids are kept at least this far from a wall, more than a boid moves in one step
+pub const WALL_THICKNESS: f32 = 3.0;
+
+// Chances per second of a flocking boid to start feeding or perching
+pub const FEED_CHANCE: f32 = 0.02;
+pub const PERCH_CHANCE: f32 = 0.5;
+pub const FEED_DURATION: f32 = 5.0;
+pub const PERCH_DURATION: f32 = 8.0;
+// Boids can only perch this close to the bottom edge
+pub const PERCH_MARGIN: f32 = 20.0;
+// Fleeing lasts this long after the last threat
+pub const FLEE_DURATION: f32 = 2.0;
+// Boids threatened for longer than this die of exhaustion
+pub const EXHAUSTION_TIME: f32 = 15.0;
+
+// Ambient light slowly goes from day to night and back
+pub const DAY_CYCLE: bool = false;
+// Seconds of simulation time from one noon to the next
+pub const DAY_LENGTH: f32 = 120.0;
+// Colors are multiplied by this at midnight
+pub const NIGHT_TINT: [f32; 3] = [0.25, 0.3, 0.55];
+// Past this darkness it is night, 0 being noon and 1 midnight
+pub const ROOST_DARKNESS: f32 = 0.7;
+// At night boids perch at the bottom edge far more often and stay until morning
+pub const NIGHT_ROOSTING: bool = true;
+pub const NIGHT_PERCH_FACTOR: f32 = 10.0;
+
+// Seconds between two checks of the steering script for changes
+pub const SCRIPT_POLL_INTERVAL: f32 = 0.5;
+// Operations one call of the steering script may take before it is stopped
+pub const SCRIPT_MAX_OPERATIONS: u64 = 100_000;
+
+// Frames kept by the profiler
+pub const PROFILER_HISTORY: usize = 240;
+// Seconds between two printed breakdowns
+pub const PROFILER_LOG_INTERVAL: f32 = 1.0;
+// Seconds between two memory usage logs
+pub const MEMORY_LOG_INTERVAL: f32 = 5.0;
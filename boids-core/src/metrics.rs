@@ -0,0 +1,175 @@
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use vecmath::{vec2_add, vec2_len, vec2_scale, vec2_sub, Vector2};
+
+use crate::behavior::state_weights;
+use crate::data::{BehaviorState, Position};
+use crate::grid::{CellOrder, Grid};
+use crate::simulation::Simulation;
+use crate::spatial::SpatialIndex;
+
+// Order parameters of the living boids, the usual quantities of flocking research.
+// Distances ignore wrapping, a flock across an edge of the world counts as two.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Metrics {
+    // Length of the mean heading, 1 when all boids fly the same way and near 0 for random headings
+    pub polarization: f32,
+    // Mean angular momentum of the headings around the center of the flock, 1 when all boids
+    // circle it the same way and near 0 for parallel or random headings
+    pub milling: f32,
+    // Mean distance of a boid to the closest other boid, 0 without two living boids
+    pub nearest_neighbor: f32,
+    // Groups of boids connected by links no longer than the perception radius
+    pub clusters: usize,
+    // Mean flying speed in world units per second, boids of every behavior state included
+    pub mean_speed: f32,
+}
+
+// Measures the metrics of a simulation, reusing its grid and buffers from one measurement to the next.
+// The grid of the simulation is built before the systems move the boids, so this one is built again.
+pub struct MetricsScratch {
+    grid: Grid,
+    world_size: [f32; 2],
+    living: Vec<usize>,
+    neighbors: Vec<usize>,
+    // Union-find forest of the clusters, indexed like the components
+    parents: Vec<usize>,
+}
+
+impl Default for MetricsScratch {
+    fn default() -> MetricsScratch {
+        MetricsScratch::new()
+    }
+}
+
+impl MetricsScratch {
+    pub fn new() -> MetricsScratch {
+        MetricsScratch {
+            grid: Grid::new(1.0, 1.0, 1.0, CellOrder::RowMajor),
+            world_size: [1.0, 1.0],
+            living: Vec::new(),
+            neighbors: Vec::new(),
+            parents: Vec::new(),
+        }
+    }
+
+    pub fn measure(&mut self, simulation: &Simulation) -> Metrics {
+        let components = &simulation.components;
+
+        self.living.clear();
+        self.living.extend(
+            (0..components.len()).filter(|i| components.behaviors[*i].state != BehaviorState::Dead)
+        );
+
+        if self.living.is_empty() {
+            return Metrics::default();
+        }
+
+        let count = self.living.len() as f32;
+        let positions = &components.positions;
+        let directions = &components.directions;
+
+        let heading_sum = self.living.iter().fold([0.0, 0.0], |sum, i| vec2_add(sum, directions[*i].direction));
+        let position_sum = self.living.iter().fold([0.0, 0.0], |sum, i| vec2_add(sum, positions[*i].value));
+        let center = vec2_scale(position_sum, 1.0 / count);
+
+        // Cross product of the unit offset from the center with the heading
+        let momentum: f32 = self.living.iter()
+            .map(|i| {
+                let offset = vec2_sub(positions[*i].value, center);
+                let distance = vec2_len(offset);
+
+                if distance > 0.0 {
+                    cross(offset, directions[*i].direction) / distance
+                }
+                else {
+                    0.0
+                }
+            })
+            .sum();
+
+        let speed_sum: f32 = self.living.iter()
+            .map(|i| simulation.speed * state_weights(components.behaviors[*i].state).speed)
+            .sum();
+
+        self.rebuild_grid(simulation);
+
+        Metrics {
+            polarization: vec2_len(heading_sum) / count,
+            milling: momentum.abs() / count,
+            nearest_neighbor: self.mean_nearest_neighbor(positions),
+            clusters: self.count_clusters(positions, simulation.perception_radius),
+            mean_speed: speed_sum / count,
+        }
+    }
+
+    // Cells as large as the perception radius, so the cluster links only reach into adjacent cells
+    fn rebuild_grid(&mut self, simulation: &Simulation) {
+        let cell_size = simulation.perception_radius.max(1.0);
+        let world_size = simulation.size();
+
+        if self.grid.cell_size != cell_size || self.world_size != world_size {
+            self.grid = Grid::new(world_size[0], world_size[1], cell_size, CellOrder::RowMajor);
+            self.world_size = world_size;
+        }
+
+        let behaviors = &simulation.components.behaviors;
+        self.grid.build(&simulation.components.positions, |i| behaviors[i].state != BehaviorState::Dead);
+    }
+
+    fn mean_nearest_neighbor(&self, positions: &[Position]) -> f32 {
+        if self.living.len() < 2 {
+            return 0.0;
+        }
+
+        let grid = &self.grid;
+
+        // The two nearest are the boid itself and its neighbor
+        let sum: f32 = self.living.par_iter()
+            .map_init(Vec::new, |nearest, i| {
+                grid.query_knn(positions, positions[*i].value, 2, nearest);
+
+                nearest.iter()
+                    .find(|j| *j != i)
+                    .map_or(0.0, |j| vec2_len(vec2_sub(positions[*j].value, positions[*i].value)))
+            })
+            .sum();
+
+        sum / self.living.len() as f32
+    }
+
+    fn count_clusters(&mut self, positions: &[Position], link_distance: f32) -> usize {
+        self.parents.clear();
+        self.parents.extend(0..positions.len());
+
+        for i in &self.living {
+            self.neighbors.clear();
+            self.grid.query_radius(positions, positions[*i].value, link_distance, &mut self.neighbors);
+
+            for j in &self.neighbors {
+                let a = find_root(&mut self.parents, *i);
+                let b = find_root(&mut self.parents, *j);
+
+                if a != b {
+                    self.parents[a.max(b)] = a.min(b);
+                }
+            }
+        }
+
+        let parents = &mut self.parents;
+        self.living.iter().filter(|i| find_root(parents, **i) == **i).count()
+    }
+}
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+// Halves the path on the way up, so later searches take fewer steps
+fn find_root(parents: &mut [usize], mut node: usize) -> usize {
+    while parents[node] != node {
+        parents[node] = parents[parents[node]];
+        node = parents[node];
+    }
+
+    node
+}
@@ -0,0 +1,79 @@
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use vecmath::{Vector2, vec2_square_len, vec2_sub};
+
+use crate::data::Position;
+use crate::diagnostics::vec_bytes;
+use crate::spatial::SpatialIndex;
+use crate::NEIGHBOR_SKIN;
+
+// Neighbors of every boid within the perception radius plus a skin, kept over several frames.
+// As long as no boid has moved more than half the skin since the build, every boid
+// that can be within the perception radius now is still on the list, so steering only
+// filters the list instead of querying the spatial index again.
+// Indices are only valid while the boid data is not reordered.
+#[derive(Default)]
+pub struct NeighborList {
+    pub enabled: bool,
+    // Neighbors of boid `i` are `neighbors[starts[i]..starts[i + 1]]`
+    starts: Vec<usize>,
+    neighbors: Vec<usize>,
+    // Positions the list was built from
+    built_positions: Vec<Position>,
+    valid: bool,
+}
+
+impl NeighborList {
+    // Forces a rebuild, needed whenever boids are added, removed or reordered
+    pub fn invalidate(&mut self) {
+        self.valid = false;
+    }
+
+    pub fn needs_rebuild(&self, positions: &[Position]) -> bool {
+        if !self.valid || positions.len() != self.built_positions.len() {
+            return true;
+        }
+
+        let max_distance = NEIGHBOR_SKIN * 0.5;
+        let max_distance_squared = max_distance * max_distance;
+
+        positions.par_iter()
+            .zip(self.built_positions.par_iter())
+            .with_min_len(1024)
+            .any(|(position, built)| vec2_square_len(vec2_sub(position.value, built.value)) > max_distance_squared)
+    }
+
+    // Queries the extended neighborhood of every boid from a freshly built index,
+    // across the edges of a world that wraps around at `wrap`
+    pub fn build(&mut self, index: &dyn SpatialIndex, positions: &[Position], perception_radius: f32, wrap: Option<Vector2<f32>>) {
+        let radius = perception_radius + NEIGHBOR_SKIN;
+
+        self.starts.clear();
+        self.neighbors.clear();
+
+        for (agent_id, position) in positions.iter().enumerate() {
+            self.starts.push(self.neighbors.len());
+
+            let start = self.neighbors.len();
+            index.query_radius_wrapped(positions, position.value, radius, wrap, &mut self.neighbors);
+
+            if let Some(own) = self.neighbors[start..].iter().position(|n| *n == agent_id) {
+                self.neighbors.remove(start + own);
+            }
+        }
+
+        self.starts.push(self.neighbors.len());
+
+        self.built_positions.clear();
+        self.built_positions.extend_from_slice(positions);
+
+        self.valid = true;
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        vec_bytes(&self.starts) + vec_bytes(&self.neighbors) + vec_bytes(&self.built_positions)
+    }
+
+    pub fn neighbors(&self, agent_id: usize) -> &[usize] {
+        &self.neighbors[self.starts[agent_id]..self.starts[agent_id + 1]]
+    }
+}
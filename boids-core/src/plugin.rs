@@ -0,0 +1,232 @@
+use std::error::Error;
+use std::env::consts::DLL_EXTENSION;
+use std::ffi::{CStr, OsStr};
+use std::fs;
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use libloading::Library;
+use vecmath::{vec2_add, vec2_normalized, vec2_square_len};
+
+use crate::data::BehaviorState;
+use crate::profiler::Stage;
+use crate::schedule::System;
+use crate::simulation::Simulation;
+
+// Steering systems compiled as dynamic libraries. A plugin is a cdylib exporting
+//
+//     #[no_mangle]
+//     pub extern "C" fn boids_plugin() -> *const PluginDescriptor
+//
+// returning a descriptor that lives as long as the library. Everything that crosses over is
+// repr(C), so plugins don't have to be built with the same compiler as the app or in Rust at all.
+// Every update the plugin is called once with the living boids and fills in a steering force for
+// each, which is added to its heading like the forces of a steering script.
+//
+// The interface only changes along with PLUGIN_ABI_VERSION, plugins built for another version
+// are turned away when they load.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+// Name of the function every plugin exports
+pub const PLUGIN_ENTRY: &str = "boids_plugin";
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PluginBoid {
+    pub x: f32,
+    pub y: f32,
+    // Unit heading
+    pub dx: f32,
+    pub dy: f32,
+    pub species: u32,
+}
+
+// What the plugin sees of one update, the pointers are only valid during the call
+#[repr(C)]
+pub struct PluginFrame {
+    pub boids: *const PluginBoid,
+    // One per boid, zeroed before the call
+    pub forces: *mut [f32; 2],
+    pub count: usize,
+    pub width: f32,
+    pub height: f32,
+    pub perception_radius: f32,
+    pub dt: f32,
+}
+
+pub type PluginSteer = unsafe extern "C" fn(frame: *const PluginFrame);
+
+#[repr(C)]
+pub struct PluginDescriptor {
+    // PLUGIN_ABI_VERSION the plugin was built for
+    pub abi_version: u32,
+    // Nul terminated UTF-8, shown with the other systems of the schedule
+    pub name: *const c_char,
+    pub steer: Option<PluginSteer>,
+}
+
+// Descriptors are never written to, plugins keep theirs in a static
+unsafe impl Sync for PluginDescriptor {}
+
+// A loaded plugin, clones share the library
+#[derive(Clone)]
+pub struct Plugin {
+    name: &'static str,
+    steer: PluginSteer,
+    // Keeps `steer` loaded, plugins linked into the program have none
+    _library: Option<Arc<Library>>,
+}
+
+impl Plugin {
+    // Runs the initialization code of the library, which has to be trusted
+    pub fn load(path: &Path) -> Result<Plugin, Box<dyn Error>> {
+        unsafe {
+            let library = Library::new(path)?;
+            let entry = library.get::<extern "C" fn() -> *const PluginDescriptor>(PLUGIN_ENTRY.as_bytes())?;
+            let descriptor = entry().as_ref().ok_or("the plugin returned no descriptor")?;
+
+            let mut plugin = Plugin::from_descriptor(descriptor)?;
+            plugin._library = Some(Arc::new(library));
+
+            Ok(plugin)
+        }
+    }
+
+    // A plugin linked into the program. Safety: `steer` has to keep working for as long as
+    // the plugin is used and `name` has to be null or point to a nul terminated string.
+    #[allow(clippy::missing_safety_doc)]
+    pub unsafe fn from_descriptor(descriptor: &PluginDescriptor) -> Result<Plugin, Box<dyn Error>> {
+        if descriptor.abi_version != PLUGIN_ABI_VERSION {
+            return Err(format!(
+                "the plugin was built for version {} of the interface instead of {}",
+                descriptor.abi_version,
+                PLUGIN_ABI_VERSION
+            ).into());
+        }
+
+        if descriptor.name.is_null() {
+            return Err("the plugin has no name".into());
+        }
+
+        let steer = descriptor.steer.ok_or("the plugin has no steer function")?;
+
+        let name = CStr::from_ptr(descriptor.name).to_str()?;
+
+        Ok(Plugin {
+            // System names are static, plugins are loaded once at startup
+            name: Box::leak(name.to_string().into_boxed_str()),
+            steer,
+            _library: None,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+// Every library in `directory` in the order of their file names, the ones that fail to load
+// are reported and skipped. A missing directory has no plugins.
+pub fn load_plugins(directory: &Path) -> Vec<Plugin> {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension() == Some(OsStr::new(DLL_EXTENSION)))
+        .collect();
+
+    paths.sort();
+
+    paths.iter()
+        .filter_map(|path| match Plugin::load(path) {
+            Ok(plugin) => {
+                println!("Loaded plugin {} from {}", plugin.name, path.display());
+                Some(plugin)
+            }
+            Err(error) => {
+                println!("Could not load plugin {}: {}", path.display(), error);
+                None
+            }
+        })
+        .collect()
+}
+
+// Runs a plugin with the steering systems, see Schedule::add_plugin
+pub(crate) struct PluginSystem {
+    plugin: Plugin,
+    // Reused between updates
+    boids: Vec<PluginBoid>,
+    forces: Vec<[f32; 2]>,
+    indices: Vec<usize>,
+}
+
+impl PluginSystem {
+    pub(crate) fn new(plugin: Plugin) -> PluginSystem {
+        PluginSystem {
+            plugin,
+            boids: Vec::new(),
+            forces: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+}
+
+impl System for PluginSystem {
+    fn name(&self) -> &'static str {
+        self.plugin.name
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Steering
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, dt: f32) {
+        let [width, height] = simulation.size();
+        let components = &mut simulation.components;
+
+        self.boids.clear();
+        self.indices.clear();
+
+        for i in 0..components.len() {
+            if components.behaviors[i].state == BehaviorState::Dead {
+                continue;
+            }
+
+            let [x, y] = components.positions[i].value;
+            let [dx, dy] = components.directions[i].direction;
+
+            self.boids.push(PluginBoid { x, y, dx, dy, species: components.species[i].id as u32 });
+            self.indices.push(i);
+        }
+
+        self.forces.clear();
+        self.forces.resize(self.boids.len(), [0.0, 0.0]);
+
+        let frame = PluginFrame {
+            boids: self.boids.as_ptr(),
+            forces: self.forces.as_mut_ptr(),
+            count: self.boids.len(),
+            width,
+            height,
+            perception_radius: simulation.perception_radius,
+            dt,
+        };
+
+        unsafe {
+            (self.plugin.steer)(&frame);
+        }
+
+        for (i, force) in self.indices.iter().zip(&self.forces) {
+            let forward = &mut components.directions[*i];
+            let heading = vec2_add(forward.direction, *force);
+
+            // Forces that aren't numbers leave the heading alone
+            if vec2_square_len(heading) > 0.0 && heading.iter().all(|value| value.is_finite()) {
+                forward.direction = vec2_normalized(heading);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::{PROFILER_HISTORY, PROFILER_LOG_INTERVAL};
+
+// Parts of a frame that are timed separately
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Stage {
+    SpatialIndex,
+    Steering,
+    Script,
+    Behavior,
+    Gusts,
+    Integration,
+    Wrap,
+    FlowField,
+    Trails,
+    GpuSimulation,
+    Upload,
+    Draw,
+}
+
+pub const STAGE_COUNT: usize = 12;
+
+pub const STAGES: [Stage; STAGE_COUNT] = [
+    Stage::SpatialIndex,
+    Stage::Steering,
+    Stage::Script,
+    Stage::Behavior,
+    Stage::Gusts,
+    Stage::Integration,
+    Stage::Wrap,
+    Stage::FlowField,
+    Stage::Trails,
+    Stage::GpuSimulation,
+    Stage::Upload,
+    Stage::Draw,
+];
+
+impl Stage {
+    // Color of the stage in the graph
+    pub fn color(self) -> [f32; 3] {
+        match self {
+            Stage::SpatialIndex => [0.9, 0.6, 0.2],
+            Stage::Steering => [0.9, 0.3, 0.3],
+            Stage::Script => [0.9, 0.5, 0.6],
+            Stage::Behavior => [0.8, 0.8, 0.3],
+            Stage::Gusts => [0.5, 0.9, 0.9],
+            Stage::Integration => [0.3, 0.8, 0.4],
+            Stage::Wrap => [0.3, 0.5, 0.3],
+            Stage::FlowField => [0.3, 0.6, 0.9],
+            Stage::Trails => [0.9, 0.9, 0.9],
+            Stage::GpuSimulation => [0.9, 0.4, 0.9],
+            Stage::Upload => [0.6, 0.4, 0.8],
+            Stage::Draw => [0.7, 0.7, 0.7],
+        }
+    }
+}
+
+// Collects milliseconds spent in each stage for the last PROFILER_HISTORY frames.
+// Timings are always collected, `enabled` only turns the graph and the log on.
+pub struct Profiler {
+    pub enabled: bool,
+    // Stages of the frame that is being measured
+    current: [f32; STAGE_COUNT],
+    // Finished frames, oldest first
+    history: VecDeque<[f32; STAGE_COUNT]>,
+    since_log: f32,
+}
+
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            enabled: false,
+            current: [0.0; STAGE_COUNT],
+            history: VecDeque::with_capacity(PROFILER_HISTORY),
+            since_log: 0.0,
+        }
+    }
+
+    // Adds the time since `start` to a stage of the current frame
+    pub fn record(&mut self, stage: Stage, start: Instant) {
+        self.record_duration(stage, start.elapsed());
+    }
+
+    pub fn record_duration(&mut self, stage: Stage, duration: Duration) {
+        self.current[stage as usize] += duration.as_secs_f32() * 1000.0;
+    }
+
+    pub fn end_frame(&mut self, delta_time: f32) {
+        if self.history.len() == PROFILER_HISTORY {
+            self.history.pop_front();
+        }
+
+        self.history.push_back(self.current);
+        self.current = [0.0; STAGE_COUNT];
+
+        if !self.enabled {
+            return;
+        }
+
+        self.since_log += delta_time;
+
+        if self.since_log >= PROFILER_LOG_INTERVAL {
+            self.since_log = 0.0;
+            self.log();
+        }
+    }
+
+    pub fn history(&self) -> &VecDeque<[f32; STAGE_COUNT]> {
+        &self.history
+    }
+
+    // Prints the average of every stage over the history
+    fn log(&self) {
+        let frames = self.history.len().max(1) as f32;
+        let mut total = 0.0;
+
+        println!("Frame breakdown over {} frames:", self.history.len());
+
+        for stage in STAGES.iter() {
+            let average = self.history.iter().map(|frame| frame[*stage as usize]).sum::<f32>() / frames;
+            total += average;
+
+            println!("  {:<14} {:>7.3} ms", format!("{:?}", stage), average);
+        }
+
+        println!("  {:<14} {:>7.3} ms", "Total", total);
+    }
+}
@@ -0,0 +1,207 @@
+use vecmath::{Vector2, vec2_square_len, vec2_sub};
+
+use crate::data::Position;
+use crate::diagnostics::vec_bytes;
+use crate::spatial::{SpatialIndex, box_distance_squared, knn_insert, knn_worst};
+
+// Leaves are split once they hold more agents than this
+const LEAF_CAPACITY: usize = 16;
+// Stops splitting when many agents share almost the same spot
+const MAX_DEPTH: usize = 16;
+
+#[derive(Clone, Copy)]
+struct Node {
+    min: Vector2<f32>,
+    max: Vector2<f32>,
+    // First of four consecutive children, leaves have none
+    children: Option<usize>,
+    // Range of `agents` inside this node
+    start: usize,
+    end: usize,
+}
+
+// Region quadtree rebuilt every frame, adapts to dense flocks in a big empty world
+// much better than a uniform grid. Agents of every node are a contiguous range
+// of `agents`, so nodes and agents are the only two allocations and both are reused.
+#[derive(Default)]
+pub struct Quadtree {
+    nodes: Vec<Node>,
+    agents: Vec<usize>,
+}
+
+// Moves items matching `pred` to the front, returns how many there are
+fn partition<F: Fn(usize) -> bool>(agents: &mut [usize], pred: F) -> usize {
+    let mut split = 0;
+
+    for i in 0..agents.len() {
+        if pred(agents[i]) {
+            agents.swap(split, i);
+            split += 1;
+        }
+    }
+
+    split
+}
+
+impl Quadtree {
+    pub fn allocated_bytes(&self) -> usize {
+        vec_bytes(&self.nodes) + vec_bytes(&self.agents)
+    }
+
+    fn split(&mut self, node: usize, positions: &[Position], depth: usize) {
+        let Node { min, max, start, end, .. } = self.nodes[node];
+
+        if end - start <= LEAF_CAPACITY || depth >= MAX_DEPTH {
+            return;
+        }
+
+        let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+
+        // Sort agents into top / bottom and then each half into left / right
+        let agents = &mut self.agents[start..end];
+        let top = partition(agents, |a| positions[a].value[1] < center[1]);
+        let top_left = partition(&mut agents[..top], |a| positions[a].value[0] < center[0]);
+        let bottom_left = partition(&mut agents[top..], |a| positions[a].value[0] < center[0]);
+
+        let bounds = [
+            (min, center),
+            ([center[0], min[1]], [max[0], center[1]]),
+            ([min[0], center[1]], [center[0], max[1]]),
+            (center, max),
+        ];
+        let ranges = [
+            (start, start + top_left),
+            (start + top_left, start + top),
+            (start + top, start + top + bottom_left),
+            (start + top + bottom_left, end),
+        ];
+
+        let first_child = self.nodes.len();
+        self.nodes[node].children = Some(first_child);
+
+        for ((min, max), (start, end)) in bounds.iter().zip(ranges.iter()) {
+            self.nodes.push(Node {
+                min: *min,
+                max: *max,
+                children: None,
+                start: *start,
+                end: *end,
+            });
+        }
+
+        for child in first_child..first_child + 4 {
+            self.split(child, positions, depth + 1);
+        }
+    }
+}
+
+impl Quadtree {
+    // Best first search, closer children are visited first so farther ones can be skipped
+    fn nearest(&self, positions: &[Position], node: usize, center: Vector2<f32>, k: usize, out: &mut Vec<usize>) {
+        let node = &self.nodes[node];
+
+        match node.children {
+            Some(first_child) => {
+                let mut children = [(0.0, 0); 4];
+
+                for (i, child) in children.iter_mut().enumerate() {
+                    let id = first_child + i;
+                    let n = &self.nodes[id];
+
+                    *child = (box_distance_squared(n.min, n.max, center), id);
+                }
+
+                children.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                for (distance, child) in children.iter() {
+                    if *distance >= knn_worst(positions, center, None, k, out) {
+                        break;
+                    }
+
+                    self.nearest(positions, *child, center, k, out);
+                }
+            }
+            None => {
+                for agent in &self.agents[node.start..node.end] {
+                    knn_insert(positions, center, None, k, out, *agent);
+                }
+            }
+        }
+    }
+}
+
+impl SpatialIndex for Quadtree {
+    fn insert_all(&mut self, positions: &[Position], include: &dyn Fn(usize) -> bool) {
+        self.nodes.clear();
+        self.agents.clear();
+        self.agents.extend((0..positions.len()).filter(|i| include(*i)));
+
+        // Root covers all agents, wherever they are
+        let mut min = [f32::MAX, f32::MAX];
+        let mut max = [f32::MIN, f32::MIN];
+
+        for agent in &self.agents {
+            let p = positions[*agent].value;
+
+            min = [min[0].min(p[0]), min[1].min(p[1])];
+            max = [max[0].max(p[0]), max[1].max(p[1])];
+        }
+
+        self.nodes.push(Node {
+            min,
+            max,
+            children: None,
+            start: 0,
+            end: self.agents.len(),
+        });
+
+        self.split(0, positions, 0);
+    }
+
+    fn query_radius(&self, positions: &[Position], center: Vector2<f32>, radius: f32, out: &mut Vec<usize>) {
+        if self.agents.is_empty() {
+            return;
+        }
+
+        let radius_squared = radius * radius;
+
+        // Tree is shallow, a small fixed stack is enough
+        let mut stack = [0; MAX_DEPTH * 3 + 4];
+        let mut len = 1;
+
+        while len > 0 {
+            len -= 1;
+            let node = &self.nodes[stack[len]];
+
+            if box_distance_squared(node.min, node.max, center) > radius_squared {
+                continue;
+            }
+
+            match node.children {
+                Some(first_child) => {
+                    for child in first_child..first_child + 4 {
+                        stack[len] = child;
+                        len += 1;
+                    }
+                }
+                None => {
+                    for agent in &self.agents[node.start..node.end] {
+                        if vec2_square_len(vec2_sub(positions[*agent].value, center)) <= radius_squared {
+                            out.push(*agent);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn query_knn(&self, positions: &[Position], center: Vector2<f32>, k: usize, out: &mut Vec<usize>) {
+        out.clear();
+
+        if k == 0 || self.agents.is_empty() {
+            return;
+        }
+
+        self.nearest(positions, 0, center, k, out);
+    }
+}
@@ -0,0 +1,65 @@
+use std::marker::PhantomData;
+
+use crate::components::Columns;
+
+// One component column of `Components`, named by its marker type in `components::column`
+pub trait Column {
+    type Item: 'static;
+    const NAME: &'static str;
+
+    // Hands out the column once, none if an earlier part of the query took it
+    fn take<'a>(columns: &mut Columns<'a>) -> Option<&'a mut Vec<Self::Item>>;
+}
+
+fn take<'a, C: Column>(columns: &mut Columns<'a>) -> &'a mut Vec<C::Item> {
+    C::take(columns).unwrap_or_else(|| panic!("Column {} is accessed twice in one query", C::NAME))
+}
+
+// Shared access to a column
+pub struct Read<C>(PhantomData<C>);
+// Exclusive access to a column
+pub struct Write<C>(PhantomData<C>);
+
+// Columns a system reads and writes, fetched together with `Components::query`.
+// A column may only appear once per query, so a write never aliases another access.
+pub trait Query<'a> {
+    type Output;
+
+    fn fetch(columns: &mut Columns<'a>) -> Self::Output;
+}
+
+impl<'a, C: Column> Query<'a> for Read<C> {
+    type Output = &'a [C::Item];
+
+    fn fetch(columns: &mut Columns<'a>) -> Self::Output {
+        take::<C>(columns)
+    }
+}
+
+impl<'a, C: Column> Query<'a> for Write<C> {
+    type Output = &'a mut [C::Item];
+
+    fn fetch(columns: &mut Columns<'a>) -> Self::Output {
+        take::<C>(columns)
+    }
+}
+
+macro_rules! tuple_query {
+    ($($query:ident),*) => {
+        impl<'a, $($query: Query<'a>),*> Query<'a> for ($($query,)*) {
+            type Output = ($($query::Output,)*);
+
+            fn fetch(columns: &mut Columns<'a>) -> Self::Output {
+                ($($query::fetch(columns),)*)
+            }
+        }
+    };
+}
+
+tuple_query!(A, B);
+tuple_query!(A, B, C);
+tuple_query!(A, B, C, D);
+tuple_query!(A, B, C, D, E);
+tuple_query!(A, B, C, D, E, F);
+tuple_query!(A, B, C, D, E, F, G);
+tuple_query!(A, B, C, D, E, F, G, H);
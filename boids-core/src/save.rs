@@ -0,0 +1,129 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+use serde::{Deserialize, Serialize};
+
+use crate::components::Components;
+use crate::data::{Boundary, FlockWeights, Gust, Obstacle, Wall};
+use crate::day_cycle::DayCycle;
+use crate::scenario::Timeline;
+use crate::simulation::Simulation;
+use crate::species::{InteractionMatrix, InteractionPreset};
+
+// Bumped whenever the saved data changes, older files are refused instead of misread
+const SAVE_VERSION: u32 = 4;
+
+// Everything a run needs to go on where it was saved.
+// Spatial indices and other caches are rebuilt by the first update after loading.
+#[derive(Serialize, Deserialize)]
+struct SavedSimulation {
+    version: u32,
+    world_size: [f32; 2],
+    seed: u64,
+    // StdRng can't be saved, the loaded simulation draws from a generator seeded with this.
+    // Loading the same file always plays out the same, but not like the run that was saved.
+    rng_seed: u64,
+
+    components: Components,
+
+    weights: FlockWeights,
+    speed: f32,
+    perception_radius: f32,
+    boundary: Boundary,
+    spawn_species: Vec<usize>,
+    cell_size: f32,
+    interaction_preset: InteractionPreset,
+    interactions: InteractionMatrix,
+
+    trails: bool,
+    since_trail: f32,
+    gusts: Vec<Gust>,
+    next_gust: f32,
+    day_cycle: DayCycle,
+
+    obstacles: Vec<Obstacle>,
+    walls: Vec<Wall>,
+    timeline: Timeline,
+}
+
+pub fn save_simulation(simulation: &Simulation, path: &Path) -> Result<(), Box<dyn Error>> {
+    write_simulation(simulation, BufWriter::new(File::create(path)?))
+}
+
+// The same data as a save file, for snapshots that don't go to a file
+pub fn write_simulation<W: Write>(simulation: &Simulation, writer: W) -> Result<(), Box<dyn Error>> {
+    let saved = SavedSimulation {
+        version: SAVE_VERSION,
+        world_size: simulation.world_size,
+        seed: simulation.seed,
+        rng_seed: simulation.rng.clone().gen(),
+
+        components: simulation.components.clone(),
+
+        weights: simulation.weights,
+        speed: simulation.speed,
+        perception_radius: simulation.perception_radius,
+        boundary: simulation.boundary,
+        spawn_species: simulation.spawn_species.clone(),
+        cell_size: simulation.grid.cell_size,
+        interaction_preset: simulation.interaction_preset,
+        interactions: simulation.interactions.clone(),
+
+        trails: simulation.trails,
+        since_trail: simulation.since_trail,
+        gusts: simulation.gusts.clone(),
+        next_gust: simulation.next_gust,
+        day_cycle: simulation.day_cycle.clone(),
+
+        obstacles: simulation.obstacles.clone(),
+        walls: simulation.walls.clone(),
+        timeline: simulation.timeline.clone(),
+    };
+
+    bincode::serialize_into(writer, &saved)?;
+
+    Ok(())
+}
+
+// Settings that aren't part of the save, like the spatial backend, start out at their defaults
+pub fn load_simulation(path: &Path) -> Result<Simulation, Box<dyn Error>> {
+    read_simulation(BufReader::new(File::open(path)?))
+}
+
+pub fn read_simulation<R: Read>(reader: R) -> Result<Simulation, Box<dyn Error>> {
+    let saved: SavedSimulation = bincode::deserialize_from(reader)?;
+
+    if saved.version != SAVE_VERSION {
+        return Err(format!("save version {} instead of {}", saved.version, SAVE_VERSION).into());
+    }
+
+    let mut simulation = Simulation::with_seed(0, saved.world_size, saved.seed);
+
+    simulation.rng = StdRng::seed_from_u64(saved.rng_seed);
+    simulation.components = saved.components;
+
+    simulation.weights = saved.weights;
+    simulation.speed = saved.speed;
+    simulation.set_perception_radius(saved.perception_radius);
+    simulation.boundary = saved.boundary;
+    simulation.spawn_species = saved.spawn_species;
+    simulation.set_cell_size(saved.cell_size);
+    simulation.interaction_preset = saved.interaction_preset;
+    simulation.interactions = saved.interactions;
+
+    simulation.trails = saved.trails;
+    simulation.since_trail = saved.since_trail;
+    simulation.gusts = saved.gusts;
+    simulation.next_gust = saved.next_gust;
+    simulation.day_cycle = saved.day_cycle;
+
+    simulation.obstacles = saved.obstacles;
+    simulation.walls = saved.walls;
+    simulation.timeline = saved.timeline;
+
+    Ok(simulation)
+}
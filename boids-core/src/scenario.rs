@@ -0,0 +1,249 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Boundary, FlockWeights, Gust, Obstacle, Wall};
+use crate::simulation::Simulation;
+use crate::species::InteractionPreset;
+use crate::SPECIES_COUNT;
+
+// World set up from a TOML file: obstacles, walls, where the first boids spawn and events
+// that happen at set times. Everything left out keeps what the simulation was built with.
+//
+//     world_size = [1600.0, 900.0]
+//     preset = "predator-prey"
+//
+//     [[obstacles]]
+//     center = [800.0, 450.0]
+//     radius = 80.0
+//
+//     [[walls]]
+//     start = [400.0, 0.0]
+//     end = [400.0, 600.0]
+//
+//     [[spawn]]
+//     count = 2000
+//     species = [0, 1]
+//     region = [0.0, 0.0, 400.0, 900.0]
+//
+//     [[events]]
+//     at = 20.0
+//     spawn = { count = 5, species = [2], region = [1500.0, 0.0, 1600.0, 900.0] }
+//
+// scenarios/ holds a few complete ones.
+#[derive(Clone, Default, Debug, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    // Replaces the configured world size, applied before the simulation is built
+    pub world_size: Option<[f32; 2]>,
+    // Name of the interaction preset, see InteractionPreset::from_name
+    pub preset: Option<String>,
+    pub boundary: Option<Boundary>,
+    pub obstacles: Vec<Obstacle>,
+    pub walls: Vec<Wall>,
+    // Boids at the start, the ones of the simulation stay without any
+    pub spawn: Vec<SpawnGroup>,
+    pub events: Vec<TimedEvent>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpawnGroup {
+    pub count: usize,
+    // Picked at random for every boid, the spawn species of the simulation without a list.
+    // The last species is the predator.
+    #[serde(default)]
+    pub species: Vec<usize>,
+    // Corners x, y and x, y of a rectangle, the whole world without one
+    #[serde(default)]
+    pub region: Option<[f32; 4]>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimedEvent {
+    // Seconds of simulated time after the scenario started
+    pub at: f32,
+    #[serde(flatten)]
+    pub action: ScenarioAction,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScenarioAction {
+    Spawn(SpawnGroup),
+    // Removes this many random boids
+    Despawn(usize),
+    Gust {
+        center: [f32; 2],
+        direction: [f32; 2],
+        radius: f32,
+        strength: f32,
+        // Seconds
+        duration: f32,
+    },
+    Preset(String),
+    Boundary(Boundary),
+    Weights(FlockWeights),
+    Obstacle(Obstacle),
+    Wall(Wall),
+}
+
+// Events of a scenario still to come. Saved with the simulation, so a loaded run goes on with them.
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Timeline {
+    // Simulated seconds since the scenario started
+    pub time: f32,
+    // Sorted by time, the ones before `next` already happened
+    events: Vec<TimedEvent>,
+    next: usize,
+}
+
+impl Scenario {
+    // Fails for a file that can't be read or parsed and for values no simulation can use
+    pub fn load(path: &Path) -> Result<Scenario, Box<dyn Error>> {
+        let scenario: Scenario = toml::from_str(&fs::read_to_string(path)?)?;
+        scenario.validate()?;
+
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if let Some([width, height]) = self.world_size {
+            if !(width > 0.0 && height > 0.0) {
+                return Err(format!("world size {} by {} isn't positive", width, height));
+            }
+        }
+
+        let spawns = self.spawn.iter().chain(self.events.iter().filter_map(|event| match &event.action {
+            ScenarioAction::Spawn(group) => Some(group),
+            _ => None,
+        }));
+
+        for group in spawns {
+            if let Some(id) = group.species.iter().find(|id| **id >= SPECIES_COUNT) {
+                return Err(format!("species {} doesn't exist, ids go from 0 to {}", id, SPECIES_COUNT - 1));
+            }
+
+            if let Some(region) = group.region {
+                if !(region[0] < region[2] && region[1] < region[3]) {
+                    return Err(format!("region {:?} has to go from its smaller corner to its larger one", region));
+                }
+            }
+        }
+
+        let presets = self.preset.iter().chain(self.events.iter().filter_map(|event| match &event.action {
+            ScenarioAction::Preset(name) => Some(name),
+            _ => None,
+        }));
+
+        for name in presets {
+            if InteractionPreset::from_name(name).is_none() {
+                return Err(format!("unknown interaction preset {}", name));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Sets up a simulation built with the world size of the scenario
+    pub fn apply(&self, simulation: &mut Simulation) {
+        if let Some(preset) = self.preset.as_deref().and_then(InteractionPreset::from_name) {
+            simulation.set_interaction_preset(preset);
+        }
+
+        if let Some(boundary) = self.boundary {
+            simulation.boundary = boundary;
+        }
+
+        simulation.obstacles = self.obstacles.clone();
+        simulation.walls = self.walls.clone();
+
+        if !self.spawn.is_empty() {
+            simulation.set_population(0);
+
+            for group in &self.spawn {
+                spawn_group(simulation, group);
+            }
+        }
+
+        simulation.timeline = Timeline::new(self.events.clone());
+    }
+}
+
+impl Timeline {
+    pub fn new(mut events: Vec<TimedEvent>) -> Timeline {
+        // Stable, events at the same time happen in the order of the file
+        events.sort_by(|a, b| a.at.total_cmp(&b.at));
+
+        Timeline {
+            time: 0.0,
+            events,
+            next: 0,
+        }
+    }
+
+    // Every event to come again, from the start
+    pub fn rewound(&self) -> Timeline {
+        Timeline {
+            time: 0.0,
+            events: self.events.clone(),
+            next: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next == self.events.len()
+    }
+
+    // Moves `dt` seconds ahead and returns the events that are due
+    pub fn advance(&mut self, dt: f32) -> &[TimedEvent] {
+        self.time += dt;
+
+        let start = self.next;
+
+        while self.next < self.events.len() && self.events[self.next].at <= self.time {
+            self.next += 1;
+        }
+
+        &self.events[start..self.next]
+    }
+}
+
+pub fn run_action(simulation: &mut Simulation, action: &ScenarioAction) {
+    match action {
+        ScenarioAction::Spawn(group) => spawn_group(simulation, group),
+        ScenarioAction::Despawn(count) => {
+            let remaining = simulation.components.len().saturating_sub(*count);
+            simulation.set_population(remaining);
+        }
+        ScenarioAction::Gust { center, direction, radius, strength, duration } => {
+            simulation.gusts.push(Gust {
+                center: *center,
+                direction: *direction,
+                radius: *radius,
+                strength: *strength,
+                age: 0.0,
+                duration: *duration,
+            });
+        }
+        ScenarioAction::Preset(name) => {
+            if let Some(preset) = InteractionPreset::from_name(name) {
+                simulation.set_interaction_preset(preset);
+            }
+        }
+        ScenarioAction::Boundary(boundary) => simulation.boundary = *boundary,
+        ScenarioAction::Weights(weights) => simulation.weights = *weights,
+        ScenarioAction::Obstacle(obstacle) => simulation.obstacles.push(*obstacle),
+        ScenarioAction::Wall(wall) => simulation.walls.push(*wall),
+    }
+}
+
+fn spawn_group(simulation: &mut Simulation, group: &SpawnGroup) {
+    let [width, height] = simulation.size();
+    let area = group.region.unwrap_or([0.0, 0.0, width, height]);
+
+    let species = if group.species.is_empty() { simulation.spawn_species.clone() } else { group.species.clone() };
+
+    simulation.spawn_in(group.count, area, &species);
+}
@@ -0,0 +1,513 @@
+use std::time::Instant;
+
+use tracing::{info_span, Level};
+
+use crate::data::Boundary;
+use crate::diagnostics::{check_boids, log_grid};
+use crate::events::Event;
+use crate::plugin::{Plugin, PluginSystem};
+use crate::profiler::{Profiler, Stage};
+use crate::scenario::run_action;
+use crate::script::{script_steering_system, ScriptQuery};
+use crate::simulation::Simulation;
+use crate::spatial::{SpatialBackend, SpatialIndex};
+use crate::systems::*;
+use crate::TRAIL_INTERVAL;
+
+// One step of the simulation update, run by the schedule in a fixed order
+pub trait System: Send + Sync {
+    fn name(&self) -> &'static str;
+    // Profiler stage the time of the system is added to
+    fn stage(&self) -> Stage;
+    fn run(&mut self, simulation: &mut Simulation, dt: f32);
+
+    // Systems the rest depend on can't be turned off
+    fn optional(&self) -> bool {
+        true
+    }
+}
+
+struct Entry {
+    system: Box<dyn System>,
+    enabled: bool,
+    // Set when the system runs a plugin
+    plugin: Option<Plugin>,
+}
+
+// Ordered list of the systems a simulation update runs, each can be turned off at runtime
+#[derive(Default)]
+pub struct Schedule {
+    entries: Vec<Entry>,
+}
+
+impl Schedule {
+    // The systems of the simulation in the order they depend on each other
+    pub fn standard() -> Schedule {
+        let mut schedule = Schedule::default();
+
+        schedule.add(ScenarioEvents);
+        schedule.add(SpatialIndexing);
+        schedule.add(Steering);
+        schedule.add(ScriptedSteering);
+        schedule.add(Leaders);
+        schedule.add(Behaviors);
+        schedule.add(Gusts);
+        schedule.add(Integration);
+        schedule.add(Obstacles);
+        schedule.add(Boundaries);
+        schedule.add(FlowFieldAveraging);
+        schedule.add(Trails);
+
+        schedule
+    }
+
+    // Runs after the systems added before it
+    pub fn add<S: System + 'static>(&mut self, system: S) {
+        self.entries.push(Entry { system: Box::new(system), enabled: true, plugin: None });
+    }
+
+    // Plugins steer after the built in steering and the script, in the order they are added,
+    // and before pinned leaders undo the steering
+    pub fn add_plugin(&mut self, plugin: Plugin) {
+        let index = self.entries.iter()
+            .position(|entry| entry.system.name() == Leaders.name())
+            .unwrap_or(self.entries.len());
+
+        let entry = Entry {
+            system: Box::new(PluginSystem::new(plugin.clone())),
+            enabled: true,
+            plugin: Some(plugin),
+        };
+
+        self.entries.insert(index, entry);
+    }
+
+    pub fn plugins(&self) -> Vec<Plugin> {
+        self.entries.iter().filter_map(|entry| entry.plugin.clone()).collect()
+    }
+
+    // At the debug level every boid is checked after every system, which slows the update down
+    pub fn run(&mut self, simulation: &mut Simulation, dt: f32, profiler: &mut Profiler) {
+        let checked = tracing::enabled!(Level::DEBUG);
+
+        for entry in self.entries.iter_mut().filter(|entry| entry.enabled) {
+            let name = entry.system.name();
+            let _span = info_span!("system", name).entered();
+            let t = Instant::now();
+
+            entry.system.run(simulation, dt);
+
+            profiler.record(entry.system.stage(), t);
+
+            if checked {
+                check_boids(simulation, name);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn name(&self, index: usize) -> &'static str {
+        self.entries[index].system.name()
+    }
+
+    pub fn is_enabled(&self, index: usize) -> bool {
+        self.entries[index].enabled
+    }
+
+    // Returns whether the system is enabled afterwards, required ones stay on
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        let entry = &mut self.entries[index];
+        entry.enabled = enabled || !entry.system.optional();
+
+        entry.enabled
+    }
+
+    // Takes over which systems are enabled from a schedule with the same systems
+    pub fn copy_enabled(&mut self, other: &Schedule) {
+        for (entry, other) in self.entries.iter_mut().zip(&other.entries) {
+            entry.enabled = other.enabled;
+        }
+    }
+}
+
+// Runs the scenario events that are due, before the boids they spawn are indexed
+struct ScenarioEvents;
+
+impl System for ScenarioEvents {
+    fn name(&self) -> &'static str {
+        "Scenario"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Behavior
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, dt: f32) {
+        if simulation.timeline.is_finished() {
+            return;
+        }
+
+        let mut timeline = std::mem::take(&mut simulation.timeline);
+
+        for event in timeline.advance(dt) {
+            run_action(simulation, &event.action);
+        }
+
+        simulation.timeline = timeline;
+    }
+}
+
+// Rebuilds the spatial index and the neighbor lists when they are out of date,
+// and keeps the directions from before steering
+struct SpatialIndexing;
+
+impl System for SpatialIndexing {
+    fn name(&self) -> &'static str {
+        "Spatial index"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::SpatialIndex
+    }
+
+    fn optional(&self) -> bool {
+        false
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, _dt: f32) {
+        let use_neighbor_list = simulation.uses_neighbor_list();
+
+        // While the cached lists hold, boids keep their order and no index is needed
+        let rebuild = !use_neighbor_list || simulation.neighbor_list.needs_rebuild(&simulation.components.positions);
+
+        if rebuild {
+            simulation.build_spatial_index();
+            log_grid(&simulation.grid);
+        }
+
+        if rebuild && use_neighbor_list {
+            let index: &dyn SpatialIndex = match simulation.spatial_backend {
+                SpatialBackend::Grid => &simulation.grid,
+                SpatialBackend::Quadtree => &simulation.quadtree,
+                SpatialBackend::KdTree => &simulation.kdtree,
+            };
+
+            simulation.neighbor_list.build(index, &simulation.components.positions, simulation.perception_radius, simulation.wrap());
+        }
+
+        simulation.components.snapshot_directions();
+    }
+}
+
+// Flocking with whichever neighbor search is in use
+struct Steering;
+
+impl System for Steering {
+    fn name(&self) -> &'static str {
+        "Steering"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Steering
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, _dt: f32) {
+        let use_neighbor_list = simulation.uses_neighbor_list();
+        let use_cell_pairs = simulation.uses_cell_pairs();
+
+        let wrap = simulation.wrap();
+        let index: &dyn SpatialIndex = match simulation.spatial_backend {
+            SpatialBackend::Grid => &simulation.grid,
+            SpatialBackend::Quadtree => &simulation.quadtree,
+            SpatialBackend::KdTree => &simulation.kdtree,
+        };
+
+        let (positions, previous_directions, directions, species, behaviors, crowding, forces) =
+            simulation.components.query::<SteeringQuery>();
+
+        if use_neighbor_list {
+            boid_neighbor_list_system(
+                positions,
+                previous_directions,
+                directions,
+                species,
+                behaviors,
+                crowding,
+                forces,
+                &simulation.interactions,
+                &simulation.weights,
+                simulation.perception_radius,
+                wrap,
+                &simulation.neighbor_list,
+                &mut simulation.steering_scratch
+            );
+        }
+        else if use_cell_pairs {
+            boid_cell_pair_system(
+                positions,
+                previous_directions,
+                directions,
+                species,
+                behaviors,
+                crowding,
+                forces,
+                &simulation.interactions,
+                &simulation.weights,
+                simulation.perception_radius,
+                wrap,
+                &simulation.grid,
+                &mut simulation.steering_scratch
+            );
+        }
+        else {
+            boid_system(
+                positions,
+                previous_directions,
+                directions,
+                species,
+                behaviors,
+                crowding,
+                forces,
+                &simulation.interactions,
+                &simulation.weights,
+                simulation.perception_radius,
+                wrap,
+                index,
+                simulation.neighborhood,
+                &mut simulation.steering_scratch
+            );
+        }
+    }
+}
+
+// Only runs while a steering script is loaded
+struct ScriptedSteering;
+
+impl System for ScriptedSteering {
+    fn name(&self) -> &'static str {
+        "Script"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Script
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, _dt: f32) {
+        let wrap = simulation.wrap();
+        let script = match &mut simulation.script {
+            Some(script) => script,
+            None => return,
+        };
+
+        let (positions, directions, species, behaviors) = simulation.components.query::<ScriptQuery>();
+        script_steering_system(
+            script,
+            positions,
+            directions,
+            species,
+            behaviors,
+            &simulation.grid,
+            simulation.perception_radius,
+            wrap
+        );
+    }
+}
+
+// Pinned leaders undo all steering before them
+struct Leaders;
+
+impl System for Leaders {
+    fn name(&self) -> &'static str {
+        "Leaders"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Steering
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, _dt: f32) {
+        let (leaders, previous_directions, directions) = simulation.components.query::<LeaderQuery>();
+        leader_system(leaders, previous_directions, directions);
+    }
+}
+
+// Advances the day and moves boids between behavior states
+struct Behaviors;
+
+impl System for Behaviors {
+    fn name(&self) -> &'static str {
+        "Behavior"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Behavior
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, dt: f32) {
+        simulation.day_cycle.update(dt);
+
+        let (behaviors, positions) = simulation.components.query::<BehaviorQuery>();
+        let died = behavior_system(
+            dt,
+            behaviors,
+            positions,
+            simulation.world_size,
+            simulation.day_cycle.roosting(),
+            &mut simulation.rng
+        );
+
+        let handles = &simulation.components.handles;
+        simulation.events.extend(died.iter().map(|index| Event::BoidEaten(handles[*index])));
+    }
+}
+
+struct Gusts;
+
+impl System for Gusts {
+    fn name(&self) -> &'static str {
+        "Gusts"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Gusts
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, dt: f32) {
+        gust_spawn_system(
+            dt,
+            &mut simulation.gusts,
+            &mut simulation.next_gust,
+            simulation.world_size,
+            &mut simulation.rng
+        );
+
+        let (positions, directions) = simulation.components.query::<GustQuery>();
+        gust_system(&simulation.gusts, positions, directions);
+    }
+}
+
+// Moves boids along their directions and beats their wings
+struct Integration;
+
+impl System for Integration {
+    fn name(&self) -> &'static str {
+        "Integration"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Integration
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, dt: f32) {
+        let (positions, directions, behaviors) = simulation.components.query::<ForwardQuery>();
+        forward_system(dt, simulation.speed, positions, directions, behaviors);
+
+        let (flaps, behaviors) = simulation.components.query::<FlapQuery>();
+        flap_system(dt, flaps, behaviors);
+    }
+}
+
+// Keeps boids out of obstacles and walls
+struct Obstacles;
+
+impl System for Obstacles {
+    fn name(&self) -> &'static str {
+        "Obstacles"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Integration
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, _dt: f32) {
+        let (positions, directions) = simulation.components.query::<ObstacleQuery>();
+        obstacle_system(&simulation.obstacles, &simulation.walls, positions, directions);
+    }
+}
+
+// Wraps or bounces boids at the edges of the world
+struct Boundaries;
+
+impl System for Boundaries {
+    fn name(&self) -> &'static str {
+        "Boundary"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Wrap
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, _dt: f32) {
+        let hit = match simulation.boundary {
+            Boundary::Wrap => {
+                wrap_screen_system(simulation.components.query::<WrapScreenQuery>(), simulation.world_size)
+            }
+            Boundary::Bounce => {
+                let (positions, directions) = simulation.components.query::<BounceQuery>();
+                bounce_system(positions, directions, simulation.world_size)
+            }
+        };
+
+        let handles = &simulation.components.handles;
+        simulation.events.extend(hit.iter().map(|index| Event::BoundaryHit(handles[*index])));
+    }
+}
+
+// Only averages while the flow field is shown
+struct FlowFieldAveraging;
+
+impl System for FlowFieldAveraging {
+    fn name(&self) -> &'static str {
+        "Flow field"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::FlowField
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, _dt: f32) {
+        if !simulation.flow_field.enabled {
+            return;
+        }
+
+        let (directions, behaviors) = simulation.components.query::<FlowFieldQuery>();
+        flow_field_system(&mut simulation.flow_field, &simulation.grid, simulation.speed, directions, behaviors);
+    }
+}
+
+// Samples a trail point every TRAIL_INTERVAL while trails are on
+struct Trails;
+
+impl System for Trails {
+    fn name(&self) -> &'static str {
+        "Trails"
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Trails
+    }
+
+    fn run(&mut self, simulation: &mut Simulation, dt: f32) {
+        if !simulation.trails {
+            return;
+        }
+
+        simulation.since_trail += dt;
+
+        if simulation.since_trail < TRAIL_INTERVAL {
+            return;
+        }
+
+        simulation.since_trail = 0.0;
+
+        let (trails, positions, behaviors) = simulation.components.query::<TrailQuery>();
+        trail_system(trails, positions, behaviors);
+    }
+}
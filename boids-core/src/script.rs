@@ -0,0 +1,239 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use rayon::prelude::*;
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST, FLOAT, INT};
+use vecmath::{Vector2, vec2_add, vec2_normalized, vec2_scale, vec2_square_len, vec2_sub};
+
+use crate::components::column;
+use crate::data::{Behavior, BehaviorState, Forward, Position, Species};
+use crate::grid::Grid;
+use crate::query::{Read, Write};
+use crate::spatial::{SpatialIndex, nearest_image};
+use crate::{SCRIPT_MAX_OPERATIONS, SCRIPT_POLL_INTERVAL};
+
+// Extra steering rule read from a Rhai script. The script defines
+//
+//     fn steer(boid, neighbors) { [x, y] }
+//
+// `boid` has the fields x, y, dx, dy and species, `neighbors` has count, the average offset
+// to the neighbors center_x and center_y, their average heading heading_x and heading_y
+// and the distance to the nearest one. The returned force is added to the boid's heading.
+// The file is loaded again whenever it changes. A script that doesn't compile keeps the
+// last one running, one that fails while running is skipped until it loads again.
+pub struct SteeringScript {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    // Seconds since the file was last checked
+    since_check: f32,
+    engine: Engine,
+    ast: Option<AST>,
+    // First line of the last compile or runtime error, kept until the script loads again
+    pub error: Option<String>,
+}
+
+impl SteeringScript {
+    // A script that fails to load is kept with its error and tried again once it changes
+    pub fn load(path: &Path) -> SteeringScript {
+        let mut engine = Engine::new();
+        // A script stuck in a loop fails instead of hanging the simulation
+        engine.set_max_operations(SCRIPT_MAX_OPERATIONS);
+
+        let mut script = SteeringScript {
+            path: path.to_path_buf(),
+            modified: modified_time(path),
+            since_check: 0.0,
+            engine,
+            ast: None,
+            error: None,
+        };
+
+        script.compile();
+
+        script
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Returns whether the file changed and was loaded again
+    pub fn reload_if_changed(&mut self, delta_time: f32) -> bool {
+        self.since_check += delta_time;
+
+        if self.since_check < SCRIPT_POLL_INTERVAL {
+            return false;
+        }
+
+        self.since_check = 0.0;
+
+        let modified = modified_time(&self.path);
+
+        if modified == self.modified {
+            return false;
+        }
+
+        self.modified = modified;
+        self.compile();
+
+        true
+    }
+
+    fn compile(&mut self) {
+        let compiled = fs::read_to_string(&self.path)
+            .map_err(|error| error.to_string())
+            .and_then(|source| self.engine.compile(source).map_err(|error| error.to_string()));
+
+        match compiled {
+            Ok(ast) => {
+                self.ast = Some(ast);
+                self.error = None;
+
+                println!("Loaded steering script {}", self.path.display());
+            }
+            Err(error) => self.fail(error),
+        }
+    }
+
+    fn fail(&mut self, error: String) {
+        println!("Steering script {} failed: {}", self.path.display(), error);
+
+        self.error = Some(error.lines().next().unwrap_or_default().to_string());
+    }
+
+    fn force(&self, ast: &AST, boid: Map, neighbors: Map) -> Result<Vector2<f32>, String> {
+        let force: Array = self.engine
+            .call_fn(&mut Scope::new(), ast, "steer", (boid, neighbors))
+            .map_err(|error| error.to_string())?;
+
+        match force.as_slice() {
+            [x, y] => Ok([to_float(x)? as f32, to_float(y)? as f32]),
+            _ => Err(format!("steer returned {} values instead of [x, y]", force.len())),
+        }
+    }
+}
+
+pub type ScriptQuery = (
+    Read<column::Positions>,
+    Write<column::Directions>,
+    Read<column::Species>,
+    Read<column::Behaviors>
+);
+
+// Asks the script for a force on every living boid and turns the boid by it.
+// Neighbors are the boids within `perception_radius` in the grid of the last rebuild,
+// across the edges of a world that wraps around at `wrap`.
+#[allow(clippy::too_many_arguments)]
+pub fn script_steering_system(
+    script: &mut SteeringScript,
+    positions: &[Position],
+    directions: &mut [Forward],
+    species: &[Species],
+    behaviors: &[Behavior],
+    grid: &Grid,
+    perception_radius: f32,
+    wrap: Option<Vector2<f32>>
+) {
+    let ast = match &script.ast {
+        Some(ast) => ast,
+        None => return,
+    };
+
+    let steer = |neighbors: &mut Vec<usize>, index: usize| -> Result<Vector2<f32>, String> {
+        if behaviors[index].state == BehaviorState::Dead {
+            return Ok([0.0, 0.0]);
+        }
+
+        let position = positions[index].value;
+        let direction = directions[index].direction;
+
+        neighbors.clear();
+        grid.query_radius_wrapped(positions, position, perception_radius, wrap, neighbors);
+        neighbors.retain(|other| *other != index && behaviors[*other].state != BehaviorState::Dead);
+
+        let mut boid = Map::new();
+        boid.insert("x".into(), float(position[0]));
+        boid.insert("y".into(), float(position[1]));
+        boid.insert("dx".into(), float(direction[0]));
+        boid.insert("dy".into(), float(direction[1]));
+        boid.insert("species".into(), Dynamic::from_int(species[index].id as INT));
+
+        script.force(ast, boid, summary(index, neighbors, positions, directions, perception_radius, wrap))
+    };
+
+    let forces: Result<Vec<Vector2<f32>>, String> = (0..positions.len())
+        .into_par_iter()
+        .map_init(Vec::new, steer)
+        .collect();
+
+    match forces {
+        Ok(forces) => {
+            for (forward, force) in directions.iter_mut().zip(forces) {
+                let heading = vec2_add(forward.direction, force);
+
+                if vec2_square_len(heading) > 0.0 {
+                    forward.direction = vec2_normalized(heading);
+                }
+            }
+        }
+        Err(error) => {
+            script.fail(error);
+            script.ast = None;
+        }
+    }
+}
+
+// What the script is told about the neighbors of a boid
+fn summary(
+    index: usize,
+    neighbors: &[usize],
+    positions: &[Position],
+    directions: &[Forward],
+    perception_radius: f32,
+    wrap: Option<Vector2<f32>>
+) -> Map {
+    let position = positions[index].value;
+    let mut center = [0.0, 0.0];
+    let mut heading = [0.0, 0.0];
+    let mut nearest = perception_radius;
+
+    for other in neighbors {
+        let offset = vec2_sub(nearest_image(position, positions[*other].value, wrap), position);
+
+        center = vec2_add(center, offset);
+        heading = vec2_add(heading, directions[*other].direction);
+        nearest = nearest.min(vec2_square_len(offset).sqrt());
+    }
+
+    if !neighbors.is_empty() {
+        let scale = 1.0 / neighbors.len() as f32;
+        center = vec2_scale(center, scale);
+        heading = vec2_scale(heading, scale);
+    }
+
+    let mut summary = Map::new();
+    summary.insert("count".into(), Dynamic::from_int(neighbors.len() as INT));
+    summary.insert("center_x".into(), float(center[0]));
+    summary.insert("center_y".into(), float(center[1]));
+    summary.insert("heading_x".into(), float(heading[0]));
+    summary.insert("heading_y".into(), float(heading[1]));
+    summary.insert("nearest".into(), float(nearest));
+
+    summary
+}
+
+fn float(value: f32) -> Dynamic {
+    Dynamic::from_float(value as FLOAT)
+}
+
+// Whole numbers are fine too, `[0, 1]` shouldn't need to be written as `[0.0, 1.0]`
+fn to_float(value: &Dynamic) -> Result<FLOAT, String> {
+    value.as_float()
+        .or_else(|_| value.as_int().map(|value| value as FLOAT))
+        .map_err(|kind| format!("steer returned a {} instead of a number", kind))
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
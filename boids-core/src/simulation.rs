@@ -0,0 +1,384 @@
+use rand::rngs::StdRng;
+use tracing::info_span;
+use vecmath::{vec2_square_len, vec2_sub};
+
+use crate::builder::SimulationBuilder;
+use crate::day_cycle::DayCycle;
+use crate::components::{BoidHandle, Components};
+use crate::diagnostics::{vec_bytes, MemoryDiagnostics};
+use crate::events::{Event, Events, Parameter};
+use crate::data::*;
+use crate::systems::*;
+use crate::species::*;
+use crate::grid::Grid;
+use crate::kdtree::KdTree;
+use crate::neighbor_list::NeighborList;
+use crate::profiler::Profiler;
+use crate::schedule::Schedule;
+use crate::scenario::Timeline;
+use crate::script::SteeringScript;
+use crate::quadtree::Quadtree;
+use crate::spatial::{Neighborhood, SpatialBackend, nearest_image};
+use crate::{NEIGHBOR_SKIN, SPECIES_COUNT, TOPOLOGICAL_NEIGHBORS};
+
+// Everything the CPU simulation steps, kept apart from the window and rendering
+// so a frame can be simulated on the thread pool while the last one is drawn.
+pub struct Simulation {
+    pub components: Components,
+    pub grid: Grid,
+    pub quadtree: Quadtree,
+    pub kdtree: KdTree,
+    // Spatial index used for neighbor queries, the grid is always built for sorting
+    pub spatial_backend: SpatialBackend,
+    pub neighborhood: Neighborhood,
+    // Steers metric grid neighborhoods one pair of cells at a time
+    pub cell_pairs: bool,
+    // Metric neighborhoods cached over several frames while enabled
+    pub neighbor_list: NeighborList,
+    pub steering_scratch: SteeringScratch,
+
+    pub interactions: InteractionMatrix,
+    pub interaction_preset: InteractionPreset,
+    pub weights: FlockWeights,
+    // Flying speed of a flocking boid in world units per second
+    pub speed: f32,
+    // Boids steer by neighbors closer than this, set through `set_perception_radius`
+    pub perception_radius: f32,
+    pub boundary: Boundary,
+    // Species new boids are picked from
+    pub spawn_species: Vec<usize>,
+
+    // Boids leave trails while enabled
+    pub trails: bool,
+    // Seconds since the last trail sample
+    pub since_trail: f32,
+
+    pub gusts: Vec<Gust>,
+    // Seconds until the next gust spawns
+    pub next_gust: f32,
+
+    // Set up by a scenario, see `scenario::Scenario`
+    pub obstacles: Vec<Obstacle>,
+    pub walls: Vec<Wall>,
+    pub timeline: Timeline,
+
+    pub day_cycle: DayCycle,
+
+    // Only averaged while enabled
+    pub flow_field: FlowField,
+
+    // Unlike ThreadRng it can be sent to the thread pool
+    pub rng: StdRng,
+    // Seed of `rng` at the start, the same seed and settings play out the same run
+    pub seed: u64,
+
+    pub world_size: [f32; 2],
+
+    // Systems stepping the simulation, in the order they run
+    pub schedule: Schedule,
+    // Sent by the systems and the app, read during the next frame
+    pub events: Events,
+    // Extra steering rule, see `script::SteeringScript`
+    pub script: Option<SteeringScript>,
+}
+
+impl Simulation {
+    pub fn new(agent_count: usize, world_size: [f32; 2]) -> Simulation {
+        SimulationBuilder::new(agent_count, world_size).build()
+    }
+
+    // Simulations with the same seed start out with the same boids and make the same random choices
+    pub fn with_seed(agent_count: usize, world_size: [f32; 2], seed: u64) -> Simulation {
+        SimulationBuilder::new(agent_count, world_size).seed(seed).build()
+    }
+
+    // Runs the enabled systems of the schedule in order
+    pub fn update(&mut self, dt: f32, profiler: &mut Profiler) {
+        let _span = info_span!("update").entered();
+
+        let mut schedule = std::mem::take(&mut self.schedule);
+        schedule.run(self, dt, profiler);
+        self.schedule = schedule;
+    }
+
+    // Grid is always built, boid data is sorted by its cells whenever the grid asks for it
+    pub(crate) fn build_spatial_index(&mut self) {
+        // The boundary can be changed at any time, the grid only rebuilds its adjacency when it did
+        self.grid.set_wrap(self.boundary == Boundary::Wrap);
+
+        let (positions, behaviors) = self.components.query::<SpatialIndexQuery>();
+        spatial_index_system(&mut self.grid, positions, behaviors);
+
+        if self.grid.sort_due() {
+            self.components.sort_by_cell(&mut self.grid);
+        }
+
+        let (positions, behaviors) = self.components.query::<SpatialIndexQuery>();
+
+        match self.spatial_backend {
+            SpatialBackend::Grid => {}
+            SpatialBackend::Quadtree => spatial_index_system(&mut self.quadtree, positions, behaviors),
+            SpatialBackend::KdTree => spatial_index_system(&mut self.kdtree, positions, behaviors),
+        }
+    }
+
+    // Neighbor lists only cache metric neighborhoods
+    pub fn uses_neighbor_list(&self) -> bool {
+        self.neighbor_list.enabled && self.neighborhood == Neighborhood::Metric
+    }
+
+    // Cell pairs only cover the grid's metric neighborhoods,
+    // and only when a neighborhood never reaches past the adjacent cells
+    pub fn uses_cell_pairs(&self) -> bool {
+        self.cell_pairs
+            && self.spatial_backend == SpatialBackend::Grid
+            && self.neighborhood == Neighborhood::Metric
+            && self.grid.cell_size >= self.perception_radius
+    }
+
+    // Trails are cleared when turned off, so old ones don't show up when turned on again
+    pub fn toggle_trails(&mut self) {
+        self.trails = !self.trails;
+
+        if !self.trails {
+            for trail in self.components.trails.iter_mut() {
+                *trail = Trail::default();
+            }
+        }
+    }
+
+    pub fn set_population(&mut self, count: usize) {
+        let current = self.components.len();
+
+        if count > current {
+            let [width, height] = self.size();
+            let species = self.spawn_species.clone();
+
+            self.spawn_in(count - current, [0.0, 0.0, width, height], &species);
+        }
+        else {
+            self.components.despawn_random(current - count, &mut self.rng);
+            self.neighbor_list.invalidate();
+        }
+    }
+
+    // Adds boids of `species` in the rectangle from the corner x, y to the corner x, y of `area`
+    pub fn spawn_in(&mut self, count: usize, area: [f32; 4], species: &[usize]) {
+        let current = self.components.len();
+
+        self.components.spawn_in(count, area, species, &mut self.rng);
+
+        for index in current..current + count {
+            let handle = self.components.handles[index];
+            self.events.send(Event::BoidSpawned(handle));
+
+            if self.is_predator(self.components.species[index].id) {
+                self.events.send(Event::PredatorSpawned(handle));
+            }
+        }
+
+        self.neighbor_list.invalidate();
+    }
+
+    // Living boids inside the rectangle between two corners
+    pub fn boids_in_area(&self, a: [f32; 2], b: [f32; 2]) -> Vec<BoidHandle> {
+        let components = &self.components;
+        let min = [a[0].min(b[0]), a[1].min(b[1])];
+        let max = [a[0].max(b[0]), a[1].max(b[1])];
+
+        (0..components.len())
+            .filter(|index| components.behaviors[*index].state != BehaviorState::Dead)
+            .filter(|index| {
+                let [x, y] = components.positions[*index].value;
+                x >= min[0] && x <= max[0] && y >= min[1] && y <= max[1]
+            })
+            .map(|index| components.handles[index])
+            .collect()
+    }
+
+    // Group operations on selected boids, handles of boids that are gone are skipped
+
+    pub fn despawn_group(&mut self, group: &[BoidHandle]) {
+        for handle in group {
+            self.components.despawn(*handle);
+        }
+
+        self.neighbor_list.invalidate();
+    }
+
+    pub fn set_group_species(&mut self, group: &[BoidHandle], species: usize) {
+        for handle in group {
+            if let Some(index) = self.components.index(*handle) {
+                self.change_species(*handle, index, species);
+            }
+        }
+    }
+
+    // Moves every boid of the group on to the next species
+    pub fn cycle_group_species(&mut self, group: &[BoidHandle]) {
+        for handle in group {
+            if let Some(index) = self.components.index(*handle) {
+                let species = (self.components.species[index].id + 1) % SPECIES_COUNT;
+                self.change_species(*handle, index, species);
+            }
+        }
+    }
+
+    fn change_species(&mut self, handle: BoidHandle, index: usize, species: usize) {
+        let was_predator = self.is_predator(self.components.species[index].id);
+        self.components.species[index].id = species;
+
+        if !was_predator && self.is_predator(species) {
+            self.events.send(Event::PredatorSpawned(handle));
+        }
+    }
+
+    // The last species hunts the others in the predator presets
+    pub fn is_predator(&self, species: usize) -> bool {
+        species == self.interactions.size() - 1
+    }
+
+    // Pins the whole group as leaders, or unpins it if it is pinned already.
+    // Returns whether the group is pinned now.
+    pub fn toggle_group_leaders(&mut self, group: &[BoidHandle]) -> bool {
+        let indices: Vec<usize> = group.iter().filter_map(|handle| self.components.index(*handle)).collect();
+        let pinned = !indices.iter().all(|index| self.components.leaders[*index].pinned);
+
+        for index in indices {
+            self.components.leaders[index].pinned = pinned;
+        }
+
+        pinned
+    }
+
+    pub fn record_memory(&self, memory: &mut MemoryDiagnostics) {
+        memory.record("Components", self.components.allocated_bytes());
+        memory.record("Grid", self.grid.allocated_bytes());
+        memory.record("Quadtree", self.quadtree.allocated_bytes());
+        memory.record("Kd-tree", self.kdtree.allocated_bytes());
+        memory.record("Neighbor lists", self.neighbor_list.allocated_bytes());
+        memory.record("Steering scratch", self.steering_scratch.allocated_bytes());
+        memory.record("Gusts", vec_bytes(&self.gusts));
+        memory.record("Flow field", vec_bytes(&self.flow_field.velocities) + vec_bytes(&self.flow_field.counts));
+    }
+
+    // Living boids the boid at `index` steers by with the current neighborhood and positions.
+    // Found by checking every boid, meant for inspecting a single one.
+    pub fn neighbors_of(&self, index: usize, neighbors: &mut Vec<usize>) {
+        let components = &self.components;
+        let position = components.positions[index].value;
+        let wrap = self.wrap();
+        let distance = |other: usize| {
+            vec2_square_len(vec2_sub(nearest_image(position, components.positions[other].value, wrap), position))
+        };
+
+        neighbors.clear();
+        neighbors.extend((0..components.len()).filter(|other| {
+            *other != index && components.behaviors[*other].state != BehaviorState::Dead
+        }));
+
+        match self.neighborhood {
+            Neighborhood::Metric => {
+                neighbors.retain(|other| distance(*other) <= self.perception_radius * self.perception_radius);
+            }
+            Neighborhood::Topological => {
+                neighbors.sort_by(|a, b| distance(*a).total_cmp(&distance(*b)));
+                neighbors.truncate(TOPOLOGICAL_NEIGHBORS);
+            }
+        }
+    }
+
+    // Living boid closest to `position` within `radius`, found through the grid.
+    // While cached neighbor lists are in use the grid lags behind the positions by up to
+    // half the skin, and the population can change between updates, so cells are searched
+    // a bit wider and indices are checked.
+    pub fn pick(&self, position: [f32; 2], radius: f32) -> Option<usize> {
+        let components = &self.components;
+        let mut nearest = None;
+        let mut nearest_distance = radius * radius;
+
+        self.grid.for_each_query_cell(position, radius + NEIGHBOR_SKIN / 2.0, |cell| {
+            for &index in self.grid.cell(cell) {
+                if index >= components.len() || components.behaviors[index].state == BehaviorState::Dead {
+                    continue;
+                }
+
+                let distance = vec2_square_len(vec2_sub(components.positions[index].value, position));
+
+                if distance <= nearest_distance {
+                    nearest = Some(index);
+                    nearest_distance = distance;
+                }
+            }
+        });
+
+        nearest
+    }
+
+    pub fn set_interaction_preset(&mut self, preset: InteractionPreset) {
+        self.interaction_preset = preset;
+        self.interactions = preset.matrix(self.interactions.size());
+        self.events.send(Event::ParameterChanged(Parameter::InteractionPreset));
+    }
+
+    // Starts a simulation with the settings of this one but new boids from `seed`
+    pub fn restarted(&self, seed: u64) -> Simulation {
+        let mut simulation = SimulationBuilder::new(self.components.len(), self.world_size)
+            .seed(seed)
+            .preset(self.interaction_preset)
+            .boundary(self.boundary)
+            .species(&self.spawn_species)
+            .build();
+
+        simulation.spatial_backend = self.spatial_backend;
+        simulation.neighborhood = self.neighborhood;
+        simulation.cell_pairs = self.cell_pairs;
+        simulation.neighbor_list.enabled = self.neighbor_list.enabled;
+        simulation.weights = self.weights;
+        simulation.speed = self.speed;
+        simulation.set_perception_radius(self.perception_radius);
+        simulation.set_cell_size(self.grid.cell_size);
+        simulation.trails = self.trails;
+        simulation.day_cycle.enabled = self.day_cycle.enabled;
+        simulation.day_cycle.roosting = self.day_cycle.roosting;
+        // New boids spawn all over the world, the scenario events start over
+        simulation.obstacles = self.obstacles.clone();
+        simulation.walls = self.walls.clone();
+        simulation.timeline = self.timeline.rewound();
+        for plugin in self.schedule.plugins() {
+            simulation.schedule.add_plugin(plugin);
+        }
+
+        simulation.schedule.copy_enabled(&self.schedule);
+        simulation.script = self.script.as_ref().map(|script| SteeringScript::load(script.path()));
+
+        simulation
+    }
+
+    // Other neighbors are found from the next update on
+    pub fn set_perception_radius(&mut self, radius: f32) {
+        self.perception_radius = radius;
+        self.neighbor_list.invalidate();
+        self.events.send(Event::ParameterChanged(Parameter::PerceptionRadius));
+    }
+
+    // Cells smaller than the perception radius work, but cell pairs are skipped then
+    pub fn set_cell_size(&mut self, cell_size: f32) {
+        self.grid.cell_size = cell_size;
+        self.grid.resize(self.world_size[0], self.world_size[1]);
+        self.neighbor_list.invalidate();
+        self.events.send(Event::ParameterChanged(Parameter::CellSize));
+    }
+
+    pub fn size(&self) -> [f32; 2] {
+        self.world_size
+    }
+
+    // Size of the world while it wraps around, neighborhoods then reach across its edges
+    pub fn wrap(&self) -> Option<[f32; 2]> {
+        match self.boundary {
+            Boundary::Wrap => Some(self.world_size),
+            Boundary::Bounce => None,
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use vecmath::{Vector2, vec2_square_len, vec2_sub};
+
+use crate::data::Position;
+
+// Neighbor lookup shared by all spatial structures, so they can be swapped
+// without touching the steering code. Indices only store agent indices,
+// positions are always passed in and must be the ones the index was built from.
+pub trait SpatialIndex: Sync {
+    // Rebuilds the index, agents for which `include` returns false are left out.
+    fn insert_all(&mut self, positions: &[Position], include: &dyn Fn(usize) -> bool);
+
+    // Appends every indexed agent within `radius` of `center` to `out`.
+    fn query_radius(&self, positions: &[Position], center: Vector2<f32>, radius: f32, out: &mut Vec<usize>);
+
+    // Replaces `out` with the `k` indexed agents closest to `center`, nearest first.
+    fn query_knn(&self, positions: &[Position], center: Vector2<f32>, k: usize, out: &mut Vec<usize>);
+
+    // Like `query_radius`, but in a world that wraps around at `wrap` agents across the edges are found as well.
+    fn query_radius_wrapped(
+        &self,
+        positions: &[Position],
+        center: Vector2<f32>,
+        radius: f32,
+        wrap: Option<Vector2<f32>>,
+        out: &mut Vec<usize>
+    ) {
+        let start = out.len();
+        let mut images = 0;
+
+        for_each_image(center, radius, wrap, |image| {
+            self.query_radius(positions, image, radius, out);
+            images += 1;
+        });
+
+        // Only a radius of half the world or more reaches an agent from two sides
+        if images > 1 && wrap.is_some_and(|size| radius * 2.0 >= size[0].min(size[1])) {
+            let mut found = out.split_off(start);
+            found.sort_unstable();
+            found.dedup();
+            out.extend(found);
+        }
+    }
+
+    // Like `query_knn`, but with distances across the edges of a world that wraps around at `wrap`.
+    // `found` holds the agents near each copy of the center before they are merged.
+    fn query_knn_wrapped(
+        &self,
+        positions: &[Position],
+        center: Vector2<f32>,
+        k: usize,
+        wrap: Option<Vector2<f32>>,
+        out: &mut Vec<usize>,
+        found: &mut Vec<usize>
+    ) {
+        if wrap.is_none() {
+            self.query_knn(positions, center, k, out);
+            return;
+        }
+
+        out.clear();
+
+        for_each_image(center, f32::INFINITY, wrap, |image| {
+            self.query_knn(positions, image, k, found);
+
+            for agent in found.iter() {
+                if !out.contains(agent) {
+                    knn_insert(positions, center, wrap, k, out, *agent);
+                }
+            }
+        });
+    }
+}
+
+// Copy of `to` closest to `from`. In a world that wraps around at `wrap` it can lie
+// past the edges, offsets and distances from `from` then go across them.
+pub fn nearest_image(from: Vector2<f32>, to: Vector2<f32>, wrap: Option<Vector2<f32>>) -> Vector2<f32> {
+    let mut image = to;
+
+    if let Some(size) = wrap {
+        for axis in 0..2 {
+            let offset = to[axis] - from[axis];
+
+            if offset > size[axis] * 0.5 {
+                image[axis] -= size[axis];
+            }
+            else if offset < -size[axis] * 0.5 {
+                image[axis] += size[axis];
+            }
+        }
+    }
+
+    image
+}
+
+// Calls `f` with `center` and its copies moved across the edges it's within `reach` of,
+// agents near a copy are near the center in a world that wraps around at `wrap`.
+// Each side only needs the copy in the direction of the farther edge.
+fn for_each_image<F>(center: Vector2<f32>, reach: f32, wrap: Option<Vector2<f32>>, mut f: F)
+where
+    F: FnMut(Vector2<f32>)
+{
+    f(center);
+
+    let size = match wrap {
+        Some(size) => size,
+        None => return,
+    };
+
+    let shift = |axis: usize| {
+        if center[axis] < size[axis] * 0.5 {
+            if center[axis] < reach { size[axis] } else { 0.0 }
+        }
+        else if size[axis] - center[axis] < reach {
+            -size[axis]
+        }
+        else {
+            0.0
+        }
+    };
+
+    let (dx, dy) = (shift(0), shift(1));
+
+    if dx != 0.0 {
+        f([center[0] + dx, center[1]]);
+    }
+    if dy != 0.0 {
+        f([center[0], center[1] + dy]);
+    }
+    if dx != 0.0 && dy != 0.0 {
+        f([center[0] + dx, center[1] + dy]);
+    }
+}
+
+fn distance_squared(positions: &[Position], agent: usize, center: Vector2<f32>, wrap: Option<Vector2<f32>>) -> f32 {
+    vec2_square_len(vec2_sub(nearest_image(center, positions[agent].value, wrap), center))
+}
+
+// Squared distance of the farthest of `k` nearest candidates, or infinity until there are `k` of them
+pub fn knn_worst(positions: &[Position], center: Vector2<f32>, wrap: Option<Vector2<f32>>, k: usize, out: &[usize]) -> f32 {
+    match out.last() {
+        Some(last) if out.len() >= k => distance_squared(positions, *last, center, wrap),
+        _ => f32::INFINITY,
+    }
+}
+
+// Adds an agent to the `k` nearest candidates in `out` if it's closer than the farthest one.
+// `out` stays sorted by distance, which goes across the edges of a world that wraps around at `wrap`.
+pub fn knn_insert(
+    positions: &[Position],
+    center: Vector2<f32>,
+    wrap: Option<Vector2<f32>>,
+    k: usize,
+    out: &mut Vec<usize>,
+    agent: usize
+) {
+    let distance = distance_squared(positions, agent, center, wrap);
+
+    if distance >= knn_worst(positions, center, wrap, k, out) {
+        return;
+    }
+
+    let at = out.partition_point(|other| distance_squared(positions, *other, center, wrap) <= distance);
+
+    out.insert(at, agent);
+    out.truncate(k);
+}
+
+// Which boids count as neighbors of a boid
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Neighborhood {
+    // All boids within the perception radius
+    Metric,
+    // A fixed number of nearest boids, no matter how far
+    Topological,
+}
+
+impl Neighborhood {
+    pub fn next(self) -> Neighborhood {
+        match self {
+            Neighborhood::Metric => Neighborhood::Topological,
+            Neighborhood::Topological => Neighborhood::Metric,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SpatialBackend {
+    Grid,
+    Quadtree,
+    KdTree,
+}
+
+impl SpatialBackend {
+    pub fn next(self) -> SpatialBackend {
+        match self {
+            SpatialBackend::Grid => SpatialBackend::Quadtree,
+            SpatialBackend::Quadtree => SpatialBackend::KdTree,
+            SpatialBackend::KdTree => SpatialBackend::Grid,
+        }
+    }
+}
+
+// Closest distance between an axis aligned box and a point, squared
+pub fn box_distance_squared(min: Vector2<f32>, max: Vector2<f32>, point: Vector2<f32>) -> f32 {
+    let dx = (min[0] - point[0]).max(0.0).max(point[0] - max[0]);
+    let dy = (min[1] - point[1]).max(0.0).max(point[1] - max[1]);
+
+    dx * dx + dy * dy
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+
+// How one species reacts to another one.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Interaction {
+    Attract(f32),
+    Repel(f32),
+    Ignore,
+}
+
+impl Interaction {
+    // Positive strength attracts, negative repels, zero ignores.
+    pub fn from_strength(strength: f32) -> Interaction {
+        if strength > 0.0 {
+            Interaction::Attract(strength)
+        }
+        else if strength < 0.0 {
+            Interaction::Repel(-strength)
+        }
+        else {
+            Interaction::Ignore
+        }
+    }
+
+    pub fn strength(&self) -> f32 {
+        match *self {
+            Interaction::Attract(s) => s,
+            Interaction::Repel(s) => -s,
+            Interaction::Ignore => 0.0,
+        }
+    }
+}
+
+// NxN table, row is the species that steers, column is the species it reacts to.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InteractionMatrix {
+    size: usize,
+    entries: Vec<Interaction>,
+}
+
+impl InteractionMatrix {
+    // Every species flocks only with its own kind.
+    pub fn segregated(size: usize) -> InteractionMatrix {
+        let mut matrix = InteractionMatrix {
+            size,
+            entries: vec![Interaction::Ignore; size * size],
+        };
+
+        for s in 0..size {
+            matrix.set(s, s, Interaction::Attract(1.0));
+        }
+
+        matrix
+    }
+
+    // All species flock together as one.
+    pub fn mixed(size: usize) -> InteractionMatrix {
+        InteractionMatrix {
+            size,
+            entries: vec![Interaction::Attract(1.0); size * size],
+        }
+    }
+
+    // The last species hunts, all the others run away from it.
+    pub fn predator_prey(size: usize) -> InteractionMatrix {
+        let mut matrix = InteractionMatrix::segregated(size);
+        let predator = size - 1;
+
+        matrix.set(predator, predator, Interaction::Ignore);
+
+        for prey in 0..predator {
+            matrix.set(prey, predator, Interaction::Repel(4.0));
+            matrix.set(predator, prey, Interaction::Attract(2.0));
+        }
+
+        matrix
+    }
+
+    // Prey gangs up on the last species and chases it away.
+    pub fn mobbing(size: usize) -> InteractionMatrix {
+        let mut matrix = InteractionMatrix::segregated(size);
+        let predator = size - 1;
+
+        matrix.set(predator, predator, Interaction::Ignore);
+
+        for prey in 0..predator {
+            matrix.set(prey, predator, Interaction::Attract(1.5));
+            matrix.set(predator, prey, Interaction::Repel(2.0));
+        }
+
+        matrix
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get(&self, species: usize, other: usize) -> Interaction {
+        self.entries[species * self.size + other]
+    }
+
+    pub fn set(&mut self, species: usize, other: usize, interaction: Interaction) {
+        self.entries[species * self.size + other] = interaction;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum InteractionPreset {
+    Segregated,
+    Mixed,
+    PredatorPrey,
+    Mobbing,
+}
+
+impl InteractionPreset {
+    // Case doesn't matter, words are separated by dashes
+    pub fn from_name(name: &str) -> Option<InteractionPreset> {
+        match name.to_ascii_lowercase().as_str() {
+            "segregated" => Some(InteractionPreset::Segregated),
+            "mixed" => Some(InteractionPreset::Mixed),
+            "predator-prey" => Some(InteractionPreset::PredatorPrey),
+            "mobbing" => Some(InteractionPreset::Mobbing),
+            _ => None,
+        }
+    }
+
+    pub fn next(self) -> InteractionPreset {
+        match self {
+            InteractionPreset::Segregated => InteractionPreset::Mixed,
+            InteractionPreset::Mixed => InteractionPreset::PredatorPrey,
+            InteractionPreset::PredatorPrey => InteractionPreset::Mobbing,
+            InteractionPreset::Mobbing => InteractionPreset::Segregated,
+        }
+    }
+
+    pub fn matrix(self, size: usize) -> InteractionMatrix {
+        match self {
+            InteractionPreset::Segregated => InteractionMatrix::segregated(size),
+            InteractionPreset::Mixed => InteractionMatrix::mixed(size),
+            InteractionPreset::PredatorPrey => InteractionMatrix::predator_prey(size),
+            InteractionPreset::Mobbing => InteractionMatrix::mobbing(size),
+        }
+    }
+}
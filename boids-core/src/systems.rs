@@ -0,0 +1,1007 @@
+use std::f32::consts::PI;
+
+use cgmath::num_traits::clamp;
+use itertools::izip;
+use rand::Rng;
+use rand::rngs::StdRng;
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
+use rayon::slice::{ParallelSlice, ParallelSliceMut};
+use vecmath::{Vector2, vec2_add, vec2_dot, vec2_len, vec2_scale, vec2_square_len, vec2_sub};
+
+use crate::{TOPOLOGICAL_NEIGHBORS, data::*};
+use crate::{GUST_DURATION, GUST_FADE_TIME, GUST_INTERVAL, GUST_RADIUS, GUST_STRENGTH};
+use crate::{OBSTACLE_AVOIDANCE, OBSTACLE_AVOID_DISTANCE, WALL_THICKNESS};
+use crate::{FLAP_FREQUENCY, TRAIL_BREAK_DISTANCE};
+use crate::{EXHAUSTION_TIME, FEED_CHANCE, FEED_DURATION, FLEE_DURATION, NIGHT_PERCH_FACTOR, PERCH_CHANCE, PERCH_DURATION, PERCH_MARGIN};
+use crate::behavior::state_weights;
+use crate::components::column;
+use crate::diagnostics::vec_bytes;
+use crate::grid::Grid;
+use crate::neighbor_list::NeighborList;
+use crate::query::{Read, Write};
+use crate::spatial::{Neighborhood, SpatialIndex, nearest_image};
+use crate::species::InteractionMatrix;
+
+// Systems working on boid columns come with a query type naming the columns they read
+// and write, in the order of their parameters. `Components::query` hands them out.
+
+// Smallest number of boids given to one parallel job,
+// smaller jobs cost more to schedule than to run.
+const MIN_CHUNK_SIZE: usize = 64;
+
+// Splits `len` boids evenly over the thread pool.
+// Never zero, so any population works, and rounded up so there is no small leftover chunk.
+fn chunk_size(len: usize) -> usize {
+    len.div_ceil(rayon::current_num_threads()).max(MIN_CHUNK_SIZE)
+}
+
+pub type ForwardQuery = (Write<column::Positions>, Read<column::Directions>, Read<column::Behaviors>);
+
+// Distance a boid in each behavior state covers this step, indexed by `state as usize`
+fn state_steps(delta_time: f32, speed: f32) -> [f32; 5] {
+    BehaviorState::ALL.map(|state| delta_time * speed * state_weights(state).speed)
+}
+
+// Moves boids forward.
+pub fn forward_system(
+    delta_time: f32,
+    speed: f32,
+    positions: &mut [Position],
+    forwards: &[Forward],
+    behaviors: &[Behavior]
+) {
+    let steps = state_steps(delta_time, speed);
+    let chunk_size = chunk_size(positions.len());
+
+    let pi = positions.par_chunks_mut(chunk_size);
+    let fi = forwards.par_chunks(chunk_size);
+    let bi = behaviors.par_chunks(chunk_size);
+    
+    pi.zip(fi).zip(bi)
+        .for_each(|((position_chunk, forward_chunk), behavior_chunk)| {
+            for (position, forward, behavior) in izip!(position_chunk, forward_chunk, behavior_chunk) {
+                position.value = vec2_add(position.value, vec2_scale(forward.direction, steps[behavior.state as usize]));
+            }
+        });
+}
+
+fn vec2_normalized_safe(v: Vector2<f32>) -> Vector2<f32> {
+    let l = vec2_len(v);
+
+    if l == 0.0 {
+        return [0.0, 0.0];
+    }
+
+    [v[0] / l, v[1] / l]
+}
+
+pub type SpatialIndexQuery = (Read<column::Positions>, Read<column::Behaviors>);
+
+// Inserts all boids into a spatial index to reduce calculations,
+// dead boids are left out of the flock entirely.
+pub fn spatial_index_system(index: &mut dyn SpatialIndex, positions: &[Position], behaviors: &[Behavior]) {
+    index.insert_all(positions, &|i| behaviors[i].state != BehaviorState::Dead);
+}
+
+// Per species average heading and position of the neighbors, and the separation from the nearest one.
+// In a world that wraps around at `wrap` neighbors across the edges count from that side.
+#[allow(clippy::too_many_arguments)]
+fn neighborhood(
+    position: Vector2<f32>,
+    neighbors: &[usize],
+    positions: &[Position],
+    wrap: Option<Vector2<f32>>,
+    forwards: &[Forward],
+    species: &[Species],
+    species_forwards: &mut [Forward],
+    species_cohesions: &mut [Position],
+    species_counts: &mut [usize]
+) -> Vector2<f32> {
+    for (species_forward, species_cohesion, count) in izip!(species_forwards.iter_mut(), species_cohesions.iter_mut(), species_counts.iter_mut()) {
+        species_forward.direction = [0.0, 0.0];
+        species_cohesion.value = [0.0, 0.0];
+        *count = 0;
+    }
+
+    let mut nearest = None;
+    let mut min_distance = f32::MAX;
+
+    for boid_id in neighbors {
+        let s = species[*boid_id].id;
+        let other = nearest_image(position, positions[*boid_id].value, wrap);
+
+        species_forwards[s].direction = vec2_add(species_forwards[s].direction, forwards[*boid_id].direction);
+        species_cohesions[s].value = vec2_add(species_cohesions[s].value, other);
+        species_counts[s] += 1;
+
+        let distance = vec2_square_len(vec2_sub(other, position));
+
+        if distance < min_distance {
+            min_distance = distance;
+            nearest = Some(other);
+        }
+    }
+
+    for (species_forward, species_cohesion, count) in izip!(species_forwards.iter_mut(), species_cohesions.iter_mut(), species_counts.iter()) {
+        species_forward.direction = vec2_normalized_safe(species_forward.direction);
+
+        if *count > 0 {
+            species_cohesion.value = vec2_scale(species_cohesion.value, 1.0 / *count as f32);
+        }
+    }
+
+    nearest.map_or([0.0, 0.0], |nearest| separation_force(position, nearest, min_distance))
+}
+
+// Pushes a boid away from its nearest neighbor, stronger the closer it is
+fn separation_force(position: Vector2<f32>, nearest: Vector2<f32>, distance_squared: f32) -> Vector2<f32> {
+    let separation = vec2_normalized_safe(vec2_sub(position, nearest));
+    let distance = distance_squared.sqrt();
+
+    if distance == 0.0 {
+        return separation;
+    }
+
+    vec2_scale(separation, clamp(1.0 / distance, 0.01, 100.0))
+}
+
+// Buffers reused by `boid_system` every frame, they are cleared but never shrunk.
+#[derive(Default)]
+pub struct SteeringScratch {
+    neighbors: Vec<usize>,
+    // Nearest boids to each copy of a boid across the edges, for topological neighborhoods in a wrapping world
+    images: Vec<usize>,
+    // Whether each boid is alive, for filtering cached neighbor lists
+    alive: Vec<bool>,
+
+    // Per species averages of the neighborhood that is being processed
+    species_forwards: Vec<Forward>,
+    species_cohesions: Vec<Position>,
+    species_counts: Vec<usize>,
+
+    // Neighborhoods of all boids at once for `boid_cell_pair_system`
+    pair_sums: CellPairSums,
+}
+
+// Neighborhood of every boid summed up pair by pair in `boid_cell_pair_system`.
+// Sums of the headings and positions are per boid and species, stored at `agent * species_count + species`.
+#[derive(Default)]
+struct CellPairSums {
+    species_count: usize,
+    forwards: Vec<Vector2<f32>>,
+    positions: Vec<Vector2<f32>>,
+    counts: Vec<usize>,
+    // Squared distance and index of the nearest neighbor of every boid
+    nearest: Vec<(f32, usize)>,
+}
+
+impl CellPairSums {
+    fn prepare(&mut self, agent_count: usize, species_count: usize) {
+        self.species_count = species_count;
+
+        self.forwards.clear();
+        self.forwards.resize(agent_count * species_count, [0.0, 0.0]);
+
+        self.positions.clear();
+        self.positions.resize(agent_count * species_count, [0.0, 0.0]);
+
+        self.counts.clear();
+        self.counts.resize(agent_count * species_count, 0);
+
+        self.nearest.clear();
+        self.nearest.resize(agent_count, (f32::MAX, 0));
+    }
+
+    // Counts `neighbor` into the neighborhood of `agent`, `neighbor_position` is the copy of it closest to `agent`
+    fn add(
+        &mut self,
+        agent: usize,
+        neighbor: usize,
+        neighbor_position: Vector2<f32>,
+        distance_squared: f32,
+        forwards: &[Forward],
+        species: &[Species]
+    ) {
+        let i = agent * self.species_count + species[neighbor].id;
+
+        self.forwards[i] = vec2_add(self.forwards[i], forwards[neighbor].direction);
+        self.positions[i] = vec2_add(self.positions[i], neighbor_position);
+        self.counts[i] += 1;
+
+        if distance_squared < self.nearest[agent].0 {
+            self.nearest[agent] = (distance_squared, neighbor);
+        }
+    }
+}
+
+impl SteeringScratch {
+    pub fn allocated_bytes(&self) -> usize {
+        let sums = &self.pair_sums;
+
+        vec_bytes(&self.neighbors)
+            + vec_bytes(&self.images)
+            + vec_bytes(&self.alive)
+            + vec_bytes(&self.species_forwards)
+            + vec_bytes(&self.species_cohesions)
+            + vec_bytes(&self.species_counts)
+            + vec_bytes(&sums.forwards)
+            + vec_bytes(&sums.positions)
+            + vec_bytes(&sums.counts)
+            + vec_bytes(&sums.nearest)
+    }
+
+    fn prepare(&mut self, species_count: usize) {
+        self.species_forwards.clear();
+        self.species_forwards.resize(species_count, Forward { direction: [0.0, 0.0] });
+
+        self.species_cohesions.clear();
+        self.species_cohesions.resize(species_count, Position { value: [0.0, 0.0] });
+
+        self.species_counts.clear();
+        self.species_counts.resize(species_count, 0);
+    }
+}
+
+// Shared by all steering systems
+pub type SteeringQuery = (
+    Read<column::Positions>,
+    Read<column::PreviousDirections>,
+    Write<column::Directions>,
+    Read<column::Species>,
+    Write<column::Behaviors>,
+    Write<column::Crowding>,
+    Write<column::Forces>,
+);
+
+// Steers every boid from the same snapshot of headings, `previous_forwards` are read
+// and `forwards` are written, so results don't depend on the order of boids.
+// Neighbors are found through the spatial index, either all boids within the perception radius
+// or the TOPOLOGICAL_NEIGHBORS nearest ones, across the edges of a world that wraps around at `wrap`.
+#[allow(clippy::too_many_arguments)]
+pub fn boid_system(
+    positions: &[Position],
+    previous_forwards: &[Forward],
+    forwards: &mut[Forward],
+    species: &[Species],
+    behaviors: &mut [Behavior],
+    crowding: &mut [Crowding],
+    forces: &mut [SteeringForces],
+    interactions: &InteractionMatrix,
+    weights: &FlockWeights,
+    perception_radius: f32,
+    wrap: Option<Vector2<f32>>,
+    index: &dyn SpatialIndex,
+    neighborhood: Neighborhood,
+    scratch: &mut SteeringScratch
+) {
+    let mut images = std::mem::take(&mut scratch.images);
+
+    steer_all(
+        positions,
+        previous_forwards,
+        forwards,
+        species,
+        behaviors,
+        crowding,
+        forces,
+        interactions,
+        weights,
+        wrap,
+        scratch,
+        |agent_id, neighbors| {
+            let position = positions[agent_id].value;
+
+            match neighborhood {
+                Neighborhood::Metric => {
+                    neighbors.clear();
+                    index.query_radius_wrapped(positions, position, perception_radius, wrap, neighbors);
+                }
+                // One more, the boid itself is always the nearest
+                Neighborhood::Topological => {
+                    index.query_knn_wrapped(positions, position, TOPOLOGICAL_NEIGHBORS + 1, wrap, neighbors, &mut images);
+                }
+            }
+
+            neighbors.retain(|neighbor_id| *neighbor_id != agent_id);
+        }
+    );
+
+    scratch.images = images;
+}
+
+// Steers like `boid_system` with a metric neighborhood, but only filters the cached
+// neighbor lists by the current distances instead of querying a spatial index.
+// Boids that died since the lists were built are skipped here.
+#[allow(clippy::too_many_arguments)]
+pub fn boid_neighbor_list_system(
+    positions: &[Position],
+    previous_forwards: &[Forward],
+    forwards: &mut[Forward],
+    species: &[Species],
+    behaviors: &mut [Behavior],
+    crowding: &mut [Crowding],
+    forces: &mut [SteeringForces],
+    interactions: &InteractionMatrix,
+    weights: &FlockWeights,
+    perception_radius: f32,
+    wrap: Option<Vector2<f32>>,
+    neighbor_list: &NeighborList,
+    scratch: &mut SteeringScratch
+) {
+    let radius_squared = perception_radius * perception_radius;
+    let mut alive = std::mem::take(&mut scratch.alive);
+
+    alive.clear();
+    alive.extend(behaviors.iter().map(|b| b.state != BehaviorState::Dead));
+
+    steer_all(
+        positions,
+        previous_forwards,
+        forwards,
+        species,
+        behaviors,
+        crowding,
+        forces,
+        interactions,
+        weights,
+        wrap,
+        scratch,
+        |agent_id, neighbors| {
+            let position = positions[agent_id].value;
+
+            neighbors.clear();
+            neighbors.extend(neighbor_list.neighbors(agent_id).iter().filter(|neighbor_id| {
+                if !alive[**neighbor_id] {
+                    return false;
+                }
+
+                let other = nearest_image(position, positions[**neighbor_id].value, wrap);
+
+                vec2_square_len(vec2_sub(other, position)) <= radius_squared
+            }));
+        }
+    );
+
+    scratch.alive = alive;
+}
+
+// Steers every living boid by the neighbors `find_neighbors` puts into the list it's given
+#[allow(clippy::too_many_arguments)]
+fn steer_all<F>(
+    positions: &[Position],
+    previous_forwards: &[Forward],
+    forwards: &mut[Forward],
+    species: &[Species],
+    behaviors: &mut [Behavior],
+    crowding: &mut [Crowding],
+    forces: &mut [SteeringForces],
+    interactions: &InteractionMatrix,
+    weights: &FlockWeights,
+    wrap: Option<Vector2<f32>>,
+    scratch: &mut SteeringScratch,
+    mut find_neighbors: F
+)
+where
+    F: FnMut(usize, &mut Vec<usize>)
+{
+    scratch.prepare(interactions.size());
+
+    let SteeringScratch {
+        neighbors,
+        species_forwards,
+        species_cohesions,
+        species_counts,
+        ..
+    } = scratch;
+
+    for agent_id in 0..positions.len() {
+        // Dead boids are not in the index and don't steer
+        if behaviors[agent_id].state == BehaviorState::Dead {
+            continue;
+        }
+
+        let position = positions[agent_id].value;
+
+        find_neighbors(agent_id, neighbors);
+        crowding[agent_id].neighbors = neighbors.len() as u32;
+
+        // Calculate general direction and center of each species in the neighborhood
+        let separation = neighborhood(
+            position,
+            neighbors,
+            positions,
+            wrap,
+            previous_forwards,
+            species,
+            species_forwards,
+            species_cohesions,
+            species_counts
+        );
+
+        forces[agent_id] = steer(
+            position,
+            previous_forwards[agent_id].direction,
+            species[agent_id].id,
+            &mut behaviors[agent_id],
+            interactions,
+            weights,
+            species_forwards,
+            species_cohesions,
+            species_counts,
+            separation
+        );
+        forwards[agent_id].direction = forces[agent_id].heading;
+    }
+}
+
+// Combines the per species neighborhood of a boid and its separation into a new heading.
+// The weighted rule vectors are returned along with it.
+#[allow(clippy::too_many_arguments)]
+fn steer(
+    position: Vector2<f32>,
+    previous_forward: Vector2<f32>,
+    own_species: usize,
+    behavior: &mut Behavior,
+    interactions: &InteractionMatrix,
+    weights: &FlockWeights,
+    species_forwards: &[Forward],
+    species_cohesions: &[Position],
+    species_counts: &[usize],
+    separation: Vector2<f32>
+) -> SteeringForces {
+    let state = state_weights(behavior.state);
+    let mut alignment = [0.0, 0.0];
+    let mut cohesion = [0.0, 0.0];
+
+    behavior.threatened = false;
+
+    // Apply directions and cohesion
+    for other_species in 0..interactions.size() {
+        if species_counts[other_species] == 0 {
+            continue;
+        }
+
+        // Negative strength turns cohesion into fleeing
+        let strength = interactions.get(own_species, other_species).strength();
+
+        if strength == 0.0 {
+            continue;
+        }
+
+        if strength < 0.0 {
+            behavior.threatened = true;
+        }
+
+        // Cohesion
+        let mut coh = vec2_sub(species_cohesions[other_species].value, position);
+        // Distance to cohesion point
+        let d2c = vec2_len(coh);
+
+        if d2c != 0.0 {
+            coh = vec2_scale(coh, clamp(1.0 / d2c, 0.01, 100.0));
+            coh = vec2_scale(coh, weights.cohesion * state.cohesion * strength);
+            cohesion = vec2_add(cohesion, coh);
+        }
+
+        // Alignment, boids only follow species they are attracted to
+        if strength > 0.0 {
+            alignment = vec2_add(alignment, vec2_scale(
+                species_forwards[other_species].direction,
+                weights.alignment * state.alignment * strength
+            ));
+        }
+    }
+
+    // Separation
+    let separation = vec2_scale(separation, weights.separation * state.separation);
+
+    let res = vec2_add(vec2_add(previous_forward, alignment), vec2_add(cohesion, separation));
+
+    SteeringForces {
+        alignment,
+        cohesion,
+        separation,
+        heading: vec2_normalized_safe(res),
+    }
+}
+
+// Steers like `boid_system` with a metric neighborhood, but walks the grid one pair
+// of neighboring cells at a time instead of querying every boid separately.
+// Each pair of boids is checked once and counted for both, and boids of one cell
+// are contiguous in memory since the data is sorted by cell.
+// Cells must be at least the perception radius wide, so all neighbors are at most one cell away.
+// In a world that wraps around at `wrap` the grid has to wrap as well, so cells at opposite edges pair up.
+#[allow(clippy::too_many_arguments)]
+pub fn boid_cell_pair_system(
+    positions: &[Position],
+    previous_forwards: &[Forward],
+    forwards: &mut [Forward],
+    species: &[Species],
+    behaviors: &mut [Behavior],
+    crowding: &mut [Crowding],
+    forces: &mut [SteeringForces],
+    interactions: &InteractionMatrix,
+    weights: &FlockWeights,
+    perception_radius: f32,
+    wrap: Option<Vector2<f32>>,
+    grid: &Grid,
+    scratch: &mut SteeringScratch
+) {
+    let species_count = interactions.size();
+    let radius_squared = perception_radius * perception_radius;
+
+    scratch.prepare(species_count);
+    scratch.pair_sums.prepare(positions.len(), species_count);
+
+    let SteeringScratch {
+        species_forwards,
+        species_cohesions,
+        species_counts,
+        pair_sums: sums,
+        ..
+    } = scratch;
+
+    for cell_index in 0..grid.cell_count() {
+        let cell = grid.cell(cell_index);
+
+        // Pairs within the cell
+        for (n, a) in cell.iter().enumerate() {
+            for b in &cell[n + 1..] {
+                add_pair(sums, *a, *b, radius_squared, wrap, positions, previous_forwards, species);
+            }
+        }
+
+        // Half of the surrounding cells, the other half pairs up with this cell from their side
+        for other_index in grid.forward_cells(cell_index) {
+            let other = grid.cell(*other_index);
+
+            for a in cell {
+                for b in other {
+                    add_pair(sums, *a, *b, radius_squared, wrap, positions, previous_forwards, species);
+                }
+            }
+        }
+    }
+
+    for agent_id in 0..positions.len() {
+        if behaviors[agent_id].state == BehaviorState::Dead {
+            continue;
+        }
+
+        let position = positions[agent_id].value;
+
+        for s in 0..species_count {
+            let i = agent_id * species_count + s;
+
+            species_forwards[s].direction = vec2_normalized_safe(sums.forwards[i]);
+            species_counts[s] = sums.counts[i];
+            species_cohesions[s].value = if sums.counts[i] > 0 {
+                vec2_scale(sums.positions[i], 1.0 / sums.counts[i] as f32)
+            }
+            else {
+                [0.0, 0.0]
+            };
+        }
+
+        crowding[agent_id].neighbors = species_counts.iter().sum::<usize>() as u32;
+
+        let (nearest_distance, nearest) = sums.nearest[agent_id];
+
+        let separation = if nearest_distance == f32::MAX {
+            [0.0, 0.0]
+        }
+        else {
+            separation_force(position, nearest_image(position, positions[nearest].value, wrap), nearest_distance)
+        };
+
+        forces[agent_id] = steer(
+            position,
+            previous_forwards[agent_id].direction,
+            species[agent_id].id,
+            &mut behaviors[agent_id],
+            interactions,
+            weights,
+            species_forwards,
+            species_cohesions,
+            species_counts,
+            separation
+        );
+        forwards[agent_id].direction = forces[agent_id].heading;
+    }
+}
+
+// Counts two boids into each other's neighborhood when they are within the perception radius
+#[allow(clippy::too_many_arguments)]
+fn add_pair(
+    sums: &mut CellPairSums,
+    a: usize,
+    b: usize,
+    radius_squared: f32,
+    wrap: Option<Vector2<f32>>,
+    positions: &[Position],
+    forwards: &[Forward],
+    species: &[Species]
+) {
+    let (position_a, position_b) = (positions[a].value, positions[b].value);
+    let image_b = nearest_image(position_a, position_b, wrap);
+    let d2 = vec2_square_len(vec2_sub(position_a, image_b));
+
+    if d2 <= radius_squared {
+        sums.add(a, b, image_b, d2, forwards, species);
+        sums.add(b, a, nearest_image(position_b, position_a, wrap), d2, forwards, species);
+    }
+}
+
+pub type LeaderQuery = (Read<column::Leaders>, Read<column::PreviousDirections>, Write<column::Directions>);
+
+// Undoes the steering of pinned leaders, they keep the heading they had before it
+pub fn leader_system(leaders: &[Leader], previous_directions: &[Forward], directions: &mut [Forward]) {
+    directions.par_iter_mut()
+        .zip(leaders)
+        .zip(previous_directions)
+        .with_min_len(MIN_CHUNK_SIZE)
+        .for_each(|((direction, leader), previous)| {
+            if leader.pinned {
+                *direction = *previous;
+            }
+        });
+}
+
+pub type BehaviorQuery = (Write<column::Behaviors>, Read<column::Positions>);
+
+// Moves each boid between behavior states and returns the indices of boids that died.
+// Threats override everything except death, the other transitions are timed or random.
+pub fn behavior_system(
+    delta_time: f32,
+    behaviors: &mut [Behavior],
+    positions: &[Position],
+    world_size: [f32; 2],
+    roosting: bool,
+    rng: &mut StdRng
+) -> Vec<usize> {
+    let bottom = world_size[1];
+    let perch_chance = if roosting { PERCH_CHANCE * NIGHT_PERCH_FACTOR } else { PERCH_CHANCE };
+    let mut died = Vec::new();
+
+    for (index, (behavior, position)) in behaviors.iter_mut().zip(positions).enumerate() {
+        if behavior.state == BehaviorState::Dead {
+            continue;
+        }
+
+        behavior.time += delta_time;
+
+        if behavior.threatened {
+            behavior.since_threat = 0.0;
+        }
+        else {
+            behavior.since_threat += delta_time;
+        }
+
+        let next_state = match behavior.state {
+            BehaviorState::Fleeing if behavior.threatened && behavior.time > EXHAUSTION_TIME => {
+                BehaviorState::Dead
+            }
+            _ if behavior.threatened => BehaviorState::Fleeing,
+            BehaviorState::Fleeing if behavior.since_threat > FLEE_DURATION => BehaviorState::Flocking,
+            BehaviorState::Feeding if behavior.time > FEED_DURATION => BehaviorState::Flocking,
+            // Roosting boids sleep through the night
+            BehaviorState::Perching if behavior.time > PERCH_DURATION && !roosting => BehaviorState::Flocking,
+            BehaviorState::Flocking if position.value[1] > bottom - PERCH_MARGIN
+                && rng.gen::<f32>() < perch_chance * delta_time => {
+                BehaviorState::Perching
+            }
+            BehaviorState::Flocking if rng.gen::<f32>() < FEED_CHANCE * delta_time => {
+                BehaviorState::Feeding
+            }
+            state => state,
+        };
+
+        if next_state != behavior.state {
+            behavior.state = next_state;
+            behavior.time = 0.0;
+
+            if next_state == BehaviorState::Dead {
+                died.push(index);
+            }
+        }
+    }
+
+    died
+}
+
+pub type FlowFieldQuery = (Read<column::Directions>, Read<column::Behaviors>);
+
+// Averages the velocities of the living boids in every grid cell. The grid is the one the
+// last rebuild made, boids that left their cell since are still counted in the old one.
+pub fn flow_field_system(
+    flow: &mut FlowField,
+    grid: &Grid,
+    agent_speed: f32,
+    directions: &[Forward],
+    behaviors: &[Behavior]
+) {
+    let cell_count = grid.cell_count();
+
+    flow.velocities.resize(cell_count, [0.0, 0.0]);
+    flow.counts.resize(cell_count, 0);
+
+    flow.velocities.par_iter_mut()
+        .zip(flow.counts.par_iter_mut())
+        .enumerate()
+        .for_each(|(cell, (velocity, count))| {
+            let mut sum = [0.0, 0.0];
+            *count = 0;
+
+            for agent_id in grid.cell(cell) {
+                // The population can shrink between rebuilds of the grid
+                let behavior = match behaviors.get(*agent_id) {
+                    Some(behavior) if behavior.state != BehaviorState::Dead => behavior,
+                    _ => continue,
+                };
+
+                let speed = agent_speed * state_weights(behavior.state).speed;
+                sum = vec2_add(sum, vec2_scale(directions[*agent_id].direction, speed));
+                *count += 1;
+            }
+
+            *velocity = if *count > 0 { vec2_scale(sum, 1.0 / *count as f32) } else { [0.0, 0.0] };
+        });
+}
+
+pub type TrailQuery = (Write<column::Trails>, Read<column::Positions>, Read<column::Behaviors>);
+
+// Adds the current position to the trail of every living boid.
+// A jump longer than TRAIL_BREAK_DISTANCE means the boid wrapped around the screen,
+// the old trail is dropped so no line is drawn across the world.
+pub fn trail_system(trails: &mut [Trail], positions: &[Position], behaviors: &[Behavior]) {
+    let break_distance_squared = TRAIL_BREAK_DISTANCE * TRAIL_BREAK_DISTANCE;
+
+    trails.par_iter_mut()
+        .zip(positions)
+        .zip(behaviors)
+        .with_min_len(MIN_CHUNK_SIZE)
+        .for_each(|((trail, position), behavior)| {
+            if behavior.state == BehaviorState::Dead {
+                return;
+            }
+
+            if let Some(last) = trail.last() {
+                if vec2_square_len(vec2_sub(position.value, last)) > break_distance_squared {
+                    *trail = Trail::default();
+                }
+            }
+
+            trail.push(position.value);
+        });
+}
+
+pub type FlapQuery = (Write<column::Flaps>, Read<column::Behaviors>);
+
+// Advances the wing beat of every boid, faster boids beat faster and resting boids hold still.
+pub fn flap_system(delta_time: f32, flaps: &mut [Flap], behaviors: &[Behavior]) {
+    flaps.par_iter_mut()
+        .zip(behaviors)
+        .with_min_len(MIN_CHUNK_SIZE)
+        .for_each(|(flap, behavior)| {
+            let beats = delta_time * FLAP_FREQUENCY * state_weights(behavior.state).speed;
+            flap.phase = (flap.phase + beats).fract();
+        });
+}
+
+pub type WrapScreenQuery = Write<column::Positions>;
+
+// Wraps boid arund the screen.
+// If boid will try to go out of a screen, it will appear on the other side.
+// Returns the indices of the boids that wrapped.
+pub fn wrap_screen_system(positions: &mut[Position], world_size: [f32; 2]) -> Vec<usize> {
+
+    let wrap_screen_job = |(index, position): (usize, &mut Position)| {
+        let before = position.value;
+
+        if position.value[0] < 0.0 {
+            position.value[0] = world_size[0];
+        }
+        else if position.value[0] > world_size[0] {
+            position.value[0] = 0.0;
+        }
+
+        if position.value[1] < 0.0 {
+            position.value[1] = world_size[1];
+        }
+        else if position.value[1] > world_size[1] {
+            position.value[1] = 0.0;
+        }
+
+        if position.value != before { Some(index) } else { None }
+    };
+
+    positions.par_iter_mut()
+        .enumerate()
+        .with_min_len(MIN_CHUNK_SIZE)
+        .filter_map(wrap_screen_job)
+        .collect()
+}
+
+pub type BounceQuery = (Write<column::Positions>, Write<column::Directions>);
+
+// Keeps boids inside the world by mirroring their heading at the edge they crossed.
+// Returns the indices of the boids that bounced.
+pub fn bounce_system(positions: &mut [Position], directions: &mut [Forward], world_size: [f32; 2]) -> Vec<usize> {
+
+    let bounce_job = |(index, (position, forward)): (usize, (&mut Position, &mut Forward))| {
+        let mut bounced = false;
+
+        for (value, (heading, size)) in position.value.iter_mut().zip(forward.direction.iter_mut().zip(&world_size)) {
+            if *value < 0.0 || *value > *size {
+                *value = clamp(*value, 0.0, *size);
+                *heading = -*heading;
+                bounced = true;
+            }
+        }
+
+        if bounced { Some(index) } else { None }
+    };
+
+    positions.par_iter_mut()
+        .zip(directions.par_iter_mut())
+        .enumerate()
+        .with_min_len(MIN_CHUNK_SIZE)
+        .filter_map(bounce_job)
+        .collect()
+}
+
+// Ages existing gusts, removes the faded ones and every now and then spawns a new one.
+pub fn gust_spawn_system(
+    delta_time: f32,
+    gusts: &mut Vec<Gust>,
+    next_gust: &mut f32,
+    world_size: [f32; 2],
+    rng: &mut StdRng
+) {
+    for gust in gusts.iter_mut() {
+        gust.age += delta_time;
+    }
+
+    gusts.retain(|gust| gust.age < gust.duration);
+
+    *next_gust -= delta_time;
+
+    if *next_gust > 0.0 {
+        return;
+    }
+
+    *next_gust = rng.gen_range(0.5..1.5) * GUST_INTERVAL;
+
+    gusts.push(random_gust(world_size, rng));
+}
+
+// Gust somewhere in the world, blowing in any direction
+pub fn random_gust(world_size: [f32; 2], rng: &mut StdRng) -> Gust {
+    let angle: f32 = rng.gen_range(0.0..PI * 2.0);
+
+    Gust {
+        center: [
+            rng.gen_range(0.0..world_size[0]),
+            rng.gen_range(0.0..world_size[1]),
+        ],
+        direction: [angle.cos(), angle.sin()],
+        radius: rng.gen_range(GUST_RADIUS[0]..GUST_RADIUS[1]),
+        strength: GUST_STRENGTH,
+        age: 0.0,
+        duration: GUST_DURATION,
+    }
+}
+
+// Gust strength ramps up after spawning and back down before it disappears
+pub fn gust_fade(gust: &Gust) -> f32 {
+    let fade_in = gust.age / GUST_FADE_TIME;
+    let fade_out = (gust.duration - gust.age) / GUST_FADE_TIME;
+
+    clamp(fade_in.min(fade_out), 0.0, 1.0)
+}
+
+pub type GustQuery = (Read<column::Positions>, Write<column::Directions>);
+
+// Pushes boids caught inside a gust along its direction.
+// The push is strongest in the center of a gust and fades towards its edge.
+pub fn gust_system(gusts: &[Gust], positions: &[Position], forwards: &mut [Forward]) {
+    if gusts.is_empty() {
+        return;
+    }
+
+    let gust_job = |position: &Position, forward: &mut Forward| {
+        let mut res = forward.direction;
+
+        for gust in gusts {
+            let distance = vec2_len(vec2_sub(position.value, gust.center));
+
+            if distance >= gust.radius {
+                continue;
+            }
+
+            let falloff = 1.0 - distance / gust.radius;
+
+            res = vec2_add(res, vec2_scale(
+                gust.direction,
+                gust.strength * gust_fade(gust) * falloff
+            ));
+        }
+
+        forward.direction = vec2_normalized_safe(res);
+    };
+
+    positions.par_iter()
+        .zip(forwards.par_iter_mut())
+        .with_min_len(MIN_CHUNK_SIZE)
+        .for_each(|(position, forward)| gust_job(position, forward));
+}
+
+pub type ObstacleQuery = (Write<column::Positions>, Write<column::Directions>);
+
+// Point of the wall closest to `point`
+pub fn closest_point_on_wall(wall: &Wall, point: Vector2<f32>) -> Vector2<f32> {
+    let along = vec2_sub(wall.end, wall.start);
+    let length_squared = vec2_square_len(along);
+
+    if length_squared == 0.0 {
+        return wall.start;
+    }
+
+    let t = clamp(vec2_dot(vec2_sub(point, wall.start), along) / length_squared, 0.0, 1.0);
+
+    vec2_add(wall.start, vec2_scale(along, t))
+}
+
+// Turns boids away from obstacles and walls closer than OBSTACLE_AVOID_DISTANCE, and puts boids
+// that got inside an obstacle back on its edge and boids too close to a wall back at WALL_THICKNESS,
+// heading along the surface or away from it.
+// A boid right on the center of an obstacle or on a wall has no way out to turn to and is left alone.
+pub fn obstacle_system(obstacles: &[Obstacle], walls: &[Wall], positions: &mut [Position], forwards: &mut [Forward]) {
+    if obstacles.is_empty() && walls.is_empty() {
+        return;
+    }
+
+    // Surface point, how far the boid may get from it before it's pushed and the boid's position
+    let avoid = |surface: Vector2<f32>, min_distance: f32, position: &mut Position, heading: &mut Vector2<f32>| {
+        let offset = vec2_sub(position.value, surface);
+        let distance = vec2_len(offset);
+
+        if distance >= min_distance + OBSTACLE_AVOID_DISTANCE || distance == 0.0 {
+            return;
+        }
+
+        let normal = vec2_scale(offset, 1.0 / distance);
+        let gap = (distance - min_distance).max(0.0);
+
+        // Like a bounce, a boid that got too close loses the part of its heading towards the surface
+        if distance < min_distance {
+            position.value = vec2_add(surface, vec2_scale(normal, min_distance));
+
+            let towards = vec2_dot(*heading, normal);
+            if towards < 0.0 {
+                *heading = vec2_sub(*heading, vec2_scale(normal, towards));
+            }
+        }
+
+        *heading = vec2_add(*heading, vec2_scale(normal, OBSTACLE_AVOIDANCE * (1.0 - gap / OBSTACLE_AVOID_DISTANCE)));
+    };
+
+    let obstacle_job = |position: &mut Position, forward: &mut Forward| {
+        let mut res = forward.direction;
+
+        for obstacle in obstacles {
+            avoid(obstacle.center, obstacle.radius, position, &mut res);
+        }
+
+        for wall in walls {
+            avoid(closest_point_on_wall(wall, position.value), WALL_THICKNESS, position, &mut res);
+        }
+
+        forward.direction = vec2_normalized_safe(res);
+    };
+
+    positions.par_iter_mut()
+        .zip(forwards.par_iter_mut())
+        .with_min_len(MIN_CHUNK_SIZE)
+        .for_each(|(position, forward)| obstacle_job(position, forward));
+}
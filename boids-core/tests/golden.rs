@@ -0,0 +1,116 @@
+// Steps seeded simulations and compares every boid against a stored snapshot in tests/golden,
+// so anything that changes what the systems compute shows up here. After an intended change
+// the snapshots are written again with
+//
+//     UPDATE_GOLDEN=1 cargo test -p boids-core --test golden
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+
+use boids_core::builder::SimulationBuilder;
+use boids_core::data::Boundary;
+use boids_core::profiler::Profiler;
+use boids_core::simulation::Simulation;
+use boids_core::species::InteractionPreset;
+
+const STEPS: usize = 120;
+const TIMESTEP: f32 = 1.0 / 60.0;
+// Steps go through sin, cos and sqrt, which can round a little differently on other platforms
+const TOLERANCE: f32 = 1e-3;
+
+fn run(mut simulation: Simulation) -> Simulation {
+    let mut profiler = Profiler::new();
+
+    for _ in 0..STEPS {
+        simulation.update(TIMESTEP, &mut profiler);
+    }
+
+    simulation
+}
+
+// One line per boid: x, y and the heading
+fn snapshot(simulation: &Simulation) -> String {
+    let components = &simulation.components;
+    let mut snapshot = String::new();
+
+    for (position, forward) in components.positions.iter().zip(&components.directions) {
+        let [x, y] = position.value;
+        let [dx, dy] = forward.direction;
+
+        writeln!(snapshot, "{} {} {} {}", x, y, dx, dy).unwrap();
+    }
+
+    snapshot
+}
+
+fn parse(snapshot: &str) -> Vec<[f32; 4]> {
+    snapshot.lines()
+        .map(|line| {
+            let values: Vec<f32> = line.split(' ').map(|value| value.parse().unwrap()).collect();
+            [values[0], values[1], values[2], values[3]]
+        })
+        .collect()
+}
+
+fn check(name: &str, simulation: &Simulation) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", &format!("{}.txt", name)].iter().collect();
+    let actual = snapshot(simulation);
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|error| panic!("Could not read {}: {}, write it with UPDATE_GOLDEN=1", path.display(), error));
+
+    let expected = parse(&expected);
+    let actual = parse(&actual);
+
+    assert_eq!(actual.len(), expected.len(), "{}: boid count differs", name);
+
+    for (index, (actual, expected)) in actual.iter().zip(&expected).enumerate() {
+        let matches = actual.iter().zip(expected).all(|(a, b)| (a - b).abs() <= TOLERANCE);
+
+        assert!(matches, "{}: boid {} is {:?} instead of {:?}", name, index, actual, expected);
+    }
+}
+
+#[test]
+fn segregated_flock_wrapping_around() {
+    let simulation = SimulationBuilder::new(300, [600.0, 400.0])
+        .seed(7)
+        .build();
+
+    check("segregated_wrap", &run(simulation));
+}
+
+#[test]
+fn predators_and_prey_bouncing_off_the_edges() {
+    let simulation = SimulationBuilder::new(300, [600.0, 400.0])
+        .seed(11)
+        .preset(InteractionPreset::PredatorPrey)
+        .boundary(Boundary::Bounce)
+        .build();
+
+    check("predator_prey_bounce", &run(simulation));
+}
+
+#[test]
+fn one_species_in_a_crowded_world() {
+    let simulation = SimulationBuilder::new(500, [300.0, 300.0])
+        .seed(3)
+        .species(&[0])
+        .build();
+
+    check("one_species_crowded", &run(simulation));
+}
+
+#[test]
+fn same_seed_gives_the_same_flock() {
+    let build = || SimulationBuilder::new(200, [400.0, 400.0]).seed(5).build();
+
+    assert_eq!(snapshot(&run(build())), snapshot(&run(build())));
+}
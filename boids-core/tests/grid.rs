@@ -0,0 +1,284 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use boids_core::data::Position;
+use boids_core::grid::{morton_code, CellOrder, Grid, GridUpdate};
+use boids_core::spatial::{nearest_image, SpatialIndex};
+use boids_core::INCREMENTAL_SORT_INTERVAL;
+
+const WIDTH: f32 = 400.0;
+const HEIGHT: f32 = 300.0;
+const CELL_SIZE: f32 = 50.0;
+// Cuts the last column off, so it's narrower than the rest
+const CUT_WIDTH: f32 = 420.0;
+
+fn random_positions(count: usize, seed: u64) -> Vec<Position> {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    (0..count)
+        .map(|_| Position { value: [rng.gen_range(0.0..WIDTH), rng.gen_range(0.0..HEIGHT)] })
+        .collect()
+}
+
+// Agents of every cell, sorted so grids filled in another order compare equal
+fn cells(grid: &Grid) -> Vec<Vec<usize>> {
+    (0..grid.cell_count())
+        .map(|cell| {
+            let mut agents = grid.cell(cell).to_vec();
+            agents.sort_unstable();
+            agents
+        })
+        .collect()
+}
+
+#[test]
+fn morton_code_interleaves_the_coordinates() {
+    assert_eq!(morton_code(0, 0), 0);
+    assert_eq!(morton_code(1, 0), 0b01);
+    assert_eq!(morton_code(0, 1), 0b10);
+    assert_eq!(morton_code(3, 3), 0b1111);
+    assert_eq!(morton_code(0b101, 0b010), 0b011001);
+    assert_eq!(morton_code(u32::MAX, 0), 0x5555_5555_5555_5555);
+}
+
+#[test]
+fn morton_order_numbers_every_cell_once() {
+    let grid = Grid::new(WIDTH, HEIGHT, CELL_SIZE, CellOrder::Morton);
+
+    let mut numbers: Vec<usize> = (0..grid.rows)
+        .flat_map(|y| (0..grid.columns).map(move |x| (x, y)))
+        .map(|(x, y)| grid.cell_at(x, y))
+        .collect();
+    numbers.sort_unstable();
+
+    assert_eq!(numbers, (0..grid.cell_count()).collect::<Vec<_>>());
+}
+
+#[test]
+fn positions_outside_the_world_go_to_the_border_cells() {
+    let grid = Grid::new(WIDTH, HEIGHT, CELL_SIZE, CellOrder::RowMajor);
+
+    assert_eq!(grid.cell_coords([-10.0, -10.0]), (0, 0));
+    assert_eq!(grid.cell_coords([WIDTH + 10.0, HEIGHT + 10.0]), (grid.columns - 1, grid.rows - 1));
+    assert_eq!(grid.cell_coords([WIDTH, 0.0]), (grid.columns - 1, 0));
+}
+
+#[test]
+fn build_puts_every_included_agent_in_its_cell() {
+    for order in [CellOrder::RowMajor, CellOrder::Morton] {
+        let mut grid = Grid::new(WIDTH, HEIGHT, CELL_SIZE, order);
+        let positions = random_positions(500, 1);
+
+        // Every third agent is left out, like dead boids
+        grid.build(&positions, |i| i % 3 != 0);
+
+        let mut seen = vec![0; positions.len()];
+
+        for cell in 0..grid.cell_count() {
+            for agent in grid.cell(cell) {
+                assert_eq!(grid.cell_index(positions[*agent].value), cell);
+                seen[*agent] += 1;
+            }
+        }
+
+        for (i, count) in seen.iter().enumerate() {
+            assert_eq!(*count, if i % 3 != 0 { 1 } else { 0 }, "agent {} with {:?}", i, order);
+        }
+    }
+}
+
+#[test]
+fn update_after_moving_matches_a_rebuild() {
+    let mut positions = random_positions(500, 2);
+
+    let mut updated = Grid::new(WIDTH, HEIGHT, CELL_SIZE, CellOrder::RowMajor);
+    updated.build(&positions, |_| true);
+
+    // Move some agents into other cells, the rest stay
+    let moved = random_positions(500, 3);
+
+    for (i, position) in positions.iter_mut().enumerate().step_by(4) {
+        *position = moved[i];
+    }
+
+    updated.update(&positions, |_| true);
+
+    let mut rebuilt = Grid::new(WIDTH, HEIGHT, CELL_SIZE, CellOrder::RowMajor);
+    rebuilt.build(&positions, |_| true);
+
+    assert_eq!(cells(&updated), cells(&rebuilt));
+}
+
+// Between sorts the agents keep their indices, so the incremental updates pile up
+#[test]
+fn incremental_updates_only_ask_for_a_sort_once_in_a_while() {
+    let mut positions = random_positions(500, 6);
+
+    let mut grid = Grid::new(WIDTH, HEIGHT, CELL_SIZE, CellOrder::RowMajor);
+    assert!(grid.sort_due());
+
+    grid.update_mode = GridUpdate::Incremental;
+    grid.insert_all(&positions, &|_| true);
+
+    let mut sorts = 0;
+
+    for frame in 0..INCREMENTAL_SORT_INTERVAL * 2 {
+        let moved = random_positions(500, 7 + frame as u64);
+
+        for (i, position) in positions.iter_mut().enumerate().skip(frame % 5).step_by(5) {
+            *position = moved[i];
+        }
+
+        grid.insert_all(&positions, &|_| true);
+
+        if grid.sort_due() {
+            sorts += 1;
+        }
+    }
+
+    let mut rebuilt = Grid::new(WIDTH, HEIGHT, CELL_SIZE, CellOrder::RowMajor);
+    rebuilt.build(&positions, |_| true);
+
+    assert_eq!(sorts, 2);
+    assert_eq!(cells(&grid), cells(&rebuilt));
+}
+
+#[test]
+fn radius_query_finds_what_a_brute_force_search_finds() {
+    let positions = random_positions(800, 4);
+    let centers = random_positions(50, 5);
+
+    for order in [CellOrder::RowMajor, CellOrder::Morton] {
+        let mut grid = Grid::new(WIDTH, HEIGHT, CELL_SIZE, order);
+        grid.build(&positions, |_| true);
+
+        // Below the cell size only the touching cells are searched, above it the bounding box
+        for radius in [20.0, CELL_SIZE, 120.0] {
+            for center in &centers {
+                let mut found = Vec::new();
+                grid.query_radius(&positions, center.value, radius, &mut found);
+                found.sort_unstable();
+
+                let expected: Vec<usize> = (0..positions.len())
+                    .filter(|i| {
+                        let dx = positions[*i].value[0] - center.value[0];
+                        let dy = positions[*i].value[1] - center.value[1];
+                        dx * dx + dy * dy <= radius * radius
+                    })
+                    .collect();
+
+                assert_eq!(found, expected, "radius {} around {:?} with {:?}", radius, center.value, order);
+            }
+        }
+    }
+}
+
+fn wrapped_distance_squared(a: [f32; 2], b: [f32; 2], size: [f32; 2]) -> f32 {
+    let image = nearest_image(a, b, Some(size));
+    let (dx, dy) = (image[0] - a[0], image[1] - a[1]);
+
+    dx * dx + dy * dy
+}
+
+#[test]
+fn set_wrap_rebuilds_the_adjacency() {
+    let mut grid = Grid::new(WIDTH, HEIGHT, CELL_SIZE, CellOrder::RowMajor);
+    let corner = grid.cell_at(0, 0);
+    let opposite = grid.cell_at(grid.columns - 1, grid.rows - 1);
+
+    assert!(!grid.adjacent_cells(corner).contains(&opposite));
+
+    grid.set_wrap(true);
+    assert!(grid.adjacent_cells(corner).contains(&opposite));
+    assert_eq!(grid.adjacent_cells(corner).len(), 9);
+
+    grid.set_wrap(false);
+    assert!(!grid.adjacent_cells(corner).contains(&opposite));
+    assert_eq!(grid.adjacent_cells(corner).len(), 4);
+}
+
+#[test]
+fn wrapped_forward_cells_pair_up_every_close_pair_once() {
+    let size = [CUT_WIDTH, HEIGHT];
+    let mut rng = StdRng::seed_from_u64(6);
+    let positions: Vec<Position> = (0..600)
+        .map(|_| Position { value: [rng.gen_range(0.0..CUT_WIDTH), rng.gen_range(0.0..HEIGHT)] })
+        .collect();
+
+    for order in [CellOrder::RowMajor, CellOrder::Morton] {
+        let mut grid = Grid::new(CUT_WIDTH, HEIGHT, CELL_SIZE, order);
+        grid.set_wrap(true);
+        grid.build(&positions, |_| true);
+
+        let close = |a: usize, b: usize| wrapped_distance_squared(positions[a].value, positions[b].value, size) <= CELL_SIZE * CELL_SIZE;
+        let mut found = Vec::new();
+
+        for cell in 0..grid.cell_count() {
+            let agents = grid.cell(cell);
+
+            for (n, a) in agents.iter().enumerate() {
+                found.extend(agents[n + 1..].iter().filter(|b| close(*a, **b)).map(|b| (*a.min(b), *a.max(b))));
+            }
+
+            for other in grid.forward_cells(cell) {
+                for a in agents {
+                    found.extend(grid.cell(*other).iter().filter(|b| close(*a, **b)).map(|b| (*a.min(b), *a.max(b))));
+                }
+            }
+        }
+
+        found.sort_unstable();
+
+        let expected: Vec<(usize, usize)> = (0..positions.len())
+            .flat_map(|a| (a + 1..positions.len()).map(move |b| (a, b)))
+            .filter(|(a, b)| close(*a, *b))
+            .collect();
+
+        assert_eq!(found, expected, "{:?}", order);
+    }
+}
+
+#[test]
+fn wrapped_queries_find_what_a_brute_force_search_finds() {
+    let size = [CUT_WIDTH, HEIGHT];
+    let mut rng = StdRng::seed_from_u64(7);
+    let positions: Vec<Position> = (0..800)
+        .map(|_| Position { value: [rng.gen_range(0.0..CUT_WIDTH), rng.gen_range(0.0..HEIGHT)] })
+        .collect();
+
+    let mut grid = Grid::new(CUT_WIDTH, HEIGHT, CELL_SIZE, CellOrder::Morton);
+    grid.set_wrap(true);
+    grid.build(&positions, |_| true);
+
+    // Near the corners and edges, where neighborhoods reach across
+    let centers = [[1.0, 1.0], [CUT_WIDTH - 2.0, HEIGHT - 3.0], [CUT_WIDTH - 5.0, 150.0], [200.0, 1.0], [200.0, 150.0]];
+
+    for center in centers {
+        let by_distance = |radius: f32| -> Vec<usize> {
+            (0..positions.len())
+                .filter(|i| wrapped_distance_squared(center, positions[*i].value, size) <= radius * radius)
+                .collect()
+        };
+
+        for radius in [20.0, CELL_SIZE, 120.0] {
+            let mut found = Vec::new();
+            grid.query_radius_wrapped(&positions, center, radius, Some(size), &mut found);
+            found.sort_unstable();
+
+            assert_eq!(found, by_distance(radius), "radius {} around {:?}", radius, center);
+        }
+
+        let mut nearest = Vec::new();
+        let mut images = Vec::new();
+        grid.query_knn_wrapped(&positions, center, 5, Some(size), &mut nearest, &mut images);
+
+        let mut expected: Vec<usize> = (0..positions.len()).collect();
+        expected.sort_by(|a, b| {
+            wrapped_distance_squared(center, positions[*a].value, size)
+                .total_cmp(&wrapped_distance_squared(center, positions[*b].value, size))
+        });
+        expected.truncate(5);
+
+        assert_eq!(nearest, expected, "5 nearest to {:?}", center);
+    }
+}
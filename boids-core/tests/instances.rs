@@ -0,0 +1,79 @@
+// Simulations share nothing, so worlds stepped side by side in one process, the way the
+// comparison view and parameter sweeps run them, end up exactly where they would on their own.
+
+use boids_core::builder::SimulationBuilder;
+use boids_core::data::{Boundary, FlockWeights};
+use boids_core::profiler::Profiler;
+use boids_core::simulation::Simulation;
+use boids_core::species::InteractionPreset;
+
+const STEPS: usize = 60;
+const TIMESTEP: f32 = 1.0 / 60.0;
+
+fn worlds() -> Vec<Simulation> {
+    let mut tight = SimulationBuilder::new(300, [600.0, 400.0]).seed(1).build();
+    tight.weights = FlockWeights { alignment: 1.0, cohesion: 0.5, separation: 2.0 };
+
+    vec![
+        tight,
+        SimulationBuilder::new(500, [800.0, 800.0]).seed(2).preset(InteractionPreset::PredatorPrey).build(),
+        SimulationBuilder::new(200, [300.0, 500.0]).seed(3).boundary(Boundary::Bounce).build(),
+    ]
+}
+
+fn assert_same(a: &Simulation, b: &Simulation, world: usize) {
+    let (a, b) = (&a.components, &b.components);
+
+    assert_eq!(a.len(), b.len(), "world {} has a different population", world);
+
+    for i in 0..a.len() {
+        assert_eq!(a.positions[i].value, b.positions[i].value, "boid {} of world {} moved differently", i, world);
+        assert_eq!(a.directions[i].direction, b.directions[i].direction, "boid {} of world {} turned differently", i, world);
+    }
+}
+
+#[test]
+fn interleaved_worlds_step_like_worlds_run_alone() {
+    let mut profiler = Profiler::new();
+
+    let mut alone = worlds();
+    for simulation in &mut alone {
+        for _ in 0..STEPS {
+            simulation.update(TIMESTEP, &mut profiler);
+        }
+    }
+
+    let mut side_by_side = worlds();
+    for _ in 0..STEPS {
+        for simulation in &mut side_by_side {
+            simulation.update(TIMESTEP, &mut profiler);
+        }
+    }
+
+    for (world, (a, b)) in alone.iter().zip(&side_by_side).enumerate() {
+        assert_same(a, b, world);
+    }
+}
+
+#[test]
+fn changing_one_world_leaves_the_others_alone() {
+    let mut profiler = Profiler::new();
+    let mut reference = worlds();
+    let mut changed = worlds();
+
+    changed[0].set_population(50);
+    changed[0].weights = FlockWeights { alignment: 0.0, cohesion: 0.0, separation: 0.0 };
+    changed[0].set_cell_size(40.0);
+
+    for _ in 0..STEPS {
+        for simulation in reference.iter_mut().chain(changed.iter_mut()) {
+            simulation.update(TIMESTEP, &mut profiler);
+        }
+    }
+
+    assert_eq!(changed[0].components.len(), 50);
+
+    for world in 1..reference.len() {
+        assert_same(&reference[world], &changed[world], world);
+    }
+}
@@ -0,0 +1,165 @@
+// Invariants that have to hold for any flock, checked on random ones.
+// Proptest shrinks a failing case down to the smallest flock and position that still fails.
+
+use proptest::prelude::*;
+
+use boids_core::builder::SimulationBuilder;
+use boids_core::data::{Boundary, Forward, Position};
+use boids_core::profiler::Profiler;
+use boids_core::simulation::Simulation;
+use boids_core::species::InteractionPreset;
+use boids_core::systems::{bounce_system, wrap_screen_system};
+use boids_core::SPECIES_COUNT;
+
+const TIMESTEP: f32 = 1.0 / 60.0;
+const PRESETS: [InteractionPreset; 4] = [
+    InteractionPreset::Segregated,
+    InteractionPreset::Mixed,
+    InteractionPreset::PredatorPrey,
+    InteractionPreset::Mobbing,
+];
+
+fn simulation(agent_count: usize, world_size: [f32; 2], seed: u64, preset: usize, bounce: bool) -> Simulation {
+    SimulationBuilder::new(agent_count, world_size)
+        .seed(seed)
+        .preset(PRESETS[preset])
+        .boundary(if bounce { Boundary::Bounce } else { Boundary::Wrap })
+        .build()
+}
+
+fn in_bounds(position: &Position, world_size: [f32; 2]) -> bool {
+    position.value.iter().zip(&world_size).all(|(value, size)| (0.0..=*size).contains(value))
+}
+
+// Steering normalizes headings, a boid with nothing to steer by can be left with none
+fn unit_or_zero(forward: &Forward) -> bool {
+    let [x, y] = forward.direction;
+    let length = (x * x + y * y).sqrt();
+
+    length == 0.0 || (length - 1.0).abs() < 1e-3
+}
+
+fn finite(simulation: &Simulation) -> bool {
+    let components = &simulation.components;
+
+    components.positions.iter().all(|position| position.value.iter().all(|value| value.is_finite()))
+        && components.directions.iter().all(|forward| forward.direction.iter().all(|value| value.is_finite()))
+}
+
+fn world_size() -> impl Strategy<Value = [f32; 2]> {
+    (10.0f32..2000.0, 10.0f32..2000.0).prop_map(|(w, h)| [w, h])
+}
+
+// Anywhere from far outside the world to far past its other side
+fn point(world_size: [f32; 2]) -> impl Strategy<Value = [f32; 2]> {
+    let [w, h] = world_size;
+    (-w..2.0 * w, -h..2.0 * h).prop_map(|(x, y)| [x, y])
+}
+
+fn points() -> impl Strategy<Value = ([f32; 2], Vec<[f32; 2]>)> {
+    world_size().prop_flat_map(|size| (Just(size), prop::collection::vec(point(size), 1..200)))
+}
+
+fn to_positions(points: &[[f32; 2]]) -> Vec<Position> {
+    points.iter().map(|value| Position { value: *value }).collect()
+}
+
+proptest! {
+    #[test]
+    fn wrapping_keeps_boids_in_the_world((world_size, points) in points()) {
+        let mut positions = to_positions(&points);
+
+        wrap_screen_system(&mut positions, world_size);
+
+        for position in &positions {
+            prop_assert!(in_bounds(position, world_size), "{:?} outside of {:?}", position.value, world_size);
+        }
+    }
+
+    #[test]
+    fn bouncing_keeps_boids_in_the_world((world_size, points) in points()) {
+        let mut positions = to_positions(&points);
+        let mut forwards = vec![Forward { direction: [0.6, -0.8] }; positions.len()];
+
+        bounce_system(&mut positions, &mut forwards, world_size);
+
+        for (position, forward) in positions.iter().zip(&forwards) {
+            prop_assert!(in_bounds(position, world_size), "{:?} outside of {:?}", position.value, world_size);
+            prop_assert!(unit_or_zero(forward), "heading {:?} after bouncing", forward.direction);
+        }
+    }
+
+    #[test]
+    fn updates_keep_boids_in_the_world_with_unit_headings(
+        agent_count in 1usize..300,
+        world_size in world_size(),
+        seed in any::<u64>(),
+        preset in 0..PRESETS.len(),
+        bounce in any::<bool>(),
+    ) {
+        let mut simulation = simulation(agent_count, world_size, seed, preset, bounce);
+        let mut profiler = Profiler::new();
+
+        for step in 0..30 {
+            simulation.update(TIMESTEP, &mut profiler);
+
+            let components = &simulation.components;
+
+            for (position, forward) in components.positions.iter().zip(&components.directions) {
+                prop_assert!(in_bounds(position, world_size), "{:?} outside of {:?} at step {}", position.value, world_size, step);
+                prop_assert!(unit_or_zero(forward), "heading {:?} at step {}", forward.direction, step);
+            }
+        }
+    }
+
+    // Every boid starts on the same spot, where every offset to a neighbor has no length
+    #[test]
+    fn stacked_boids_stay_finite(
+        agent_count in 2usize..100,
+        seed in any::<u64>(),
+        preset in 0..PRESETS.len(),
+        x in 0.0f32..400.0,
+        y in 0.0f32..400.0,
+    ) {
+        let mut simulation = simulation(agent_count, [400.0, 400.0], seed, preset, false);
+        let mut profiler = Profiler::new();
+
+        for position in simulation.components.positions.iter_mut() {
+            position.value = [x, y];
+        }
+
+        for _ in 0..10 {
+            simulation.update(TIMESTEP, &mut profiler);
+        }
+
+        prop_assert!(finite(&simulation));
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(8))]
+
+    #[test]
+    fn thousands_of_steps_stay_finite(
+        agent_count in 1usize..150,
+        seed in any::<u64>(),
+        preset in 0..PRESETS.len(),
+        bounce in any::<bool>(),
+        species in prop::collection::vec(0..SPECIES_COUNT, 1..=SPECIES_COUNT),
+    ) {
+        let mut simulation = SimulationBuilder::new(agent_count, [500.0, 400.0])
+            .seed(seed)
+            .preset(PRESETS[preset])
+            .boundary(if bounce { Boundary::Bounce } else { Boundary::Wrap })
+            .species(&species)
+            .build();
+
+        let mut profiler = Profiler::new();
+
+        for step in 0..3000 {
+            simulation.update(TIMESTEP, &mut profiler);
+
+            prop_assert!(finite(&simulation), "not finite at step {}", step);
+        }
+    }
+}
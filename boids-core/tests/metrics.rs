@@ -0,0 +1,76 @@
+use std::f32::consts::PI;
+
+use boids_core::builder::SimulationBuilder;
+use boids_core::data::{Behavior, BehaviorState, Forward, Position};
+use boids_core::metrics::MetricsScratch;
+use boids_core::simulation::Simulation;
+
+// Boids at the given places and headings, all flocking
+fn flock(boids: &[([f32; 2], [f32; 2])]) -> Simulation {
+    let mut simulation = SimulationBuilder::new(boids.len(), [1000.0, 1000.0]).seed(1).build();
+    let components = &mut simulation.components;
+
+    for (i, (position, direction)) in boids.iter().enumerate() {
+        components.positions[i] = Position { value: *position };
+        components.directions[i] = Forward { direction: *direction };
+        components.behaviors[i] = Behavior::default();
+    }
+
+    simulation
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!((actual - expected).abs() < 1e-4, "{} instead of {}", actual, expected);
+}
+
+#[test]
+fn parallel_flock_is_polarized_and_not_milling() {
+    let boids: Vec<_> = (0..10).map(|i| ([100.0 + 10.0 * i as f32, 500.0], [0.0, 1.0])).collect();
+
+    let metrics = MetricsScratch::new().measure(&flock(&boids));
+
+    assert_close(metrics.polarization, 1.0);
+    assert_close(metrics.milling, 0.0);
+    assert_close(metrics.nearest_neighbor, 10.0);
+}
+
+#[test]
+fn circling_flock_mills_without_polarization() {
+    let count = 36;
+    let boids: Vec<_> = (0..count)
+        .map(|i| {
+            let angle = 2.0 * PI * i as f32 / count as f32;
+            let (sin, cos) = angle.sin_cos();
+
+            ([500.0 + 200.0 * cos, 500.0 + 200.0 * sin], [-sin, cos])
+        })
+        .collect();
+
+    let metrics = MetricsScratch::new().measure(&flock(&boids));
+
+    assert_close(metrics.polarization, 0.0);
+    assert_close(metrics.milling, 1.0);
+}
+
+#[test]
+fn groups_out_of_reach_are_separate_clusters() {
+    let mut boids = Vec::new();
+
+    for center in [[100.0, 100.0], [500.0, 500.0], [900.0, 100.0]] {
+        for i in 0..5 {
+            boids.push(([center[0] + 10.0 * i as f32, center[1]], [1.0, 0.0]));
+        }
+    }
+
+    let mut simulation = flock(&boids);
+    let mut scratch = MetricsScratch::new();
+
+    assert_eq!(scratch.measure(&simulation).clusters, 3);
+
+    // Dead boids are left out, the middle group goes away
+    for behavior in &mut simulation.components.behaviors[5..10] {
+        behavior.state = BehaviorState::Dead;
+    }
+
+    assert_eq!(scratch.measure(&simulation).clusters, 2);
+}
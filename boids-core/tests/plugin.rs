@@ -0,0 +1,109 @@
+// Plugins are checked through descriptors linked into the test, the way a loaded library hands
+// over its own, so no cdylib has to be built first.
+
+use std::path::Path;
+use std::slice;
+
+use boids_core::builder::SimulationBuilder;
+use boids_core::plugin::{load_plugins, Plugin, PluginDescriptor, PluginFrame, PLUGIN_ABI_VERSION};
+use boids_core::profiler::Profiler;
+
+unsafe extern "C" fn steer_east(frame: *const PluginFrame) {
+    let frame = &*frame;
+
+    for force in slice::from_raw_parts_mut(frame.forces, frame.count) {
+        *force = [1000.0, 0.0];
+    }
+}
+
+unsafe extern "C" fn steer_nowhere(frame: *const PluginFrame) {
+    let frame = &*frame;
+
+    for force in slice::from_raw_parts_mut(frame.forces, frame.count) {
+        *force = [f32::NAN, 0.0];
+    }
+}
+
+fn plugin(name: &'static [u8], steer: unsafe extern "C" fn(*const PluginFrame)) -> Plugin {
+    let descriptor = PluginDescriptor {
+        abi_version: PLUGIN_ABI_VERSION,
+        name: name.as_ptr() as *const _,
+        steer: Some(steer),
+    };
+
+    unsafe { Plugin::from_descriptor(&descriptor).unwrap() }
+}
+
+fn names(simulation: &boids_core::simulation::Simulation) -> Vec<&'static str> {
+    let schedule = &simulation.schedule;
+    (0..schedule.len()).map(|i| schedule.name(i)).collect()
+}
+
+#[test]
+fn plugins_steer_between_the_script_and_the_leaders() {
+    let mut simulation = SimulationBuilder::new(10, [400.0, 400.0]).seed(1).build();
+
+    simulation.schedule.add_plugin(plugin(b"East\0", steer_east));
+    simulation.schedule.add_plugin(plugin(b"Nowhere\0", steer_nowhere));
+
+    let names = names(&simulation);
+    let script = names.iter().position(|name| *name == "Script").unwrap();
+
+    assert_eq!(&names[script + 1..script + 4], &["East", "Nowhere", "Leaders"]);
+}
+
+#[test]
+fn plugin_forces_turn_the_boids() {
+    let mut simulation = SimulationBuilder::new(100, [400.0, 400.0]).seed(2).build();
+    simulation.schedule.add_plugin(plugin(b"East\0", steer_east));
+
+    simulation.update(1.0 / 60.0, &mut Profiler::new());
+
+    for (i, forward) in simulation.components.directions.iter().enumerate() {
+        assert!(forward.direction[0] > 0.99, "boid {} heads {:?}", i, forward.direction);
+    }
+}
+
+#[test]
+fn forces_that_are_not_numbers_are_ignored() {
+    let mut simulation = SimulationBuilder::new(100, [400.0, 400.0]).seed(3).build();
+    simulation.schedule.add_plugin(plugin(b"Nowhere\0", steer_nowhere));
+
+    simulation.update(1.0 / 60.0, &mut Profiler::new());
+
+    for forward in &simulation.components.directions {
+        assert!(forward.direction.iter().all(|value| value.is_finite()));
+    }
+}
+
+#[test]
+fn restarted_simulations_keep_their_plugins() {
+    let mut simulation = SimulationBuilder::new(10, [400.0, 400.0]).seed(4).build();
+    simulation.schedule.add_plugin(plugin(b"East\0", steer_east));
+
+    let restarted = simulation.restarted(5);
+
+    assert_eq!(names(&restarted), names(&simulation));
+    assert_eq!(restarted.schedule.plugins().len(), 1);
+}
+
+#[test]
+fn descriptors_for_another_interface_are_refused() {
+    let mut descriptor = PluginDescriptor {
+        abi_version: PLUGIN_ABI_VERSION + 1,
+        name: b"Future\0".as_ptr() as *const _,
+        steer: Some(steer_east),
+    };
+
+    assert!(unsafe { Plugin::from_descriptor(&descriptor) }.is_err());
+
+    descriptor.abi_version = PLUGIN_ABI_VERSION;
+    descriptor.steer = None;
+
+    assert!(unsafe { Plugin::from_descriptor(&descriptor) }.is_err());
+}
+
+#[test]
+fn a_missing_directory_has_no_plugins() {
+    assert!(load_plugins(Path::new("no such plugins")).is_empty());
+}
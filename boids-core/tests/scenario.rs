@@ -0,0 +1,135 @@
+use boids_core::builder::SimulationBuilder;
+use boids_core::data::{Forward, Obstacle, Position, Wall};
+use boids_core::profiler::Profiler;
+use boids_core::scenario::{Scenario, ScenarioAction, SpawnGroup, TimedEvent, Timeline};
+use boids_core::systems::obstacle_system;
+use boids_core::{OBSTACLE_AVOID_DISTANCE, WALL_THICKNESS};
+
+const WORLD_SIZE: [f32; 2] = [400.0, 200.0];
+
+fn despawn(at: f32, count: usize) -> TimedEvent {
+    TimedEvent { at, action: ScenarioAction::Despawn(count) }
+}
+
+fn spawn(count: usize, species: &[usize], region: Option<[f32; 4]>) -> SpawnGroup {
+    SpawnGroup { count, species: species.to_vec(), region }
+}
+
+#[test]
+fn boids_inside_an_obstacle_are_put_on_its_edge() {
+    let obstacle = Obstacle { center: [100.0, 100.0], radius: 20.0 };
+
+    let mut positions = vec![Position { value: [105.0, 100.0] }];
+    let mut forwards = vec![Forward { direction: [-1.0, 0.0] }];
+
+    obstacle_system(&[obstacle], &[], &mut positions, &mut forwards);
+
+    let [x, y] = positions[0].value;
+    assert!((x - 120.0).abs() < 1e-4 && (y - 100.0).abs() < 1e-4, "boid is at {:?}", positions[0].value);
+    // Heading out of the obstacle instead of into it
+    assert_eq!(forwards[0].direction, [1.0, 0.0]);
+}
+
+#[test]
+fn boids_turn_away_from_walls_they_get_close_to() {
+    let wall = Wall { start: [200.0, 0.0], end: [200.0, 200.0] };
+    let close = 200.0 - WALL_THICKNESS - OBSTACLE_AVOID_DISTANCE * 0.5;
+    let far = 200.0 - WALL_THICKNESS - OBSTACLE_AVOID_DISTANCE * 2.0;
+
+    let mut positions = vec![Position { value: [close, 100.0] }, Position { value: [far, 100.0] }];
+    let mut forwards = vec![Forward { direction: [0.0, 1.0] }; 2];
+
+    obstacle_system(&[], &[wall], &mut positions, &mut forwards);
+
+    assert!(forwards[0].direction[0] < 0.0, "close boid heads {:?}", forwards[0].direction);
+    assert_eq!(forwards[1].direction, [0.0, 1.0]);
+
+    // A boid past the end of the wall is pushed from the end, not from the line through it
+    let mut positions = vec![Position { value: [200.0, 201.0] }];
+    obstacle_system(&[], &[wall], &mut positions, &mut forwards[..1]);
+
+    assert!((positions[0].value[1] - (200.0 + WALL_THICKNESS)).abs() < 1e-4, "boid is at {:?}", positions[0].value);
+}
+
+#[test]
+fn spawn_groups_replace_the_boids_in_their_regions() {
+    let mut simulation = SimulationBuilder::new(100, WORLD_SIZE).seed(3).build();
+
+    let scenario = Scenario {
+        spawn: vec![
+            spawn(30, &[0], Some([0.0, 0.0, 50.0, 50.0])),
+            spawn(20, &[1, 2], Some([300.0, 100.0, 400.0, 200.0])),
+        ],
+        walls: vec![Wall { start: [200.0, 0.0], end: [200.0, 200.0] }],
+        ..Default::default()
+    };
+    scenario.apply(&mut simulation);
+
+    let components = &simulation.components;
+    assert_eq!(components.len(), 50);
+    assert_eq!(simulation.walls.len(), 1);
+
+    for i in 0..components.len() {
+        let [x, y] = components.positions[i].value;
+        let species = components.species[i].id;
+
+        if species == 0 {
+            assert!(x <= 50.0 && y <= 50.0, "boid {} of species 0 is at {:?}", i, [x, y]);
+        }
+        else {
+            assert!(x >= 300.0 && y >= 100.0, "boid {} of species {} is at {:?}", i, species, [x, y]);
+        }
+    }
+}
+
+#[test]
+fn timeline_runs_events_once_in_order_of_time() {
+    let mut timeline = Timeline::new(vec![despawn(2.0, 1), despawn(0.5, 2), despawn(2.0, 3)]);
+
+    assert!(timeline.advance(0.25).is_empty());
+
+    let due: Vec<f32> = timeline.advance(0.5).iter().map(|event| event.at).collect();
+    assert_eq!(due, [0.5]);
+
+    // Events at the same time keep their order
+    let due: Vec<_> = timeline.advance(2.0).iter().map(|event| format!("{:?}", event.action)).collect();
+    assert_eq!(due, ["Despawn(1)", "Despawn(3)"]);
+
+    assert!(timeline.is_finished());
+    assert!(timeline.advance(10.0).is_empty());
+    assert!(!timeline.rewound().is_finished());
+}
+
+#[test]
+fn scenario_events_happen_during_updates() {
+    let mut simulation = SimulationBuilder::new(100, WORLD_SIZE).seed(5).build();
+
+    let scenario = Scenario {
+        events: vec![
+            despawn(0.05, 40),
+            TimedEvent { at: 0.25, action: ScenarioAction::Spawn(spawn(10, &[], None)) },
+            TimedEvent { at: 0.25, action: ScenarioAction::Obstacle(Obstacle { center: [50.0, 50.0], radius: 10.0 }) },
+        ],
+        ..Default::default()
+    };
+    scenario.apply(&mut simulation);
+
+    // Without spawn groups the boids of the simulation stay
+    assert_eq!(simulation.components.len(), 100);
+
+    let mut profiler = Profiler::new();
+    let mut populations = Vec::new();
+
+    for _ in 0..5 {
+        simulation.update(0.1, &mut profiler);
+        populations.push(simulation.components.len());
+    }
+
+    assert_eq!(populations, [60, 60, 70, 70, 70]);
+    assert_eq!(simulation.obstacles.len(), 1);
+
+    // A restart keeps the world and runs the events again
+    let restarted = simulation.restarted(6);
+    assert_eq!(restarted.obstacles.len(), 1);
+    assert!(!restarted.timeline.is_finished());
+}
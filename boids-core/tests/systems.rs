@@ -0,0 +1,163 @@
+use boids_core::behavior::state_weights;
+use boids_core::builder::SimulationBuilder;
+use boids_core::data::{Behavior, BehaviorState, Boundary, Forward, Position};
+use boids_core::profiler::Profiler;
+use boids_core::simulation::Simulation;
+use boids_core::spatial::{Neighborhood, SpatialBackend};
+use boids_core::systems::{bounce_system, forward_system, wrap_screen_system};
+
+const WORLD_SIZE: [f32; 2] = [200.0, 100.0];
+
+fn position(x: f32, y: f32) -> Position {
+    Position { value: [x, y] }
+}
+
+fn forward(dx: f32, dy: f32) -> Forward {
+    Forward { direction: [dx, dy] }
+}
+
+fn behavior(state: BehaviorState) -> Behavior {
+    Behavior { state, ..Default::default() }
+}
+
+#[test]
+fn forward_moves_boids_along_their_heading() {
+    let states = [BehaviorState::Flocking, BehaviorState::Feeding, BehaviorState::Fleeing, BehaviorState::Dead];
+    let count = 39;
+
+    let mut positions: Vec<Position> = (0..count).map(|i| position(i as f32, 2.0 * i as f32)).collect();
+    let forwards: Vec<Forward> = (0..count).map(|i| forward((i as f32).cos(), (i as f32).sin())).collect();
+    let behaviors: Vec<Behavior> = (0..count).map(|i| behavior(states[i % states.len()])).collect();
+
+    let (dt, speed) = (0.1, 50.0);
+    forward_system(dt, speed, &mut positions, &forwards, &behaviors);
+
+    for i in 0..count {
+        let step = dt * speed * state_weights(behaviors[i].state).speed;
+        let expected = [i as f32 + forwards[i].direction[0] * step, 2.0 * i as f32 + forwards[i].direction[1] * step];
+
+        for (actual, expected) in positions[i].value.iter().zip(&expected) {
+            assert!((actual - expected).abs() < 1e-4, "boid {} is at {:?} instead of {:?}", i, positions[i].value, expected);
+        }
+    }
+}
+
+#[test]
+fn forward_leaves_dead_boids_in_place() {
+    let mut positions = vec![position(10.0, 20.0)];
+
+    forward_system(1.0, 50.0, &mut positions, &[forward(1.0, 0.0)], &[behavior(BehaviorState::Dead)]);
+
+    assert_eq!(positions[0].value, [10.0, 20.0]);
+}
+
+#[test]
+fn wrap_moves_boids_to_the_opposite_edge() {
+    let mut positions = vec![
+        position(50.0, 50.0),
+        position(-1.0, 50.0),
+        position(201.0, 50.0),
+        position(50.0, -1.0),
+        position(50.0, 101.0),
+        position(-1.0, 101.0),
+    ];
+
+    let wrapped = wrap_screen_system(&mut positions, WORLD_SIZE);
+
+    assert_eq!(wrapped, vec![1, 2, 3, 4, 5]);
+    assert_eq!(positions[0].value, [50.0, 50.0]);
+    assert_eq!(positions[1].value, [200.0, 50.0]);
+    assert_eq!(positions[2].value, [0.0, 50.0]);
+    assert_eq!(positions[3].value, [50.0, 100.0]);
+    assert_eq!(positions[4].value, [50.0, 0.0]);
+    assert_eq!(positions[5].value, [200.0, 0.0]);
+}
+
+#[test]
+fn wrap_keeps_boids_on_the_edge() {
+    let mut positions = vec![position(0.0, 0.0), position(200.0, 100.0)];
+
+    assert!(wrap_screen_system(&mut positions, WORLD_SIZE).is_empty());
+    assert_eq!(positions[0].value, [0.0, 0.0]);
+    assert_eq!(positions[1].value, [200.0, 100.0]);
+}
+
+#[test]
+fn bounce_mirrors_the_heading_at_the_crossed_edge() {
+    let mut positions = vec![position(50.0, 50.0), position(-5.0, 50.0), position(250.0, 120.0)];
+    let mut forwards = vec![forward(1.0, 0.0), forward(-0.6, 0.8), forward(0.6, 0.8)];
+
+    let bounced = bounce_system(&mut positions, &mut forwards, WORLD_SIZE);
+
+    assert_eq!(bounced, vec![1, 2]);
+
+    assert_eq!(positions[0].value, [50.0, 50.0]);
+    assert_eq!(forwards[0].direction, [1.0, 0.0]);
+
+    assert_eq!(positions[1].value, [0.0, 50.0]);
+    assert_eq!(forwards[1].direction, [0.6, 0.8]);
+
+    assert_eq!(positions[2].value, [200.0, 100.0]);
+    assert_eq!(forwards[2].direction, [-0.6, -0.8]);
+}
+
+// Sets up a fresh simulation to find neighbors one way
+type Search = fn(&mut Simulation);
+
+// Each way of finding neighbors in a wrapping world
+fn wrapped_searches() -> Vec<(&'static str, Search)> {
+    vec![
+        ("cell pairs", |simulation| simulation.cell_pairs = true),
+        ("grid", |simulation| simulation.cell_pairs = false),
+        ("neighbor list", |simulation| simulation.neighbor_list.enabled = true),
+        ("quadtree", |simulation| {
+            simulation.cell_pairs = false;
+            simulation.spatial_backend = SpatialBackend::Quadtree;
+        }),
+        ("kd-tree", |simulation| {
+            simulation.cell_pairs = false;
+            simulation.spatial_backend = SpatialBackend::KdTree;
+        }),
+    ]
+}
+
+#[test]
+fn boids_across_a_wrapped_edge_are_neighbors() {
+    for (name, configure) in wrapped_searches() {
+        let mut simulation = SimulationBuilder::new(2, WORLD_SIZE).seed(1).boundary(Boundary::Wrap).build();
+        configure(&mut simulation);
+
+        simulation.components.positions[0].value = [2.0, 1.0];
+        simulation.components.positions[1].value = [WORLD_SIZE[0] - 2.0, WORLD_SIZE[1] - 1.0];
+        simulation.update(1.0 / 60.0, &mut Profiler::new());
+
+        let crowding = &simulation.components.crowding;
+        assert_eq!((crowding[0].neighbors, crowding[1].neighbors), (1, 1), "{}", name);
+    }
+}
+
+#[test]
+fn neighbor_searches_steer_alike_in_a_wrapping_world() {
+    // The last grid column is cut off by the edge of the world
+    let world_size = [430.0, 270.0];
+    let mut headings = Vec::new();
+
+    for (name, configure) in wrapped_searches() {
+        let mut simulation = SimulationBuilder::new(400, world_size).seed(2).boundary(Boundary::Wrap).build();
+        simulation.neighborhood = Neighborhood::Metric;
+        configure(&mut simulation);
+        simulation.update(1.0 / 60.0, &mut Profiler::new());
+
+        headings.push((name, simulation.components.directions.clone()));
+    }
+
+    let (_, expected) = &headings[0];
+
+    for (name, directions) in &headings[1..] {
+        for (actual, expected) in directions.iter().zip(expected) {
+            let [dx, dy] = [actual.direction[0] - expected.direction[0], actual.direction[1] - expected.direction[1]];
+
+            assert!(dx.abs() < 1e-4 && dy.abs() < 1e-4, "{} steers {:?} instead of {:?}", name, actual.direction, expected.direction);
+        }
+    }
+}
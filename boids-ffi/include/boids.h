@@ -0,0 +1,57 @@
+// Generated by cbindgen from boids-ffi/src/lib.rs, don't edit by hand
+
+#ifndef BOIDS_H
+#define BOIDS_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum BoidsBoundary {
+  BOIDS_BOUNDARY_WRAP,
+  BOIDS_BOUNDARY_BOUNCE,
+} BoidsBoundary;
+
+typedef enum BoidsPreset {
+  BOIDS_PRESET_SEGREGATED,
+  BOIDS_PRESET_MIXED,
+  BOIDS_PRESET_PREDATOR_PREY,
+  BOIDS_PRESET_MOBBING,
+} BoidsPreset;
+
+typedef struct BoidsSimulation BoidsSimulation;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+BoidsSimulation *boids_create(size_t agent_count,
+                              float width,
+                              float height,
+                              uint64_t seed,
+                              BoidsPreset preset,
+                              BoidsBoundary boundary);
+
+void boids_destroy(BoidsSimulation *simulation);
+
+bool boids_step(BoidsSimulation *simulation, float dt, size_t steps);
+
+size_t boids_count(const BoidsSimulation *simulation);
+
+void boids_set_population(BoidsSimulation *simulation, size_t count);
+
+const float *boids_positions(const BoidsSimulation *simulation);
+
+const float *boids_headings(const BoidsSimulation *simulation);
+
+size_t boids_species(const BoidsSimulation *simulation, uint32_t *out, size_t capacity);
+
+void boids_set_weights(BoidsSimulation *simulation, float alignment, float cohesion, float separation);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* BOIDS_H */
@@ -0,0 +1,182 @@
+// C API over the core simulation, for game engines and other languages embedding the
+// flock as a native plugin. include/boids.h declares it, a host runs something like
+//
+//     BoidsSimulation *simulation = boids_create(500, 800.0f, 600.0f, 7, BOIDS_PRESET_SEGREGATED, BOIDS_BOUNDARY_WRAP);
+//
+//     boids_step(simulation, 1.0f / 60.0f, 1);
+//     const float *positions = boids_positions(simulation);  // x and y of boids_count(simulation) boids
+//
+//     boids_destroy(simulation);
+//
+// The arrays belong to the simulation, they stay valid until the next call that changes it.
+//
+// Every function taking a simulation takes null as well, any other pointer has to come from
+// `boids_create` and not be destroyed yet. Only one thread may use a simulation at a time.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+
+use boids_core::builder::SimulationBuilder;
+use boids_core::data::Boundary;
+use boids_core::profiler::Profiler;
+use boids_core::simulation::Simulation;
+use boids_core::species::InteractionPreset;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum BoidsPreset {
+    Segregated,
+    Mixed,
+    PredatorPrey,
+    Mobbing,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub enum BoidsBoundary {
+    Wrap,
+    Bounce,
+}
+
+// Opaque to C, only handled through pointers from `boids_create`
+pub struct BoidsSimulation {
+    simulation: Simulation,
+    profiler: Profiler,
+}
+
+impl BoidsPreset {
+    fn to_core(self) -> InteractionPreset {
+        match self {
+            BoidsPreset::Segregated => InteractionPreset::Segregated,
+            BoidsPreset::Mixed => InteractionPreset::Mixed,
+            BoidsPreset::PredatorPrey => InteractionPreset::PredatorPrey,
+            BoidsPreset::Mobbing => InteractionPreset::Mobbing,
+        }
+    }
+}
+
+impl BoidsBoundary {
+    fn to_core(self) -> Boundary {
+        match self {
+            BoidsBoundary::Wrap => Boundary::Wrap,
+            BoidsBoundary::Bounce => Boundary::Bounce,
+        }
+    }
+}
+
+// Null for a world without a positive, finite size
+#[no_mangle]
+pub extern "C" fn boids_create(
+    agent_count: usize,
+    width: f32,
+    height: f32,
+    seed: u64,
+    preset: BoidsPreset,
+    boundary: BoidsBoundary
+) -> *mut BoidsSimulation {
+    let valid = |size: f32| size.is_finite() && size > 0.0;
+
+    if !valid(width) || !valid(height) {
+        return std::ptr::null_mut();
+    }
+
+    let simulation = SimulationBuilder::new(agent_count, [width, height])
+        .seed(seed)
+        .preset(preset.to_core())
+        .boundary(boundary.to_core())
+        .build();
+
+    Box::into_raw(Box::new(BoidsSimulation {
+        simulation,
+        profiler: Profiler::new(),
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn boids_destroy(simulation: *mut BoidsSimulation) {
+    if !simulation.is_null() {
+        drop(Box::from_raw(simulation));
+    }
+}
+
+// False for null or when a step panicked, the simulation should only be destroyed after that.
+// Panics can't unwind into C, so they are stopped here.
+#[no_mangle]
+pub unsafe extern "C" fn boids_step(simulation: *mut BoidsSimulation, dt: f32, steps: usize) -> bool {
+    let simulation = match simulation.as_mut() {
+        Some(simulation) => simulation,
+        None => return false,
+    };
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        for _ in 0..steps {
+            simulation.simulation.update(dt, &mut simulation.profiler);
+            // Nothing reads the events here, they are dropped every step
+            simulation.simulation.events.next_frame();
+        }
+    }));
+
+    result.is_ok()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn boids_count(simulation: *const BoidsSimulation) -> usize {
+    simulation.as_ref().map_or(0, |simulation| simulation.simulation.components.len())
+}
+
+// Boids are spawned or despawned at random to reach the count
+#[no_mangle]
+pub unsafe extern "C" fn boids_set_population(simulation: *mut BoidsSimulation, count: usize) {
+    if let Some(simulation) = simulation.as_mut() {
+        simulation.simulation.set_population(count);
+    }
+}
+
+// x and y of every boid, two floats each. Indices change as boids are sorted by their cell,
+// the same index in both arrays is always the same boid.
+#[no_mangle]
+pub unsafe extern "C" fn boids_positions(simulation: *const BoidsSimulation) -> *const f32 {
+    simulation.as_ref().map_or(std::ptr::null(), |simulation| {
+        simulation.simulation.components.positions.as_ptr() as *const f32
+    })
+}
+
+// Unit length headings, two floats each like the positions
+#[no_mangle]
+pub unsafe extern "C" fn boids_headings(simulation: *const BoidsSimulation) -> *const f32 {
+    simulation.as_ref().map_or(std::ptr::null(), |simulation| {
+        simulation.simulation.components.directions.as_ptr() as *const f32
+    })
+}
+
+// Writes the species id of up to `capacity` boids and returns how many were written
+#[no_mangle]
+pub unsafe extern "C" fn boids_species(simulation: *const BoidsSimulation, out: *mut u32, capacity: usize) -> usize {
+    let simulation = match simulation.as_ref() {
+        Some(simulation) if !out.is_null() => simulation,
+        _ => return 0,
+    };
+
+    let species = &simulation.simulation.components.species;
+    let count = species.len().min(capacity);
+    let out = slice::from_raw_parts_mut(out, count);
+
+    for (out, species) in out.iter_mut().zip(species) {
+        *out = species.id as u32;
+    }
+
+    count
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn boids_set_weights(simulation: *mut BoidsSimulation, alignment: f32, cohesion: f32, separation: f32) {
+    if let Some(simulation) = simulation.as_mut() {
+        let weights = &mut simulation.simulation.weights;
+
+        weights.alignment = alignment;
+        weights.cohesion = cohesion;
+        weights.separation = separation;
+    }
+}
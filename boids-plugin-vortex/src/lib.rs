@@ -0,0 +1,51 @@
+// Example steering plugin: every boid is turned a little around the center of the world, so the
+// flock circles it. Build it and copy the library into the plugins directory of the app:
+//
+//     cargo build --release -p boids-plugin-vortex
+//     cp target/release/libvortex.so plugins/
+//
+// The library is vortex.dll on Windows and libvortex.dylib on macOS.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::slice;
+
+use boids_core::plugin::{PluginDescriptor, PluginFrame, PLUGIN_ABI_VERSION};
+
+// Steering per second toward circling, in units of the heading
+const STRENGTH: f32 = 2.0;
+
+static DESCRIPTOR: PluginDescriptor = PluginDescriptor {
+    abi_version: PLUGIN_ABI_VERSION,
+    name: b"Vortex\0".as_ptr() as *const _,
+    steer: Some(steer),
+};
+
+#[no_mangle]
+pub extern "C" fn boids_plugin() -> *const PluginDescriptor {
+    &DESCRIPTOR
+}
+
+pub unsafe extern "C" fn steer(frame: *const PluginFrame) {
+    let frame = &*frame;
+
+    if frame.count == 0 {
+        return;
+    }
+
+    let boids = slice::from_raw_parts(frame.boids, frame.count);
+    let forces = slice::from_raw_parts_mut(frame.forces, frame.count);
+    let center = [frame.width / 2.0, frame.height / 2.0];
+
+    for (boid, force) in boids.iter().zip(forces) {
+        let offset = [boid.x - center[0], boid.y - center[1]];
+        let distance = (offset[0] * offset[0] + offset[1] * offset[1]).sqrt();
+
+        if distance > 0.0 {
+            // Counterclockwise around the center
+            let tangent = [-offset[1] / distance, offset[0] / distance];
+
+            *force = [tangent[0] * STRENGTH * frame.dt, tangent[1] * STRENGTH * frame.dt];
+        }
+    }
+}
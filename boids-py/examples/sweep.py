@@ -0,0 +1,21 @@
+# Sweeps the cohesion weight and prints how spread out and ordered the flock ends up.
+# Needs the module installed with `maturin develop --release` in boids-py.
+
+import numpy as np
+
+import boids
+
+for cohesion in np.linspace(0.0, 1.0, 6):
+    simulation = boids.Simulation(1000, (800.0, 600.0), seed=42)
+    simulation.cohesion = cohesion
+    simulation.step(steps=600)
+
+    positions = simulation.positions()
+    spread = np.linalg.norm(positions - positions.mean(axis=0), axis=1).mean()
+
+    metrics = simulation.metrics()
+
+    print(
+        f"cohesion {cohesion:.1f}: mean distance to the center {spread:.1f}, "
+        f"polarization {metrics['polarization']:.2f}, {metrics['clusters']} clusters"
+    )
@@ -0,0 +1,244 @@
+// Python module `boids` over the core simulation, for parameter sweeps and analysis
+// without touching Rust. Built and installed into the active environment with
+//
+//     cd boids-py && maturin develop --release
+//
+// and used like
+//
+//     import boids
+//
+//     simulation = boids.Simulation(500, (800.0, 600.0), seed=7, preset="predator-prey")
+//     simulation.cohesion = 0.4
+//     simulation.step(steps=600)
+//     positions = simulation.positions()  # (n, 2) float32 array
+
+use numpy::{PyArray1, PyArray2};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use boids_core::builder::SimulationBuilder;
+use boids_core::data::{BehaviorState, Boundary};
+use boids_core::metrics::MetricsScratch;
+use boids_core::profiler::Profiler;
+use boids_core::simulation::Simulation;
+use boids_core::species::InteractionPreset;
+use boids_core::SPECIES_COUNT;
+
+#[pyclass(name = "Simulation")]
+struct PySimulation {
+    simulation: Simulation,
+    profiler: Profiler,
+    metrics: MetricsScratch,
+}
+
+#[pymethods]
+impl PySimulation {
+    // Species are ids below SPECIES_COUNT, all of them without a list
+    #[new]
+    #[pyo3(signature = (agent_count, world_size, seed = None, preset = "segregated", boundary = "wrap", species = None))]
+    fn new(
+        agent_count: usize,
+        world_size: (f32, f32),
+        seed: Option<u64>,
+        preset: &str,
+        boundary: &str,
+        species: Option<Vec<usize>>
+    ) -> PyResult<PySimulation> {
+        let preset = InteractionPreset::from_name(preset)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown preset {}", preset)))?;
+
+        let boundary = Boundary::from_name(boundary)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown boundary {}", boundary)))?;
+
+        let mut builder = SimulationBuilder::new(agent_count, [world_size.0, world_size.1])
+            .preset(preset)
+            .boundary(boundary);
+
+        if let Some(seed) = seed {
+            builder = builder.seed(seed);
+        }
+
+        if let Some(species) = species {
+            if species.is_empty() || species.iter().any(|id| *id >= SPECIES_COUNT) {
+                return Err(PyValueError::new_err(format!("Species ids go from 0 to {}", SPECIES_COUNT - 1)));
+            }
+
+            builder = builder.species(&species);
+        }
+
+        Ok(PySimulation {
+            simulation: builder.build(),
+            profiler: Profiler::new(),
+            metrics: MetricsScratch::new(),
+        })
+    }
+
+    // Steps run without holding the GIL, so other Python threads keep going
+    #[pyo3(signature = (dt = 1.0 / 60.0, steps = 1))]
+    fn step(&mut self, py: Python<'_>, dt: f32, steps: usize) {
+        let simulation = &mut self.simulation;
+        let profiler = &mut self.profiler;
+
+        py.allow_threads(|| {
+            for _ in 0..steps {
+                simulation.update(dt, profiler);
+                // Nothing reads the events here, they are dropped every step
+                simulation.events.next_frame();
+            }
+        });
+    }
+
+    // Indices change as boids are sorted by their cell, rows of the arrays
+    // returned together always belong to the same boids
+    fn positions<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray2<f32>> {
+        let values = self.simulation.components.positions.iter().flat_map(|position| position.value);
+
+        rows(py, values.collect())
+    }
+
+    // Unit length headings
+    fn headings<'py>(&self, py: Python<'py>) -> PyResult<&'py PyArray2<f32>> {
+        let values = self.simulation.components.directions.iter().flat_map(|forward| forward.direction);
+
+        rows(py, values.collect())
+    }
+
+    fn species<'py>(&self, py: Python<'py>) -> &'py PyArray1<usize> {
+        let species = self.simulation.components.species.iter().map(|species| species.id);
+
+        PyArray1::from_iter(py, species)
+    }
+
+    // False for boids eaten by a predator, they stay until they are despawned
+    fn alive<'py>(&self, py: Python<'py>) -> &'py PyArray1<bool> {
+        let alive = self.simulation.components.behaviors.iter().map(|behavior| behavior.state != BehaviorState::Dead);
+
+        PyArray1::from_iter(py, alive)
+    }
+
+    // Order parameters of the living boids, see boids_core::metrics::Metrics
+    fn metrics<'py>(&mut self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let metrics = self.metrics.measure(&self.simulation);
+        let dict = PyDict::new(py);
+
+        dict.set_item("polarization", metrics.polarization)?;
+        dict.set_item("milling", metrics.milling)?;
+        dict.set_item("nearest_neighbor", metrics.nearest_neighbor)?;
+        dict.set_item("clusters", metrics.clusters)?;
+        dict.set_item("mean_speed", metrics.mean_speed)?;
+
+        Ok(dict)
+    }
+
+    fn __len__(&self) -> usize {
+        self.simulation.components.len()
+    }
+
+    #[getter]
+    fn seed(&self) -> u64 {
+        self.simulation.seed
+    }
+
+    #[getter]
+    fn world_size(&self) -> (f32, f32) {
+        let [w, h] = self.simulation.size();
+        (w, h)
+    }
+
+    // Boids are spawned or despawned at random to reach it
+    #[getter]
+    fn population(&self) -> usize {
+        self.simulation.components.len()
+    }
+
+    #[setter]
+    fn set_population(&mut self, count: usize) {
+        self.simulation.set_population(count);
+    }
+
+    #[getter]
+    fn alignment(&self) -> f32 {
+        self.simulation.weights.alignment
+    }
+
+    #[setter]
+    fn set_alignment(&mut self, value: f32) {
+        self.simulation.weights.alignment = value;
+    }
+
+    #[getter]
+    fn cohesion(&self) -> f32 {
+        self.simulation.weights.cohesion
+    }
+
+    #[setter]
+    fn set_cohesion(&mut self, value: f32) {
+        self.simulation.weights.cohesion = value;
+    }
+
+    #[getter]
+    fn separation(&self) -> f32 {
+        self.simulation.weights.separation
+    }
+
+    #[setter]
+    fn set_separation(&mut self, value: f32) {
+        self.simulation.weights.separation = value;
+    }
+
+    #[getter]
+    fn speed(&self) -> f32 {
+        self.simulation.speed
+    }
+
+    #[setter]
+    fn set_speed(&mut self, value: f32) {
+        self.simulation.speed = value;
+    }
+
+    #[getter]
+    fn perception_radius(&self) -> f32 {
+        self.simulation.perception_radius
+    }
+
+    #[setter]
+    fn set_perception_radius(&mut self, value: f32) {
+        self.simulation.set_perception_radius(value);
+    }
+
+    // Replaces the interaction matrix with the one of a preset
+    fn set_preset(&mut self, name: &str) -> PyResult<()> {
+        let preset = InteractionPreset::from_name(name)
+            .ok_or_else(|| PyValueError::new_err(format!("Unknown preset {}", name)))?;
+
+        self.simulation.set_interaction_preset(preset);
+
+        Ok(())
+    }
+
+    // Same settings from another seed, or a random one without it
+    #[pyo3(signature = (seed = None))]
+    fn restarted(&self, seed: Option<u64>) -> PySimulation {
+        PySimulation {
+            simulation: self.simulation.restarted(seed.unwrap_or_else(rand::random)),
+            profiler: Profiler::new(),
+            metrics: MetricsScratch::new(),
+        }
+    }
+}
+
+// Two columns of x and y
+fn rows(py: Python<'_>, values: Vec<f32>) -> PyResult<&PyArray2<f32>> {
+    let count = values.len() / 2;
+
+    PyArray1::from_vec(py, values).reshape([count, 2])
+}
+
+#[pymodule]
+fn boids(_py: Python<'_>, module: &PyModule) -> PyResult<()> {
+    module.add_class::<PySimulation>()?;
+    module.add("SPECIES_COUNT", SPECIES_COUNT)?;
+
+    Ok(())
+}
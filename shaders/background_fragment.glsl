@@ -0,0 +1,14 @@
+#version 140
+
+in vec2 uv;
+
+uniform sampler2D image;
+uniform vec3 tint;
+
+out vec4 color;
+
+void main() {
+    vec4 texel = texture(image, uv);
+
+    color = vec4(texel.rgb * tint, texel.a);
+}
@@ -0,0 +1,15 @@
+#version 140
+
+// Unit quad stretched over the whole render target
+in vec2 position;
+
+// Part of the image on screen, scaled around its center and then shifted
+uniform float uv_scale;
+uniform vec2 uv_offset;
+
+out vec2 uv;
+
+void main() {
+    uv = 0.5 + (position - 0.5) * uv_scale + uv_offset;
+    gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+}
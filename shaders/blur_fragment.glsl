@@ -0,0 +1,23 @@
+#version 140
+
+in vec2 uv;
+
+uniform sampler2D image;
+// One texel along the blur direction
+uniform vec2 direction;
+
+out vec4 color;
+
+// Gaussian weights of the center and the four texels to each side
+const float WEIGHTS[5] = float[](0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+
+void main() {
+    vec3 sum = texture(image, uv).rgb * WEIGHTS[0];
+
+    for (int i = 1; i < 5; i++) {
+        sum += texture(image, uv + direction * float(i)).rgb * WEIGHTS[i];
+        sum += texture(image, uv - direction * float(i)).rgb * WEIGHTS[i];
+    }
+
+    color = vec4(sum, 1.0);
+}
@@ -0,0 +1,15 @@
+#version 140
+
+in vec3 vertex_color;
+in vec2 local;
+
+// Fades boids out from their center, blended they read as density instead of clumps
+uniform bool soft;
+
+out vec4 color;
+
+void main() {
+    float alpha = soft ? 1.0 - smoothstep(0.0, 1.0, length(local)) : 1.0;
+
+    color = vec4(vertex_color, alpha);
+}
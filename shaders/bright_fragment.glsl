@@ -0,0 +1,17 @@
+#version 140
+
+in vec2 uv;
+
+uniform sampler2D image;
+// Brightness below which nothing blooms
+uniform float threshold;
+
+out vec4 color;
+
+void main() {
+    vec3 c = texture(image, uv).rgb;
+    float brightness = max(c.r, max(c.g, c.b));
+    float amount = max(brightness - threshold, 0.0) / max(1.0 - threshold, 0.0001);
+
+    color = vec4(c * amount, 1.0);
+}
@@ -0,0 +1,13 @@
+#version 140
+
+in vec2 uv;
+
+uniform sampler2D scene;
+uniform sampler2D bloom;
+uniform float intensity;
+
+out vec4 color;
+
+void main() {
+    color = vec4(texture(scene, uv).rgb + texture(bloom, uv).rgb * intensity, 1.0);
+}
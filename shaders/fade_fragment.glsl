@@ -0,0 +1,9 @@
+#version 140
+
+uniform vec4 fade_color;
+
+out vec4 color;
+
+void main() {
+    color = fade_color;
+}
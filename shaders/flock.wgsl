@@ -0,0 +1,86 @@
+// The flock pass of the wgpu backend, does what vertex.glsl and boid_fragment.glsl do for glium
+
+struct Globals {
+    perspective: mat4x4<f32>,
+    // Extra length along the heading per unit of speed, the width shrinks to keep the area
+    velocity_stretch: f32,
+    // Part of the width folded in at the bottom of a wing beat
+    flap_amplitude: f32,
+    // Half the size of a boid before scaling
+    agent_radius: f32,
+    drawn_shape: u32,
+    // Fades boids out from their center
+    soft: u32,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+struct Vertex {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+// Packed boid instance, the tint is in the first three bytes and the shape in the last
+struct Instance {
+    @location(2) value: vec2<f32>,
+    @location(3) heading: vec2<f32>,
+    @location(4) tint_shape: vec4<u32>,
+    @location(5) scale: f32,
+    @location(6) flap: f32,
+    @location(7) speed: f32,
+}
+
+struct Varyings {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    // Position in the mesh relative to its radius, for the soft falloff
+    @location(1) local: vec2<f32>,
+}
+
+const TAU: f32 = 6.28318530718;
+
+@vertex
+fn vs_main(vertex: Vertex, instance: Instance) -> Varyings {
+    var out: Varyings;
+
+    // Outside of the clip space, the whole triangle is culled
+    if (instance.tint_shape.w != globals.drawn_shape) {
+        out.position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        out.color = vec3<f32>(0.0);
+        out.local = vec2<f32>(0.0);
+        return out;
+    }
+
+    // Rotates the mesh so it points along the heading
+    let rotation = mat2x2<f32>(
+        instance.heading.x, instance.heading.y,
+        -instance.heading.y, instance.heading.x
+    );
+
+    // Folds the sides of the mesh towards its axis and back out, the axis itself stays put
+    let beat = 0.5 + 0.5 * sin(instance.flap * TAU);
+    let flapped = vec2<f32>(vertex.position.x, vertex.position.y * (1.0 - globals.flap_amplitude * beat));
+
+    let stretch = 1.0 + globals.velocity_stretch * instance.speed;
+    let stretched = flapped * vec2<f32>(stretch, 1.0 / stretch);
+
+    let tint = vec3<f32>(instance.tint_shape.xyz) / 255.0;
+    let world = rotation * stretched * instance.scale + instance.value;
+
+    out.position = globals.perspective * vec4<f32>(world, 0.0, 1.0);
+    out.color = vertex.color * tint;
+    out.local = vertex.position / globals.agent_radius;
+    return out;
+}
+
+@fragment
+fn fs_main(in: Varyings) -> @location(0) vec4<f32> {
+    var alpha = 1.0;
+
+    if (globals.soft != 0u) {
+        alpha = 1.0 - smoothstep(0.0, 1.0, length(in.local));
+    }
+
+    return vec4<f32>(in.color, alpha);
+}
@@ -0,0 +1,9 @@
+#version 140
+
+in vec4 deposit;
+
+out vec4 color;
+
+void main() {
+    color = deposit;
+}
@@ -0,0 +1,14 @@
+#version 140
+
+in vec2 boid_position;
+in vec2 boid_direction;
+
+uniform vec2 world_size;
+
+out vec4 deposit;
+
+void main() {
+    // Each boid adds its heading and a count of one to the texel it's in
+    deposit = vec4(boid_direction, 1.0, 0.0);
+    gl_Position = vec4(boid_position / world_size * 2.0 - 1.0, 0.0, 1.0);
+}
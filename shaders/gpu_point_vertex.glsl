@@ -0,0 +1,12 @@
+#version 140
+
+in vec2 boid_position;
+
+uniform mat4 perspective;
+
+out vec3 vertex_color;
+
+void main() {
+    vertex_color = vec3(1.0);
+    gl_Position = perspective * vec4(boid_position, 0.0, 1.0);
+}
@@ -0,0 +1,21 @@
+#version 140
+
+in vec2 position;
+in vec3 color;
+in vec2 boid_position;
+in vec2 boid_direction;
+
+uniform mat4 perspective;
+
+out vec3 vertex_color;
+
+void main() {
+    // Same rotation as the CPU simulation builds in vertex.glsl
+    mat2 rotation = mat2(
+        boid_direction.x, boid_direction.y,
+        -boid_direction.y, boid_direction.x
+    );
+
+    vertex_color = color;
+    gl_Position = perspective * vec4(rotation * position + boid_position, 0.0, 1.0);
+}
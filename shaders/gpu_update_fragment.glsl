@@ -0,0 +1,8 @@
+#version 140
+
+out vec4 color;
+
+void main() {
+    // Only the transform feedback output of the update pass matters
+    color = vec4(0.0);
+}
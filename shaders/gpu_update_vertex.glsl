@@ -0,0 +1,88 @@
+#version 140
+
+in vec2 boid_position;
+in vec2 boid_direction;
+
+// Sum of headings in xy and boid count in z for each texel of the world
+uniform sampler2D field;
+uniform vec2 world_size;
+uniform float delta_time;
+uniform float speed;
+
+uniform float alignment_weight;
+uniform float cohesion_weight;
+uniform float separation_weight;
+
+// How many texels around the boid are considered its neighborhood
+const int RADIUS = 2;
+// Spreads the fallback separation directions of boids evenly around the circle
+const float GOLDEN_ANGLE = 2.39996;
+
+out vec2 out_position;
+out vec2 out_direction;
+
+vec2 normalized_safe(vec2 v) {
+    float l = length(v);
+
+    if (l == 0.0) {
+        return vec2(0.0);
+    }
+
+    return v / l;
+}
+
+void main() {
+    vec2 texel = 1.0 / vec2(textureSize(field, 0));
+    vec2 uv = boid_position / world_size;
+
+    // The boid itself was deposited into the field too
+    vec4 own = texture(field, uv) - vec4(boid_direction, 1.0, 0.0);
+
+    vec2 heading = own.xy;
+    vec2 center = vec2(0.0);
+    float count = own.z;
+
+    for (int y = -RADIUS; y <= RADIUS; y++) {
+        for (int x = -RADIUS; x <= RADIUS; x++) {
+            if (x == 0 && y == 0) {
+                continue;
+            }
+
+            vec4 neighbors = texture(field, uv + vec2(x, y) * texel);
+
+            heading += neighbors.xy;
+            center += vec2(x, y) * neighbors.z;
+            count += neighbors.z;
+        }
+    }
+
+    vec2 res = boid_direction;
+
+    if (count > 0.0) {
+        // Alignment
+        res += normalized_safe(heading) * alignment_weight;
+
+        // Cohesion, towards the weighted center of the neighborhood
+        res += normalized_safe(center) * cohesion_weight;
+
+        // Separation, away from the other boids sharing the texel. Where they are within it
+        // isn't in the field, so the boid pushes off from the texel center, which spreads
+        // a crowded texel out. Boids right at the center get a direction of their own.
+        vec2 offset = fract(uv * vec2(textureSize(field, 0))) - 0.5;
+        float angle = float(gl_VertexID) * GOLDEN_ANGLE;
+        vec2 away = length(offset) > 0.01 ? normalize(offset) : vec2(cos(angle), sin(angle));
+
+        res += away * separation_weight * own.z / count;
+    }
+
+    out_direction = normalized_safe(res);
+
+    if (out_direction == vec2(0.0)) {
+        out_direction = boid_direction;
+    }
+
+    // Wrap around the world
+    out_position = mod(boid_position + out_direction * speed * delta_time, world_size);
+
+    gl_Position = vec4(0.0, 0.0, 0.0, 1.0);
+}
@@ -0,0 +1,18 @@
+#version 140
+
+in vec2 uv;
+
+uniform sampler2D density;
+uniform float max_density;
+uniform float alpha;
+
+out vec4 color;
+
+const vec3 COOL_COLOR = vec3(0.2, 0.4, 1.0);
+const vec3 HOT_COLOR = vec3(1.0, 0.25, 0.15);
+
+void main() {
+    float t = clamp(texture(density, uv).r / max_density, 0.0, 1.0);
+
+    color = vec4(mix(COOL_COLOR, HOT_COLOR, t), alpha * t);
+}
@@ -0,0 +1,14 @@
+#version 140
+
+// Unit quad stretched over the whole world
+in vec2 position;
+
+uniform mat4 perspective;
+uniform vec2 world_size;
+
+out vec2 uv;
+
+void main() {
+    uv = position;
+    gl_Position = perspective * vec4(position * world_size, 0.0, 1.0);
+}
@@ -0,0 +1,14 @@
+#version 140
+
+// End of a line segment
+in vec2 position;
+in vec4 color;
+
+uniform mat4 perspective;
+
+out vec4 vertex_color;
+
+void main() {
+    vertex_color = color;
+    gl_Position = perspective * vec4(position, 0.0, 1.0);
+}
@@ -0,0 +1,16 @@
+#version 140
+
+// Position and tint of a packed boid instance, one vertex per boid
+in vec2 value;
+in vec3 tint;
+
+uniform mat4 perspective;
+// Brightness one boid adds, overlapping boids add up to show density
+uniform float alpha;
+
+out vec4 vertex_color;
+
+void main() {
+    vertex_color = vec4(tint, alpha);
+    gl_Position = perspective * vec4(value, 0.0, 1.0);
+}
@@ -0,0 +1,16 @@
+#version 140
+
+// Unit quad stretched over a rectangle
+in vec2 position;
+// Top left corner and size of the rectangle
+in vec4 rect;
+in vec4 fill;
+
+uniform mat4 perspective;
+
+out vec4 vertex_color;
+
+void main() {
+    vertex_color = fill;
+    gl_Position = perspective * vec4(rect.xy + position * rect.zw, 0.0, 1.0);
+}
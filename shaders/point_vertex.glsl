@@ -0,0 +1,17 @@
+#version 140
+
+// Position and tint of a packed boid instance, one vertex per boid
+in vec2 value;
+in vec3 tint;
+
+uniform mat4 perspective;
+
+out vec3 vertex_color;
+// Points are too small for the soft falloff, they stay solid
+out vec2 local;
+
+void main() {
+    vertex_color = tint;
+    local = vec2(0.0);
+    gl_Position = perspective * vec4(value, 0.0, 1.0);
+}
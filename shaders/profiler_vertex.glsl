@@ -0,0 +1,16 @@
+#version 140
+
+in vec2 position;
+in vec3 color;
+// Top left corner and size of the bar
+in vec4 rect;
+in vec3 tint;
+
+uniform mat4 perspective;
+
+out vec3 vertex_color;
+
+void main() {
+    vertex_color = color * tint;
+    gl_Position = perspective * vec4(rect.xy + position * rect.zw, 0.0, 1.0);
+}
@@ -0,0 +1,11 @@
+#version 140
+
+// Unit quad stretched over the whole render target
+in vec2 position;
+
+out vec2 uv;
+
+void main() {
+    uv = position;
+    gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+}
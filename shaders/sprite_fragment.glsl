@@ -0,0 +1,14 @@
+#version 140
+
+in vec2 sprite_uv;
+in vec3 vertex_color;
+
+uniform sampler2D sprite;
+
+out vec4 color;
+
+void main() {
+    vec4 texel = texture(sprite, sprite_uv);
+
+    color = vec4(texel.rgb * vertex_color, texel.a);
+}
@@ -0,0 +1,34 @@
+#version 140
+
+in vec2 position;
+in vec2 uv;
+// Packed boid instance, heading and tint arrive already normalized to floats
+in vec2 value;
+in vec2 heading;
+in vec3 tint;
+// Size of the boid relative to the mesh
+in float scale;
+// Speed relative to the normal flying speed
+in float speed;
+
+uniform mat4 perspective;
+// Extra length along the heading per unit of speed, the width shrinks to keep the area
+uniform float velocity_stretch;
+
+out vec2 sprite_uv;
+out vec3 vertex_color;
+
+void main() {
+    // Rotates the quad so the texture points along the heading
+    mat2 rotation = mat2(
+        heading.x, heading.y,
+        -heading.y, heading.x
+    );
+
+    float stretch = 1.0 + velocity_stretch * speed;
+    vec2 stretched = position * vec2(stretch, 1.0 / stretch);
+
+    sprite_uv = uv;
+    vertex_color = tint;
+    gl_Position = perspective * vec4(rotation * stretched * scale + value, 0.0, 1.0);
+}
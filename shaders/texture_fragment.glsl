@@ -0,0 +1,11 @@
+#version 140
+
+in vec2 uv;
+
+uniform sampler2D image;
+
+out vec4 color;
+
+void main() {
+    color = texture(image, uv);
+}
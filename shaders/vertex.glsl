@@ -2,13 +2,57 @@
 
 in vec2 position;
 in vec3 color;
-in mat4 transform;
+// Packed boid instance, heading and tint arrive already normalized to floats
+in vec2 value;
+in vec2 heading;
+in vec3 tint;
+// Size of the boid relative to the mesh
+in float scale;
+// Speed relative to the normal flying speed
+in float speed;
+// Point of the wing beat from 0 to 1
+in float flap;
+// Index of the boid's shape, other shapes are drawn in their own pass
+in uint shape;
 
 uniform mat4 perspective;
+// Extra length along the heading per unit of speed, the width shrinks to keep the area
+uniform float velocity_stretch;
+uniform uint drawn_shape;
+// Part of the width folded in at the bottom of a wing beat
+uniform float flap_amplitude;
+// Half the size of a boid before scaling
+uniform float agent_radius;
+
+const float TAU = 6.28318530718;
 
 out vec3 vertex_color;
+// Position in the mesh relative to its radius, for the soft falloff
+out vec2 local;
 
 void main() {
-    vertex_color = color;
-    gl_Position = perspective * transform * vec4(position, 0.0, 1.0);
+    // Outside of the clip space, the whole triangle is culled
+    if (shape != drawn_shape) {
+        vertex_color = vec3(0.0);
+        local = vec2(0.0);
+        gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+        return;
+    }
+
+    // Rotates the mesh so it points along the heading
+    mat2 rotation = mat2(
+        heading.x, heading.y,
+        -heading.y, heading.x
+    );
+
+    // Folds the sides of the mesh towards its axis and back out, the axis itself stays put
+    float beat = 0.5 + 0.5 * sin(flap * TAU);
+    vec2 flapped = vec2(position.x, position.y * (1.0 - flap_amplitude * beat));
+
+    float stretch = 1.0 + velocity_stretch * speed;
+    vec2 stretched = flapped * vec2(stretch, 1.0 / stretch);
+
+    vertex_color = color * tint;
+    local = position / agent_radius;
+    gl_Position = perspective * vec4(rotation * stretched * scale + value, 0.0, 1.0);
 }
@@ -1,147 +1,1898 @@
-use std::f32::consts::PI;
+use std::path::Path;
+use std::time::Instant;
 
-use glium::{Display, Frame, Program, Surface, VertexBuffer};
-use glium::glutin::dpi::PhysicalSize;
+use glium::{Display, Frame, Surface};
 use rand::Rng;
-use rand::prelude::ThreadRng;
+use rayon::ThreadPool;
+use tracing::{info_span, warn};
+use glium::glutin::dpi::PhysicalSize;
+use glium::glutin::event::{ElementState, KeyboardInput, ModifiersState, MouseButton, VirtualKeyCode};
 use vecmath::Matrix4;
 
+use crate::error::Result;
 use crate::graphics::*;
-use crate::data::*;
-use crate::systems::*;
-use crate::{AGENT_COUNT, AGENT_SIZE, INITIAL_DISPLAY_SIZE};
+use crate::graphics::background::Background;
+use crate::graphics::bloom::Bloom;
+use crate::graphics::capture::{read_frame, save_screenshot};
+use crate::graphics::debug_vectors::DebugVectors;
+use crate::graphics::flow_field::FlowFieldOverlay;
+use crate::graphics::flock::{FlockRenderer, FLOCK_FRAGMENT_SHADER, FLOCK_VERTEX_SHADER};
+use crate::graphics::gif_history::GifHistory;
+use crate::graphics::gpu_sim::GpuSimulation;
+use crate::graphics::grid_overlay::GridOverlay;
+use crate::graphics::heatmap::Heatmap;
+use crate::graphics::letterbox::Letterbox;
+use crate::graphics::minimap::Minimap;
+use crate::graphics::motion_blur::MotionBlur;
+use crate::graphics::hud::{Hud, MARGIN};
+use crate::graphics::metric_plots::MetricPlots;
+use crate::graphics::profiler_graph::ProfilerGraph;
+use crate::graphics::renderer::{DrawTo, Renderer};
+use crate::graphics::recorder::Recorder;
+use crate::autosave::Autosave;
+use crate::cli::Cli;
+use crate::network::{NetworkClient, NetworkHost};
+use crate::websocket::WebSocketStream;
+use crate::live_control::{Control, LiveControl};
+use crate::audio::{AudioReactive, Band};
+use crate::sonification::Sonification;
+use crate::console::{Command, Console, HELP};
+use crate::presets::Presets;
+use crate::trajectory::{TrajectoryFormat, TrajectoryRecorder};
+use crate::graphics::selection_box::SelectionBox;
+use crate::graphics::shader_watch::ShaderWatcher;
+use crate::graphics::world_geometry::WorldGeometry;
+use boids_core::data::*;
+use crate::graphics::vertices::*;
+use crate::comparison::{Comparison, Half};
+use crate::config::Config;
+use boids_core::species::*;
+use boids_core::grid::CellOrder;
+use boids_core::diagnostics::MemoryDiagnostics;
+use boids_core::events::{Event, Parameter};
+use boids_core::metrics::{Metrics, MetricsScratch};
+use crate::governor::PopulationGovernor;
+use boids_core::profiler::{Profiler, Stage};
+use crate::camera::Camera;
+use crate::coloring::ColorMode;
+use boids_core::components::{get_random_directions, get_random_positions, BoidHandle};
+use boids_core::save::{load_simulation, save_simulation};
+use boids_core::simulation::Simulation;
+use crate::snapshot::RenderSnapshot;
+use crate::pacing::{FramePacer, SimulationClock};
+use crate::theme::{find_theme, Theme, THEMES};
+use crate::threads::ThreadSettings;
+use boids_core::SPECIES_COUNT;
+use crate::{GPU_AGENT_COUNT, TARGET_FPS, HOVER_RADIUS, RECORDING_FPS, UI_SCALE, AGENT_SHAPE, BACKGROUND_PATH, BACKGROUND_TINT, VELOCITY_STRETCH, COMPARISON_WEIGHTS, TUNING_FACTOR, PERCEPTION_RADIUS_RANGE, FIXED_TIMESTEP, TIME_SCALE_FACTOR, SAVE_PATH};
 
 pub struct App {
     pub display: Display,
+    // Size of the window in physical pixels
     pub display_size: PhysicalSize<u32>,
-    
+    // Physical pixels per screen pixel, the window's scale factor times UI_SCALE
+    pub scale_factor: f64,
+    // Size of the window in screen pixels, overlays are laid out in them.
+    // The world keeps its configured size and is fit into the window by the camera.
+    pub screen_size: PhysicalSize<u32>,
+    pub window_mode: WindowMode,
+    // Modifier keys currently held
+    pub modifiers: ModifiersState,
+
+    // Screen space projection for overlays
     pub perspective: Matrix4<f32>,
-    pub shader: Program,
-    pub agent_mesh: Mesh,
-    pub instance_buffer: VertexBuffer<Transform>,
+    // Projection of the world through the camera
+    pub view: Matrix4<f32>,
+    pub camera: Camera,
+    // Boid the camera follows, its neighborhood is highlighted
+    pub selected: Option<BoidHandle>,
+    // Mouse position in the window
+    pub cursor: Option<[f32; 2]>,
+    // Boids picked with a shift drag, group commands act on them
+    pub group: Vec<BoidHandle>,
+    // Screen position where a shift drag started, while the button is held
+    pub drag_start: Option<[f32; 2]>,
+    pub selection_box: SelectionBox,
+    pub flock_renderer: FlockRenderer,
+    // Image behind the world, only there when BACKGROUND_PATH is set and loads
+    pub background: Option<Background>,
+    // Hides the window outside the world
+    pub letterbox: Letterbox,
+    // Drawn under the CPU flock
+    pub heatmap: Heatmap,
+    pub world_geometry: WorldGeometry,
+    // The world is drawn through it while enabled
+    pub motion_blur: MotionBlur,
+    pub bloom: Bloom,
 
-    pub components: Components,
-}
+    pub simulation: Simulation,
+    // Drawn while the next frame is simulated into the back snapshot
+    pub front_snapshot: RenderSnapshot,
+    pub back_snapshot: RenderSnapshot,
+    // Simulates the next frame while the current one is drawn
+    pub pipelined: bool,
+    // All parallel systems run in this pool instead of the global one
+    pub thread_pool: ThreadPool,
 
-pub struct Components {
-    pub directions: Vec<Forward>,
-    pub positions: Vec<Position>,
-    pub transforms: Vec<Transform>,
+    pub color_mode: ColorMode,
+    // Index into THEMES
+    pub theme: usize,
+
+    // Entry of the interaction matrix edited with the keyboard
+    pub interaction_cursor: usize,
+    // System of the schedule turned on and off with the keyboard
+    pub system_cursor: usize,
+
+    pub governor: PopulationGovernor,
+    pub profiler: Profiler,
+    pub profiler_graph: ProfilerGraph,
+    pub metric_plots: MetricPlots,
+    pub grid_overlay: GridOverlay,
+    pub debug_vectors: DebugVectors,
+    pub minimap: Minimap,
+    pub flow_field: FlowFieldOverlay,
+    pub hud: Hud,
+    pub memory: MemoryDiagnostics,
+    // Saves the next finished frame
+    pub screenshot_requested: bool,
+    // Every finished frame is recorded while set
+    pub recorder: Option<Recorder>,
+    // Samples the boids every few frames while set, see TrajectoryRecorder
+    pub trajectory: Option<TrajectoryRecorder>,
+    pub trajectory_format: TrajectoryFormat,
+    pub trajectory_interval: u64,
+    // Saves checkpoints of the CPU simulation while enabled
+    pub autosave: Autosave,
+    // Sends the CPU simulation to clients, see NetworkHost
+    pub network_host: Option<NetworkHost>,
+    // Replaces the simulation with the snapshots of a host, see NetworkClient
+    pub network_client: Option<NetworkClient>,
+    // Streams the CPU simulation to WebSocket viewers, see WebSocketStream
+    pub websocket: Option<WebSocketStream>,
+    // Parameters set by OSC and MIDI controllers
+    pub live_control: LiveControl,
+    // Lets the microphone drive the CPU simulation while enabled
+    pub audio: AudioReactive,
+    // Plays the metrics of the CPU simulation while enabled
+    pub sonification: Sonification,
+    // Takes the keyboard while open
+    pub console: Console,
+    // Named parameter sets of the CPU simulation cycled with Up
+    pub presets: Presets,
+    // Order parameters of the flock shown on the HUD and the plots or played, only measured while in use
+    pub metrics: Metrics,
+    pub metrics_scratch: MetricsScratch,
+    pub gif_history: GifHistory,
+    // Reloads the flock shaders when they change on disk
+    pub shader_watcher: ShaderWatcher,
+    // First line of the last failed shader reload, shown until a reload succeeds
+    pub shader_error: Option<String>,
+    // Holds the render loop to the frame rate cap
+    pub pacer: FramePacer,
+    // Vsync asked for, the window loop creates the display again when it differs from the current one
+    pub vsync: bool,
+    // Decides how many fixed steps a frame simulates, and pauses the simulation
+    pub clock: SimulationClock,
+
+    // Replaces the CPU simulation while enabled
+    pub gpu_simulation: Option<GpuSimulation>,
+    // Second simulation drawn next to the main one while comparing weights
+    pub comparison: Option<Comparison>,
 }
 
-fn get_random_positions(count: usize, rng: &mut ThreadRng) -> Vec<Position> {
-    let mut positions = Vec::with_capacity(count);
+impl App {
+    pub fn new(display: Display, threads: ThreadSettings, pacer: FramePacer, config: &Config) -> Result<App> {
+        let (display_size, scale_factor) = {
+            let gl_window = display.gl_window();
+            let window = gl_window.window();
 
-    for _ in 0..count {
-        positions.push(Position {
-            value: [
-                rng.gen_range(0.0..INITIAL_DISPLAY_SIZE[0] as f32),
-                rng.gen_range(0.0..INITIAL_DISPLAY_SIZE[1] as f32),
-            ]
+            (window.inner_size(), window.scale_factor() * UI_SCALE)
+        };
+
+        let screen_size = scaled_size(display_size, scale_factor);
+
+        let simulation = config.create_simulation();
+
+        let thread_pool = threads.build_pool()?;
+        threads.pin_current_thread();
+
+        let theme = find_theme(&config.colors.theme);
+
+        let mut front_snapshot = RenderSnapshot::default();
+        let species_shapes = [AGENT_SHAPE; SPECIES_COUNT];
+        thread_pool.install(|| {
+            front_snapshot.copy_from(&simulation, ColorMode::Plain, &THEMES[theme], &species_shapes)
         });
+
+        let flock_renderer = FlockRenderer::new(&display, &front_snapshot, config.flock.agent_size)?;
+        let profiler_graph = ProfilerGraph::new(&display)?;
+        let metric_plots = MetricPlots::new(&display)?;
+        let grid_overlay = GridOverlay::new(&display)?;
+        let debug_vectors = DebugVectors::new(&display)?;
+        let minimap = Minimap::new(&display)?;
+        let flow_field = FlowFieldOverlay::new(&display)?;
+        let selection_box = SelectionBox::new(&display)?;
+        let hud = Hud::new(&display)?;
+        let heatmap = Heatmap::new(&display, simulation.size())?;
+        let letterbox = Letterbox::new(&display, simulation.size())?;
+        let world_geometry = WorldGeometry::new(&display)?;
+        let background = BACKGROUND_PATH.and_then(|path| Background::load(&display, path));
+        let motion_blur = MotionBlur::new(&display)?;
+        let bloom = Bloom::new(&display)?;
+        let camera = Camera::new(simulation.size(), [screen_size.width as f32, screen_size.height as f32]);
+
+        let window_mode = set_window_mode(&display, config.window.mode);
+
+        Ok(App {
+            display,
+            display_size,
+            scale_factor,
+            screen_size,
+            window_mode,
+            modifiers: ModifiersState::default(),
+
+            perspective: perspective(screen_size.width, screen_size.height),
+            view: camera.view(),
+            camera,
+            selected: None,
+            cursor: None,
+            group: Vec::new(),
+            drag_start: None,
+            selection_box,
+            flock_renderer,
+            background,
+            letterbox,
+            heatmap,
+            world_geometry,
+            motion_blur,
+            bloom,
+
+            simulation,
+            front_snapshot,
+            back_snapshot: RenderSnapshot::default(),
+            pipelined: true,
+            thread_pool,
+
+            color_mode: ColorMode::Plain,
+            theme,
+
+            interaction_cursor: 0,
+            system_cursor: 0,
+
+            governor: PopulationGovernor::new(TARGET_FPS),
+            profiler: Profiler::new(),
+            profiler_graph,
+            metric_plots,
+            grid_overlay,
+            debug_vectors,
+            minimap,
+            flow_field,
+            hud,
+            memory: MemoryDiagnostics::new(),
+            screenshot_requested: false,
+            recorder: None,
+            trajectory: None,
+            trajectory_format: config.trajectory.format,
+            trajectory_interval: config.trajectory.interval,
+            autosave: Autosave::new(config.autosave.interval, config.autosave.keep),
+            network_host: None,
+            network_client: None,
+            websocket: None,
+            live_control: LiveControl::new(),
+            audio: AudioReactive::new(config.audio.clone()),
+            sonification: Sonification::new(),
+            console: Console::new(),
+            presets: Presets::new(&config.presets, &config.path),
+            metrics: Metrics::default(),
+            metrics_scratch: MetricsScratch::new(),
+            gif_history: GifHistory::new(),
+            shader_watcher: ShaderWatcher::new(&[FLOCK_VERTEX_SHADER, FLOCK_FRAGMENT_SHADER]),
+            shader_error: None,
+            pacer,
+            vsync: config.window.vsync,
+            clock: SimulationClock::default(),
+
+            gpu_simulation: None,
+            comparison: None,
+        })
     }
 
-    positions
-}
+    pub fn theme(&self) -> &'static Theme {
+        &THEMES[self.theme]
+    }
+
+    // Background of the theme in the current ambient light
+    pub fn background_color(&self) -> [f32; 4] {
+        self.simulation.day_cycle.shade_rgba(self.theme().background)
+    }
+
+    // Simulates and draws one frame.
+    // When pipelined, the front snapshot holds the result of the last update. It is drawn
+    // on this thread while the thread pool steps the simulation into the back snapshot,
+    // and the snapshots are swapped once both are done. Frames show up one step late.
+    // While recording every frame steps the same time, so the video plays at real speed.
+    pub fn frame(&mut self, dt: f32, target: &mut Frame) {
+        let dt = match self.recorder {
+            Some(_) => 1.0 / RECORDING_FPS as f32,
+            None => dt,
+        };
+
+        self.receive_snapshot();
+        self.live_control.apply(&mut self.simulation);
+        self.presets.update(&mut self.simulation, dt);
+        self.audio.update(&mut self.simulation, dt, self.clock.paused);
+        self.handle_events();
+        self.update_camera(dt);
+
+        let steps = self.clock.advance(dt);
+
+        if self.gpu_simulation.is_some() || !self.pipelined || self.comparison.is_some() {
+            self.update(steps);
+            self.measure_metrics(steps);
+            self.record_trajectory(steps);
+            self.autosave(steps);
+            self.send_snapshot(steps);
+            self.render(target);
+            return;
+        }
+
+        let background_color = self.background_color();
+        let simulation = &mut self.simulation;
+        let back_snapshot = &mut self.back_snapshot;
+        let color_mode = self.color_mode;
+        let theme = &THEMES[self.theme];
+        let selected = self.selected;
+        let group = &self.group;
+        let species_shapes = self.flock_renderer.species_shapes;
+        let profiler = &mut self.profiler;
+
+        let flock_renderer = &mut self.flock_renderer;
+        let background = self.background.as_ref();
+        let heatmap = &mut self.heatmap;
+        let world_geometry = &mut self.world_geometry;
+        let flow_field = &mut self.flow_field;
+        let motion_blur = &mut self.motion_blur;
+        let bloom = &mut self.bloom;
+        let front_snapshot = &self.front_snapshot;
+        let display = &self.display;
+        let view = self.view;
+
+        let mut upload_start = Instant::now();
+        let mut draw_start = Instant::now();
+        let mut draw_end = Instant::now();
 
-fn get_random_directions(count: usize, rng: &mut ThreadRng) -> Vec<Forward> {
-    let mut forwards = Vec::with_capacity(count);
+        self.thread_pool.in_place_scope(|scope| {
+            scope.spawn(|_| {
+                let _span = info_span!("simulate", steps).entered();
 
-    const TWO_PI: f32 = PI * 2.0;
-    let mut angle: f32;
+                for _ in 0..steps {
+                    simulation.update(FIXED_TIMESTEP, profiler);
+                }
 
-    for _ in 0..count {
-        angle = rng.gen_range(0.0..TWO_PI);
+                back_snapshot.copy_from(simulation, color_mode, theme, &species_shapes);
+                back_snapshot.highlight_group(simulation, group);
 
-        forwards.push(Forward {
-            direction: [angle.cos(), angle.sin()]
+                if let Some(handle) = selected {
+                    back_snapshot.highlight_neighborhood(simulation, handle);
+                }
+            });
+
+            let upload = info_span!("upload").entered();
+            upload_start = Instant::now();
+            heatmap.update(&front_snapshot.instances);
+            let uploaded = world_geometry.upload(display, front_snapshot)
+                .and(flow_field.upload(display, front_snapshot))
+                .and(flock_renderer.upload(display, front_snapshot));
+            log_failed_pass("upload", uploaded);
+            upload.exit();
+
+            let _draw = info_span!("draw").entered();
+            draw_start = Instant::now();
+            let world = World::Cpu { background, heatmap, world_geometry, flock_renderer };
+            log_failed_pass("world", draw_world(display, target, motion_blur, bloom, &world, view, background_color));
+            draw_end = Instant::now();
         });
+
+        self.profiler.record_duration(Stage::Upload, draw_start - upload_start);
+        self.profiler.record_duration(Stage::Draw, draw_end - draw_start);
+
+        std::mem::swap(&mut self.front_snapshot, &mut self.back_snapshot);
+        self.measure_metrics(steps);
+        self.record_trajectory(steps);
+        self.autosave(steps);
+        self.send_snapshot(steps);
+
+        self.render_overlays(target, true);
     }
 
-    forwards
-}
+    fn render(&mut self, target: &mut Frame) {
+        if let Some(gpu_simulation) = &self.gpu_simulation {
+            let _span = info_span!("draw").entered();
+            let t = Instant::now();
+            let world = World::Gpu {
+                background: self.background.as_ref(),
+                gpu_simulation,
+                mesh: self.flock_renderer.mesh(),
+            };
+            let background = self.background_color();
+            let drawn = draw_world(&self.display, target, &mut self.motion_blur, &mut self.bloom, &world, self.view, background);
+            log_failed_pass("world", drawn);
+            self.profiler.record(Stage::Draw, t);
+        }
+        else {
+            let front_snapshot = &mut self.front_snapshot;
+            let simulation = &self.simulation;
+            let color_mode = self.color_mode;
+            let theme = &THEMES[self.theme];
+            let species_shapes = self.flock_renderer.species_shapes;
+            self.thread_pool.install(|| {
+                front_snapshot.copy_from(simulation, color_mode, theme, &species_shapes)
+            });
 
-impl App {
-    pub fn new(display: Display) -> App {
-        let shader = load_program(
-            &display,
-            "shaders/vertex.glsl",
-            "shaders/fragment.glsl"
-        );
+            self.front_snapshot.highlight_group(&self.simulation, &self.group);
 
-        let (vertices, indices) = create_agent_shape(
-            AGENT_SIZE, 
-            [1.0, 1.0, 1.0]
-        );
-        let agent_mesh = create_mesh(&display, &vertices, &indices);
+            if let Some(handle) = self.selected {
+                self.front_snapshot.highlight_neighborhood(&self.simulation, handle);
+            }
+
+            let upload = info_span!("upload").entered();
+            let t = Instant::now();
+            self.heatmap.update(&self.front_snapshot.instances);
+            let uploaded = self.world_geometry.upload(&self.display, &self.front_snapshot)
+                .and(self.flow_field.upload(&self.display, &self.front_snapshot))
+                .and(self.flock_renderer.upload(&self.display, &self.front_snapshot));
+            log_failed_pass("upload", uploaded);
+
+            if let Some(comparison) = &mut self.comparison {
+                let main = (&self.flock_renderer, &self.heatmap, &self.world_geometry);
+                log_failed_pass("comparison upload", comparison.upload(&self.display, color_mode, theme, main));
+            }
+
+            self.profiler.record(Stage::Upload, t);
+            upload.exit();
+
+            let _span = info_span!("draw").entered();
+            let t = Instant::now();
+
+            if self.comparison.is_some() {
+                let drawn = self.render_comparison(target);
+                log_failed_pass("comparison", drawn);
+            }
+            else {
+                let world = World::Cpu {
+                    background: self.background.as_ref(),
+                    heatmap: &self.heatmap,
+                    world_geometry: &self.world_geometry,
+                    flock_renderer: &self.flock_renderer,
+                };
+                let background = self.background_color();
+                let drawn = draw_world(&self.display, target, &mut self.motion_blur, &mut self.bloom, &world, self.view, background);
+                log_failed_pass("world", drawn);
+            }
+
+            self.profiler.record(Stage::Draw, t);
+        }
+
+        // World overlays follow the camera, which the comparison doesn't use
+        self.render_overlays(target, self.comparison.is_none());
+    }
+
+    // A pass that fails is skipped for this frame, the passes after it are still drawn
+    fn render_overlays(&mut self, target: &mut Frame, world_overlays: bool) {
+        let _span = info_span!("overlays").entered();
+
+        if world_overlays {
+            log_failed_pass("letterbox", self.letterbox.draw(target, self.view, None));
+            let drawn = self.render_flow_field(target);
+            log_failed_pass("flow field", drawn);
+            let drawn = self.render_grid_overlay(target);
+            log_failed_pass("grid overlay", drawn);
+            let drawn = self.render_debug_vectors(target);
+            log_failed_pass("debug vectors", drawn);
+            let drawn = self.render_minimap(target);
+            log_failed_pass("minimap", drawn);
+        }
 
-        let mut rng = rand::thread_rng();
+        let drawn = self.render_selection_box(target);
+        log_failed_pass("selection box", drawn);
+        let drawn = self.render_profiler(target);
+        log_failed_pass("profiler", drawn);
+        let drawn = self.render_metric_plots(target);
+        log_failed_pass("metric plots", drawn);
+        let drawn = self.render_hud(target);
+        log_failed_pass("HUD", drawn);
+        let drawn = self.render_console(target);
+        log_failed_pass("console", drawn);
+    }
 
-        let components = Components {
-            directions: get_random_directions(AGENT_COUNT, &mut rng),
-            positions: get_random_positions(AGENT_COUNT, &mut rng),
-            transforms: vec![default_transform(); AGENT_COUNT]
+    // Main simulation on the left half of the window and the comparison on the right,
+    // each showing the whole world. The letterboxes are drawn last, as both worlds can
+    // reach a little into the other half.
+    fn render_comparison(&mut self, target: &mut Frame) -> Result<()> {
+        let comparison = match &self.comparison {
+            Some(comparison) => comparison,
+            None => return Ok(()),
         };
 
-        let instance_buffer = VertexBuffer::dynamic(
-            &display, 
-            &components.transforms
-        ).unwrap();
+        let screen = [self.screen_size.width as f32, self.screen_size.height as f32];
+        let world_size = self.simulation.size();
+        let left = Half::Left.view(world_size, screen);
+        let right = Half::Right.view(world_size, screen);
 
-        App {
-            display,
-            display_size: PhysicalSize {
-                width: INITIAL_DISPLAY_SIZE[0],
-                height: INITIAL_DISPLAY_SIZE[1]
-            },
+        if let Some(background) = &self.background {
+            background.draw(target)?;
+        }
+
+        let world = World::Cpu {
+            background: None,
+            heatmap: &self.heatmap,
+            world_geometry: &self.world_geometry,
+            flock_renderer: &self.flock_renderer,
+        };
+        world.draw(target, left)?;
+        comparison.draw(target, right)?;
 
-            perspective: perspective(
-                INITIAL_DISPLAY_SIZE[0], 
-                INITIAL_DISPLAY_SIZE[1]
-            ),
-            shader,
-            agent_mesh,
-            instance_buffer,
+        Half::Left.draw_letterbox(target, &self.letterbox, left)?;
+        Half::Right.draw_letterbox(target, &self.letterbox, right)
+    }
 
-            components
+    // Restarts the main simulation next to one with COMPARISON_WEIGHTS, both from the same seed
+    fn toggle_comparison(&mut self) {
+        if self.comparison.take().is_some() {
+            println!("Comparison: false");
+            return;
         }
+
+        if self.gpu_simulation.is_some() {
+            println!("Comparison needs the CPU simulation");
+            return;
+        }
+
+        // Drawn from the running simulation, so seeded runs compare the same flocks every time
+        let seed = self.simulation.rng.gen();
+        let simulation = self.simulation.restarted(seed);
+
+        let comparison = match Comparison::new(
+            &self.display,
+            &simulation,
+            seed,
+            COMPARISON_WEIGHTS,
+            self.flock_renderer.agent_size()
+        ) {
+            Ok(comparison) => comparison,
+            Err(error) => {
+                println!("Could not start the comparison: {}", error);
+                return;
+            }
+        };
+
+        self.simulation = simulation;
+        self.comparison = Some(comparison);
+        self.selected = None;
+        self.group.clear();
+
+        println!("Comparison: true, {:?} against {:?}", self.simulation.weights, COMPARISON_WEIGHTS);
     }
 
+    // The GPU simulation has no boid handles, the camera rests on the whole world there
+    fn update_camera(&mut self, dt: f32) {
+        let components = &self.simulation.components;
 
-    pub fn render(&self, target: &mut Frame) {
-        self.instance_buffer.write(&self.components.transforms);
+        let target = self.selected
+            .filter(|_| self.gpu_simulation.is_none())
+            .and_then(|handle| components.index(handle))
+            .filter(|index| components.behaviors[*index].state != BehaviorState::Dead)
+            .map(|index| components.positions[index].value);
 
-        target.draw(
-            (&self.agent_mesh.v_buffer, self.instance_buffer.per_instance().unwrap()),
-            &self.agent_mesh.i_buffer,
-            &self.shader,
-            &uniform! {
-                perspective: self.perspective,
-            },
-            &Default::default()
-        ).unwrap();
+        // Selected boid is gone, release the camera
+        if target.is_none() {
+            self.selected = None;
+        }
+
+        self.camera.update(dt, target, self.simulation.size());
+
+        if let Some(background) = &mut self.background {
+            background.follow(&self.camera, self.simulation.size());
+            background.tint = self.simulation.day_cycle.shade(BACKGROUND_TINT);
+        }
+        self.view = self.camera.view();
     }
 
-    pub fn update(&mut self, dt: f32) {
-        boid_system(&self.components.positions, &mut self.components.directions);
+    // Reacts to the events of the last frame and opens the next one
+    fn handle_events(&mut self) {
+        self.simulation.events.next_frame();
 
-        forward_system(dt, 50.0, &mut self.components.positions, &self.components.directions);
+        if let Some(comparison) = &mut self.comparison {
+            comparison.simulation.events.next_frame();
+        }
 
-        wrap_screen_system(&mut self.components.positions, &self.display_size);
+        let eaten = self.simulation.events.iter()
+            .any(|event| matches!(event, Event::BoidEaten(handle) if Some(*handle) == self.selected));
 
-        caluclate_transform_system(
-            &mut self.components.transforms, 
-            &self.components.positions, 
-            &self.components.directions
-        );
+        // The camera moves on instead of staying with a dead boid
+        if eaten {
+            self.select_random_boid();
+
+            println!("Followed boid was eaten, following boid: {:?}", self.selected);
+        }
+    }
+
+    // Picks a random living boid for the camera to follow
+    fn select_random_boid(&mut self) {
+        let components = &self.simulation.components;
+
+        let alive: Vec<usize> = (0..components.len())
+            .filter(|index| components.behaviors[*index].state != BehaviorState::Dead)
+            .collect();
+
+        if alive.is_empty() {
+            return;
+        }
+
+        let index = alive[self.simulation.rng.gen_range(0..alive.len())];
+
+        self.selected = Some(components.handles[index]);
+        self.camera.following = true;
+    }
+
+    fn render_flow_field(&mut self, target: &mut Frame) -> Result<()> {
+        if self.gpu_simulation.is_some() || !self.simulation.flow_field.enabled {
+            return Ok(());
+        }
+
+        self.flow_field.draw(target, self.view)
+    }
+
+    fn render_grid_overlay(&mut self, target: &mut Frame) -> Result<()> {
+        if self.gpu_simulation.is_some() {
+            return Ok(());
+        }
+
+        let query = self.hovered_boid().map(|index| {
+            (self.simulation.components.positions[index].value, self.simulation.perception_radius)
+        });
+
+        self.grid_overlay.draw(
+            &self.display,
+            target,
+            self.view,
+            &self.simulation.grid,
+            query,
+            self.camera.scale()
+        )
+    }
+
+    // Vectors of the selected boid, or of the hovered one if none is selected
+    fn render_debug_vectors(&mut self, target: &mut Frame) -> Result<()> {
+        if self.gpu_simulation.is_some() {
+            return Ok(());
+        }
+
+        let components = &self.simulation.components;
+        let selected = self.selected
+            .and_then(|handle| components.index(handle))
+            .or_else(|| self.hovered_boid());
+
+        self.debug_vectors.draw(&self.display, target, self.view, components, selected)
+    }
+
+    // Index of the living boid closest to the cursor, if it is within HOVER_RADIUS pixels
+    fn hovered_boid(&self) -> Option<usize> {
+        let cursor = self.cursor?;
+        let world = self.camera.screen_to_world(cursor);
+
+        self.simulation.pick(world, HOVER_RADIUS / self.camera.scale())
+    }
+
+    // Left click selects the boid under the cursor, clicking next to every boid clears the selection.
+    // Dragging with shift held picks the group of boids inside the dragged rectangle.
+    pub fn on_mouse_input(&mut self, button: MouseButton, state: ElementState) {
+        if button != MouseButton::Left || self.gpu_simulation.is_some() {
+            return;
+        }
+
+        if state == ElementState::Released {
+            if let (Some(start), Some(end)) = (self.drag_start.take(), self.cursor) {
+                self.select_group(start, end);
+            }
+            return;
+        }
+
+        if self.modifiers.shift() {
+            self.drag_start = self.cursor;
+            return;
+        }
+
+        self.selected = self.hovered_boid().map(|index| self.simulation.components.handles[index]);
+
+        match self.selected {
+            Some(handle) => println!("Selected boid {:?}", handle),
+            None => println!("Selection cleared"),
+        }
+    }
+
+    fn select_group(&mut self, start: [f32; 2], end: [f32; 2]) {
+        let a = self.camera.screen_to_world(start);
+        let b = self.camera.screen_to_world(end);
+        self.group = self.simulation.boids_in_area(a, b);
+
+        println!("Group: {} boids", self.group.len());
+    }
+
+    fn render_selection_box(&mut self, target: &mut Frame) -> Result<()> {
+        match (self.drag_start, self.cursor) {
+            (Some(start), Some(end)) => self.selection_box.draw(target, self.perspective, start, end),
+            _ => Ok(()),
+        }
+    }
+
+    // Takes the position in physical pixels
+    pub fn on_character(&mut self, character: char) {
+        if self.console.open {
+            self.console.type_character(character);
+        }
+    }
+
+    // Keys that edit the input, characters arrive through on_character
+    fn on_console_key(&mut self, key: Option<VirtualKeyCode>) {
+        match key {
+            Some(VirtualKeyCode::Grave) | Some(VirtualKeyCode::Escape) => self.console.toggle(),
+            Some(VirtualKeyCode::Back) => self.console.backspace(),
+            Some(VirtualKeyCode::Up) => self.console.previous(),
+            Some(VirtualKeyCode::Down) => self.console.next(),
+            Some(VirtualKeyCode::Return) => {
+                if let Some(command) = self.console.submit() {
+                    let output = self.run_command(command);
+                    self.console.print(output);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Commands act on the CPU simulation, returns what happened
+    fn run_command(&mut self, command: Command) -> String {
+        match command {
+            Command::Set(control) => control.apply(&mut self.simulation),
+            Command::Spawn(count) => {
+                let population = self.simulation.components.len() + count;
+                Control::Population(population).apply(&mut self.simulation)
+            }
+            Command::Seed(seed) => {
+                self.restart(seed);
+                format!("Seed: {}", seed)
+            }
+            Command::Preset(name) => self.presets.select(&name, &mut self.simulation).unwrap_or_else(|error| error),
+            Command::SavePreset(name) => self.presets.save(&name, &self.simulation),
+            Command::Save(path) => self.save(&path),
+            Command::Load(path) => self.load(&path),
+            Command::Help => HELP.join("\n"),
+        }
+    }
+
+    pub fn on_cursor_moved(&mut self, position: [f32; 2]) {
+        let scale = self.scale_factor as f32;
+        self.cursor = Some([position[0] / scale, position[1] / scale]);
+    }
+
+    fn render_minimap(&mut self, target: &mut Frame) -> Result<()> {
+        if self.gpu_simulation.is_some() {
+            return Ok(());
+        }
+
+        self.minimap.draw(
+            target,
+            self.perspective,
+            self.screen_size,
+            self.simulation.size(),
+            &self.camera,
+            self.flock_renderer.instances()
+        )
+    }
+
+    fn render_profiler(&mut self, target: &mut Frame) -> Result<()> {
+        if !self.profiler.enabled {
+            return Ok(());
+        }
+
+        self.profiler_graph.draw(
+            target,
+            self.perspective,
+            &self.profiler,
+            self.screen_size.height as f32
+        )
+    }
+
+    fn render_metric_plots(&mut self, target: &mut Frame) -> Result<()> {
+        self.metric_plots.draw(&self.display, target, self.perspective, self.screen_size, &mut self.hud)
+    }
+
+    // The console takes the place of the HUD while it is open
+    fn render_hud(&mut self, target: &mut Frame) -> Result<()> {
+        if !self.hud.enabled || self.console.open {
+            return Ok(());
+        }
+
+        let agent_count = match self.gpu_simulation {
+            Some(_) => GPU_AGENT_COUNT,
+            None => self.simulation.components.len(),
+        };
+
+        let mut lines = vec![
+            format!("FPS: {:.0}", self.hud.fps),
+            format!("Frame: {:.2} ms", self.hud.frame_time),
+            format!("Boids: {}", agent_count),
+            format!("Seed: {}", self.simulation.seed),
+            format!("Alignment: {:.2}", self.simulation.weights.alignment),
+            format!("Cohesion: {:.2}", self.simulation.weights.cohesion),
+            format!("Separation: {:.2}", self.simulation.weights.separation),
+            format!("Speed: {:.1}", self.simulation.speed),
+            format!("Perception: {:.1}", self.simulation.perception_radius),
+            format!("Time scale: {:.2}x", self.clock.time_scale),
+        ];
+
+        if self.gpu_simulation.is_none() {
+            lines.push(format!("Polarization: {:.2}", self.metrics.polarization));
+            lines.push(format!("Milling: {:.2}", self.metrics.milling));
+            lines.push(format!("Nearest neighbor: {:.1}", self.metrics.nearest_neighbor));
+            lines.push(format!("Clusters: {}", self.metrics.clusters));
+        }
+
+        if let Some(preset) = self.presets.current() {
+            lines.push(format!("Preset: {}", preset));
+        }
+
+        if self.audio.enabled {
+            lines.push(format!(
+                "Audio: {:.2}, bass {:.2}, treble {:.2}",
+                self.audio.level(Band::Loudness),
+                self.audio.level(Band::Bass),
+                self.audio.level(Band::Treble)
+            ));
+        }
+
+        if self.clock.paused {
+            lines.push("Paused, right arrow steps".to_string());
+        }
+
+        if let Some(error) = &self.shader_error {
+            lines.push(format!("Shader error: {}", error));
+        }
+
+        if let Some(error) = self.simulation.script.as_ref().and_then(|script| script.error.as_ref()) {
+            lines.push(format!("Script error: {}", error));
+        }
+
+        self.hud.draw(&self.display, target, self.perspective, &lines)
+    }
+
+    fn render_console(&mut self, target: &mut Frame) -> Result<()> {
+        if !self.console.open {
+            return Ok(());
+        }
+
+        let lines = self.console.lines();
+        self.hud.draw_text(&self.display, target, self.perspective, &lines, [MARGIN, MARGIN])
+    }
+
+    // Takes `steps` steps of FIXED_TIMESTEP
+    fn update(&mut self, steps: u32) {
+        let _span = info_span!("simulate", steps).entered();
+
+        if let Some(gpu_simulation) = &mut self.gpu_simulation {
+            let t = Instant::now();
+            let weights = self.simulation.weights;
+            let speed = self.simulation.speed;
+            let display = &self.display;
+
+            let stepped = (0..steps).try_for_each(|_| gpu_simulation.update(
+                display,
+                FIXED_TIMESTEP,
+                speed,
+                [weights.alignment, weights.cohesion, weights.separation]
+            ));
+            log_failed_pass("GPU simulation", stepped);
+
+            self.profiler.record(Stage::GpuSimulation, t);
+            return;
+        }
+
+        let simulation = &mut self.simulation;
+        let profiler = &mut self.profiler;
+        // Only the simulation of the comparison goes to the pool, its renderers can't leave this thread
+        let mut comparison = self.comparison.as_mut().map(|comparison| &mut comparison.simulation);
+        self.thread_pool.install(|| {
+            for _ in 0..steps {
+                simulation.update(FIXED_TIMESTEP, profiler);
+
+                if let Some(comparison) = &mut comparison {
+                    comparison.update(FIXED_TIMESTEP, profiler);
+                }
+            }
+        });
     }
 
+    // Only the view changes, the world keeps its size so the simulation doesn't depend on the window
     pub fn on_window_resize(&mut self, size: &PhysicalSize<u32>) {
         self.display_size = *size;
+        self.screen_size = scaled_size(*size, self.scale_factor);
+
+        let screen = [self.screen_size.width as f32, self.screen_size.height as f32];
+        self.camera.resize(screen, self.simulation.size());
 
         self.perspective = perspective(
-            self.display_size.width, 
-            self.display_size.height
+            self.screen_size.width,
+            self.screen_size.height
         );
     }
-}
\ No newline at end of file
+
+    // The window was created again, it can come back with another size and without the title
+    pub fn on_display_rebuilt(&mut self) {
+        let size = self.display.gl_window().window().inner_size();
+
+        self.on_window_resize(&size);
+        self.update_title();
+    }
+
+    // Moving to a monitor with another scale factor keeps overlays the same size on screen
+    pub fn on_scale_factor_changed(&mut self, scale_factor: f64, size: &PhysicalSize<u32>) {
+        self.scale_factor = scale_factor * UI_SCALE;
+        self.cursor = None;
+        self.on_window_resize(size);
+
+        println!("Scale factor: {:.2}", self.scale_factor);
+    }
+
+    pub fn on_keyboard(&mut self, input: &KeyboardInput) {
+        if input.state != ElementState::Pressed {
+            return;
+        }
+
+        if self.console.open {
+            return self.on_console_key(input.virtual_keycode);
+        }
+
+        if input.virtual_keycode == Some(VirtualKeyCode::Return) && self.modifiers.alt() {
+            self.switch_window_mode();
+            return;
+        }
+
+        if self.modifiers.ctrl() {
+            match input.virtual_keycode {
+                Some(VirtualKeyCode::S) => return println!("{}", self.save(SAVE_PATH)),
+                Some(VirtualKeyCode::L) => return println!("{}", self.load(SAVE_PATH)),
+                _ => {}
+            }
+        }
+
+        if let Some(key) = input.virtual_keycode {
+            if self.tune(key) {
+                return;
+            }
+        }
+
+        let simulation = &mut self.simulation;
+        let size = simulation.interactions.size();
+
+        match input.virtual_keycode {
+            Some(VirtualKeyCode::M) => {
+                simulation.set_interaction_preset(simulation.interaction_preset.next());
+
+                println!("Interaction preset: {:?}", simulation.interaction_preset);
+            }
+            Some(VirtualKeyCode::LBracket) => {
+                self.interaction_cursor = (self.interaction_cursor + size * size - 1) % (size * size);
+                self.print_interaction();
+            }
+            Some(VirtualKeyCode::RBracket) => {
+                self.interaction_cursor = (self.interaction_cursor + 1) % (size * size);
+                self.print_interaction();
+            }
+            Some(VirtualKeyCode::Tab) => {
+                self.system_cursor = (self.system_cursor + 1) % simulation.schedule.len();
+                self.print_system();
+            }
+            Some(VirtualKeyCode::Back) => {
+                let schedule = &mut simulation.schedule;
+                let enabled = !schedule.is_enabled(self.system_cursor);
+                schedule.set_enabled(self.system_cursor, enabled);
+
+                self.print_system();
+            }
+            Some(VirtualKeyCode::G) => self.toggle_gpu_simulation(),
+            Some(VirtualKeyCode::Space) => {
+                self.clock.toggle_pause();
+
+                println!("Paused: {}", self.clock.paused);
+            }
+            Some(VirtualKeyCode::Right) => self.clock.request_step(),
+            Some(VirtualKeyCode::Key9) => {
+                self.clock.scale_time(1.0 / TIME_SCALE_FACTOR);
+
+                println!("Time scale: {:.2}x", self.clock.time_scale);
+            }
+            Some(VirtualKeyCode::Key0) => {
+                self.clock.scale_time(TIME_SCALE_FACTOR);
+
+                println!("Time scale: {:.2}x", self.clock.time_scale);
+            }
+            Some(VirtualKeyCode::B) => {
+                simulation.spatial_backend = simulation.spatial_backend.next();
+
+                println!("Spatial index: {:?}", simulation.spatial_backend);
+            }
+            Some(VirtualKeyCode::N) => {
+                simulation.neighborhood = simulation.neighborhood.next();
+
+                println!("Neighborhood: {:?}", simulation.neighborhood);
+            }
+            Some(VirtualKeyCode::C) => {
+                simulation.cell_pairs = !simulation.cell_pairs;
+
+                println!("Cell pair steering: {} (in use: {})", simulation.cell_pairs, simulation.uses_cell_pairs());
+            }
+            Some(VirtualKeyCode::V) => {
+                simulation.neighbor_list.enabled = !simulation.neighbor_list.enabled;
+                simulation.neighbor_list.invalidate();
+
+                println!("Neighbor lists: {} (in use: {})", simulation.neighbor_list.enabled, simulation.uses_neighbor_list());
+            }
+            Some(VirtualKeyCode::O) => {
+                let order = match simulation.grid.order {
+                    CellOrder::RowMajor => CellOrder::Morton,
+                    CellOrder::Morton => CellOrder::RowMajor,
+                };
+
+                simulation.grid.set_order(order);
+
+                println!("Cell order: {:?}", order);
+            }
+            Some(VirtualKeyCode::I) => {
+                simulation.grid.update_mode = simulation.grid.update_mode.next();
+
+                println!("Grid update: {:?}", simulation.grid.update_mode);
+            }
+            Some(VirtualKeyCode::F) => {
+                self.governor.enabled = !self.governor.enabled;
+
+                println!("Frame rate governor: {}", self.governor.enabled);
+            }
+            Some(VirtualKeyCode::P) => {
+                self.profiler.enabled = !self.profiler.enabled;
+
+                println!("Profiler: {}", self.profiler.enabled);
+            }
+            Some(VirtualKeyCode::U) => {
+                self.memory.enabled = !self.memory.enabled;
+
+                println!("Memory diagnostics: {}", self.memory.enabled);
+            }
+            Some(VirtualKeyCode::T) => {
+                simulation.toggle_trails();
+
+                println!("Trails: {}", simulation.trails);
+            }
+            Some(VirtualKeyCode::H) => {
+                self.color_mode = self.color_mode.next();
+
+                println!("Color mode: {:?}", self.color_mode);
+            }
+            Some(VirtualKeyCode::A) => {
+                self.minimap.enabled = !self.minimap.enabled;
+
+                println!("Minimap: {}", self.minimap.enabled);
+            }
+            Some(VirtualKeyCode::W) => {
+                self.theme = (self.theme + 1) % THEMES.len();
+
+                println!("Theme: {}", self.theme().name);
+            }
+            Some(VirtualKeyCode::Y) => {
+                self.heatmap.enabled = !self.heatmap.enabled;
+
+                println!("Density heatmap: {}", self.heatmap.enabled);
+            }
+            Some(VirtualKeyCode::J) => {
+                self.select_random_boid();
+
+                println!("Following boid: {:?}", self.selected);
+            }
+            Some(VirtualKeyCode::Delete) => {
+                simulation.despawn_group(&self.group);
+
+                println!("Deleted {} boids", self.group.len());
+                self.group.clear();
+            }
+            Some(VirtualKeyCode::Key2) => {
+                simulation.set_group_species(&self.group, SPECIES_COUNT - 1);
+
+                println!("Turned {} boids into predators", self.group.len());
+            }
+            Some(VirtualKeyCode::Key3) => {
+                simulation.cycle_group_species(&self.group);
+
+                println!("Changed species of {} boids", self.group.len());
+            }
+            Some(VirtualKeyCode::Key4) => {
+                let pinned = simulation.toggle_group_leaders(&self.group);
+
+                println!("Leaders pinned: {} ({} boids)", pinned, self.group.len());
+            }
+            Some(VirtualKeyCode::Key5) => {
+                simulation.day_cycle.enabled = !simulation.day_cycle.enabled;
+
+                println!("Day cycle: {}", simulation.day_cycle.enabled);
+            }
+            Some(VirtualKeyCode::Key6) => {
+                self.toggle_comparison();
+            }
+            Some(VirtualKeyCode::Key7) => {
+                simulation.flow_field.enabled = !simulation.flow_field.enabled;
+
+                println!("Flow field: {}", simulation.flow_field.enabled);
+            }
+            Some(VirtualKeyCode::Key8) => {
+                simulation.boundary = simulation.boundary.next();
+
+                println!("Boundary: {:?}", simulation.boundary);
+            }
+            Some(VirtualKeyCode::Escape) => {
+                self.group.clear();
+
+                println!("Group cleared");
+            }
+            Some(VirtualKeyCode::K) => {
+                self.camera.following = !self.camera.following;
+
+                println!("Camera follow: {}", self.camera.following);
+            }
+            Some(VirtualKeyCode::X) => {
+                self.grid_overlay.enabled = !self.grid_overlay.enabled;
+
+                println!("Grid overlay: {}", self.grid_overlay.enabled);
+            }
+            Some(VirtualKeyCode::D) => {
+                self.debug_vectors.mode = self.debug_vectors.mode.next();
+
+                println!("Steering vectors: {:?}", self.debug_vectors.mode);
+            }
+            Some(VirtualKeyCode::Home) => {
+                self.metric_plots.enabled = !self.metric_plots.enabled;
+
+                println!("Metric plots: {}", self.metric_plots.enabled);
+            }
+            Some(VirtualKeyCode::F1) => {
+                self.hud.enabled = !self.hud.enabled;
+
+                println!("HUD: {}", self.hud.enabled);
+            }
+            Some(VirtualKeyCode::F2) => {
+                self.world_geometry.enabled = !self.world_geometry.enabled;
+
+                println!("World geometry: {}", self.world_geometry.enabled);
+            }
+            Some(VirtualKeyCode::F12) => self.screenshot_requested = true,
+            Some(VirtualKeyCode::F9) => self.toggle_recording(),
+            Some(VirtualKeyCode::Insert) => self.toggle_trajectory(),
+            Some(VirtualKeyCode::PageUp) => {
+                self.audio.toggle(&mut self.simulation);
+
+                println!("Audio reactive: {}", self.audio.enabled);
+            }
+            Some(VirtualKeyCode::Grave) => self.console.toggle(),
+            Some(VirtualKeyCode::Up) => println!("{}", self.presets.next(&mut self.simulation)),
+            Some(VirtualKeyCode::PageDown) => {
+                self.sonification.toggle();
+
+                println!("Sonification: {}", self.sonification.enabled);
+            }
+            Some(VirtualKeyCode::End) => {
+                self.autosave.toggle();
+
+                println!("Autosave: {}", self.autosave.enabled);
+            }
+            Some(VirtualKeyCode::F10) => {
+                self.gif_history.toggle();
+
+                println!("GIF capture: {}", self.gif_history.enabled);
+            }
+            Some(VirtualKeyCode::F11) => match self.gif_history.export() {
+                Ok(path) => println!("Saving last {:.1} s to {}", self.gif_history.seconds(), path.display()),
+                Err(error) => println!("Could not save GIF: {}", error),
+            },
+            Some(VirtualKeyCode::E) => {
+                self.motion_blur.toggle();
+
+                println!("Motion blur: {}", self.motion_blur.enabled);
+            }
+            Some(VirtualKeyCode::R) => {
+                self.bloom.enabled = !self.bloom.enabled;
+
+                println!("Bloom: {}", self.bloom.enabled);
+            }
+            Some(VirtualKeyCode::Q) => self.change_shape(),
+            Some(VirtualKeyCode::Key1) => {
+                let stretch = &mut self.flock_renderer.velocity_stretch;
+                *stretch = if *stretch > 0.0 { 0.0 } else { VELOCITY_STRETCH };
+
+                println!("Velocity stretch: {}", stretch);
+            }
+            Some(VirtualKeyCode::S) => {
+                self.flock_renderer.soft = !self.flock_renderer.soft;
+
+                println!("Soft boids: {}", self.flock_renderer.soft);
+            }
+            Some(VirtualKeyCode::Z) if self.modifiers.shift() => {
+                self.vsync = !self.vsync;
+
+                println!("Vsync: {}", self.vsync);
+            }
+            Some(VirtualKeyCode::Z) => {
+                self.pacer.toggle();
+
+                println!("Frame limit: {:?}", self.pacer.limit);
+            }
+            Some(VirtualKeyCode::L) => {
+                self.pipelined = !self.pipelined;
+
+                println!("Pipelined update: {}", self.pipelined);
+            }
+            Some(VirtualKeyCode::Minus) => self.change_interaction(-0.5),
+            Some(VirtualKeyCode::Equals) => self.change_interaction(0.5),
+            _ => {}
+        }
+    }
+
+    // Takes a requested screenshot of the finished frame, failures are only reported
+    fn take_screenshot(&mut self) {
+        if !self.screenshot_requested {
+            return;
+        }
+
+        self.screenshot_requested = false;
+
+        match save_screenshot(&self.display) {
+            Ok(path) => println!("Saved screenshot to {}", path.display()),
+            Err(error) => println!("Could not save screenshot: {}", error),
+        }
+    }
+
+    fn record_frame(&mut self) {
+        let recorder = match &mut self.recorder {
+            Some(recorder) => recorder,
+            None => return,
+        };
+
+        let result = read_frame(&self.display).and_then(|frame| recorder.write_frame(&frame));
+
+        if let Err(error) = result {
+            println!("Recording stopped: {}", error);
+            self.stop_recording();
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        if self.recorder.is_some() {
+            self.stop_recording();
+            return;
+        }
+
+        match Recorder::start(self.display_size.width, self.display_size.height) {
+            Ok(recorder) => {
+                self.recorder = Some(recorder);
+                self.update_title();
+
+                println!("Recording started");
+            }
+            Err(error) => println!("Could not start recording: {}", error),
+        }
+    }
+
+    fn stop_recording(&mut self) {
+        let recorder = match self.recorder.take() {
+            Some(recorder) => recorder,
+            None => return,
+        };
+
+        self.update_title();
+
+        let seconds = recorder.seconds();
+
+        match recorder.finish() {
+            Ok(path) => println!("Saved {:.1} s recording to {}", seconds, path.display()),
+            Err(error) => println!("Could not save recording: {}", error),
+        }
+    }
+
+    // Paused frames keep the last metrics, the GPU simulation has none
+    fn measure_metrics(&mut self, steps: u32) {
+        let used = self.hud.enabled || self.metric_plots.enabled || self.sonification.enabled;
+
+        if steps == 0 || self.gpu_simulation.is_some() || !used {
+            return;
+        }
+
+        self.metrics = self.metrics_scratch.measure(&self.simulation);
+        self.sonification.update(&self.metrics);
+    }
+
+    // Paused frames and frames of the GPU simulation leave the CPU boids where they are, they are skipped
+    fn record_trajectory(&mut self, steps: u32) {
+        if steps == 0 || self.gpu_simulation.is_some() {
+            return;
+        }
+
+        let trajectory = match &mut self.trajectory {
+            Some(trajectory) => trajectory,
+            None => return,
+        };
+
+        if let Err(error) = trajectory.record(&self.simulation, steps as f32 * FIXED_TIMESTEP) {
+            println!("Trajectory stopped: {}", error);
+            self.stop_trajectory();
+        }
+    }
+
+    fn autosave(&mut self, steps: u32) {
+        if steps == 0 || self.gpu_simulation.is_some() {
+            return;
+        }
+
+        self.autosave.advance(&self.simulation, steps as f32 * FIXED_TIMESTEP);
+    }
+
+    fn toggle_trajectory(&mut self) {
+        if self.trajectory.is_some() {
+            self.stop_trajectory();
+            return;
+        }
+
+        match TrajectoryRecorder::start_in_recordings(self.trajectory_format, self.trajectory_interval) {
+            Ok(trajectory) => {
+                self.trajectory = Some(trajectory);
+
+                println!("Trajectory started, every {} frames", self.trajectory_interval.max(1));
+            }
+            Err(error) => println!("Could not start trajectory: {}", error),
+        }
+    }
+
+    // Also called when the window closes, a Parquet file can't be read before
+    pub fn stop_trajectory(&mut self) {
+        let trajectory = match self.trajectory.take() {
+            Some(trajectory) => trajectory,
+            None => return,
+        };
+
+        let rows = trajectory.rows;
+
+        match trajectory.finish() {
+            Ok(path) => println!("Saved {} trajectory rows to {}", rows, path.display()),
+            Err(error) => println!("Could not save trajectory: {}", error),
+        }
+    }
+
+    fn reload_shaders(&mut self, delta_time: f32) {
+        if !self.shader_watcher.changed(delta_time) {
+            return;
+        }
+
+        match self.flock_renderer.reload_shaders(&self.display) {
+            Ok(()) => {
+                self.shader_error = None;
+
+                println!("Reloaded shaders");
+            }
+            Err(error) => {
+                let message = error.to_string();
+                self.shader_error = Some(message.lines().next().unwrap_or_default().to_string());
+
+                println!("Shader reload failed, keeping the old program:\n{}", message);
+            }
+        }
+
+        self.update_title();
+    }
+
+    // Shows a running recording and shader errors next to the name
+    fn update_title(&self) {
+        let mut title = String::from("Boids");
+
+        if self.recorder.is_some() {
+            title.push_str(" - recording");
+        }
+
+        if self.shader_error.is_some() {
+            title.push_str(" - shader error");
+        }
+
+        self.display.gl_window().window().set_title(&title);
+    }
+
+    // Key pairs scale a flocking parameter down and up, returns whether `key` was one of them.
+    // F3 and F4 tune alignment, F5 and F6 cohesion, F7 and F8 separation,
+    // comma and period the speed and semicolon and apostrophe the perception radius.
+    fn tune(&mut self, key: VirtualKeyCode) -> bool {
+        let factor = match key {
+            VirtualKeyCode::F3
+            | VirtualKeyCode::F5
+            | VirtualKeyCode::F7
+            | VirtualKeyCode::Comma
+            | VirtualKeyCode::Semicolon => 1.0 / TUNING_FACTOR,
+            VirtualKeyCode::F4
+            | VirtualKeyCode::F6
+            | VirtualKeyCode::F8
+            | VirtualKeyCode::Period
+            | VirtualKeyCode::Apostrophe => TUNING_FACTOR,
+            _ => return false,
+        };
+
+        let simulation = &mut self.simulation;
+
+        match key {
+            VirtualKeyCode::F3 | VirtualKeyCode::F4 => {
+                simulation.weights.alignment *= factor;
+                simulation.events.send(Event::ParameterChanged(Parameter::Alignment));
+                println!("Alignment: {:.2}", simulation.weights.alignment);
+            }
+            VirtualKeyCode::F5 | VirtualKeyCode::F6 => {
+                simulation.weights.cohesion *= factor;
+                simulation.events.send(Event::ParameterChanged(Parameter::Cohesion));
+                println!("Cohesion: {:.2}", simulation.weights.cohesion);
+            }
+            VirtualKeyCode::F7 | VirtualKeyCode::F8 => {
+                simulation.weights.separation *= factor;
+                simulation.events.send(Event::ParameterChanged(Parameter::Separation));
+                println!("Separation: {:.2}", simulation.weights.separation);
+            }
+            VirtualKeyCode::Comma | VirtualKeyCode::Period => {
+                simulation.speed *= factor;
+                simulation.events.send(Event::ParameterChanged(Parameter::Speed));
+                println!("Speed: {:.1}", simulation.speed);
+            }
+            _ => {
+                let [min, max] = PERCEPTION_RADIUS_RANGE;
+                simulation.set_perception_radius((simulation.perception_radius * factor).clamp(min, max));
+                println!("Perception radius: {:.1}", simulation.perception_radius);
+            }
+        }
+
+        true
+    }
+
+    // Cycles windowed, borderless and exclusive fullscreen
+    fn switch_window_mode(&mut self) {
+        self.window_mode = set_window_mode(&self.display, self.window_mode.next());
+
+        // The resize event follows, but the world should match the new size right away
+        let size = self.display.gl_window().window().inner_size();
+        self.on_window_resize(&size);
+
+        println!("Window mode: {:?}", self.window_mode);
+    }
+
+    // Closes the profiled frame and lets the governor adjust the population of the CPU simulation
+    pub fn on_frame_finished(&mut self, delta_time: f32, frame_time: f32) {
+        self.take_screenshot();
+        self.record_frame();
+
+        if let Err(error) = self.gif_history.update(&self.display, delta_time) {
+            println!("GIF capture stopped: {}", error);
+            self.gif_history.toggle();
+        }
+
+        self.reload_shaders(delta_time);
+
+        if let Some(script) = &mut self.simulation.script {
+            script.reload_if_changed(delta_time);
+        }
+
+        self.profiler.end_frame(delta_time);
+        self.hud.record_frame(delta_time, frame_time);
+        self.metric_plots.record(self.hud.fps, &self.metrics);
+
+        self.record_memory();
+        self.memory.end_frame(delta_time);
+
+        // Frame times don't mean much while recording,
+        // and both sides of a comparison have to keep the same population
+        if self.gpu_simulation.is_some() || self.recorder.is_some() || self.comparison.is_some() {
+            return;
+        }
+
+        let population = self.simulation.components.len();
+
+        if let Some(count) = self.governor.update(delta_time, frame_time, population) {
+            self.simulation.set_population(count);
+
+            println!("Population: {}", count);
+        }
+    }
+
+    fn record_memory(&mut self) {
+        self.simulation.record_memory(&mut self.memory);
+        self.flock_renderer.record_memory(&mut self.memory);
+
+        let snapshots = self.front_snapshot.allocated_bytes() + self.back_snapshot.allocated_bytes();
+        self.memory.record("Render snapshots", snapshots);
+        self.memory.record("Heatmap", self.heatmap.allocated_bytes());
+        self.memory.record("World geometry", self.world_geometry.gpu_bytes());
+        self.memory.record("Flow field arrows", self.flow_field.gpu_bytes());
+        self.memory.record("Debug vectors", self.debug_vectors.gpu_bytes());
+
+        let gpu_boids = self.gpu_simulation.as_ref().map_or(0, |gpu_simulation| gpu_simulation.gpu_bytes());
+        self.memory.record("GPU boid buffers", gpu_boids);
+    }
+
+    // Switches between the CPU and the GPU simulation.
+    // The GPU one starts with a fresh and much bigger flock.
+    // Only the CPU simulation is saved, also while the GPU one is shown.
+    // Saving and loading return what happened, for the terminal or the console.
+    pub fn save(&self, path: &str) -> String {
+        match save_simulation(&self.simulation, Path::new(path)) {
+            Ok(()) => format!("Saved simulation to {}", path),
+            Err(error) => format!("Could not save simulation to {}: {}", path, error),
+        }
+    }
+
+    pub fn load(&mut self, path: &str) -> String {
+        let simulation = match load_simulation(Path::new(path)) {
+            Ok(simulation) => simulation,
+            Err(error) => return format!("Could not load simulation from {}: {}", path, error),
+        };
+
+        if let Err(error) = self.replace_simulation(simulation) {
+            return format!("Could not load simulation from {}: {}", path, error);
+        }
+
+        self.comparison = None;
+        self.selected = None;
+        self.group.clear();
+
+        format!("Loaded simulation from {} with {} boids", path, self.simulation.components.len())
+    }
+
+    // Starts the CPU simulation over from `seed` with the current parameters
+    fn restart(&mut self, seed: u64) {
+        self.simulation = self.simulation.restarted(seed);
+        self.comparison = None;
+        self.selected = None;
+        self.group.clear();
+    }
+
+    // Keeps the script and the plugins of the current simulation, which saves don't hold
+    fn replace_simulation(&mut self, simulation: Simulation) -> Result<()> {
+        // The new world can have another size
+        self.follow_world_size(simulation.size())?;
+
+        let script = self.simulation.script.take();
+        let plugins = self.simulation.schedule.plugins();
+
+        self.simulation = simulation;
+        self.simulation.script = script;
+
+        for plugin in plugins {
+            self.simulation.schedule.add_plugin(plugin);
+        }
+
+        Ok(())
+    }
+
+    // Resizes what is drawn over the whole world before the simulation takes `world_size`
+    fn follow_world_size(&mut self, world_size: [f32; 2]) -> Result<()> {
+        if world_size == self.simulation.size() {
+            return Ok(());
+        }
+
+        let mut heatmap = Heatmap::new(&self.display, world_size)?;
+        let letterbox = Letterbox::new(&self.display, world_size)?;
+
+        heatmap.enabled = self.heatmap.enabled;
+        self.heatmap = heatmap;
+        self.letterbox = letterbox;
+
+        if let Some(gpu_simulation) = &mut self.gpu_simulation {
+            gpu_simulation.resize(&self.display, world_size)?;
+        }
+
+        let screen = [self.screen_size.width as f32, self.screen_size.height as f32];
+        self.camera.resize(screen, world_size);
+
+        Ok(())
+    }
+
+    // Hosts, connects, streams and listens to controllers as given on the command line,
+    // a failure is reported and the app runs without it
+    pub fn start_connections(&mut self, cli: &Cli, config: &Config) {
+        if let Some(address) = &cli.host {
+            match NetworkHost::start(address) {
+                Ok(host) => {
+                    self.network_host = Some(host);
+
+                    println!("Hosting on {}", address);
+                }
+                Err(error) => println!("Could not host on {}: {}", address, error),
+            }
+        }
+
+        if let Some(address) = &cli.connect {
+            match NetworkClient::connect(address) {
+                Ok(client) => {
+                    self.network_client = Some(client);
+
+                    println!("Connected to {}", address);
+                }
+                Err(error) => println!("Could not connect to {}: {}", address, error),
+            }
+        }
+
+        if let Some(address) = &cli.websocket {
+            match WebSocketStream::start(address) {
+                Ok(websocket) => {
+                    self.websocket = Some(websocket);
+
+                    println!("Streaming to WebSocket viewers on {}", address);
+                }
+                Err(error) => println!("Could not stream on {}: {}", address, error),
+            }
+        }
+
+        if let Some(address) = &cli.osc {
+            match self.live_control.start_osc(address) {
+                Ok(()) => println!("Listening to OSC on {}", address),
+                Err(error) => println!("Could not listen to OSC on {}: {}", address, error),
+            }
+        }
+
+        if let Some(port) = &cli.midi {
+            match self.live_control.start_midi(port, config) {
+                Ok(name) => println!("Listening to MIDI input {}", name),
+                Err(error) => println!("Could not listen to MIDI: {}", error),
+            }
+        }
+    }
+
+    // To network clients and WebSocket viewers
+    fn send_snapshot(&mut self, steps: u32) {
+        if steps == 0 || self.gpu_simulation.is_some() {
+            return;
+        }
+
+        let dt = steps as f32 * FIXED_TIMESTEP;
+
+        if let Some(host) = &mut self.network_host {
+            host.advance(&self.simulation, dt);
+        }
+
+        if let Some(websocket) = &mut self.websocket {
+            websocket.advance(&self.simulation, dt);
+        }
+    }
+
+    // Boids that stay keep their handles, the selection and the group stay as well
+    fn receive_snapshot(&mut self) {
+        let client = match &mut self.network_client {
+            Some(client) => client,
+            None => return,
+        };
+
+        let state = match client.latest() {
+            Ok(Some(state)) => state,
+            Ok(None) => return,
+            Err(error) => {
+                self.network_client = None;
+
+                println!("Disconnected from {}", error);
+                return;
+            }
+        };
+
+        if let Err(error) = self.follow_world_size(state.world_size) {
+            println!("Could not show the simulation of the host: {}", error);
+            return;
+        }
+
+        if let Some(client) = &mut self.network_client {
+            client.apply(&state, &mut self.simulation);
+        }
+    }
+
+    fn toggle_gpu_simulation(&mut self) {
+        if self.gpu_simulation.take().is_some() {
+            println!("CPU simulation");
+            return;
+        }
+
+        // The comparison only runs on the CPU
+        self.comparison = None;
+
+        let world_size = self.simulation.size();
+
+        let positions = get_random_positions(GPU_AGENT_COUNT, world_size, &mut self.simulation.rng);
+        let directions = get_random_directions(GPU_AGENT_COUNT, &mut self.simulation.rng);
+
+        let boids: Vec<GpuBoid> = positions.iter().zip(&directions)
+            .map(|(position, forward)| GpuBoid {
+                boid_position: position.value,
+                boid_direction: forward.direction,
+            })
+            .collect();
+
+        match GpuSimulation::new(&self.display, &boids, world_size) {
+            Ok(gpu_simulation) => self.gpu_simulation = Some(gpu_simulation),
+            Err(error) => {
+                println!("Could not start the GPU simulation: {}", error);
+                return;
+            }
+        }
+
+        println!("GPU simulation with {} boids", GPU_AGENT_COUNT);
+    }
+
+    // Cycles the shape of all species, or with shift only the one of the species
+    // in the selected row of the interaction matrix
+    fn change_shape(&mut self) {
+        let shapes = &mut self.flock_renderer.species_shapes;
+
+        if self.modifiers.shift() {
+            let species = self.interaction_cursor / self.simulation.interactions.size();
+            shapes[species] = shapes[species].next();
+        }
+        else {
+            let shape = shapes[0].next();
+            *shapes = [shape; SPECIES_COUNT];
+        }
+
+        println!("Shapes: {:?}", shapes);
+    }
+
+    // Shifts the selected entry of the interaction matrix,
+    // crossing zero flips between attraction and repulsion.
+    fn change_interaction(&mut self, amount: f32) {
+        let interactions = &mut self.simulation.interactions;
+        let size = interactions.size();
+        let species = self.interaction_cursor / size;
+        let other = self.interaction_cursor % size;
+
+        let strength = interactions.get(species, other).strength() + amount;
+        interactions.set(species, other, Interaction::from_strength(strength));
+        self.simulation.events.send(Event::ParameterChanged(Parameter::Interaction));
+
+        self.print_interaction();
+    }
+
+    fn print_interaction(&self) {
+        let interactions = &self.simulation.interactions;
+        let size = interactions.size();
+        let species = self.interaction_cursor / size;
+        let other = self.interaction_cursor % size;
+
+        println!(
+            "Species {} -> {}: {:?}",
+            species,
+            other,
+            interactions.get(species, other)
+        );
+    }
+
+    fn print_system(&self) {
+        let schedule = &self.simulation.schedule;
+
+        println!(
+            "System {}: {}",
+            schedule.name(self.system_cursor),
+            schedule.is_enabled(self.system_cursor)
+        );
+    }
+}
+
+// What is drawn under the overlays, passed through the post processing effects
+enum World<'a> {
+    Cpu {
+        background: Option<&'a Background>,
+        heatmap: &'a Heatmap,
+        world_geometry: &'a WorldGeometry,
+        flock_renderer: &'a FlockRenderer,
+    },
+    Gpu {
+        background: Option<&'a Background>,
+        gpu_simulation: &'a GpuSimulation,
+        mesh: &'a Mesh,
+    },
+}
+
+impl World<'_> {
+    fn draw<S: Surface>(&self, target: &mut S, view: [[f32; 4]; 4]) -> Result<()> {
+        match self {
+            World::Cpu { background, heatmap, world_geometry, flock_renderer } => {
+                if let Some(background) = background {
+                    background.draw(target)?;
+                }
+
+                heatmap.draw(target, view)?;
+                world_geometry.draw(target, view)?;
+                flock_renderer.draw(target, view)
+            }
+            World::Gpu { background, gpu_simulation, mesh } => {
+                if let Some(background) = background {
+                    background.draw(target)?;
+                }
+
+                gpu_simulation.render(target, mesh, view)
+            }
+        }
+    }
+}
+
+// Draws the world through bloom and motion blur, whichever are enabled.
+// Their layers are cleared to `background`. An effect whose textures can't be
+// created is turned off and the world is drawn without it.
+fn draw_world(
+    display: &Display,
+    target: &mut Frame,
+    motion_blur: &mut MotionBlur,
+    bloom: &mut Bloom,
+    world: &World,
+    view: [[f32; 4]; 4],
+    background: [f32; 4]
+) -> Result<()> {
+    if !bloom.enabled {
+        return draw_motion_blurred(display, target, motion_blur, world, view, background);
+    }
+
+    match bloom.begin(display, target.get_dimensions(), background) {
+        Ok(mut scene) => draw_motion_blurred(display, &mut scene, motion_blur, world, view, background)?,
+        Err(error) => {
+            println!("Bloom turned off: {}", error);
+            bloom.enabled = false;
+
+            return draw_motion_blurred(display, target, motion_blur, world, view, background);
+        }
+    }
+
+    if let Err(error) = bloom.present(display, target) {
+        println!("Bloom turned off: {}", error);
+        bloom.enabled = false;
+    }
+
+    Ok(())
+}
+
+fn draw_motion_blurred<S: Surface>(
+    display: &Display,
+    target: &mut S,
+    motion_blur: &mut MotionBlur,
+    world: &World,
+    view: [[f32; 4]; 4],
+    background: [f32; 4]
+) -> Result<()> {
+    if !motion_blur.enabled {
+        return world.draw(target, view);
+    }
+
+    match motion_blur.begin(display, target.get_dimensions(), background) {
+        Ok(mut layer) => world.draw(&mut layer, view)?,
+        Err(error) => {
+            println!("Motion blur turned off: {}", error);
+            motion_blur.enabled = false;
+
+            return world.draw(target, view);
+        }
+    }
+
+    motion_blur.present(target)
+}
+
+// Failed passes are logged and left out of the frame, the next frame tries them again
+fn log_failed_pass(pass: &str, result: Result<()>) {
+    if let Err(error) = result {
+        warn!("Skipped the {} pass: {}", pass, error);
+    }
+}
+
+// Size in screen pixels of a window of `size` physical pixels
+fn scaled_size(size: PhysicalSize<u32>, scale_factor: f64) -> PhysicalSize<u32> {
+    PhysicalSize {
+        width: ((size.width as f64 / scale_factor).round() as u32).max(1),
+        height: ((size.height as f64 / scale_factor).round() as u32).max(1),
+    }
+}
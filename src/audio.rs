@@ -0,0 +1,407 @@
+use std::error::Error;
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, SampleFormat, SizedSample, Stream, StreamConfig};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rustfft::num_complex::Complex;
+use rustfft::{Fft, FftPlanner};
+use serde::Deserialize;
+
+use boids_core::simulation::Simulation;
+use boids_core::systems::random_gust;
+
+// Samples the spectrum is taken over, about 20 ms at 48 kHz
+const FFT_SIZE: usize = 1024;
+
+// Parts of the sound a parameter can follow, loudness is all of it
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Band {
+    Loudness,
+    Bass,
+    Mid,
+    Treble,
+}
+
+const BAND_COUNT: usize = 4;
+
+// What the sound drives. Noise turns boids by random angles, it has no value of its own to scale.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioParameter {
+    Speed,
+    Alignment,
+    Cohesion,
+    Separation,
+    Noise,
+}
+
+impl AudioParameter {
+    // In the order of their discriminants, so `parameter as usize` indexes it
+    const ALL: [AudioParameter; 5] = [
+        AudioParameter::Speed,
+        AudioParameter::Alignment,
+        AudioParameter::Cohesion,
+        AudioParameter::Separation,
+        AudioParameter::Noise,
+    ];
+
+    fn value(self, simulation: &mut Simulation) -> Option<&mut f32> {
+        match self {
+            AudioParameter::Speed => Some(&mut simulation.speed),
+            AudioParameter::Alignment => Some(&mut simulation.weights.alignment),
+            AudioParameter::Cohesion => Some(&mut simulation.weights.cohesion),
+            AudioParameter::Separation => Some(&mut simulation.weights.separation),
+            AudioParameter::Noise => None,
+        }
+    }
+}
+
+// One row of the mapping table. At the loudest recent level of `band` the parameter grows
+// by `gain` times its value, or boids turn by up to `gain` radians per second for noise.
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct AudioMapping {
+    pub band: Band,
+    pub parameter: AudioParameter,
+    pub gain: f32,
+}
+
+// How audio reactive mode listens and what the bands drive, see AudioReactive
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct AudioConfig {
+    // Rate per second levels follow the sound at
+    pub smoothing: f32,
+    // Part of the loudest recent level of a band left after a second, never below noise_floor
+    pub gain_decay: f32,
+    pub noise_floor: f32,
+    // An onset is this many times louder than the average, which follows at average_rate
+    // per second, and sets off a gust at most every beat_cooldown seconds
+    pub beat_threshold: f32,
+    pub average_rate: f32,
+    pub beat_cooldown: f32,
+    // Hz below which sound is bass and above which it is treble, mid is in between
+    pub bass_cutoff: f32,
+    pub treble_cutoff: f32,
+    pub mappings: Vec<AudioMapping>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            smoothing: 8.0,
+            gain_decay: 0.8,
+            noise_floor: 0.01,
+            beat_threshold: 2.0,
+            average_rate: 1.0,
+            beat_cooldown: 0.25,
+            bass_cutoff: 250.0,
+            treble_cutoff: 4000.0,
+            mappings: vec![
+                AudioMapping { band: Band::Loudness, parameter: AudioParameter::Speed, gain: 1.5 },
+                AudioMapping { band: Band::Loudness, parameter: AudioParameter::Separation, gain: 2.0 },
+                AudioMapping { band: Band::Bass, parameter: AudioParameter::Cohesion, gain: 3.0 },
+                AudioMapping { band: Band::Treble, parameter: AudioParameter::Noise, gain: 2.0 },
+            ],
+        }
+    }
+}
+
+// Levels of the default input device. The audio thread keeps the highest level of every band
+// since the last read, as the bits of a non negative f32 they compare like the numbers.
+struct AudioInput {
+    peaks: Arc<[AtomicU32; BAND_COUNT]>,
+    // Recording stops when it is dropped
+    _stream: Stream,
+}
+
+impl AudioInput {
+    fn start(config: &AudioConfig) -> Result<AudioInput, Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or("no audio input device")?;
+
+        let supported = device.default_input_config()?;
+        let stream_config = supported.config();
+        let spectrum = Spectrum::new(config, stream_config.sample_rate.0 as f32);
+        let peaks: Arc<[AtomicU32; BAND_COUNT]> = Arc::default();
+
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, spectrum, peaks.clone())?,
+            SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, spectrum, peaks.clone())?,
+            SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, spectrum, peaks.clone())?,
+            format => return Err(format!("unsupported sample format {}", format).into()),
+        };
+
+        stream.play()?;
+
+        Ok(AudioInput { peaks, _stream: stream })
+    }
+
+    // Root mean square of every band at its highest since the last call, indexed by `band as usize`
+    fn take_peaks(&self) -> [f32; BAND_COUNT] {
+        let mut peaks = [0.0; BAND_COUNT];
+
+        for (peak, stored) in peaks.iter_mut().zip(self.peaks.iter()) {
+            *peak = f32::from_bits(stored.swap(0, Ordering::Relaxed));
+        }
+
+        peaks
+    }
+}
+
+// Splits the input into bands FFT_SIZE mono samples at a time, on the audio thread
+struct Spectrum {
+    fft: Arc<dyn Fft<f32>>,
+    // Samples waiting for a full window
+    samples: Vec<f32>,
+    bins: Vec<Complex<f32>>,
+    scratch: Vec<Complex<f32>>,
+    // First bin of the mid and of the treble, bin 0 only holds the offset and is left out
+    mid_start: usize,
+    treble_start: usize,
+}
+
+impl Spectrum {
+    fn new(config: &AudioConfig, sample_rate: f32) -> Spectrum {
+        let fft = FftPlanner::new().plan_fft_forward(FFT_SIZE);
+        let scratch = vec![Complex::default(); fft.get_inplace_scratch_len()];
+        // Bins above half of the window mirror the ones below
+        let bin = |hz: f32| ((hz * FFT_SIZE as f32 / sample_rate) as usize).clamp(1, FFT_SIZE / 2);
+        let mid_start = bin(config.bass_cutoff);
+
+        Spectrum {
+            fft,
+            samples: Vec::with_capacity(FFT_SIZE),
+            bins: vec![Complex::default(); FFT_SIZE],
+            scratch,
+            mid_start,
+            treble_start: bin(config.treble_cutoff).max(mid_start),
+        }
+    }
+
+    // Root mean square of the full window and of each band of its spectrum
+    fn levels(&mut self) -> [f32; BAND_COUNT] {
+        let n = FFT_SIZE as f32;
+        let loudness = (self.samples.iter().map(|sample| sample * sample).sum::<f32>() / n).sqrt();
+
+        // A Hann window, so the edges of the window don't spread into every bin
+        for (i, (bin, sample)) in self.bins.iter_mut().zip(&self.samples).enumerate() {
+            let window = 0.5 - 0.5 * (TAU * i as f32 / n).cos();
+            *bin = Complex::new(sample * window, 0.0);
+        }
+
+        self.fft.process_with_scratch(&mut self.bins, &mut self.scratch);
+
+        // Each bin of the lower half also stands for its mirror in the upper one
+        let band = |bins: &[Complex<f32>]| (2.0 * bins.iter().map(|bin| bin.norm_sqr()).sum::<f32>()).sqrt() / n;
+
+        [
+            loudness,
+            band(&self.bins[1..self.mid_start]),
+            band(&self.bins[self.mid_start..self.treble_start]),
+            band(&self.bins[self.treble_start..FFT_SIZE / 2]),
+        ]
+    }
+}
+
+fn build_stream<T: SizedSample>(
+    device: &Device,
+    config: &StreamConfig,
+    mut spectrum: Spectrum,
+    peaks: Arc<[AtomicU32; BAND_COUNT]>
+) -> Result<Stream, Box<dyn Error>>
+where
+    f32: cpal::FromSample<T>
+{
+    let channels = config.channels.max(1) as usize;
+
+    let stream = device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            for frame in data.chunks(channels) {
+                let sum: f32 = frame.iter().map(|sample| sample.to_sample::<f32>()).sum();
+                spectrum.samples.push(sum / frame.len() as f32);
+
+                if spectrum.samples.len() < FFT_SIZE {
+                    continue;
+                }
+
+                for (peak, level) in peaks.iter().zip(spectrum.levels()) {
+                    peak.fetch_max(level.to_bits(), Ordering::Relaxed);
+                }
+
+                spectrum.samples.clear();
+            }
+        },
+        |error| println!("Audio input error: {}", error),
+        None
+    )?;
+
+    Ok(stream)
+}
+
+// A parameter before and after the sound scaled it last
+#[derive(Clone, Copy)]
+struct Scaled {
+    unscaled: f32,
+    written: f32,
+}
+
+// Lets the microphone drive the flock. An FFT splits the sound into bands and every row of the
+// mapping table in AudioConfig lets one band raise a parameter, by default the loudness speeds
+// the boids up and keeps them apart, the bass pulses their cohesion and the treble jitters their
+// headings. A sudden onset like a clap or a drum hit sets off a gust. Every band is measured
+// against its loudest recent level, which fades by gain_decay per second, so quiet rooms and loud
+// concerts both cover the whole range.
+//
+// The live values of the parameters are scaled. One that was changed since the last frame, by
+// a key, a preset or a controller, is scaled from its new value, and turning the mode off only
+// puts back the values nothing else changed.
+pub struct AudioReactive {
+    pub enabled: bool,
+
+    config: AudioConfig,
+    // Opened the first time the mode is turned on
+    input: Option<AudioInput>,
+    // Smoothed level of every band, from 0 to 1 of its loudest recent level
+    levels: [f32; BAND_COUNT],
+    loudest: [f32; BAND_COUNT],
+    // Slowly following loudness, onsets stand out against it
+    average: f32,
+    since_beat: f32,
+    // Indexed by `parameter as usize`, noise is never scaled
+    scaled: [Option<Scaled>; AudioParameter::ALL.len()],
+    // Noise and gusts follow the sound, they draw from their own generator so the simulation's
+    // stays the same as in a run without audio
+    rng: StdRng,
+}
+
+impl AudioReactive {
+    pub fn new(config: AudioConfig) -> AudioReactive {
+        AudioReactive {
+            enabled: false,
+
+            input: None,
+            levels: [0.0; BAND_COUNT],
+            loudest: [config.noise_floor; BAND_COUNT],
+            average: 0.0,
+            since_beat: 0.0,
+            scaled: [None; AudioParameter::ALL.len()],
+            rng: StdRng::from_entropy(),
+            config,
+        }
+    }
+
+    pub fn level(&self, band: Band) -> f32 {
+        self.levels[band as usize]
+    }
+
+    // Stays off when there is no audio input to listen to
+    pub fn toggle(&mut self, simulation: &mut Simulation) {
+        if self.enabled {
+            self.enabled = false;
+            self.restore(simulation);
+            return;
+        }
+
+        if self.input.is_none() {
+            match AudioInput::start(&self.config) {
+                Ok(input) => self.input = Some(input),
+                Err(error) => {
+                    println!("Could not listen to audio: {}", error);
+                    return;
+                }
+            }
+        }
+
+        self.enabled = true;
+        self.levels = [0.0; BAND_COUNT];
+    }
+
+    // Puts back the values the sound scaled, unless something else changed them since
+    fn restore(&mut self, simulation: &mut Simulation) {
+        for parameter in AudioParameter::ALL {
+            let scaled = self.scaled[parameter as usize].take();
+
+            if let (Some(scaled), Some(value)) = (scaled, parameter.value(simulation)) {
+                if *value == scaled.written {
+                    *value = scaled.unscaled;
+                }
+            }
+        }
+    }
+
+    // `dt` is the real time since the last call, sound doesn't follow the time scale. While
+    // `paused` the levels still follow the sound but the boids aren't turned or blown around.
+    pub fn update(&mut self, simulation: &mut Simulation, dt: f32, paused: bool) {
+        let input = match &self.input {
+            Some(input) if self.enabled => input,
+            _ => return,
+        };
+
+        let peaks = input.take_peaks();
+        let config = &self.config;
+        let decay = config.gain_decay.powf(dt);
+        let smoothing = (config.smoothing * dt).min(1.0);
+
+        for (band, peak) in peaks.iter().enumerate() {
+            self.loudest[band] = (self.loudest[band] * decay).max(*peak).max(config.noise_floor);
+            self.levels[band] += ((peak / self.loudest[band]).min(1.0) - self.levels[band]) * smoothing;
+        }
+
+        let mut factors = [1.0; AudioParameter::ALL.len()];
+
+        for mapping in &config.mappings {
+            factors[mapping.parameter as usize] += mapping.gain * self.levels[mapping.band as usize];
+        }
+
+        for parameter in AudioParameter::ALL {
+            let index = parameter as usize;
+            let value = match parameter.value(simulation) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let unscaled = match self.scaled[index] {
+                Some(scaled) if scaled.written == *value => scaled.unscaled,
+                // Changed by something else since the last frame
+                _ => *value,
+            };
+
+            *value = unscaled * factors[index];
+            self.scaled[index] = Some(Scaled { unscaled, written: *value });
+        }
+
+        // Noise starts from 1 like the factors of the others
+        let max_turn = (factors[AudioParameter::Noise as usize] - 1.0) * dt;
+
+        if max_turn > 0.0 && !paused {
+            for forward in &mut simulation.components.directions {
+                let (sin, cos) = self.rng.gen_range(-max_turn..max_turn).sin_cos();
+                let [x, y] = forward.direction;
+
+                forward.direction = [x * cos - y * sin, x * sin + y * cos];
+            }
+        }
+
+        let loudness = peaks[Band::Loudness as usize];
+
+        self.average += (loudness - self.average) * (config.average_rate * dt).min(1.0);
+        self.since_beat += dt;
+
+        let onset = loudness > config.noise_floor && loudness > self.average * config.beat_threshold;
+
+        if onset && self.since_beat > config.beat_cooldown && !paused {
+            self.since_beat = 0.0;
+
+            let world_size = simulation.size();
+            let gust = random_gust(world_size, &mut self.rng);
+            simulation.gusts.push(gust);
+        }
+    }
+}
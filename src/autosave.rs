@@ -0,0 +1,107 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use boids_core::save::save_simulation;
+use boids_core::simulation::Simulation;
+
+use crate::{AUTOSAVE_DIR, AUTOSAVE_INTERVAL};
+
+// Saves the simulation to AUTOSAVE_DIR every `interval` seconds of simulated time, so a crash or a
+// lost graphics context costs at most that much of a long run. Checkpoints are numbered on from the
+// newest one already in the directory and only the `keep` newest stay, older runs included.
+// Saving happens on the calling thread, with very large flocks it shows as a hitch.
+pub struct Autosave {
+    pub enabled: bool,
+    interval: f32,
+    keep: usize,
+    // Simulated seconds since the last checkpoint
+    since: f32,
+}
+
+impl Autosave {
+    // An interval of 0 starts disabled, turned on later it saves every AUTOSAVE_INTERVAL seconds
+    pub fn new(interval: f32, keep: usize) -> Autosave {
+        Autosave {
+            enabled: interval > 0.0,
+            interval: if interval > 0.0 { interval } else { AUTOSAVE_INTERVAL },
+            keep: keep.max(1),
+            since: 0.0,
+        }
+    }
+
+    // `dt` is the simulated time since the last call
+    pub fn advance(&mut self, simulation: &Simulation, dt: f32) {
+        if !self.enabled {
+            return;
+        }
+
+        self.since += dt;
+
+        if self.since < self.interval {
+            return;
+        }
+
+        self.since = 0.0;
+
+        match self.save(simulation) {
+            Ok(path) => println!("Autosaved to {}", path.display()),
+            Err(error) => println!("Could not autosave: {}", error),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.since = 0.0;
+    }
+
+    fn save(&self, simulation: &Simulation) -> Result<PathBuf, Box<dyn Error>> {
+        fs::create_dir_all(AUTOSAVE_DIR)?;
+
+        let mut checkpoints = checkpoints(Path::new(AUTOSAVE_DIR))?;
+        let number = checkpoints.last().map_or(0, |(number, _)| number + 1);
+        let path = Path::new(AUTOSAVE_DIR).join(format!("checkpoint-{}.save", number));
+
+        save_simulation(simulation, &path)?;
+
+        // Only once the new checkpoint is written, a failed save keeps the old ones
+        checkpoints.push((number, path.clone()));
+
+        let stale = checkpoints.len().saturating_sub(self.keep);
+        for (_, old) in &checkpoints[..stale] {
+            if let Err(error) = fs::remove_file(old) {
+                println!("Could not remove checkpoint {}: {}", old.display(), error);
+            }
+        }
+
+        Ok(path)
+    }
+}
+
+// The newest checkpoint in AUTOSAVE_DIR, if there is one
+pub fn latest_checkpoint() -> Option<PathBuf> {
+    checkpoints(Path::new(AUTOSAVE_DIR)).ok()?.pop().map(|(_, path)| path)
+}
+
+// Checkpoint files in `dir` with their numbers, oldest first
+fn checkpoints(dir: &Path) -> Result<Vec<(u64, PathBuf)>, Box<dyn Error>> {
+    let mut checkpoints = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        let number = path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix("checkpoint-"))
+            .and_then(|name| name.strip_suffix(".save"))
+            .and_then(|number| number.parse().ok());
+
+        if let Some(number) = number {
+            checkpoints.push((number, path));
+        }
+    }
+
+    checkpoints.sort_unstable_by_key(|(number, _)| *number);
+
+    Ok(checkpoints)
+}
@@ -0,0 +1,103 @@
+use cgmath::conv::array4x4;
+use vecmath::{Matrix4, Vector2};
+
+use crate::{CAMERA_FOLLOW_ZOOM, CAMERA_SMOOTHING};
+
+// View of the world. Without a target it rests on the whole world, with one it
+// glides towards the target and zooms in. Both move smoothly with CAMERA_SMOOTHING.
+// At zoom 1 the whole world fits into the window, the rest of the window is letterboxed.
+pub struct Camera {
+    pub center: Vector2<f32>,
+    pub zoom: f32,
+    // Follows the selected boid while set, cleared to release the camera
+    pub following: bool,
+
+    // Window size in screen pixels
+    screen: Vector2<f32>,
+    // Screen pixels per world unit at zoom 1
+    fit: f32,
+}
+
+impl Camera {
+    pub fn new(world_size: [f32; 2], screen: [f32; 2]) -> Camera {
+        let mut camera = Camera {
+            center: [world_size[0] / 2.0, world_size[1] / 2.0],
+            zoom: 1.0,
+            following: false,
+
+            screen,
+            fit: 1.0,
+        };
+
+        camera.resize(screen, world_size);
+
+        camera
+    }
+
+    pub fn resize(&mut self, screen: [f32; 2], world_size: [f32; 2]) {
+        self.screen = screen;
+        self.fit = (screen[0] / world_size[0]).min(screen[1] / world_size[1]);
+    }
+
+    // Screen pixels per world unit
+    pub fn scale(&self) -> f32 {
+        self.fit * self.zoom
+    }
+
+    // Moves towards the target, or back to the whole world without one
+    pub fn update(&mut self, delta_time: f32, target: Option<Vector2<f32>>, world_size: [f32; 2]) {
+        let (center, zoom) = match target {
+            Some(target) if self.following => (target, CAMERA_FOLLOW_ZOOM),
+            _ => ([world_size[0] / 2.0, world_size[1] / 2.0], 1.0),
+        };
+
+        // Targets wrapping around the screen are jumped to instead of panning over the whole world
+        if (center[0] - self.center[0]).abs() > world_size[0] / 2.0
+            || (center[1] - self.center[1]).abs() > world_size[1] / 2.0 {
+            self.center = center;
+        }
+
+        // Frame rate independent exponential smoothing
+        let t = 1.0 - (-CAMERA_SMOOTHING * delta_time).exp();
+
+        self.center[0] += (center[0] - self.center[0]) * t;
+        self.center[1] += (center[1] - self.center[1]) * t;
+        self.zoom += (zoom - self.zoom) * t;
+    }
+
+    // World position under a point of the window
+    pub fn screen_to_world(&self, screen: Vector2<f32>) -> Vector2<f32> {
+        [
+            self.center[0] + (screen[0] - self.screen[0] / 2.0) / self.scale(),
+            self.center[1] + (screen[1] - self.screen[1] / 2.0) / self.scale(),
+        ]
+    }
+
+    // Area on screen as x, y, width and height in world units, letterboxes included
+    pub fn visible_area(&self) -> [f32; 4] {
+        let w = self.screen[0] / self.scale();
+        let h = self.screen[1] / self.scale();
+
+        [self.center[0] - w / 2.0, self.center[1] - h / 2.0, w, h]
+    }
+
+    // Projection of the world as seen by the camera, y points down like in the world
+    pub fn view(&self) -> Matrix4<f32> {
+        const Z_NEAR: f32 = -1.0;
+        const Z_FAR: f32 = 1.0;
+
+        let half_w = self.screen[0] / 2.0 / self.scale();
+        let half_h = self.screen[1] / 2.0 / self.scale();
+
+        let ortho = cgmath::ortho::<f32>(
+            self.center[0] - half_w,
+            self.center[0] + half_w,
+            self.center[1] + half_h,
+            self.center[1] - half_h,
+            Z_NEAR,
+            Z_FAR
+        );
+
+        array4x4(ortho)
+    }
+}
@@ -0,0 +1,107 @@
+use clap::Parser;
+use tracing::level_filters::LevelFilter;
+
+use crate::config::Config;
+use crate::graphics::WindowMode;
+use crate::autosave::latest_checkpoint;
+use crate::{AUTOSAVE_DIR, CONFIG_PATH};
+
+// Flags given on the command line win over the values of the config file
+#[derive(Parser, Debug)]
+#[clap(name = "flocking", about = "Flocking simulation")]
+pub struct Cli {
+    #[clap(long, help = "Number of boids at startup")]
+    pub agents: Option<usize>,
+    #[clap(long, help = "Seed of the random number generator, random without one")]
+    pub seed: Option<u64>,
+    #[clap(long, default_value = CONFIG_PATH, help = "Config file to load")]
+    pub config: String,
+    #[clap(long, help = "Start in borderless fullscreen")]
+    pub fullscreen: bool,
+    #[clap(long, help = "Step the simulation without opening a window")]
+    pub headless: bool,
+    #[cfg(feature = "wgpu")]
+    #[clap(long, help = "Draw with wgpu instead of OpenGL. Only the flock is drawn, without overlays or controls")]
+    pub wgpu: bool,
+    #[clap(long, help = "Steps of a headless run, runs until interrupted without one")]
+    pub steps: Option<u64>,
+    #[clap(long, help = "Interaction preset: segregated, mixed, predator-prey or mobbing")]
+    pub preset: Option<String>,
+    #[clap(long, help = "Rhai file with an extra steering rule, reloaded when it changes")]
+    pub script: Option<String>,
+    #[clap(long, help = "TOML file with obstacles, walls, spawn regions and timed events, see scenarios/")]
+    pub scenario: Option<String>,
+    #[clap(long, help = "Saved simulation to start from, its boids and parameters replace the configured ones")]
+    pub load: Option<String>,
+    #[clap(long, conflicts_with = "load", help = "Start from the newest autosaved checkpoint")]
+    pub resume: bool,
+    #[clap(long, help = "Address to send the simulation to clients from, like 0.0.0.0:7878. Works headless as well")]
+    pub host: Option<String>,
+    #[clap(long, conflicts_with = "host", help = "Address of a --host whose simulation is shown and kept in sync")]
+    pub connect: Option<String>,
+    #[clap(long, help = "Address to stream the boids to WebSocket viewers from, like 0.0.0.0:9001, see examples/websocket.html")]
+    pub websocket: Option<String>,
+    #[clap(long, help = "Address to receive OSC messages setting parameters on, like 0.0.0.0:9000")]
+    pub osc: Option<String>,
+    #[clap(long, help = "Part of the name of the MIDI input whose sliders set parameters, see [midi] in the config")]
+    pub midi: Option<String>,
+    #[clap(long, help = "Where a headless run with --steps saves the simulation at the end")]
+    pub save: Option<String>,
+    #[clap(long, help = "File a headless run writes the id, position and heading of every boid to, Parquet for a .parquet file and CSV otherwise. Parquet files are only complete once --steps ran")]
+    pub trajectory: Option<String>,
+    #[clap(long, help = "Frames from one trajectory sample to the next")]
+    pub trajectory_interval: Option<u64>,
+    #[clap(long, default_value = "warn", help = "Least severe log messages shown: off, error, warn, info, debug or trace. Debug also checks every boid after every system")]
+    pub log_level: LevelFilter,
+    #[clap(long, help = "File the spans of every frame and system are written to as a Chrome trace")]
+    pub chrome_trace: Option<String>,
+    #[clap(long, help = "File the spans are written to as folded stacks, for inferno-flamegraph")]
+    pub flamegraph: Option<String>,
+}
+
+impl Cli {
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(agents) = self.agents {
+            config.flock.agent_count = agents;
+        }
+
+        if let Some(seed) = self.seed {
+            config.flock.seed = Some(seed);
+        }
+
+        if let Some(preset) = &self.preset {
+            config.flock.preset = preset.clone();
+        }
+
+        if let Some(script) = &self.script {
+            config.flock.script = Some(script.clone());
+        }
+
+        if let Some(scenario) = &self.scenario {
+            config.flock.scenario = Some(scenario.clone());
+        }
+
+        if let Some(interval) = self.trajectory_interval {
+            config.trajectory.interval = interval;
+        }
+
+        if self.fullscreen {
+            config.window.mode = WindowMode::Borderless;
+        }
+    }
+
+    // Saved simulation given with --load, or the newest checkpoint with --resume
+    pub fn start_from(&self) -> Option<String> {
+        if !self.resume {
+            return self.load.clone();
+        }
+
+        let checkpoint = latest_checkpoint().map(|path| path.display().to_string());
+
+        if checkpoint.is_none() {
+            println!("No checkpoint in {} to resume from, starting a new simulation", AUTOSAVE_DIR);
+        }
+
+        checkpoint
+    }
+}
@@ -0,0 +1,88 @@
+use std::f32::consts::PI;
+
+use boids_core::behavior::{state_weights, MAX_SPEED_FACTOR};
+use boids_core::data::{Behavior, BehaviorState, Crowding, Forward, Species};
+use crate::theme::Theme;
+use crate::DENSITY_COLOR_NEIGHBORS;
+
+// What the color of a boid shows
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorMode {
+    // Species color of the theme
+    Plain,
+    // Direction as a hue on the color wheel, aligned groups share a color
+    Heading,
+    // Current speed from cool to hot
+    Speed,
+    // Neighbor count from cool when isolated to hot when crowded
+    Density,
+}
+
+impl ColorMode {
+    pub fn next(self) -> ColorMode {
+        match self {
+            ColorMode::Plain => ColorMode::Heading,
+            ColorMode::Heading => ColorMode::Speed,
+            ColorMode::Speed => ColorMode::Density,
+            ColorMode::Density => ColorMode::Plain,
+        }
+    }
+}
+
+const COOL_COLOR: [f32; 3] = [0.2, 0.4, 1.0];
+const HOT_COLOR: [f32; 3] = [1.0, 0.25, 0.15];
+
+// Dead boids have the dead color of the theme in every mode
+pub fn boid_color(
+    mode: ColorMode,
+    theme: &Theme,
+    forward: &Forward,
+    behavior: &Behavior,
+    crowding: &Crowding,
+    species: &Species
+) -> [f32; 3] {
+    if behavior.state == BehaviorState::Dead {
+        return theme.dead;
+    }
+
+    match mode {
+        ColorMode::Plain => theme.species[species.id],
+        ColorMode::Heading => {
+            let angle = forward.direction[1].atan2(forward.direction[0]);
+
+            hsv_to_rgb((angle / (2.0 * PI)).rem_euclid(1.0), 0.8, 1.0)
+        }
+        ColorMode::Speed => heat_color(state_weights(behavior.state).speed / MAX_SPEED_FACTOR),
+        ColorMode::Density => heat_color(crowding.neighbors as f32 / DENSITY_COLOR_NEIGHBORS),
+    }
+}
+
+// Blends from cool at 0 to hot at 1
+pub fn heat_color(t: f32) -> [f32; 3] {
+    let t = t.clamp(0.0, 1.0);
+
+    [
+        COOL_COLOR[0] + (HOT_COLOR[0] - COOL_COLOR[0]) * t,
+        COOL_COLOR[1] + (HOT_COLOR[1] - COOL_COLOR[1]) * t,
+        COOL_COLOR[2] + (HOT_COLOR[2] - COOL_COLOR[2]) * t,
+    ]
+}
+
+// All components are in 0..1, hue wraps around
+pub fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> [f32; 3] {
+    let h = hue * 6.0;
+    let c = value * saturation;
+    let x = c * (1.0 - (h % 2.0 - 1.0).abs());
+    let m = value - c;
+
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+
+    [r + m, g + m, b + m]
+}
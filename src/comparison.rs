@@ -0,0 +1,126 @@
+use cgmath::{Matrix4, Vector3};
+use cgmath::conv::array4x4;
+use glium::{Display, Frame, Rect, Surface};
+
+use crate::camera::Camera;
+use crate::coloring::ColorMode;
+use boids_core::data::FlockWeights;
+use crate::error::Result;
+use crate::graphics::flock::FlockRenderer;
+use crate::graphics::heatmap::Heatmap;
+use crate::graphics::letterbox::Letterbox;
+use crate::graphics::renderer::{DrawTo, Renderer};
+use crate::graphics::world_geometry::WorldGeometry;
+use boids_core::simulation::Simulation;
+use crate::snapshot::RenderSnapshot;
+use crate::theme::Theme;
+
+// Second simulation shown on the right half of the window, next to the main one on the left.
+// Both start from the same seed and only differ in their flocking weights, so whatever
+// differs between the halves comes from the weights. Bloom and motion blur are skipped
+// while comparing, they post process the whole window at once.
+pub struct Comparison {
+    pub simulation: Simulation,
+    snapshot: RenderSnapshot,
+    flock_renderer: FlockRenderer,
+    heatmap: Heatmap,
+    world_geometry: WorldGeometry,
+}
+
+impl Comparison {
+    // `main` is the simulation compared against, the comparison takes over its settings
+    pub fn new(
+        display: &Display,
+        main: &Simulation,
+        seed: u64,
+        weights: FlockWeights,
+        agent_size: f32
+    ) -> Result<Comparison> {
+        let mut simulation = main.restarted(seed);
+        simulation.weights = weights;
+
+        let snapshot = RenderSnapshot::default();
+        let flock_renderer = FlockRenderer::new(display, &snapshot, agent_size)?;
+        let heatmap = Heatmap::new(display, simulation.size())?;
+
+        Ok(Comparison {
+            simulation,
+            snapshot,
+            flock_renderer,
+            heatmap,
+            world_geometry: WorldGeometry::new(display)?,
+        })
+    }
+
+    // Uploads the simulation with the same render settings as the main flock,
+    // so the halves only differ in what is simulated
+    pub fn upload(
+        &mut self,
+        display: &Display,
+        color_mode: ColorMode,
+        theme: &Theme,
+        main: (&FlockRenderer, &Heatmap, &WorldGeometry)
+    ) -> Result<()> {
+        let (flock_renderer, heatmap, world_geometry) = main;
+
+        self.flock_renderer.species_shapes = flock_renderer.species_shapes;
+        self.flock_renderer.soft = flock_renderer.soft;
+        self.flock_renderer.velocity_stretch = flock_renderer.velocity_stretch;
+        self.heatmap.enabled = heatmap.enabled;
+        self.world_geometry.enabled = world_geometry.enabled;
+
+        let species_shapes = self.flock_renderer.species_shapes;
+        self.snapshot.copy_from(&self.simulation, color_mode, theme, &species_shapes);
+
+        self.heatmap.update(&self.snapshot.instances);
+        self.world_geometry.upload(display, &self.snapshot)?;
+        self.flock_renderer.upload(display, &self.snapshot)
+    }
+
+    pub fn draw(&self, target: &mut Frame, view: [[f32; 4]; 4]) -> Result<()> {
+        self.heatmap.draw(target, view)?;
+        self.world_geometry.draw(target, view)?;
+        self.flock_renderer.draw(target, view)
+    }
+}
+
+// Both sides of the comparison, the main simulation is on the left
+#[derive(Clone, Copy, PartialEq)]
+pub enum Half {
+    Left,
+    Right,
+}
+
+impl Half {
+    // View of the whole world fit into this half of a window of `screen` size
+    pub fn view(self, world_size: [f32; 2], screen: [f32; 2]) -> [[f32; 4]; 4] {
+        let camera = Camera::new(world_size, [screen[0] / 2.0, screen[1]]);
+
+        let offset = match self {
+            Half::Left => -0.5,
+            Half::Right => 0.5,
+        };
+
+        let into_half = Matrix4::from_translation(Vector3::new(offset, 0.0, 0.0))
+            * Matrix4::from_nonuniform_scale(0.5, 1.0, 1.0);
+
+        array4x4(into_half * Matrix4::from(camera.view()))
+    }
+
+    // Part of the framebuffer covered by this half, in framebuffer pixels
+    pub fn scissor(self, target: &Frame) -> Rect {
+        let (width, height) = target.get_dimensions();
+        let half = width / 2;
+
+        match self {
+            Half::Left => Rect { left: 0, bottom: 0, width: half, height },
+            Half::Right => Rect { left: half, bottom: 0, width: width - half, height },
+        }
+    }
+
+    // Hides whatever the worlds drew outside this half's world
+    pub fn draw_letterbox(self, target: &mut Frame, letterbox: &Letterbox, view: [[f32; 4]; 4]) -> Result<()> {
+        let scissor = self.scissor(target);
+        letterbox.draw(target, view, Some(scissor))
+    }
+}
@@ -0,0 +1,415 @@
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use boids_core::data::{Boundary, FlockWeights};
+use boids_core::builder::SimulationBuilder;
+use boids_core::plugin::load_plugins;
+use boids_core::scenario::Scenario;
+use boids_core::script::SteeringScript;
+use boids_core::simulation::Simulation;
+use boids_core::species::InteractionPreset;
+use boids_core::{AGENT_SPEED, ALIGNMENT_WEIGHT, CELL_SIZE, COHESION_WEIGHT, SEPARATION_WEIGHT};
+
+use crate::audio::AudioConfig;
+use crate::graphics::WindowMode;
+use crate::live_control::MidiConfig;
+use crate::presets::ParameterPreset;
+use crate::trajectory::TrajectoryFormat;
+use crate::{AGENT_COUNT, AGENT_SIZE, AUTOSAVE_INTERVAL, AUTOSAVE_KEEP, FRAME_RATE_CAP, INITIAL_DISPLAY_SIZE, MSAA_SAMPLES, PLUGIN_DIR, THEME, TRAJECTORY_INTERVAL, VSYNC, WINDOW_MODE, WORLD_SIZE};
+
+// Settings read from CONFIG_PATH at startup, so other values can be tried without a rebuild.
+// Every value the file leaves out keeps the constant of the same name.
+#[derive(Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub flock: FlockConfig,
+    pub colors: ColorConfig,
+    pub trajectory: TrajectoryConfig,
+    pub autosave: AutosaveConfig,
+    // Control change numbers of a --midi controller
+    pub midi: MidiConfig,
+    // Bands of the microphone and the parameters they drive in audio reactive mode
+    pub audio: AudioConfig,
+    // Parameter presets next to the built in ones, see Presets
+    pub presets: Vec<ParameterPreset>,
+
+    // File the config was loaded from, saved presets are appended to it
+    #[serde(skip)]
+    pub path: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct WindowConfig {
+    // Logical pixels
+    pub size: [u32; 2],
+    pub world_size: [u32; 2],
+    pub mode: WindowMode,
+    pub msaa_samples: u16,
+    pub vsync: bool,
+    // Frames per second, 0 runs as fast as possible
+    pub frame_rate_cap: f32,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct FlockConfig {
+    pub agent_count: usize,
+    pub agent_size: f32,
+    pub agent_speed: f32,
+    pub cell_size: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub separation: f32,
+    // Random without one
+    pub seed: Option<u64>,
+    // Name of the interaction preset of the species, see InteractionPreset::from_name
+    pub preset: String,
+    pub boundary: Boundary,
+    // Rhai file with an extra steering rule, see SteeringScript
+    pub script: Option<String>,
+    // TOML file with obstacles, walls, spawn regions and timed events, see Scenario
+    pub scenario: Option<String>,
+    // Directory with steering plugins, see boids_core::plugin
+    pub plugins: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct ColorConfig {
+    // Name of one of the built in themes
+    pub theme: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct TrajectoryConfig {
+    // Frames from one sample of every boid to the next
+    pub interval: u64,
+    // Format of the trajectories started with Insert
+    pub format: TrajectoryFormat,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct AutosaveConfig {
+    // Seconds of simulated time from one checkpoint to the next, 0 turns autosaving off
+    pub interval: f32,
+    // Checkpoints kept, older ones are removed
+    pub keep: usize,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            size: INITIAL_DISPLAY_SIZE,
+            world_size: WORLD_SIZE,
+            mode: WINDOW_MODE,
+            msaa_samples: MSAA_SAMPLES,
+            vsync: VSYNC,
+            frame_rate_cap: FRAME_RATE_CAP.unwrap_or(0.0),
+        }
+    }
+}
+
+impl Default for FlockConfig {
+    fn default() -> Self {
+        FlockConfig {
+            agent_count: AGENT_COUNT,
+            agent_size: AGENT_SIZE,
+            agent_speed: AGENT_SPEED,
+            cell_size: CELL_SIZE,
+            alignment: ALIGNMENT_WEIGHT,
+            cohesion: COHESION_WEIGHT,
+            separation: SEPARATION_WEIGHT,
+            seed: None,
+            preset: "segregated".to_string(),
+            boundary: Boundary::Wrap,
+            script: None,
+            scenario: None,
+            plugins: PLUGIN_DIR.to_string(),
+        }
+    }
+}
+
+impl Default for ColorConfig {
+    fn default() -> Self {
+        ColorConfig { theme: THEME.to_string() }
+    }
+}
+
+impl Default for TrajectoryConfig {
+    fn default() -> Self {
+        TrajectoryConfig {
+            interval: TRAJECTORY_INTERVAL,
+            format: TrajectoryFormat::Csv,
+        }
+    }
+}
+
+impl Default for AutosaveConfig {
+    fn default() -> Self {
+        AutosaveConfig {
+            interval: AUTOSAVE_INTERVAL,
+            keep: AUTOSAVE_KEEP,
+        }
+    }
+}
+
+impl WindowConfig {
+    pub fn frame_rate_cap(&self) -> Option<f32> {
+        if self.frame_rate_cap > 0.0 { Some(self.frame_rate_cap) } else { None }
+    }
+
+    pub fn world_size(&self) -> [f32; 2] {
+        [self.world_size[0] as f32, self.world_size[1] as f32]
+    }
+
+    // Returns what was wrong, wrong values are left at their defaults
+    fn validate(&mut self, path: &str) -> Vec<String> {
+        let defaults = WindowConfig::default();
+        let mut problems = Vec::new();
+
+        for (name, value, default) in [
+            ("size", &mut self.size, defaults.size),
+            ("world_size", &mut self.world_size, defaults.world_size),
+        ] {
+            if value.contains(&0) {
+                problems.push(format!("window.{} in {} can't be 0, not {:?}, using {:?}", name, path, value, default));
+                *value = default;
+            }
+        }
+
+        problems
+    }
+}
+
+impl FlockConfig {
+    pub fn weights(&self) -> FlockWeights {
+        FlockWeights {
+            alignment: self.alignment,
+            cohesion: self.cohesion,
+            separation: self.separation,
+        }
+    }
+
+    // Returns what was wrong, wrong values are left at their defaults
+    fn validate(&mut self, path: &str) -> Vec<String> {
+        let defaults = FlockConfig::default();
+        let mut problems = Vec::new();
+
+        for (name, value, default) in [
+            ("agent_size", &mut self.agent_size, defaults.agent_size),
+            ("agent_speed", &mut self.agent_speed, defaults.agent_speed),
+            ("cell_size", &mut self.cell_size, defaults.cell_size),
+        ] {
+            if value.is_nan() || *value <= 0.0 {
+                problems.push(format!("flock.{} in {} has to be positive, not {}, using {}", name, path, value, default));
+                *value = default;
+            }
+        }
+
+        problems
+    }
+}
+
+impl Config {
+    // The simulation described by the flock and world settings, shared by the window and headless runs
+    pub fn create_simulation(&self) -> Simulation {
+        let scenario = self.load_scenario();
+        let world_size = scenario.as_ref()
+            .and_then(|scenario| scenario.world_size)
+            .unwrap_or_else(|| self.window.world_size());
+
+        let mut builder = SimulationBuilder::new(self.flock.agent_count, world_size);
+
+        if let Some(seed) = self.flock.seed {
+            builder = builder.seed(seed);
+        }
+
+        match InteractionPreset::from_name(&self.flock.preset) {
+            Some(preset) => builder = builder.preset(preset),
+            None => println!("Unknown interaction preset {}, using the default", self.flock.preset),
+        }
+
+        let mut simulation = builder.boundary(self.flock.boundary).build();
+
+        println!("Seed: {}", simulation.seed);
+
+        simulation.weights = self.flock.weights();
+        simulation.speed = self.flock.agent_speed;
+        simulation.set_cell_size(self.flock.cell_size);
+
+        self.load_script(&mut simulation);
+        self.load_plugins(&mut simulation);
+
+        if let Some(scenario) = &scenario {
+            scenario.apply(&mut simulation);
+        }
+
+        simulation
+    }
+
+    // A scenario that can't be loaded is reported and the simulation starts without one
+    fn load_scenario(&self) -> Option<Scenario> {
+        let path = self.flock.scenario.as_ref()?;
+
+        match Scenario::load(Path::new(path)) {
+            Ok(scenario) => Some(scenario),
+            Err(error) => {
+                println!("Could not load scenario {}: {}", path, error);
+                None
+            }
+        }
+    }
+
+    // Saves don't hold the script, loaded simulations get it from here as well
+    pub fn load_script(&self, simulation: &mut Simulation) {
+        if let Some(path) = &self.flock.script {
+            simulation.script = Some(SteeringScript::load(Path::new(path)));
+        }
+    }
+
+    // Saves don't hold the plugins either
+    pub fn load_plugins(&self, simulation: &mut Simulation) {
+        for plugin in load_plugins(Path::new(&self.flock.plugins)) {
+            simulation.schedule.add_plugin(plugin);
+        }
+    }
+
+    // Without a file everything is left at its default.
+    // A file that can't be read or parsed, like one with an unknown window mode, is reported
+    // and ignored as a whole. Sizes and speeds that aren't positive are reported and left at their defaults.
+    pub fn load(path: &str) -> Config {
+        let (config, problems) = Config::load_checked(path);
+
+        for problem in problems {
+            println!("{}", problem);
+        }
+
+        config
+    }
+
+    // Like `load`, but returns the problems instead of printing them
+    fn load_checked(path: &str) -> (Config, Vec<String>) {
+        let mut problems = Vec::new();
+
+        let mut config = match Config::read(path) {
+            Ok(config) => config,
+            Err(error) => {
+                problems.push(format!("Could not load {}: {}, using defaults", path, error));
+                Config::default()
+            }
+        };
+
+        config.path = path.to_string();
+        problems.extend(config.window.validate(path));
+        problems.extend(config.flock.validate(path));
+
+        (config, problems)
+    }
+
+    fn read(path: &str) -> Result<Config, Box<dyn Error>> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(Config::default()),
+            Err(error) => return Err(error.into()),
+        };
+
+        Ok(toml::from_str(&text)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    // Loads `text` from a file of its own in the temporary directory
+    fn load_text(name: &str, text: &str) -> (Config, Vec<String>) {
+        let path = env::temp_dir().join(format!("boids-config-{}-{}.toml", process::id(), name));
+        fs::write(&path, text).unwrap();
+
+        let loaded = Config::load_checked(path.to_str().unwrap());
+        fs::remove_file(&path).ok();
+
+        loaded
+    }
+
+    #[test]
+    fn a_missing_file_loads_the_defaults() {
+        let path = env::temp_dir().join(format!("boids-config-{}-missing.toml", process::id()));
+        let (config, problems) = Config::load_checked(path.to_str().unwrap());
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(config.window.size, INITIAL_DISPLAY_SIZE);
+        assert_eq!(config.flock.agent_count, AGENT_COUNT);
+        assert_eq!(config.flock.boundary, Boundary::Wrap);
+        assert_eq!(config.path, path.to_str().unwrap());
+    }
+
+    #[test]
+    fn missing_keys_keep_their_defaults() {
+        let (config, problems) = load_text("partial", "[window]\nvsync = true\n\n[flock]\nagent_count = 12\n");
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert!(config.window.vsync);
+        assert_eq!(config.flock.agent_count, 12);
+
+        assert_eq!(config.window.size, INITIAL_DISPLAY_SIZE);
+        assert_eq!(config.window.mode, WINDOW_MODE);
+        assert_eq!(config.flock.agent_speed, AGENT_SPEED);
+        assert_eq!(config.flock.cell_size, CELL_SIZE);
+        assert_eq!(config.colors.theme, THEME);
+        assert_eq!(config.trajectory.interval, TRAJECTORY_INTERVAL);
+        assert_eq!(config.autosave.keep, AUTOSAVE_KEEP);
+        assert!(config.presets.is_empty());
+    }
+
+    #[test]
+    fn sizes_and_speeds_that_are_not_positive_are_rejected() {
+        let text = "[window]\nsize = [0, 720]\nworld_size = [1280, 0]\n\n\
+            [flock]\nagent_size = 0.0\nagent_speed = -5.0\ncell_size = nan\n";
+        let (config, problems) = load_text("not-positive", text);
+
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+
+        for key in ["window.size", "window.world_size", "flock.agent_size", "flock.agent_speed", "flock.cell_size"] {
+            assert!(problems.iter().any(|problem| problem.starts_with(key)), "no message about {}", key);
+        }
+
+        assert_eq!(config.window.size, INITIAL_DISPLAY_SIZE);
+        assert_eq!(config.window.world_size, WORLD_SIZE);
+        assert_eq!(config.flock.agent_size, AGENT_SIZE);
+        assert_eq!(config.flock.agent_speed, AGENT_SPEED);
+        assert_eq!(config.flock.cell_size, CELL_SIZE);
+    }
+
+    #[test]
+    fn unknown_names_and_negative_counts_reject_the_file() {
+        let files = [
+            ("mode", "[window]\nmode = \"sideways\"\n", "sideways"),
+            ("boundary", "[flock]\nboundary = \"teleport\"\nagent_count = 12\n", "teleport"),
+            ("format", "[trajectory]\nformat = \"xml\"\n", "xml"),
+            ("negative", "[window]\nsize = [-1, 720]\n", "window"),
+        ];
+
+        for (name, text, mentioned) in files {
+            let (config, problems) = load_text(name, text);
+
+            assert_eq!(problems.len(), 1, "{:?}", problems);
+            assert!(problems[0].starts_with("Could not load"), "{}", problems[0]);
+            assert!(problems[0].contains(mentioned), "{}", problems[0]);
+
+            // The whole file is ignored
+            assert_eq!(config.flock.agent_count, AGENT_COUNT);
+            assert_eq!(config.window.size, INITIAL_DISPLAY_SIZE);
+        }
+    }
+}
@@ -0,0 +1,287 @@
+use std::collections::VecDeque;
+
+use crate::live_control::Control;
+use crate::CONSOLE_LINES;
+
+pub const HELP: [&str; 4] = [
+    "Commands: set PARAMETER VALUE, spawn COUNT, seed SEED, save PATH, load PATH, help",
+    "Presets: preset NAME switches to one, preset save NAME saves the current parameters",
+    "Parameters: alignment, cohesion, separation, speed, perception, population, preset",
+    "Up and down go through earlier commands, the backtick or escape closes the console",
+];
+
+// What a line typed into the console does, parameters are set through the same controls as OSC
+#[derive(Clone, Debug)]
+pub enum Command {
+    Set(Control),
+    // Boids added at random places
+    Spawn(usize),
+    // Starts the simulation over from the seed
+    Seed(u64),
+    // Glides to the parameter preset
+    Preset(String),
+    SavePreset(String),
+    Save(String),
+    Load(String),
+    Help,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice() {
+            ["set", name, value] => {
+                let name = name.to_lowercase();
+
+                if !Control::NAMES.contains(&name.as_str()) {
+                    return Err(format!("Unknown parameter {}", name));
+                }
+
+                Control::from_name(&name, value.parse().ok(), Some(value))
+                    .map(Command::Set)
+                    .ok_or_else(|| format!("{} can't be set to {}", name, value))
+            }
+            ["spawn", count] => count.parse()
+                .map(Command::Spawn)
+                .map_err(|_| format!("{} is not a number of boids", count)),
+            ["seed", seed] => seed.parse()
+                .map(Command::Seed)
+                .map_err(|_| format!("{} is not a seed", seed)),
+            ["preset", "save", name] => Ok(Command::SavePreset(name.to_string())),
+            ["preset", name] => Ok(Command::Preset(name.to_string())),
+            ["save", path] => Ok(Command::Save(path.to_string())),
+            ["load", path] => Ok(Command::Load(path.to_string())),
+            ["help"] => Ok(Command::Help),
+            [] => Err("Type help for the commands".to_string()),
+            _ => Err(format!("Unknown command {}, type help for the commands", line.trim())),
+        }
+    }
+}
+
+// Drop-down console opened with the backtick. While it is open keys type into it instead of
+// controlling the app. The last CONSOLE_LINES lines of output stay on screen, they are printed
+// to the terminal as well.
+#[derive(Default)]
+pub struct Console {
+    pub open: bool,
+
+    input: String,
+    output: VecDeque<String>,
+    // Lines entered before, oldest first
+    history: Vec<String>,
+    // Entry of the history shown in the input, none while typing a new line
+    recalled: Option<usize>,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        Console::default()
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn type_character(&mut self, character: char) {
+        // The backtick that opens the console arrives as a character as well
+        if character.is_control() || character == '`' {
+            return;
+        }
+
+        self.input.push(character);
+        self.recalled = None;
+    }
+
+    pub fn backspace(&mut self) {
+        self.input.pop();
+        self.recalled = None;
+    }
+
+    // Empties the input and returns the command typed into it, reporting lines that don't parse
+    pub fn submit(&mut self) -> Option<Command> {
+        let line = std::mem::take(&mut self.input);
+        self.recalled = None;
+
+        self.print(format!("> {}", line));
+
+        if !line.trim().is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+        }
+
+        match Command::parse(&line) {
+            Ok(command) => Some(command),
+            Err(error) => {
+                self.print(error);
+                None
+            }
+        }
+    }
+
+    // Recalls the line entered before the recalled one, or the last one
+    pub fn previous(&mut self) {
+        let index = match self.recalled {
+            Some(index) => index.saturating_sub(1),
+            None if self.history.is_empty() => return,
+            None => self.history.len() - 1,
+        };
+
+        self.recall(Some(index));
+    }
+
+    // Recalls the line entered after the recalled one, past the last one the input is empty
+    pub fn next(&mut self) {
+        match self.recalled {
+            Some(index) if index + 1 < self.history.len() => self.recall(Some(index + 1)),
+            Some(_) => self.recall(None),
+            None => {}
+        }
+    }
+
+    fn recall(&mut self, index: Option<usize>) {
+        self.recalled = index;
+        self.input = index.map(|index| self.history[index].clone()).unwrap_or_default();
+    }
+
+    pub fn print(&mut self, text: String) {
+        println!("{}", text);
+
+        self.output.extend(text.lines().map(str::to_string));
+
+        while self.output.len() > CONSOLE_LINES {
+            self.output.pop_front();
+        }
+    }
+
+    // Output with the input line below it
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self.output.iter().cloned().collect();
+        lines.push(format!("> {}_", self.input));
+
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Types `line` into the console and enters it
+    fn enter(console: &mut Console, line: &str) -> Option<Command> {
+        line.chars().for_each(|character| console.type_character(character));
+        console.submit()
+    }
+
+    fn input(console: &Console) -> String {
+        console.lines().last().unwrap().clone()
+    }
+
+    #[test]
+    fn set_parses_known_parameters() {
+        assert!(matches!(
+            Command::parse("set Alignment 1.5"),
+            Ok(Command::Set(Control::Alignment(value))) if value == 1.5
+        ));
+        assert!(matches!(
+            Command::parse("set population 300"),
+            Ok(Command::Set(Control::Population(300)))
+        ));
+        assert!(matches!(
+            Command::parse("set preset mixed"),
+            Ok(Command::Set(Control::Preset(_)))
+        ));
+    }
+
+    #[test]
+    fn set_rejects_unknown_parameters_and_values() {
+        assert_eq!(Command::parse("set gravity 1").unwrap_err(), "Unknown parameter gravity");
+        assert_eq!(Command::parse("set speed fast").unwrap_err(), "speed can't be set to fast");
+        assert_eq!(Command::parse("set preset calm").unwrap_err(), "preset can't be set to calm");
+        assert!(Command::parse("set speed").is_err());
+    }
+
+    #[test]
+    fn spawn_and_seed_need_numbers() {
+        assert!(matches!(Command::parse("spawn 20"), Ok(Command::Spawn(20))));
+        assert!(matches!(Command::parse("seed 7"), Ok(Command::Seed(7))));
+        assert_eq!(Command::parse("spawn -3").unwrap_err(), "-3 is not a number of boids");
+        assert_eq!(Command::parse("seed x").unwrap_err(), "x is not a seed");
+    }
+
+    #[test]
+    fn preset_save_is_told_apart_from_a_preset_name() {
+        assert!(matches!(Command::parse("preset save calm"), Ok(Command::SavePreset(name)) if name == "calm"));
+        assert!(matches!(Command::parse("preset calm"), Ok(Command::Preset(name)) if name == "calm"));
+        assert!(matches!(Command::parse("preset save"), Ok(Command::Preset(name)) if name == "save"));
+    }
+
+    #[test]
+    fn paths_and_help() {
+        assert!(matches!(Command::parse("save a.bin"), Ok(Command::Save(path)) if path == "a.bin"));
+        assert!(matches!(Command::parse("load a.bin"), Ok(Command::Load(path)) if path == "a.bin"));
+        assert!(matches!(Command::parse("  help  "), Ok(Command::Help)));
+        assert!(Command::parse("help me").is_err());
+    }
+
+    #[test]
+    fn empty_and_unknown_lines_are_errors() {
+        assert_eq!(Command::parse("").unwrap_err(), "Type help for the commands");
+        assert_eq!(Command::parse("   ").unwrap_err(), "Type help for the commands");
+        assert_eq!(
+            Command::parse(" jump high ").unwrap_err(),
+            "Unknown command jump high, type help for the commands"
+        );
+    }
+
+    #[test]
+    fn history_stops_at_the_oldest_line_and_clears_past_the_newest() {
+        let mut console = Console::new();
+
+        // Nothing to recall yet
+        console.previous();
+        console.next();
+        assert_eq!(input(&console), "> _");
+
+        enter(&mut console, "help");
+        enter(&mut console, "spawn 5");
+        enter(&mut console, "spawn 5");
+        enter(&mut console, "  ");
+
+        console.previous();
+        assert_eq!(input(&console), "> spawn 5_");
+        console.previous();
+        assert_eq!(input(&console), "> help_");
+        console.previous();
+        assert_eq!(input(&console), "> help_");
+
+        console.next();
+        assert_eq!(input(&console), "> spawn 5_");
+        console.next();
+        assert_eq!(input(&console), "> _");
+        console.next();
+        assert_eq!(input(&console), "> _");
+    }
+
+    #[test]
+    fn typing_starts_a_new_line_after_recalling() {
+        let mut console = Console::new();
+        enter(&mut console, "help");
+
+        console.previous();
+        console.type_character('!');
+        assert_eq!(input(&console), "> help!_");
+
+        // Not recalling anymore, so next leaves the input alone
+        console.next();
+        assert_eq!(input(&console), "> help!_");
+    }
+
+    #[test]
+    fn submit_reports_lines_that_do_not_parse() {
+        let mut console = Console::new();
+
+        assert!(enter(&mut console, "fly").is_none());
+        assert!(console.lines().contains(&"Unknown command fly, type help for the commands".to_string()));
+        assert!(matches!(enter(&mut console, "help"), Some(Command::Help)));
+    }
+}
@@ -0,0 +1,75 @@
+use std::io;
+
+#[cfg(feature = "glium")]
+use glium::backend::glutin::DisplayCreationError;
+#[cfg(feature = "glium")]
+use glium::framebuffer::ValidationError;
+#[cfg(feature = "glium")]
+use glium::texture::TextureCreationError;
+#[cfg(feature = "glium")]
+use glium::{index, vertex, DrawError, ProgramCreationError};
+use rayon::ThreadPoolBuildError;
+use thiserror::Error;
+
+// What can go wrong while the window and its graphics are set up or drawn
+#[derive(Debug, Error)]
+pub enum Error {
+    #[cfg(feature = "glium")]
+    #[error("could not create the display: {0}")]
+    Display(#[from] DisplayCreationError),
+    #[error("could not read shader {path}: {source}")]
+    Shader {
+        path: String,
+        source: io::Error,
+    },
+    #[cfg(feature = "glium")]
+    #[error("could not build {vertex} and {fragment}: {source}")]
+    Program {
+        vertex: String,
+        fragment: String,
+        source: ProgramCreationError,
+    },
+    #[cfg(feature = "glium")]
+    #[error("could not create a vertex buffer: {0}")]
+    VertexBuffer(#[from] vertex::BufferCreationError),
+    #[cfg(feature = "glium")]
+    #[error("could not create an index buffer: {0}")]
+    IndexBuffer(#[from] index::BufferCreationError),
+    #[error("could not load image {path}: {source}")]
+    Image {
+        path: String,
+        source: image::ImageError,
+    },
+    #[cfg(feature = "glium")]
+    #[error("could not create a texture: {0}")]
+    Texture(#[from] TextureCreationError),
+    #[cfg(feature = "glium")]
+    #[error("could not create a framebuffer: {0}")]
+    Framebuffer(#[from] ValidationError),
+    #[cfg(feature = "glium")]
+    #[error("could not draw: {0}")]
+    Draw(#[from] DrawError),
+    // Glium's errors of these two can't be named, only their message is kept
+    #[error("instanced drawing is not supported")]
+    Instancing,
+    #[error("could not capture transform feedback: {0}")]
+    TransformFeedback(String),
+    #[error("a range outside of its buffer was drawn")]
+    BufferRange,
+    #[error("could not create the thread pool: {0}")]
+    ThreadPool(#[from] ThreadPoolBuildError),
+    #[cfg(feature = "wgpu")]
+    #[error("could not create the window: {0}")]
+    Window(#[from] winit::error::OsError),
+    #[cfg(feature = "wgpu")]
+    #[error("could not create the surface: {0}")]
+    Surface(#[from] wgpu::CreateSurfaceError),
+    #[cfg(feature = "wgpu")]
+    #[error("no graphics adapter can draw to the window")]
+    Adapter,
+    #[cfg(feature = "wgpu")]
+    #[error("could not open the graphics device: {0}")]
+    Device(#[from] wgpu::RequestDeviceError),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,51 @@
+use crate::{GOVERNOR_HEADROOM, GOVERNOR_INTERVAL, GOVERNOR_MAX_STEP, GOVERNOR_SMOOTHING, MAX_AGENT_COUNT, MIN_AGENT_COUNT};
+
+// Grows or shrinks the flock to hold a target frame rate.
+// Frame times are noisy, so they are smoothed with a moving average and the population
+// only changes every GOVERNOR_INTERVAL seconds, giving the signal time to settle.
+pub struct PopulationGovernor {
+    pub enabled: bool,
+    pub target_fps: f32,
+    // Seconds spent simulating and drawing one frame, averaged
+    smoothed_frame_time: f32,
+    since_change: f32,
+}
+
+impl PopulationGovernor {
+    pub fn new(target_fps: f32) -> PopulationGovernor {
+        PopulationGovernor {
+            enabled: false,
+            target_fps,
+            smoothed_frame_time: 1.0 / target_fps,
+            since_change: 0.0,
+        }
+    }
+
+    // Takes the time since the last frame and how long that frame took to simulate and draw.
+    // Returns the population that should be alive next, if it should change.
+    pub fn update(&mut self, delta_time: f32, frame_time: f32, population: usize) -> Option<usize> {
+        self.smoothed_frame_time += (frame_time - self.smoothed_frame_time) * GOVERNOR_SMOOTHING;
+        self.since_change += delta_time;
+
+        if !self.enabled || self.since_change < GOVERNOR_INTERVAL || population == 0 {
+            return None;
+        }
+
+        self.since_change = 0.0;
+
+        // Frame time grows about linearly with the population,
+        // so scale it by how far off the budget the frames are
+        let budget = GOVERNOR_HEADROOM / self.target_fps;
+        let scale = (budget / self.smoothed_frame_time.max(1e-6))
+            .clamp(1.0 - GOVERNOR_MAX_STEP, 1.0 + GOVERNOR_MAX_STEP);
+
+        let target = ((population as f32 * scale) as usize).clamp(MIN_AGENT_COUNT, MAX_AGENT_COUNT);
+
+        // Small corrections would only chase noise
+        if (target as f32 - population as f32).abs() < population as f32 * 0.02 {
+            return None;
+        }
+
+        Some(target)
+    }
+}
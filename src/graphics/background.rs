@@ -0,0 +1,81 @@
+use glium::texture::SrgbTexture2d;
+use glium::uniforms::SamplerWrapFunction;
+use glium::{Blend, Display, DrawParameters, Program, Surface};
+
+use crate::camera::Camera;
+use crate::error::Result;
+use crate::graphics::{create_unit_quad, load_program, load_texture, Mesh};
+use crate::{BACKGROUND_PARALLAX, BACKGROUND_TINT};
+
+// Image stretched over the screen behind the world. It moves and zooms with the
+// camera by BACKGROUND_PARALLAX, less than the world does, so it reads as far away.
+pub struct Background {
+    image: SrgbTexture2d,
+    quad: Mesh,
+    program: Program,
+    // Part of the image on screen, updated as the camera moves
+    uv_scale: f32,
+    uv_offset: [f32; 2],
+    // Color the image is multiplied with, BACKGROUND_TINT shaded by the day cycle
+    pub tint: [f32; 3],
+}
+
+impl Background {
+    // None if the image can't be loaded, the background color is used then
+    pub fn load(display: &Display, path: &str) -> Option<Background> {
+        match Background::new(display, path) {
+            Ok(background) => Some(background),
+            Err(error) => {
+                println!("Could not load background: {}", error);
+                None
+            }
+        }
+    }
+
+    fn new(display: &Display, path: &str) -> Result<Background> {
+        Ok(Background {
+            image: load_texture(display, path)?,
+            quad: create_unit_quad(display)?,
+            program: load_program(
+                display,
+                "shaders/background_vertex.glsl",
+                "shaders/background_fragment.glsl"
+            )?,
+            uv_scale: 1.0,
+            uv_offset: [0.0, 0.0],
+            tint: BACKGROUND_TINT,
+        })
+    }
+
+    pub fn follow(&mut self, camera: &Camera, world_size: [f32; 2]) {
+        self.uv_scale = 1.0 / (1.0 + (camera.zoom - 1.0) * BACKGROUND_PARALLAX);
+
+        // Image y points up, world y down
+        self.uv_offset = [
+            (camera.center[0] / world_size[0] - 0.5) * BACKGROUND_PARALLAX,
+            (0.5 - camera.center[1] / world_size[1]) * BACKGROUND_PARALLAX,
+        ];
+    }
+
+    pub fn draw<S: Surface>(&self, target: &mut S) -> Result<()> {
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target.draw(
+            &self.quad.v_buffer,
+            &self.quad.i_buffer,
+            &self.program,
+            &uniform! {
+                image: self.image.sampled().wrap_function(SamplerWrapFunction::Mirror),
+                tint: self.tint,
+                uv_scale: self.uv_scale,
+                uv_offset: self.uv_offset,
+            },
+            &params
+        )?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::uniforms::{MagnifySamplerFilter, Sampler, SamplerWrapFunction, Uniforms};
+use glium::{Display, Program, Surface, Texture2d};
+
+use crate::error::Result;
+use crate::graphics::{create_unit_quad, load_program, Mesh};
+use crate::{BLOOM_BLUR_PASSES, BLOOM_INTENSITY, BLOOM_THRESHOLD};
+
+// Textures of one screen size, the blur runs at half resolution
+struct Targets {
+    size: (u32, u32),
+    scene: Texture2d,
+    // Bright parts are blurred back and forth between these
+    blur: [Texture2d; 2],
+}
+
+impl Targets {
+    fn new(display: &Display, size: (u32, u32)) -> Result<Targets> {
+        let half = ((size.0 / 2).max(1), (size.1 / 2).max(1));
+        let texture = |(w, h): (u32, u32)| Texture2d::empty(display, w, h);
+
+        Ok(Targets {
+            size,
+            scene: texture(size)?,
+            blur: [texture(half)?, texture(half)?],
+        })
+    }
+}
+
+// Glow around bright boids. The world is drawn into a texture, its bright parts are
+// extracted and blurred at half resolution, and both are added up on the screen.
+pub struct Bloom {
+    pub enabled: bool,
+
+    quad: Mesh,
+    bright_program: Program,
+    blur_program: Program,
+    composite_program: Program,
+    // Created at the size of the screen on first use
+    targets: Option<Targets>,
+}
+
+impl Bloom {
+    pub fn new(display: &Display) -> Result<Bloom> {
+        let program = |fragment_shader| load_program(display, "shaders/screen_vertex.glsl", fragment_shader);
+
+        Ok(Bloom {
+            enabled: false,
+
+            quad: create_unit_quad(display)?,
+            bright_program: program("shaders/bright_fragment.glsl")?,
+            blur_program: program("shaders/blur_fragment.glsl")?,
+            composite_program: program("shaders/composite_fragment.glsl")?,
+            targets: None,
+        })
+    }
+
+    // Clears and returns the surface the world is drawn on
+    pub fn begin(&mut self, display: &Display, size: (u32, u32), background: [f32; 4]) -> Result<SimpleFrameBuffer<'_>> {
+        let targets = match self.targets.take() {
+            Some(targets) if targets.size == size => self.targets.insert(targets),
+            _ => self.targets.insert(Targets::new(display, size)?),
+        };
+
+        let mut surface = SimpleFrameBuffer::new(display, &targets.scene)?;
+        let [r, g, b, a] = background;
+        surface.clear_color(r, g, b, a);
+
+        Ok(surface)
+    }
+
+    // Blurs the bright parts of the drawn world and draws both to `target`
+    pub fn present<S: Surface>(&self, display: &Display, target: &mut S) -> Result<()> {
+        let targets = match &self.targets {
+            Some(targets) => targets,
+            None => return Ok(()),
+        };
+
+        self.pass(display, &targets.blur[0], &self.bright_program, &uniform! {
+            image: sampled(&targets.scene),
+            threshold: BLOOM_THRESHOLD,
+        })?;
+
+        let texel = [1.0 / targets.blur[0].width() as f32, 1.0 / targets.blur[0].height() as f32];
+
+        for _ in 0..BLOOM_BLUR_PASSES {
+            self.pass(display, &targets.blur[1], &self.blur_program, &uniform! {
+                image: sampled(&targets.blur[0]),
+                direction: [texel[0], 0.0],
+            })?;
+
+            self.pass(display, &targets.blur[0], &self.blur_program, &uniform! {
+                image: sampled(&targets.blur[1]),
+                direction: [0.0, texel[1]],
+            })?;
+        }
+
+        target.draw(
+            &self.quad.v_buffer,
+            &self.quad.i_buffer,
+            &self.composite_program,
+            &uniform! {
+                scene: sampled(&targets.scene),
+                bloom: sampled(&targets.blur[0]),
+                intensity: BLOOM_INTENSITY,
+            },
+            &Default::default()
+        )?;
+
+        Ok(())
+    }
+
+    // Draws the whole quad into `output`
+    fn pass<U: Uniforms>(&self, display: &Display, output: &Texture2d, program: &Program, uniforms: &U) -> Result<()> {
+        let mut surface = SimpleFrameBuffer::new(display, output)?;
+
+        surface.draw(
+            &self.quad.v_buffer,
+            &self.quad.i_buffer,
+            program,
+            uniforms,
+            &Default::default()
+        )?;
+
+        Ok(())
+    }
+}
+
+fn sampled(texture: &Texture2d) -> Sampler<'_, Texture2d> {
+    texture.sampled()
+        .magnify_filter(MagnifySamplerFilter::Linear)
+        .wrap_function(SamplerWrapFunction::Clamp)
+}
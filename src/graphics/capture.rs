@@ -0,0 +1,46 @@
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use glium::Display;
+use glium::texture::RawImage2d;
+
+use crate::SCREENSHOT_DIR;
+
+// RGBA pixels of a presented frame, rows from top to bottom
+pub struct CapturedFrame {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Reads back the last frame, must be called after the frame is finished
+pub fn read_frame(display: &Display) -> Result<CapturedFrame, Box<dyn Error>> {
+    let image: RawImage2d<u8> = display.read_front_buffer()?;
+
+    // OpenGL stores the bottom row first
+    let row = image.width as usize * 4;
+    let pixels = image.data.chunks(row).rev().flatten().copied().collect();
+
+    Ok(CapturedFrame {
+        pixels,
+        width: image.width,
+        height: image.height,
+    })
+}
+
+// Saves the last frame as a PNG named after the current time, returns its path
+pub fn save_screenshot(display: &Display) -> Result<PathBuf, Box<dyn Error>> {
+    let frame = read_frame(display)?;
+
+    fs::create_dir_all(SCREENSHOT_DIR)?;
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH)?;
+    let path = PathBuf::from(SCREENSHOT_DIR)
+        .join(format!("screenshot-{}-{:03}.png", time.as_secs(), time.subsec_millis()));
+
+    image::save_buffer(&path, &frame.pixels, frame.width, frame.height, image::ColorType::Rgba8)?;
+
+    Ok(path)
+}
@@ -0,0 +1,107 @@
+use glium::{Display, Frame};
+use vecmath::{vec2_add, vec2_scale, Vector2};
+
+use boids_core::components::Components;
+use boids_core::data::BehaviorState;
+use crate::error::Result;
+use crate::graphics::vertices::LineVertex;
+use crate::graphics::lines::LineRenderer;
+use crate::DEBUG_VECTOR_SCALE;
+
+const ALIGNMENT_COLOR: [f32; 4] = [0.2, 1.0, 0.3, 0.8];
+const COHESION_COLOR: [f32; 4] = [0.3, 0.5, 1.0, 0.8];
+const SEPARATION_COLOR: [f32; 4] = [1.0, 0.25, 0.2, 0.8];
+const HEADING_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+
+// Which boids the steering vectors are drawn for
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DebugVectorMode {
+    Off,
+    // Selected boid, or the hovered one if none is selected
+    Selected,
+    All,
+}
+
+impl DebugVectorMode {
+    pub fn next(self) -> DebugVectorMode {
+        match self {
+            DebugVectorMode::Off => DebugVectorMode::Selected,
+            DebugVectorMode::Selected => DebugVectorMode::All,
+            DebugVectorMode::All => DebugVectorMode::Off,
+        }
+    }
+}
+
+// Draws the weighted alignment, cohesion and separation vectors of the last
+// steering pass and the resulting heading as lines starting at the boid.
+pub struct DebugVectors {
+    pub mode: DebugVectorMode,
+
+    lines: LineRenderer,
+    vertices: Vec<LineVertex>,
+}
+
+impl DebugVectors {
+    pub fn new(display: &Display) -> Result<DebugVectors> {
+        Ok(DebugVectors {
+            mode: DebugVectorMode::Off,
+
+            lines: LineRenderer::new(display)?,
+            vertices: Vec::new(),
+        })
+    }
+
+    // `target` is the index of the boid drawn in the selected mode
+    pub fn draw(
+        &mut self,
+        display: &Display,
+        frame: &mut Frame,
+        view: [[f32; 4]; 4],
+        components: &Components,
+        target: Option<usize>
+    ) -> Result<()> {
+        self.vertices.clear();
+
+        match self.mode {
+            DebugVectorMode::Off => return Ok(()),
+            DebugVectorMode::Selected => {
+                if let Some(index) = target {
+                    self.push_boid(components, index);
+                }
+            }
+            DebugVectorMode::All => {
+                for index in 0..components.len() {
+                    self.push_boid(components, index);
+                }
+            }
+        }
+
+        self.lines.upload(display, &self.vertices)?;
+        self.lines.draw(frame, view)
+    }
+
+    fn push_boid(&mut self, components: &Components, index: usize) {
+        if components.behaviors[index].state == BehaviorState::Dead {
+            return;
+        }
+
+        let position = components.positions[index].value;
+        let forces = &components.forces[index];
+
+        self.push_vector(position, forces.alignment, ALIGNMENT_COLOR);
+        self.push_vector(position, forces.cohesion, COHESION_COLOR);
+        self.push_vector(position, forces.separation, SEPARATION_COLOR);
+        self.push_vector(position, forces.heading, HEADING_COLOR);
+    }
+
+    fn push_vector(&mut self, start: Vector2<f32>, vector: Vector2<f32>, color: [f32; 4]) {
+        let end = vec2_add(start, vec2_scale(vector, DEBUG_VECTOR_SCALE));
+
+        self.vertices.push(LineVertex { position: start, color });
+        self.vertices.push(LineVertex { position: end, color });
+    }
+
+    pub fn gpu_bytes(&self) -> usize {
+        self.lines.gpu_bytes()
+    }
+}
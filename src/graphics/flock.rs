@@ -0,0 +1,239 @@
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::SrgbTexture2d;
+use glium::{Blend, Display, DrawParameters, Program, Surface, VertexBuffer};
+
+use crate::error::{Error, Result};
+use crate::graphics::vertices::BoidInstance;
+use boids_core::diagnostics::MemoryDiagnostics;
+use crate::graphics::instances::InstanceBuffers;
+use crate::graphics::{create_mesh, create_sprite_shape, load_program, load_texture, Mesh};
+use crate::graphics::lines::LineRenderer;
+use crate::graphics::renderer::{DrawTo, Renderer};
+use crate::graphics::shapes::{AgentShape, SHAPES};
+use crate::snapshot::RenderSnapshot;
+use boids_core::SPECIES_COUNT;
+use crate::{AGENT_SHAPE, FLAP_AMPLITUDE, LOD_AGENT_COUNT, LOD_POINT_SIZE, SOFT_BOIDS, SPRITE_PATH, VELOCITY_STRETCH};
+
+// Draws the CPU flock, one instance of the agent mesh per boid.
+// Big flocks switch to one point per boid, at that density shapes can't be told apart anyway.
+// Trails are drawn first as blended lines, so boids stay on top of them.
+pub struct FlockRenderer {
+    shader: Program,
+    point_shader: Program,
+    // One mesh per entry of SHAPES
+    shape_meshes: Vec<Mesh>,
+    // Shape of every species, boids are drawn once per shape in use
+    pub species_shapes: [AgentShape; SPECIES_COUNT],
+    // Boids fade out from their center and are blended
+    pub soft: bool,
+    // Extra length of a boid along its heading per unit of speed, 0 turns stretching off
+    pub velocity_stretch: f32,
+    // Width of a boid in pixels
+    agent_size: f32,
+    // Replaces the agent mesh when SPRITE_PATH is set and loads
+    sprite: Option<Sprite>,
+    instance_buffers: InstanceBuffers<BoidInstance>,
+
+    trails: LineRenderer,
+    // Outline around the highlighted boid
+    highlight: LineRenderer,
+}
+
+pub const FLOCK_VERTEX_SHADER: &str = "shaders/vertex.glsl";
+pub const FLOCK_FRAGMENT_SHADER: &str = "shaders/boid_fragment.glsl";
+
+fn create_shape_mesh(display: &Display, shape: AgentShape, size: f32) -> Result<Mesh> {
+    let (vertices, indices) = shape.mesh_geometry(size);
+
+    create_mesh(display, &vertices, &indices)
+}
+
+// Textured quad drawn for every boid
+struct Sprite {
+    shader: Program,
+    texture: SrgbTexture2d,
+    quad: Mesh,
+}
+
+impl Sprite {
+    // None if the sprite can't be loaded, the agent mesh is drawn then
+    fn load(display: &Display, path: &str, size: f32) -> Option<Sprite> {
+        match Sprite::new(display, path, size) {
+            Ok(sprite) => Some(sprite),
+            Err(error) => {
+                println!("Could not load sprite: {}", error);
+                None
+            }
+        }
+    }
+
+    fn new(display: &Display, path: &str, size: f32) -> Result<Sprite> {
+        let (vertices, indices) = create_sprite_shape(size, [1.0, 1.0, 1.0]);
+
+        Ok(Sprite {
+            shader: load_program(
+                display,
+                "shaders/sprite_vertex.glsl",
+                "shaders/sprite_fragment.glsl"
+            )?,
+            texture: load_texture(display, path)?,
+            quad: create_mesh(display, &vertices, &indices)?,
+        })
+    }
+}
+
+impl FlockRenderer {
+    pub fn new(display: &Display, snapshot: &RenderSnapshot, agent_size: f32) -> Result<FlockRenderer> {
+        Ok(FlockRenderer {
+            shader: load_program(display, FLOCK_VERTEX_SHADER, FLOCK_FRAGMENT_SHADER)?,
+            point_shader: load_program(
+                display,
+                "shaders/point_vertex.glsl",
+                FLOCK_FRAGMENT_SHADER
+            )?,
+            shape_meshes: SHAPES.iter()
+                .map(|shape| create_shape_mesh(display, *shape, agent_size))
+                .collect::<Result<_>>()?,
+            species_shapes: [AGENT_SHAPE; SPECIES_COUNT],
+            soft: SOFT_BOIDS,
+            velocity_stretch: VELOCITY_STRETCH,
+            agent_size,
+            sprite: SPRITE_PATH.and_then(|path| Sprite::load(display, path, agent_size)),
+            instance_buffers: InstanceBuffers::new(display, &snapshot.instances)?,
+
+            trails: LineRenderer::new(display)?,
+            highlight: LineRenderer::new(display)?,
+        })
+    }
+
+    // Recompiles the flock shaders, the current program stays in use if that fails
+    pub fn reload_shaders(&mut self, display: &Display) -> Result<()> {
+        let shader = load_program(display, FLOCK_VERTEX_SHADER, FLOCK_FRAGMENT_SHADER)?;
+        let point_shader = load_program(display, "shaders/point_vertex.glsl", FLOCK_FRAGMENT_SHADER)?;
+
+        self.shader = shader;
+        self.point_shader = point_shader;
+
+        Ok(())
+    }
+
+    // Mesh of the first species, the GPU simulation has no species
+    pub fn mesh(&self) -> &Mesh {
+        &self.shape_meshes[self.species_shapes[0] as usize]
+    }
+
+    pub fn agent_size(&self) -> f32 {
+        self.agent_size
+    }
+
+    // Instances of the last upload
+    pub fn instances(&self) -> &VertexBuffer<BoidInstance> {
+        self.instance_buffers.current()
+    }
+
+    pub fn record_memory(&self, memory: &mut MemoryDiagnostics) {
+        memory.record("Instance copies", self.instance_buffers.cpu_bytes());
+        memory.record("Instance buffers", self.instance_buffers.gpu_bytes());
+        memory.record("Trail buffer", self.trails.gpu_bytes());
+    }
+
+    fn draw_boids<S: Surface>(&self, target: &mut S, perspective: [[f32; 4]; 4]) -> Result<()> {
+        if self.instance_buffers.current().len() > LOD_AGENT_COUNT {
+            return self.draw_points(target, perspective);
+        }
+
+        if let Some(sprite) = &self.sprite {
+            return self.draw_sprites(target, perspective, sprite);
+        }
+
+        let params = DrawParameters {
+            blend: if self.soft { Blend::alpha_blending() } else { Default::default() },
+            ..Default::default()
+        };
+
+        for (i, shape) in self.species_shapes.iter().enumerate() {
+            // Drawn already for an earlier species
+            if self.species_shapes[..i].contains(shape) {
+                continue;
+            }
+
+            let mesh = &self.shape_meshes[*shape as usize];
+
+            target.draw(
+                (&mesh.v_buffer, self.instance_buffers.current().per_instance().map_err(|_| Error::Instancing)?),
+                &mesh.i_buffer,
+                &self.shader,
+                &uniform! {
+                    perspective: perspective,
+                    drawn_shape: *shape as u32,
+                    flap_amplitude: FLAP_AMPLITUDE,
+                    agent_radius: self.agent_size / 2.0,
+                    velocity_stretch: self.velocity_stretch,
+                    soft: self.soft,
+                },
+                &params
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_sprites<S: Surface>(&self, target: &mut S, perspective: [[f32; 4]; 4], sprite: &Sprite) -> Result<()> {
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target.draw(
+            (&sprite.quad.v_buffer, self.instance_buffers.current().per_instance().map_err(|_| Error::Instancing)?),
+            &sprite.quad.i_buffer,
+            &sprite.shader,
+            &uniform! {
+                perspective: perspective,
+                sprite: &sprite.texture,
+                velocity_stretch: self.velocity_stretch,
+            },
+            &params
+        )?;
+
+        Ok(())
+    }
+
+    fn draw_points<S: Surface>(&self, target: &mut S, perspective: [[f32; 4]; 4]) -> Result<()> {
+        let params = DrawParameters {
+            point_size: Some(LOD_POINT_SIZE),
+            ..Default::default()
+        };
+
+        target.draw(
+            self.instance_buffers.current(),
+            NoIndices(PrimitiveType::Points),
+            &self.point_shader,
+            &uniform! {
+                perspective: perspective,
+                soft: false,
+            },
+            &params
+        )?;
+
+        Ok(())
+    }
+}
+
+impl Renderer for FlockRenderer {
+    type Context = Display;
+
+    fn upload(&mut self, display: &Display, snapshot: &RenderSnapshot) -> Result<()> {
+        self.instance_buffers.upload(display, &snapshot.instances)?;
+        self.trails.upload(display, &snapshot.trail_vertices)?;
+        self.highlight.upload(display, &snapshot.highlight_vertices)
+    }
+}
+
+impl<S: Surface> DrawTo<S> for FlockRenderer {
+    fn draw(&self, target: &mut S, perspective: [[f32; 4]; 4]) -> Result<()> {
+        self.trails.draw(target, perspective)?;
+        self.draw_boids(target, perspective)?;
+        self.highlight.draw(target, perspective)
+    }
+}
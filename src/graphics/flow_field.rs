@@ -0,0 +1,31 @@
+use glium::{Display, Surface};
+
+use crate::error::Result;
+use crate::graphics::lines::LineRenderer;
+use crate::snapshot::RenderSnapshot;
+
+// Arrows of the average boid velocity per grid cell, drawn over the flock.
+// The field itself is averaged by the simulation while `Simulation::flow_field` is enabled.
+pub struct FlowFieldOverlay {
+    lines: LineRenderer,
+}
+
+impl FlowFieldOverlay {
+    pub fn new(display: &Display) -> Result<FlowFieldOverlay> {
+        Ok(FlowFieldOverlay {
+            lines: LineRenderer::new(display)?,
+        })
+    }
+
+    pub fn upload(&mut self, display: &Display, snapshot: &RenderSnapshot) -> Result<()> {
+        self.lines.upload(display, &snapshot.flow_vertices)
+    }
+
+    pub fn draw<S: Surface>(&self, target: &mut S, perspective: [[f32; 4]; 4]) -> Result<()> {
+        self.lines.draw(target, perspective)
+    }
+
+    pub fn gpu_bytes(&self) -> usize {
+        self.lines.gpu_bytes()
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use glium::Display;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, RgbaImage};
+
+use crate::graphics::capture::{read_frame, CapturedFrame};
+use crate::{GIF_DURATION, GIF_FPS, GIF_WIDTH, RECORDING_DIR};
+
+// Keeps the last GIF_DURATION seconds as small frames, so an interesting moment
+// can be saved as a GIF after it happened. Frames are only captured while enabled,
+// every capture reads back the whole window.
+pub struct GifHistory {
+    pub enabled: bool,
+    frames: VecDeque<CapturedFrame>,
+    // Seconds since the last captured frame
+    since_capture: f32,
+}
+
+impl GifHistory {
+    pub fn new() -> GifHistory {
+        GifHistory {
+            enabled: false,
+            frames: VecDeque::new(),
+            since_capture: 0.0,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+
+        if !self.enabled {
+            self.frames.clear();
+        }
+    }
+
+    pub fn seconds(&self) -> f32 {
+        self.frames.len() as f32 / GIF_FPS as f32
+    }
+
+    // Called after every finished frame, captures one every 1 / GIF_FPS seconds
+    pub fn update(&mut self, display: &Display, delta_time: f32) -> Result<(), Box<dyn Error>> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        self.since_capture += delta_time;
+
+        if self.since_capture < 1.0 / GIF_FPS as f32 {
+            return Ok(());
+        }
+
+        self.since_capture = 0.0;
+
+        let frame = downscale(&read_frame(display)?, GIF_WIDTH);
+
+        // Frames of an older window size can't go into the same GIF
+        if self.frames.front().is_some_and(|first| first.width != frame.width || first.height != frame.height) {
+            self.frames.clear();
+        }
+
+        if self.frames.len() >= (GIF_DURATION * GIF_FPS as f32) as usize {
+            self.frames.pop_front();
+        }
+
+        self.frames.push_back(frame);
+
+        Ok(())
+    }
+
+    // Encodes the kept frames on a background thread, returns the path the GIF is written to
+    pub fn export(&self) -> Result<PathBuf, Box<dyn Error>> {
+        if self.frames.is_empty() {
+            return Err("no frames captured yet".into());
+        }
+
+        fs::create_dir_all(RECORDING_DIR)?;
+
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let path = PathBuf::from(RECORDING_DIR).join(format!("clip-{}.gif", time.as_secs()));
+
+        let mut frames = Vec::with_capacity(self.frames.len());
+
+        for frame in &self.frames {
+            let image = RgbaImage::from_raw(frame.width, frame.height, frame.pixels.clone())
+                .ok_or("frame does not match its size")?;
+
+            frames.push(image::Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(1000, GIF_FPS)));
+        }
+
+        let file = BufWriter::new(File::create(&path)?);
+        let output = path.clone();
+
+        thread::spawn(move || {
+            let mut encoder = GifEncoder::new(file);
+
+            let result = encoder.set_repeat(Repeat::Infinite)
+                .and_then(|_| encoder.encode_frames(frames));
+
+            match result {
+                Ok(()) => println!("Saved GIF to {}", output.display()),
+                Err(error) => println!("Could not save GIF: {}", error),
+            }
+        });
+
+        Ok(path)
+    }
+}
+
+// Nearest neighbor downscale to at most `max_width` pixels wide
+fn downscale(frame: &CapturedFrame, max_width: u32) -> CapturedFrame {
+    let step = frame.width.div_ceil(max_width).max(1);
+    let width = frame.width / step;
+    let height = frame.height / step;
+
+    let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = (((y * step) * frame.width + x * step) * 4) as usize;
+            pixels.extend_from_slice(&frame.pixels[i..i + 4]);
+        }
+    }
+
+    CapturedFrame { pixels, width, height }
+}
@@ -0,0 +1,214 @@
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::{MipmapsOption, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::MagnifySamplerFilter;
+use glium::vertex::TransformFeedbackSession;
+use glium::{Blend, BlendingFunction, Display, DrawParameters, LinearBlendingFactor, Program, Surface, VertexBuffer};
+
+use crate::error::{Error, Result};
+use crate::graphics::vertices::GpuBoid;
+use crate::graphics::{load_feedback_program, load_program, Mesh};
+use crate::{LOD_AGENT_COUNT, LOD_POINT_SIZE};
+
+// Size of one field texel in world units
+const FIELD_CELL_SIZE: f32 = 8.0;
+
+// Simulation that lives entirely on the GPU.
+// Boids are stored in two vertex buffers, the update pass reads one and writes
+// the other with transform feedback, and they are swapped after every step.
+// Neighbors are found through a field texture every boid deposits its heading into.
+pub struct GpuSimulation {
+    boids: [VertexBuffer<GpuBoid>; 2],
+    // Index of the buffer holding the current state
+    current: usize,
+
+    field: Texture2d,
+    // Target of the update pass, only its transform feedback output is used
+    dummy_target: Texture2d,
+
+    field_program: Program,
+    update_program: Program,
+    render_program: Program,
+    point_program: Program,
+
+    world_size: [f32; 2],
+}
+
+impl GpuSimulation {
+    pub fn gpu_bytes(&self) -> usize {
+        self.boids.iter().map(|buffer| buffer.get_size()).sum()
+    }
+
+    pub fn new(display: &Display, boids: &[GpuBoid], world_size: [f32; 2]) -> Result<GpuSimulation> {
+        let field = field_texture(display, world_size)?;
+
+        Ok(GpuSimulation {
+            boids: [
+                VertexBuffer::dynamic(display, boids)?,
+                VertexBuffer::dynamic(display, boids)?,
+            ],
+            current: 0,
+
+            field,
+            dummy_target: Texture2d::empty(display, 1, 1)?,
+
+            field_program: load_program(
+                display,
+                "shaders/gpu_field_vertex.glsl",
+                "shaders/gpu_field_fragment.glsl"
+            )?,
+            update_program: load_feedback_program(
+                display,
+                "shaders/gpu_update_vertex.glsl",
+                "shaders/gpu_update_fragment.glsl",
+                &["out_position", "out_direction"]
+            )?,
+            render_program: load_program(
+                display,
+                "shaders/gpu_render_vertex.glsl",
+                "shaders/fragment.glsl"
+            )?,
+            point_program: load_program(
+                display,
+                "shaders/gpu_point_vertex.glsl",
+                "shaders/fragment.glsl"
+            )?,
+
+            world_size,
+        })
+    }
+
+    // Follows a new world size like the CPU grid does,
+    // boids outside of the new world wrap back in on the next step
+    pub fn resize(&mut self, display: &Display, world_size: [f32; 2]) -> Result<()> {
+        self.field = field_texture(display, world_size)?;
+        self.world_size = world_size;
+
+        Ok(())
+    }
+
+    pub fn update(&mut self, display: &Display, dt: f32, speed: f32, weights: [f32; 3]) -> Result<()> {
+        self.deposit_field()?;
+
+        let (current, next) = if self.current == 0 {
+            let (a, b) = self.boids.split_at_mut(1);
+            (&a[0], &mut b[0])
+        }
+        else {
+            let (a, b) = self.boids.split_at_mut(1);
+            (&b[0], &mut a[0])
+        };
+
+        let session = TransformFeedbackSession::new(display, &self.update_program, next)
+            .map_err(|error| Error::TransformFeedback(error.to_string()))?;
+
+        let params = DrawParameters {
+            transform_feedback: Some(&session),
+            ..Default::default()
+        };
+
+        let field = self.field.sampled().magnify_filter(MagnifySamplerFilter::Linear);
+
+        let mut target = self.dummy_target.as_surface();
+
+        target.draw(
+            current,
+            NoIndices(PrimitiveType::Points),
+            &self.update_program,
+            &uniform! {
+                field: field,
+                world_size: self.world_size,
+                delta_time: dt,
+                speed: speed,
+                alignment_weight: weights[0],
+                cohesion_weight: weights[1],
+                separation_weight: weights[2],
+            },
+            &params
+        )?;
+
+        self.current = 1 - self.current;
+
+        Ok(())
+    }
+
+    // Accumulates headings and boid counts of every texel with additive blending
+    fn deposit_field(&self) -> Result<()> {
+        let mut target = self.field.as_surface();
+        target.clear_color(0.0, 0.0, 0.0, 0.0);
+
+        let additive = BlendingFunction::Addition {
+            source: LinearBlendingFactor::One,
+            destination: LinearBlendingFactor::One,
+        };
+
+        let params = DrawParameters {
+            blend: Blend {
+                color: additive,
+                alpha: additive,
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+            ..Default::default()
+        };
+
+        target.draw(
+            &self.boids[self.current],
+            NoIndices(PrimitiveType::Points),
+            &self.field_program,
+            &uniform! {
+                world_size: self.world_size,
+            },
+            &params
+        )?;
+
+        Ok(())
+    }
+
+    // Big flocks are drawn as points, same as on the CPU
+    pub fn render<S: Surface>(&self, target: &mut S, mesh: &Mesh, perspective: [[f32; 4]; 4]) -> Result<()> {
+        let boids = &self.boids[self.current];
+
+        if boids.len() > LOD_AGENT_COUNT {
+            let params = DrawParameters {
+                point_size: Some(LOD_POINT_SIZE),
+                ..Default::default()
+            };
+
+            target.draw(
+                boids,
+                NoIndices(PrimitiveType::Points),
+                &self.point_program,
+                &uniform! {
+                    perspective: perspective,
+                },
+                &params
+            )?;
+
+            return Ok(());
+        }
+
+        target.draw(
+            (&mesh.v_buffer, boids.per_instance().map_err(|_| Error::Instancing)?),
+            &mesh.i_buffer,
+            &self.render_program,
+            &uniform! {
+                perspective: perspective,
+            },
+            &Default::default()
+        )?;
+
+        Ok(())
+    }
+}
+
+// One texel for every FIELD_CELL_SIZE of the world
+fn field_texture(display: &Display, world_size: [f32; 2]) -> Result<Texture2d> {
+    let texture = Texture2d::empty_with_format(
+        display,
+        UncompressedFloatFormat::F32F32F32F32,
+        MipmapsOption::NoMipmap,
+        ((world_size[0] / FIELD_CELL_SIZE).ceil() as u32).max(1),
+        ((world_size[1] / FIELD_CELL_SIZE).ceil() as u32).max(1)
+    )?;
+
+    Ok(texture)
+}
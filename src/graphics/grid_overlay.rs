@@ -0,0 +1,136 @@
+use glium::{Blend, Display, DrawParameters, Frame, Program, Surface, VertexBuffer};
+
+use crate::error::{Error, Result};
+use crate::graphics::{create_unit_quad, grow_dynamic_buffer, load_program, Mesh};
+use boids_core::grid::Grid;
+use crate::GRID_OVERLAY_MAX_OCCUPANCY;
+
+const OCCUPANCY_COLOR: [f32; 3] = [0.2, 0.6, 1.0];
+const OCCUPANCY_ALPHA: f32 = 0.5;
+const QUERY_COLOR: [f32; 4] = [1.0, 0.9, 0.2, 0.35];
+const LINE_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.25];
+// Width of cell boundaries in pixels, kept constant while zooming
+const LINE_WIDTH: f32 = 1.0;
+
+#[derive(Clone, Copy)]
+struct Rectangle {
+    rect: [f32; 4],
+    fill: [f32; 4],
+}
+implement_vertex!(Rectangle, rect, fill);
+
+// Debug view of the spatial grid. Cells are shaded by how many boids they hold,
+// the cells a neighbor query of the hovered boid checks are highlighted and
+// cell boundaries are drawn on top. Everything is instances of one blended quad.
+pub struct GridOverlay {
+    pub enabled: bool,
+
+    quad: Mesh,
+    program: Program,
+    buffer: VertexBuffer<Rectangle>,
+    rectangles: Vec<Rectangle>,
+    // Cells checked by the query of the hovered boid
+    query_cells: Vec<bool>,
+}
+
+impl GridOverlay {
+    pub fn new(display: &Display) -> Result<GridOverlay> {
+        Ok(GridOverlay {
+            enabled: false,
+
+            quad: create_unit_quad(display)?,
+            program: load_program(
+                display,
+                "shaders/overlay_vertex.glsl",
+                "shaders/alpha_fragment.glsl"
+            )?,
+            buffer: VertexBuffer::empty_dynamic(display, 0)?,
+            rectangles: Vec::new(),
+            query_cells: Vec::new(),
+        })
+    }
+
+    // `query` is the position and perception radius of the boid under the cursor, `zoom` keeps lines one pixel wide
+    pub fn draw(
+        &mut self,
+        display: &Display,
+        target: &mut Frame,
+        view: [[f32; 4]; 4],
+        grid: &Grid,
+        query: Option<([f32; 2], f32)>,
+        zoom: f32
+    ) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        self.query_cells.clear();
+        self.query_cells.resize(grid.cell_count(), false);
+
+        if let Some((position, radius)) = query {
+            let query_cells = &mut self.query_cells;
+            grid.for_each_query_cell(position, radius, |cell| query_cells[cell] = true);
+        }
+
+        self.rectangles.clear();
+
+        let size = grid.cell_size;
+        let width = grid.columns as f32 * size;
+        let height = grid.rows as f32 * size;
+
+        for y in 0..grid.rows {
+            for x in 0..grid.columns {
+                let cell = grid.cell_at(x, y);
+                let rect = [x as f32 * size, y as f32 * size, size, size];
+
+                let occupancy = grid.cell(cell).len() as f32 / GRID_OVERLAY_MAX_OCCUPANCY;
+
+                if occupancy > 0.0 {
+                    let [r, g, b] = OCCUPANCY_COLOR;
+                    let fill = [r, g, b, OCCUPANCY_ALPHA * occupancy.min(1.0)];
+
+                    self.rectangles.push(Rectangle { rect, fill });
+                }
+
+                if self.query_cells[cell] {
+                    self.rectangles.push(Rectangle { rect, fill: QUERY_COLOR });
+                }
+            }
+        }
+
+        let line_width = LINE_WIDTH / zoom;
+
+        for x in 0..=grid.columns {
+            let rect = [x as f32 * size, 0.0, line_width, height];
+            self.rectangles.push(Rectangle { rect, fill: LINE_COLOR });
+        }
+
+        for y in 0..=grid.rows {
+            let rect = [0.0, y as f32 * size, width, line_width];
+            self.rectangles.push(Rectangle { rect, fill: LINE_COLOR });
+        }
+
+        let fitting = grow_dynamic_buffer(display, &mut self.buffer, self.rectangles.len(), "grid overlay");
+        self.rectangles.truncate(fitting);
+
+        let rectangles = self.buffer.slice(0..self.rectangles.len()).ok_or(Error::BufferRange)?;
+        rectangles.write(&self.rectangles);
+
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target.draw(
+            (&self.quad.v_buffer, rectangles.per_instance().map_err(|_| Error::Instancing)?),
+            &self.quad.i_buffer,
+            &self.program,
+            &uniform! {
+                perspective: view,
+            },
+            &params
+        )?;
+
+        Ok(())
+    }
+}
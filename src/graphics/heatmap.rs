@@ -0,0 +1,160 @@
+use std::borrow::Cow;
+
+use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, SamplerWrapFunction};
+use glium::{Blend, Display, DrawParameters, Program, Rect, Surface, Texture2d};
+
+use crate::error::Result;
+use crate::graphics::vertices::BoidInstance;
+use boids_core::diagnostics::vec_bytes;
+use crate::graphics::{create_unit_quad, load_program, Mesh};
+use crate::{HEATMAP_ALPHA, HEATMAP_BLUR_PASSES, HEATMAP_CELL_SIZE, HEATMAP_MAX_DENSITY};
+
+// Translucent boid density over the whole world, drawn under the flock.
+// Boids are counted into coarse cells on the CPU every frame and blurred,
+// the counts are uploaded as a one channel texture and colored in the shader.
+pub struct Heatmap {
+    pub enabled: bool,
+
+    quad: Mesh,
+    program: Program,
+    texture: Texture2d,
+
+    world_size: [f32; 2],
+    columns: usize,
+    rows: usize,
+    counts: Vec<f32>,
+    // Target of every other blur pass
+    blurred: Vec<f32>,
+}
+
+impl Heatmap {
+    pub fn new(display: &Display, world_size: [f32; 2]) -> Result<Heatmap> {
+        let (columns, rows) = dimensions(world_size);
+
+        Ok(Heatmap {
+            enabled: false,
+
+            quad: create_unit_quad(display)?,
+            program: load_program(
+                display,
+                "shaders/heatmap_vertex.glsl",
+                "shaders/heatmap_fragment.glsl"
+            )?,
+            texture: create_texture(display, columns, rows)?,
+
+            world_size,
+            columns,
+            rows,
+            counts: vec![0.0; columns * rows],
+            blurred: vec![0.0; columns * rows],
+        })
+    }
+
+    pub fn update(&mut self, instances: &[BoidInstance]) {
+        if !self.enabled {
+            return;
+        }
+
+        for count in self.counts.iter_mut() {
+            *count = 0.0;
+        }
+
+        for instance in instances {
+            let x = ((instance.value[0] / HEATMAP_CELL_SIZE) as usize).min(self.columns - 1);
+            let y = ((instance.value[1] / HEATMAP_CELL_SIZE) as usize).min(self.rows - 1);
+
+            self.counts[y * self.columns + x] += 1.0;
+        }
+
+        for _ in 0..HEATMAP_BLUR_PASSES {
+            self.blur(1, 0);
+            self.blur(0, 1);
+        }
+
+        self.texture.write(
+            Rect {
+                left: 0,
+                bottom: 0,
+                width: self.columns as u32,
+                height: self.rows as u32,
+            },
+            RawImage2d {
+                data: Cow::Borrowed(&self.counts),
+                width: self.columns as u32,
+                height: self.rows as u32,
+                format: ClientFormat::F32,
+            }
+        );
+    }
+
+    // One [1 2 1] pass along the given axis, the result ends up back in `counts`
+    fn blur(&mut self, dx: usize, dy: usize) {
+        let columns = self.columns;
+        let rows = self.rows;
+
+        for y in 0..rows {
+            for x in 0..columns {
+                // Edges repeat the border cell
+                let prev = self.counts[y.saturating_sub(dy) * columns + x.saturating_sub(dx)];
+                let next = self.counts[(y + dy).min(rows - 1) * columns + (x + dx).min(columns - 1)];
+
+                self.blurred[y * columns + x] = (prev + 2.0 * self.counts[y * columns + x] + next) * 0.25;
+            }
+        }
+
+        std::mem::swap(&mut self.counts, &mut self.blurred);
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        vec_bytes(&self.counts) + vec_bytes(&self.blurred)
+    }
+
+    pub fn draw<S: Surface>(&self, target: &mut S, perspective: [[f32; 4]; 4]) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let density = self.texture.sampled()
+            .magnify_filter(MagnifySamplerFilter::Linear)
+            .wrap_function(SamplerWrapFunction::Clamp);
+
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target.draw(
+            &self.quad.v_buffer,
+            &self.quad.i_buffer,
+            &self.program,
+            &uniform! {
+                perspective: perspective,
+                world_size: self.world_size,
+                density: density,
+                max_density: HEATMAP_MAX_DENSITY,
+                alpha: HEATMAP_ALPHA,
+            },
+            &params
+        )?;
+
+        Ok(())
+    }
+}
+
+fn dimensions(world_size: [f32; 2]) -> (usize, usize) {
+    (
+        ((world_size[0] / HEATMAP_CELL_SIZE).ceil() as usize).max(1),
+        ((world_size[1] / HEATMAP_CELL_SIZE).ceil() as usize).max(1),
+    )
+}
+
+fn create_texture(display: &Display, columns: usize, rows: usize) -> Result<Texture2d> {
+    Ok(Texture2d::empty_with_format(
+        display,
+        UncompressedFloatFormat::F32,
+        MipmapsOption::NoMipmap,
+        columns as u32,
+        rows as u32
+    )?)
+}
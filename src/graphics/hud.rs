@@ -0,0 +1,216 @@
+use glium::{Blend, Display, DrawParameters, Frame, Program, Surface, VertexBuffer};
+
+use crate::error::{Error, Result};
+use crate::graphics::{create_unit_quad, grow_dynamic_buffer, load_program, Mesh};
+use crate::{HUD_SMOOTHING, HUD_TEXT_SCALE};
+
+pub const MARGIN: f32 = 10.0;
+// Glyphs are 3 by 5 font pixels, with one pixel between characters and lines
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+
+#[derive(Clone, Copy)]
+struct Rectangle {
+    rect: [f32; 4],
+    fill: [f32; 4],
+}
+implement_vertex!(Rectangle, rect, fill);
+
+// Text lines in the top left corner. Characters come from a tiny built in
+// pixel font, every lit font pixel is an instance of one blended quad.
+pub struct Hud {
+    pub enabled: bool,
+
+    quad: Mesh,
+    program: Program,
+    buffer: VertexBuffer<Rectangle>,
+    rectangles: Vec<Rectangle>,
+
+    // Moving averages, in frames per second and milliseconds
+    pub fps: f32,
+    pub frame_time: f32,
+}
+
+impl Hud {
+    pub fn new(display: &Display) -> Result<Hud> {
+        Ok(Hud {
+            enabled: true,
+
+            quad: create_unit_quad(display)?,
+            program: load_program(
+                display,
+                "shaders/overlay_vertex.glsl",
+                "shaders/alpha_fragment.glsl"
+            )?,
+            buffer: VertexBuffer::empty_dynamic(display, 0)?,
+            rectangles: Vec::new(),
+
+            fps: 0.0,
+            frame_time: 0.0,
+        })
+    }
+
+    // Both times in seconds, `frame_time` is the part of the frame spent working
+    pub fn record_frame(&mut self, delta_time: f32, frame_time: f32) {
+        if delta_time <= 0.0 {
+            return;
+        }
+
+        if self.fps == 0.0 {
+            self.fps = 1.0 / delta_time;
+            self.frame_time = frame_time * 1000.0;
+            return;
+        }
+
+        self.fps += (1.0 / delta_time - self.fps) * HUD_SMOOTHING;
+        self.frame_time += (frame_time * 1000.0 - self.frame_time) * HUD_SMOOTHING;
+    }
+
+    pub fn draw(
+        &mut self,
+        display: &Display,
+        target: &mut Frame,
+        perspective: [[f32; 4]; 4],
+        lines: &[String]
+    ) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        self.draw_text(display, target, perspective, lines, [MARGIN, MARGIN])
+    }
+
+    // Lines on their own background with the first character at `origin`, whether the HUD is shown or not
+    pub fn draw_text(
+        &mut self,
+        display: &Display,
+        target: &mut Frame,
+        perspective: [[f32; 4]; 4],
+        lines: &[String],
+        origin: [f32; 2]
+    ) -> Result<()> {
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        let pixel = HUD_TEXT_SCALE;
+        let advance = (GLYPH_WIDTH + 1) as f32 * pixel;
+        let line_height = (GLYPH_HEIGHT + 2) as f32 * pixel;
+
+        let columns = lines.iter().map(|line| line.chars().count()).max().unwrap_or(0);
+
+        self.rectangles.clear();
+        self.rectangles.push(Rectangle {
+            rect: [
+                origin[0] - pixel * 2.0,
+                origin[1] - pixel * 2.0,
+                columns as f32 * advance + pixel * 3.0,
+                lines.len() as f32 * line_height + pixel * 2.0,
+            ],
+            fill: BACKGROUND_COLOR,
+        });
+
+        for (row, line) in lines.iter().enumerate() {
+            let y = origin[1] + row as f32 * line_height;
+
+            for (column, character) in line.chars().enumerate() {
+                let x = origin[0] + column as f32 * advance;
+                let glyph = glyph(character);
+
+                for (glyph_y, bits) in glyph.iter().enumerate() {
+                    for glyph_x in 0..GLYPH_WIDTH {
+                        if bits & (1 << (GLYPH_WIDTH - 1 - glyph_x)) == 0 {
+                            continue;
+                        }
+
+                        self.rectangles.push(Rectangle {
+                            rect: [x + glyph_x as f32 * pixel, y + glyph_y as f32 * pixel, pixel, pixel],
+                            fill: TEXT_COLOR,
+                        });
+                    }
+                }
+            }
+        }
+
+        let fitting = grow_dynamic_buffer(display, &mut self.buffer, self.rectangles.len(), "HUD");
+        self.rectangles.truncate(fitting);
+
+        let rectangles = self.buffer.slice(0..self.rectangles.len()).ok_or(Error::BufferRange)?;
+        rectangles.write(&self.rectangles);
+
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target.draw(
+            (&self.quad.v_buffer, rectangles.per_instance().map_err(|_| Error::Instancing)?),
+            &self.quad.i_buffer,
+            &self.program,
+            &uniform! {
+                perspective: perspective,
+            },
+            &params
+        )?;
+
+        Ok(())
+    }
+}
+
+// Rows of a character from top to bottom, the highest of the three bits is the left pixel.
+// Letters are upper case only, unknown characters show up as a question mark.
+fn glyph(character: char) -> [u8; GLYPH_HEIGHT] {
+    match character.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0b000, 0b000, 0b000, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        _ => [0b111, 0b001, 0b011, 0b000, 0b010],
+    }
+}
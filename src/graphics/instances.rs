@@ -0,0 +1,92 @@
+use glium::{Display, Vertex, VertexBuffer};
+
+use boids_core::diagnostics::vec_bytes;
+use crate::error::{Error, Result};
+
+// Frames can be in flight at once, writing a buffer the GPU still draws from would stall
+const BUFFER_COUNT: usize = 3;
+// Instances compared and uploaded together
+const BLOCK_SIZE: usize = 256;
+
+// One instance attribute of the flock, cycled through several buffers so uploading a frame never
+// waits for the previous draw. Every buffer keeps a copy of what it holds, and only
+// blocks of instances that changed since its last use are written again.
+pub struct InstanceBuffers<T: Vertex + PartialEq> {
+    buffers: Vec<VertexBuffer<T>>,
+    contents: Vec<Vec<T>>,
+    // Buffer written by the last upload
+    current: usize,
+}
+
+// Persistent mapped buffers avoid a copy in the driver, older GL falls back to dynamic ones
+fn create_buffer<T: Vertex>(display: &Display, data: &[T]) -> Result<VertexBuffer<T>> {
+    Ok(VertexBuffer::persistent(display, data).or_else(|_| VertexBuffer::dynamic(display, data))?)
+}
+
+impl<T: Vertex + PartialEq> InstanceBuffers<T> {
+    pub fn new(display: &Display, data: &[T]) -> Result<InstanceBuffers<T>> {
+        Ok(InstanceBuffers {
+            buffers: (0..BUFFER_COUNT).map(|_| create_buffer(display, data)).collect::<Result<_>>()?,
+            contents: vec![data.to_vec(); BUFFER_COUNT],
+            current: 0,
+        })
+    }
+
+    // Writes the instances into the next buffer, which becomes the current one
+    pub fn upload(&mut self, display: &Display, data: &[T]) -> Result<()> {
+        self.current = (self.current + 1) % BUFFER_COUNT;
+
+        // Population changed, buffers are created anew. The old ones are drawn until that works.
+        if self.contents[self.current].len() != data.len() {
+            match InstanceBuffers::new(display, data) {
+                Ok(buffers) => *self = buffers,
+                Err(error) => println!("Could not resize the instance buffers: {}", error),
+            }
+
+            return Ok(());
+        }
+
+        let buffer = &self.buffers[self.current];
+        let contents = &mut self.contents[self.current];
+
+        // Start of a run of changed blocks that wasn't written yet
+        let mut dirty_start = None;
+
+        for start in (0..data.len()).step_by(BLOCK_SIZE) {
+            let end = (start + BLOCK_SIZE).min(data.len());
+            let dirty = data[start..end] != contents[start..end];
+
+            if dirty && dirty_start.is_none() {
+                dirty_start = Some(start);
+            }
+
+            // Write the run once it ends, consecutive blocks go in one call
+            if let Some(run_start) = dirty_start {
+                if !dirty || end == data.len() {
+                    let run_end = if dirty { end } else { start };
+
+                    buffer.slice(run_start..run_end).ok_or(Error::BufferRange)?.write(&data[run_start..run_end]);
+                    contents[run_start..run_end].copy_from_slice(&data[run_start..run_end]);
+
+                    dirty_start = None;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Video memory of all buffers
+    pub fn gpu_bytes(&self) -> usize {
+        self.buffers.iter().map(|buffer| buffer.get_size()).sum()
+    }
+
+    // The copies of buffer contents kept to find changes
+    pub fn cpu_bytes(&self) -> usize {
+        self.contents.iter().map(vec_bytes).sum()
+    }
+
+    pub fn current(&self) -> &VertexBuffer<T> {
+        &self.buffers[self.current]
+    }
+}
@@ -0,0 +1,67 @@
+use glium::{Display, DrawParameters, Frame, Program, Rect, Surface, VertexBuffer};
+
+use crate::error::{Error, Result};
+use crate::graphics::{create_unit_quad, load_program, Mesh};
+use crate::LETTERBOX_COLOR;
+
+// Far enough past the world to cover the window at any aspect ratio
+const EXTENT: f32 = 1.0e6;
+
+#[derive(Clone, Copy)]
+struct Rectangle {
+    rect: [f32; 4],
+    fill: [f32; 4],
+}
+implement_vertex!(Rectangle, rect, fill);
+
+// Covers everything outside the world, where the window is wider or taller than the world.
+// Four rectangles around the world drawn through the camera, so they follow it while zoomed in.
+pub struct Letterbox {
+    quad: Mesh,
+    program: Program,
+    rectangles: VertexBuffer<Rectangle>,
+}
+
+impl Letterbox {
+    pub fn new(display: &Display, world_size: [f32; 2]) -> Result<Letterbox> {
+        let [w, h] = world_size;
+        let rectangle = |rect| Rectangle { rect, fill: LETTERBOX_COLOR };
+
+        let rectangles = [
+            rectangle([-EXTENT, -EXTENT, w + 2.0 * EXTENT, EXTENT]),
+            rectangle([-EXTENT, h, w + 2.0 * EXTENT, EXTENT]),
+            rectangle([-EXTENT, 0.0, EXTENT, h]),
+            rectangle([w, 0.0, EXTENT, h]),
+        ];
+
+        Ok(Letterbox {
+            quad: create_unit_quad(display)?,
+            program: load_program(
+                display,
+                "shaders/overlay_vertex.glsl",
+                "shaders/alpha_fragment.glsl"
+            )?,
+            rectangles: VertexBuffer::new(display, &rectangles)?,
+        })
+    }
+
+    // `scissor` keeps the letterbox to a part of the window, all of it without one
+    pub fn draw(&self, target: &mut Frame, view: [[f32; 4]; 4], scissor: Option<Rect>) -> Result<()> {
+        let params = DrawParameters {
+            scissor,
+            ..Default::default()
+        };
+
+        target.draw(
+            (&self.quad.v_buffer, self.rectangles.per_instance().map_err(|_| Error::Instancing)?),
+            &self.quad.i_buffer,
+            &self.program,
+            &uniform! {
+                perspective: view,
+            },
+            &params
+        )?;
+
+        Ok(())
+    }
+}
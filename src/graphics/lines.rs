@@ -0,0 +1,68 @@
+use glium::index::{NoIndices, PrimitiveType};
+use glium::{Blend, Display, DrawParameters, Program, Surface, VertexBuffer};
+
+use crate::error::{Error, Result};
+use crate::graphics::vertices::LineVertex;
+use crate::graphics::{grow_dynamic_buffer, load_program};
+
+// Blended line segments, two vertices each. The buffer grows as needed
+// and only the vertices of the last upload are drawn.
+pub struct LineRenderer {
+    program: Program,
+    buffer: VertexBuffer<LineVertex>,
+    vertex_count: usize,
+}
+
+impl LineRenderer {
+    pub fn new(display: &Display) -> Result<LineRenderer> {
+        Ok(LineRenderer {
+            program: load_program(
+                display,
+                "shaders/line_vertex.glsl",
+                "shaders/alpha_fragment.glsl"
+            )?,
+            buffer: VertexBuffer::empty_dynamic(display, 0)?,
+            vertex_count: 0,
+        })
+    }
+
+    pub fn upload(&mut self, display: &Display, vertices: &[LineVertex]) -> Result<()> {
+        self.vertex_count = vertices.len();
+
+        if vertices.is_empty() {
+            return Ok(());
+        }
+
+        self.vertex_count = grow_dynamic_buffer(display, &mut self.buffer, vertices.len(), "line");
+        self.buffer.slice(0..self.vertex_count).ok_or(Error::BufferRange)?.write(&vertices[..self.vertex_count]);
+
+        Ok(())
+    }
+
+    pub fn draw<S: Surface>(&self, target: &mut S, perspective: [[f32; 4]; 4]) -> Result<()> {
+        if self.vertex_count == 0 {
+            return Ok(());
+        }
+
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target.draw(
+            self.buffer.slice(0..self.vertex_count).ok_or(Error::BufferRange)?,
+            NoIndices(PrimitiveType::LinesList),
+            &self.program,
+            &uniform! {
+                perspective: perspective,
+            },
+            &params
+        )?;
+
+        Ok(())
+    }
+
+    pub fn gpu_bytes(&self) -> usize {
+        self.buffer.get_size()
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+
+use glium::glutin::dpi::PhysicalSize;
+use glium::{Blend, Display, DrawParameters, Frame, Program, Surface, VertexBuffer};
+
+use boids_core::metrics::Metrics;
+
+use crate::error::{Error, Result};
+use crate::graphics::hud::Hud;
+use crate::graphics::lines::LineRenderer;
+use crate::graphics::vertices::LineVertex;
+use crate::graphics::{create_unit_quad, load_program, Mesh};
+use crate::{HUD_TEXT_SCALE, PLOT_HISTORY, PLOT_SIZE};
+
+const MARGIN: f32 = 10.0;
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const PLOT_COUNT: usize = 3;
+
+#[derive(Clone, Copy)]
+struct Rectangle {
+    rect: [f32; 4],
+    fill: [f32; 4],
+}
+implement_vertex!(Rectangle, rect, fill);
+
+struct Plot {
+    name: &'static str,
+    color: [f32; 4],
+    // Values from 0 to this fill the plot, without one the largest value in the history does
+    range: Option<f32>,
+    samples: VecDeque<f32>,
+}
+
+impl Plot {
+    fn new(name: &'static str, color: [f32; 4], range: Option<f32>) -> Plot {
+        Plot {
+            name,
+            color,
+            range,
+            samples: VecDeque::with_capacity(PLOT_HISTORY),
+        }
+    }
+
+    fn push(&mut self, value: f32) {
+        if self.samples.len() == PLOT_HISTORY {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(value);
+    }
+
+    fn top(&self) -> f32 {
+        self.range.unwrap_or_else(|| {
+            let max = self.samples.iter().fold(0.0f32, |max, value| max.max(*value));
+            if max > 0.0 { max * 1.1 } else { 1.0 }
+        })
+    }
+}
+
+// Rolling plots of a few quantities stacked in the bottom right corner, one sample per frame
+// with the newest on the right, so the effect of a parameter change shows up as it happens.
+// Each plot is labeled with its name and latest value through the HUD font.
+pub struct MetricPlots {
+    pub enabled: bool,
+
+    plots: [Plot; PLOT_COUNT],
+    quad: Mesh,
+    program: Program,
+    backgrounds: VertexBuffer<Rectangle>,
+    lines: LineRenderer,
+    vertices: Vec<LineVertex>,
+}
+
+impl MetricPlots {
+    pub fn new(display: &Display) -> Result<MetricPlots> {
+        let empty = Rectangle { rect: [0.0; 4], fill: [0.0; 4] };
+
+        Ok(MetricPlots {
+            enabled: false,
+
+            plots: [
+                Plot::new("Polarization", [0.4, 0.9, 1.0, 0.9], Some(1.0)),
+                Plot::new("FPS", [0.5, 1.0, 0.4, 0.9], None),
+                Plot::new("Mean speed", [1.0, 0.8, 0.3, 0.9], None),
+            ],
+            quad: create_unit_quad(display)?,
+            program: load_program(
+                display,
+                "shaders/overlay_vertex.glsl",
+                "shaders/alpha_fragment.glsl"
+            )?,
+            backgrounds: VertexBuffer::dynamic(display, &[empty; PLOT_COUNT])?,
+            lines: LineRenderer::new(display)?,
+            vertices: Vec::new(),
+        })
+    }
+
+    // Once per frame, samples are taken while hidden as well so the plots are full when shown
+    pub fn record(&mut self, fps: f32, metrics: &Metrics) {
+        let [polarization, fps_plot, speed] = &mut self.plots;
+
+        polarization.push(metrics.polarization);
+        fps_plot.push(fps);
+        speed.push(metrics.mean_speed);
+    }
+
+    // `screen_size` is the size of the window in screen pixels, as `perspective` covers it
+    pub fn draw(
+        &mut self,
+        display: &Display,
+        target: &mut Frame,
+        perspective: [[f32; 4]; 4],
+        screen_size: PhysicalSize<u32>,
+        hud: &mut Hud
+    ) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let [width, height] = PLOT_SIZE;
+        let left = screen_size.width as f32 - MARGIN - width;
+        let step = width / (PLOT_HISTORY - 1) as f32;
+
+        let mut backgrounds = [Rectangle { rect: [0.0; 4], fill: [0.0; 4] }; PLOT_COUNT];
+        let mut labels = Vec::with_capacity(PLOT_COUNT);
+
+        self.vertices.clear();
+
+        // The first plot is at the top
+        for (i, plot) in self.plots.iter().enumerate() {
+            let top = screen_size.height as f32 - (PLOT_COUNT - i) as f32 * (height + MARGIN);
+            let bottom = top + height;
+            let scale = height / plot.top();
+
+            backgrounds[i] = Rectangle { rect: [left, top, width, height], fill: BACKGROUND_COLOR };
+
+            // Right aligned, a history that isn't full yet starts further right
+            let start = left + (PLOT_HISTORY - plot.samples.len()) as f32 * step;
+            let points = plot.samples.iter()
+                .enumerate()
+                .map(|(j, value)| [start + j as f32 * step, bottom - value.clamp(0.0, plot.top()) * scale]);
+
+            for (a, b) in points.clone().zip(points.skip(1)) {
+                self.vertices.push(LineVertex { position: a, color: plot.color });
+                self.vertices.push(LineVertex { position: b, color: plot.color });
+            }
+
+            let latest = plot.samples.back().copied().unwrap_or(0.0);
+            labels.push((format!("{}: {:.2}", plot.name, latest), [left + HUD_TEXT_SCALE * 2.0, top + HUD_TEXT_SCALE * 2.0]));
+        }
+
+        self.backgrounds.write(&backgrounds);
+
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target.draw(
+            (&self.quad.v_buffer, self.backgrounds.per_instance().map_err(|_| Error::Instancing)?),
+            &self.quad.i_buffer,
+            &self.program,
+            &uniform! {
+                perspective: perspective,
+            },
+            &params
+        )?;
+
+        for (label, origin) in &labels {
+            hud.draw_text(display, target, perspective, std::slice::from_ref(label), *origin)?;
+        }
+
+        self.lines.upload(display, &self.vertices)?;
+        self.lines.draw(target, perspective)
+    }
+}
@@ -0,0 +1,150 @@
+use cgmath::{Matrix4, Vector3};
+use cgmath::conv::array4x4;
+use glium::glutin::dpi::PhysicalSize;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::{Blend, BlendingFunction, Display, DrawParameters, Frame, LinearBlendingFactor, Program, Surface, VertexBuffer};
+
+use crate::error::{Error, Result};
+use crate::camera::Camera;
+use crate::graphics::vertices::BoidInstance;
+use crate::graphics::{create_unit_quad, load_program, Mesh};
+use crate::{MINIMAP_MIN_ZOOM, MINIMAP_POINT_ALPHA, MINIMAP_WIDTH};
+
+const MARGIN: f32 = 10.0;
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const VIEWPORT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.8];
+// Width of the viewport outline in screen pixels
+const LINE_WIDTH: f32 = 1.0;
+
+#[derive(Clone, Copy)]
+struct Rectangle {
+    rect: [f32; 4],
+    fill: [f32; 4],
+}
+implement_vertex!(Rectangle, rect, fill);
+
+// Whole world shrunk into the top right corner while the camera is zoomed in.
+// Boids are additive points so crowded places light up, the part of the
+// world on screen is outlined. Drawn in world coordinates through a matrix
+// that maps the world onto the corner.
+pub struct Minimap {
+    pub enabled: bool,
+
+    quad: Mesh,
+    rect_program: Program,
+    point_program: Program,
+    // Background and the four edges of the viewport outline
+    rectangles: VertexBuffer<Rectangle>,
+}
+
+impl Minimap {
+    pub fn new(display: &Display) -> Result<Minimap> {
+        let empty = Rectangle { rect: [0.0; 4], fill: [0.0; 4] };
+
+        Ok(Minimap {
+            enabled: true,
+
+            quad: create_unit_quad(display)?,
+            rect_program: load_program(
+                display,
+                "shaders/overlay_vertex.glsl",
+                "shaders/alpha_fragment.glsl"
+            )?,
+            point_program: load_program(
+                display,
+                "shaders/minimap_vertex.glsl",
+                "shaders/alpha_fragment.glsl"
+            )?,
+            rectangles: VertexBuffer::dynamic(display, &[empty; 5])?,
+        })
+    }
+
+    // `screen_size` is the size of the window in screen pixels, as `perspective` covers it
+    pub fn draw(
+        &self,
+        target: &mut Frame,
+        perspective: [[f32; 4]; 4],
+        screen_size: PhysicalSize<u32>,
+        world_size: [f32; 2],
+        camera: &Camera,
+        instances: &VertexBuffer<BoidInstance>
+    ) -> Result<()> {
+        if !self.enabled || camera.zoom < MINIMAP_MIN_ZOOM {
+            return Ok(());
+        }
+
+        let scale = MINIMAP_WIDTH / world_size[0];
+        let corner = [screen_size.width as f32 - MARGIN - MINIMAP_WIDTH, MARGIN];
+
+        let world_to_map = Matrix4::from(perspective)
+            * Matrix4::from_translation(Vector3::new(corner[0], corner[1], 0.0))
+            * Matrix4::from_nonuniform_scale(scale, scale, 1.0);
+
+        let map = array4x4(world_to_map);
+
+        // Outline edges stay one screen pixel wide however big the world is
+        let line = LINE_WIDTH / scale;
+        let [x, y, w, h] = camera.visible_area();
+
+        self.rectangles.write(&[
+            Rectangle { rect: [0.0, 0.0, world_size[0], world_size[1]], fill: BACKGROUND_COLOR },
+            Rectangle { rect: [x, y, w, line], fill: VIEWPORT_COLOR },
+            Rectangle { rect: [x, y + h - line, w, line], fill: VIEWPORT_COLOR },
+            Rectangle { rect: [x, y, line, h], fill: VIEWPORT_COLOR },
+            Rectangle { rect: [x + w - line, y, line, h], fill: VIEWPORT_COLOR },
+        ]);
+
+        let blended = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target.draw(
+            (&self.quad.v_buffer, self.rectangles.slice(0..1).ok_or(Error::BufferRange)?.per_instance().map_err(|_| Error::Instancing)?),
+            &self.quad.i_buffer,
+            &self.rect_program,
+            &uniform! {
+                perspective: map,
+            },
+            &blended
+        )?;
+
+        let additive = BlendingFunction::Addition {
+            source: LinearBlendingFactor::SourceAlpha,
+            destination: LinearBlendingFactor::One,
+        };
+
+        let params = DrawParameters {
+            blend: Blend {
+                color: additive,
+                alpha: additive,
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+            ..Default::default()
+        };
+
+        target.draw(
+            instances,
+            NoIndices(PrimitiveType::Points),
+            &self.point_program,
+            &uniform! {
+                perspective: map,
+                alpha: MINIMAP_POINT_ALPHA,
+            },
+            &params
+        )?;
+
+        // Outline on top of the boids
+        target.draw(
+            (&self.quad.v_buffer, self.rectangles.slice(1..5).ok_or(Error::BufferRange)?.per_instance().map_err(|_| Error::Instancing)?),
+            &self.quad.i_buffer,
+            &self.rect_program,
+            &uniform! {
+                perspective: map,
+            },
+            &blended
+        )?;
+
+        Ok(())
+    }
+}
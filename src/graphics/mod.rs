@@ -1,15 +1,84 @@
-use std::fs;
+#[cfg(feature = "glium")]
+pub mod background;
+#[cfg(feature = "glium")]
+pub mod flow_field;
+#[cfg(feature = "glium")]
+pub mod flock;
+#[cfg(feature = "glium")]
+pub mod gpu_sim;
+#[cfg(feature = "glium")]
+pub mod grid_overlay;
+#[cfg(feature = "glium")]
+pub mod gif_history;
+#[cfg(feature = "glium")]
+pub mod heatmap;
+#[cfg(feature = "glium")]
+pub mod hud;
+#[cfg(feature = "glium")]
+pub mod bloom;
+#[cfg(feature = "glium")]
+pub mod capture;
+#[cfg(feature = "glium")]
+pub mod debug_vectors;
+#[cfg(feature = "glium")]
+pub mod instances;
+#[cfg(feature = "glium")]
+pub mod letterbox;
+#[cfg(feature = "glium")]
+pub mod lines;
+#[cfg(feature = "glium")]
+pub mod metric_plots;
+#[cfg(feature = "glium")]
+pub mod minimap;
+#[cfg(feature = "glium")]
+pub mod motion_blur;
+pub mod obj;
+#[cfg(feature = "glium")]
+pub mod profiler_graph;
+#[cfg(feature = "glium")]
+pub mod recorder;
+#[cfg(feature = "glium")]
+pub mod selection_box;
+pub mod renderer;
+pub mod shader_watch;
+pub mod shaders;
+pub mod shapes;
+pub mod vertices;
+#[cfg(feature = "wgpu")]
+pub mod wgpu_flock;
+#[cfg(feature = "glium")]
+pub mod world_geometry;
 
 use cgmath::conv::array4x4;
+#[cfg(feature = "glium")]
 use glium::index::PrimitiveType;
-use glium::{Display, IndexBuffer, Program, VertexBuffer};
-use glium::glutin::ContextBuilder;
-use glium::glutin::dpi::PhysicalSize;
+#[cfg(feature = "glium")]
+use glium::program::{ProgramCreationInput, TransformFeedbackMode};
+#[cfg(feature = "glium")]
+use glium::texture::{RawImage2d, SrgbTexture2d};
+#[cfg(feature = "glium")]
+use glium::{Display, IndexBuffer, Program, ProgramCreationError, VertexBuffer};
+#[cfg(feature = "glium")]
+use glium::backend::glutin::DisplayCreationError;
+#[cfg(feature = "glium")]
+use glium::glutin::{ContextBuilder, NotCurrent};
+#[cfg(feature = "glium")]
+use glium::glutin::dpi::LogicalSize;
+#[cfg(feature = "glium")]
 use glium::glutin::event_loop::EventLoop;
-use glium::glutin::window::WindowBuilder;
+#[cfg(feature = "glium")]
+use glium::glutin::window::{Fullscreen, WindowBuilder};
+use serde::Deserialize;
+#[cfg(feature = "glium")]
+use tracing::warn;
 
-use crate::data::{Transform, Vertex};
+#[cfg(feature = "glium")]
+use crate::error::{Error, Result};
+use crate::graphics::vertices::Vertex;
+#[cfg(feature = "glium")]
+use crate::graphics::shaders::read_shader;
 
+#[cfg(feature = "glium")]
 pub struct Mesh {
     pub v_buffer: VertexBuffer<Vertex>,
     pub i_buffer: IndexBuffer<u16>,
@@ -18,11 +87,11 @@ pub struct Mesh {
 pub fn create_agent_shape(size: f32, color: [f32; 3]) -> ([Vertex; 3], [u16; 3]) {
     let size_h = size / 2.0;
 
-    // Create a simple square
+    // Triangle pointing along +x
     let vertices = [
-        Vertex { position: [-size_h,  size_h], color },
-        Vertex { position: [ size_h + 5.0, 0.0], color },
-        Vertex { position: [-size_h, -size_h], color },
+        Vertex { position: [-size_h,  size_h], color, uv: [0.0, 1.0] },
+        Vertex { position: [ size_h, 0.0], color, uv: [1.0, 0.5] },
+        Vertex { position: [-size_h, -size_h], color, uv: [0.0, 0.0] },
     ];
 
     let indices = [
@@ -32,49 +101,261 @@ pub fn create_agent_shape(size: f32, color: [f32; 3]) -> ([Vertex; 3], [u16; 3])
     (vertices, indices)
 }
 
-pub fn create_mesh(display: &Display, vertices: &[Vertex], indices: &[u16]) -> Mesh {
+// Square centered on the boid, the texture points along +x like the triangle does
+pub fn create_sprite_shape(size: f32, color: [f32; 3]) -> ([Vertex; 4], [u16; 6]) {
+    let size_h = size / 2.0;
+
+    let vertices = [
+        Vertex { position: [-size_h, -size_h], color, uv: [0.0, 0.0] },
+        Vertex { position: [ size_h, -size_h], color, uv: [1.0, 0.0] },
+        Vertex { position: [ size_h,  size_h], color, uv: [1.0, 1.0] },
+        Vertex { position: [-size_h,  size_h], color, uv: [0.0, 1.0] },
+    ];
+
+    let indices = [
+        0, 1, 2,
+        0, 2, 3,
+    ];
+
+    (vertices, indices)
+}
+
+// Grows `buffer` to the next power of two that holds `len` items and returns how many of them fit.
+// The old buffer is kept when a bigger one can't be made, items past its end are dropped then.
+#[cfg(feature = "glium")]
+pub fn grow_dynamic_buffer<T: glium::Vertex>(display: &Display, buffer: &mut VertexBuffer<T>, len: usize, name: &str) -> usize {
+    if len > buffer.len() {
+        match VertexBuffer::empty_dynamic(display, len.next_power_of_two()) {
+            Ok(grown) => *buffer = grown,
+            Err(error) => warn!("Could not grow the {} buffer: {}", name, error),
+        }
+    }
+
+    len.min(buffer.len())
+}
+
+// Quad from (0, 0) to (1, 1), meant to be stretched in the vertex shader
+#[cfg(feature = "glium")]
+pub fn create_unit_quad(display: &Display) -> Result<Mesh> {
+    let color = [1.0, 1.0, 1.0];
+
+    let vertices = [
+        Vertex { position: [0.0, 0.0], color, uv: [0.0, 0.0] },
+        Vertex { position: [1.0, 0.0], color, uv: [1.0, 0.0] },
+        Vertex { position: [1.0, 1.0], color, uv: [1.0, 1.0] },
+        Vertex { position: [0.0, 1.0], color, uv: [0.0, 1.0] },
+    ];
+
+    let indices = [
+        0, 1, 2,
+        0, 2, 3,
+    ];
+
+    create_mesh(display, &vertices, &indices)
+}
+
+// Loads a PNG or any other image format as an RGBA texture
+#[cfg(feature = "glium")]
+pub fn load_texture(display: &Display, path: &str) -> Result<SrgbTexture2d> {
+    let image = image::open(path)
+        .map_err(|source| Error::Image { path: path.to_string(), source })?
+        .into_rgba8();
+    let dimensions = image.dimensions();
+
+    // Images are stored top row first, GL textures bottom row first
+    let raw = RawImage2d::from_raw_rgba_reversed(&image.into_raw(), dimensions);
+
+    Ok(SrgbTexture2d::new(display, raw)?)
+}
+
+#[cfg(feature = "glium")]
+pub fn create_mesh(display: &Display, vertices: &[Vertex], indices: &[u16]) -> Result<Mesh> {
     let v_buffer = VertexBuffer::new(
         display,
         vertices
-    ).expect("Error creating vertex buffer");
+    )?;
 
     let i_buffer = IndexBuffer::new(
         display,
         PrimitiveType::TrianglesList,
         indices
-    ).expect("Error creating index buffer");
+    )?;
 
-    Mesh { v_buffer, i_buffer }
+    Ok(Mesh { v_buffer, i_buffer })
 }
 
-pub fn create_display(event_loop: &EventLoop<()>, w: u32, h: u32) -> Display {
-    let display = Display::new(
-        WindowBuilder::new()
-            .with_inner_size(PhysicalSize {
-                width: w,
-                height: h
-            })
-            .with_title("Boids"),
-        ContextBuilder::new(),
-        &event_loop
-    ).expect("Could not create display");
+// Asks for `samples` MSAA samples per pixel, 0 turns anti-aliasing off.
+// If the sample count isn't supported the next lower one is tried.
+#[cfg(feature = "glium")]
+pub fn create_display(event_loop: &EventLoop<()>, w: u32, h: u32, samples: u16, vsync: bool) -> Result<Display> {
+    let window = WindowBuilder::new()
+        .with_inner_size(LogicalSize {
+            width: w,
+            height: h
+        })
+        .with_title("Boids");
 
-    display
+    build_context(samples, vsync, |context| Display::new(window.clone(), context, event_loop))
 }
 
-pub fn load_program(display: &Display, vertex_shader: &str, fragment_shader: &str) -> Program {
-    let vertex_source = fs::read_to_string(vertex_shader)
-        .expect("Error while loading vertex shader");
+// Vsync can't be changed on a running context, so the window and context of `display` are created
+// again. Textures and buffers are shared with the new context, the window keeps its size, position
+// and fullscreen mode but not its title. On failure the old window stays.
+#[cfg(feature = "glium")]
+pub fn rebuild_display(display: &Display, event_loop: &EventLoop<()>, samples: u16, vsync: bool) -> Result<()> {
+    let window = {
+        let gl_window = display.gl_window();
+        let window = gl_window.window();
+        let builder = WindowBuilder::new()
+            .with_inner_size(window.inner_size())
+            .with_title("Boids")
+            .with_maximized(window.is_maximized())
+            .with_fullscreen(window.fullscreen());
 
-    let fragment_source = fs::read_to_string(fragment_shader)
-        .expect("Error while loading fragment shader");
+        match window.outer_position() {
+            Ok(position) => builder.with_position(position),
+            Err(_) => builder,
+        }
+    };
+
+    build_context(samples, vsync, |context| display.rebuild(window.clone(), context, event_loop))
+}
+
+// Calls `build` with a context of `samples` MSAA samples, halving them while that fails
+#[cfg(feature = "glium")]
+fn build_context<T, F>(samples: u16, vsync: bool, mut build: F) -> Result<T>
+where
+    F: FnMut(ContextBuilder<NotCurrent>) -> std::result::Result<T, DisplayCreationError>
+{
+    // Sample counts have to be powers of two
+    let mut samples = match samples {
+        0 => 0,
+        _ => samples.next_power_of_two(),
+    };
+
+    loop {
+        let context = ContextBuilder::new()
+            .with_multisampling(samples)
+            .with_vsync(vsync);
+
+        match build(context) {
+            Ok(built) => return Ok(built),
+            Err(error) if samples > 0 => {
+                println!("Could not create display with {}x MSAA: {}", samples, error);
+                samples /= 2;
+            }
+            Err(error) => return Err(error.into()),
+        }
+    }
+}
+
+// How the window covers the screen
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    Windowed,
+    // Fullscreen window without a mode switch
+    Borderless,
+    // Fullscreen with the video mode of the monitor taken over
+    Exclusive,
+}
+
+impl WindowMode {
+    pub fn next(self) -> WindowMode {
+        match self {
+            WindowMode::Windowed => WindowMode::Borderless,
+            WindowMode::Borderless => WindowMode::Exclusive,
+            WindowMode::Exclusive => WindowMode::Windowed,
+        }
+    }
+}
+
+// Returns the mode that was applied. Exclusive fullscreen uses the biggest and fastest
+// video mode of the current monitor and falls back to borderless without one.
+#[cfg(feature = "glium")]
+pub fn set_window_mode(display: &Display, mode: WindowMode) -> WindowMode {
+    let gl_window = display.gl_window();
+    let window = gl_window.window();
+
+    let video_mode = window.current_monitor().and_then(|monitor| {
+        monitor.video_modes().max_by_key(|video_mode| {
+            let size = video_mode.size();
+            (size.width * size.height, video_mode.refresh_rate_millihertz())
+        })
+    });
+
+    match (mode, video_mode) {
+        (WindowMode::Windowed, _) => {
+            window.set_fullscreen(None);
+            WindowMode::Windowed
+        }
+        (WindowMode::Exclusive, Some(video_mode)) => {
+            window.set_fullscreen(Some(Fullscreen::Exclusive(video_mode)));
+            WindowMode::Exclusive
+        }
+        (WindowMode::Borderless, _) | (WindowMode::Exclusive, None) => {
+            window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+            WindowMode::Borderless
+        }
+    }
+}
+
+// A missing file or compile error is returned, so reloads can keep the old program
+#[cfg(feature = "glium")]
+pub fn load_program(display: &Display, vertex_shader: &str, fragment_shader: &str) -> Result<Program> {
+    let vertex_source = load_shader(vertex_shader)?;
+    let fragment_source = load_shader(fragment_shader)?;
 
     Program::from_source(
         display,
         vertex_source.as_str(),
         fragment_source.as_str(),
         None
-    ).expect("Error in shader program compilation")
+    ).map_err(|source| program_error(vertex_shader, fragment_shader, source))
+}
+
+#[cfg(feature = "glium")]
+fn load_shader(path: &str) -> Result<String> {
+    read_shader(path).map_err(|source| Error::Shader { path: path.to_string(), source })
+}
+
+#[cfg(feature = "glium")]
+fn program_error(vertex_shader: &str, fragment_shader: &str, source: ProgramCreationError) -> Error {
+    Error::Program {
+        vertex: vertex_shader.to_string(),
+        fragment: fragment_shader.to_string(),
+        source,
+    }
+}
+
+// Loads a program whose vertex shader outputs are captured with transform feedback.
+// Varyings are interleaved in the given order.
+#[cfg(feature = "glium")]
+pub fn load_feedback_program(
+    display: &Display,
+    vertex_shader: &str,
+    fragment_shader: &str,
+    varyings: &[&str]
+) -> Result<Program> {
+    let vertex_source = load_shader(vertex_shader)?;
+    let fragment_source = load_shader(fragment_shader)?;
+
+    Program::new(
+        display,
+        ProgramCreationInput::SourceCode {
+            vertex_shader: vertex_source.as_str(),
+            tessellation_control_shader: None,
+            tessellation_evaluation_shader: None,
+            geometry_shader: None,
+            fragment_shader: fragment_source.as_str(),
+            transform_feedback_varyings: Some((
+                varyings.iter().map(|v| v.to_string()).collect(),
+                TransformFeedbackMode::Interleaved
+            )),
+            outputs_srgb: false,
+            uses_point_size: false,
+        }
+    ).map_err(|source| program_error(vertex_shader, fragment_shader, source))
 }
 
 pub fn perspective(display_w: u32, display_h: u32) -> [[f32; 4]; 4] {
@@ -92,14 +373,3 @@ pub fn perspective(display_w: u32, display_h: u32) -> [[f32; 4]; 4] {
 
     array4x4(ortho)
 }
-
-pub fn default_transform() -> Transform {
-    Transform { 
-        transform: [
-            [1.0, 0.0 ,0.0, 0.0],
-            [0.0, 1.0 ,0.0, 0.0],
-            [0.0, 0.0 ,1.0, 0.0],
-            [0.0, 0.0 ,0.0, 1.0],
-        ]
-    }
-}
\ No newline at end of file
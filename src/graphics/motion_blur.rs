@@ -0,0 +1,98 @@
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::uniforms::MagnifySamplerFilter;
+use glium::{Blend, Display, DrawParameters, Program, Surface, Texture2d};
+
+use crate::error::Result;
+use crate::graphics::{create_unit_quad, load_program, Mesh};
+use crate::MOTION_BLUR_FADE;
+
+// Long luminous trails. The world is drawn into a texture that is only faded towards
+// the background every frame instead of cleared, then the texture is copied to the screen.
+pub struct MotionBlur {
+    pub enabled: bool,
+
+    quad: Mesh,
+    fade_program: Program,
+    copy_program: Program,
+    // Created at the size of the screen on first use
+    texture: Option<Texture2d>,
+}
+
+impl MotionBlur {
+    pub fn new(display: &Display) -> Result<MotionBlur> {
+        Ok(MotionBlur {
+            enabled: false,
+
+            quad: create_unit_quad(display)?,
+            fade_program: load_program(
+                display,
+                "shaders/screen_vertex.glsl",
+                "shaders/fade_fragment.glsl"
+            )?,
+            copy_program: load_program(
+                display,
+                "shaders/screen_vertex.glsl",
+                "shaders/texture_fragment.glsl"
+            )?,
+            texture: None,
+        })
+    }
+
+    // Old contents are dropped, so turning it on again doesn't bring back old trails
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.texture = None;
+    }
+
+    // Fades the accumulated frames and returns the surface the world is drawn on
+    pub fn begin(&mut self, display: &Display, size: (u32, u32), background: [f32; 4]) -> Result<SimpleFrameBuffer<'_>> {
+        let (texture, resized) = match self.texture.take() {
+            Some(texture) if (texture.width(), texture.height()) == size => (self.texture.insert(texture), false),
+            _ => (self.texture.insert(Texture2d::empty(display, size.0, size.1)?), true),
+        };
+
+        let mut surface = SimpleFrameBuffer::new(display, &*texture)?;
+
+        if resized {
+            let [r, g, b, a] = background;
+            surface.clear_color(r, g, b, a);
+        }
+
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        surface.draw(
+            &self.quad.v_buffer,
+            &self.quad.i_buffer,
+            &self.fade_program,
+            &uniform! {
+                fade_color: [background[0], background[1], background[2], MOTION_BLUR_FADE],
+            },
+            &params
+        )?;
+
+        Ok(surface)
+    }
+
+    // Copies the accumulated frames to the screen, replacing what is there
+    pub fn present<S: Surface>(&self, target: &mut S) -> Result<()> {
+        let texture = match &self.texture {
+            Some(texture) => texture,
+            None => return Ok(()),
+        };
+
+        target.draw(
+            &self.quad.v_buffer,
+            &self.quad.i_buffer,
+            &self.copy_program,
+            &uniform! {
+                image: texture.sampled().magnify_filter(MagnifySamplerFilter::Nearest),
+            },
+            &Default::default()
+        )?;
+
+        Ok(())
+    }
+}
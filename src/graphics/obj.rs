@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::fs;
+
+use crate::graphics::vertices::Vertex;
+
+// Reads the 2D shape of an agent from a Wavefront OBJ file.
+// Only `v` and `f` lines are used, z coordinates and texture or normal indices are ignored
+// and polygons are split into fans. The shape is scaled so its longest side is `size`
+// and should point along +x.
+pub fn load_obj(path: &str, size: f32, color: [f32; 3]) -> Result<(Vec<Vertex>, Vec<u16>), Box<dyn Error>> {
+    let source = fs::read_to_string(path)?;
+
+    let mut positions: Vec<[f32; 2]> = Vec::new();
+    let mut indices: Vec<u16> = Vec::new();
+
+    for (line_number, line) in source.lines().enumerate() {
+        let mut parts = line.split_whitespace();
+
+        match parts.next() {
+            Some("v") => {
+                let x: f32 = parts.next().ok_or("Missing x coordinate")?.parse()?;
+                let y: f32 = parts.next().ok_or("Missing y coordinate")?.parse()?;
+
+                positions.push([x, y]);
+            }
+            Some("f") => {
+                let mut face = Vec::new();
+
+                for part in parts {
+                    // Indices are one based, negative ones count back from the last vertex
+                    let index: i64 = part.split('/').next().unwrap_or_default().parse()?;
+
+                    let index = if index < 0 {
+                        positions.len() as i64 + index
+                    }
+                    else {
+                        index - 1
+                    };
+
+                    if index < 0 || index >= positions.len() as i64 || index > u16::MAX as i64 {
+                        return Err(format!("Invalid vertex index on line {}", line_number + 1).into());
+                    }
+
+                    face.push(index as u16);
+                }
+
+                for i in 1..face.len().saturating_sub(1) {
+                    indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if indices.is_empty() {
+        return Err("No faces".into());
+    }
+
+    Ok((fit(&positions, size, color), indices))
+}
+
+// Centers the shape and scales its longest side to `size`
+fn fit(positions: &[[f32; 2]], size: f32, color: [f32; 3]) -> Vec<Vertex> {
+    let mut min = [f32::MAX, f32::MAX];
+    let mut max = [f32::MIN, f32::MIN];
+
+    for position in positions {
+        for axis in 0..2 {
+            min[axis] = min[axis].min(position[axis]);
+            max[axis] = max[axis].max(position[axis]);
+        }
+    }
+
+    let extent = [max[0] - min[0], max[1] - min[1]];
+    let scale = size / extent[0].max(extent[1]).max(f32::EPSILON);
+    let center = [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0];
+
+    positions.iter()
+        .map(|position| Vertex {
+            position: [(position[0] - center[0]) * scale, (position[1] - center[1]) * scale],
+            color,
+            uv: [
+                (position[0] - min[0]) / extent[0].max(f32::EPSILON),
+                (position[1] - min[1]) / extent[1].max(f32::EPSILON),
+            ],
+        })
+        .collect()
+}
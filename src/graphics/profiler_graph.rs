@@ -0,0 +1,102 @@
+use glium::{Display, Frame, Program, Surface, VertexBuffer};
+
+use crate::error::{Error, Result};
+use crate::graphics::{create_unit_quad, load_program, Mesh};
+use boids_core::profiler::{Profiler, STAGES, STAGE_COUNT};
+use boids_core::PROFILER_HISTORY;
+use crate::{PROFILER_GRAPH_SCALE, TARGET_FPS};
+
+// Width of one frame in the graph in pixels
+const BAR_WIDTH: f32 = 2.0;
+const MARGIN: f32 = 10.0;
+const BUDGET_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+
+#[derive(Clone, Copy)]
+struct Bar {
+    rect: [f32; 4],
+    tint: [f32; 3],
+}
+implement_vertex!(Bar, rect, tint);
+
+// Rolling graph in the bottom left corner, one bar of stacked stage timings
+// per frame with the newest frame on the right. Bars are instances of one quad
+// stretched over their rectangles.
+pub struct ProfilerGraph {
+    quad: Mesh,
+    program: Program,
+    bars: VertexBuffer<Bar>,
+    rectangles: Vec<Bar>,
+}
+
+fn rectangle(x: f32, y: f32, w: f32, h: f32, color: [f32; 3]) -> Bar {
+    Bar {
+        rect: [x, y, w, h],
+        tint: color,
+    }
+}
+
+impl ProfilerGraph {
+    pub fn new(display: &Display) -> Result<ProfilerGraph> {
+        let color = [1.0, 1.0, 1.0];
+
+        // Every stage of every frame plus the frame budget line, unused ones stay empty
+        let rectangles = vec![rectangle(0.0, 0.0, 0.0, 0.0, color); PROFILER_HISTORY * STAGE_COUNT + 1];
+
+        Ok(ProfilerGraph {
+            quad: create_unit_quad(display)?,
+            program: load_program(
+                display,
+                "shaders/profiler_vertex.glsl",
+                "shaders/fragment.glsl"
+            )?,
+            bars: VertexBuffer::dynamic(display, &rectangles)?,
+            rectangles,
+        })
+    }
+
+    pub fn draw(
+        &mut self,
+        target: &mut Frame,
+        perspective: [[f32; 4]; 4],
+        profiler: &Profiler,
+        display_height: f32
+    ) -> Result<()> {
+        let bottom = display_height - MARGIN;
+
+        for (i, frame) in profiler.history().iter().enumerate() {
+            let x = MARGIN + i as f32 * BAR_WIDTH;
+            let mut y = bottom;
+
+            for stage in STAGES.iter() {
+                let h = frame[*stage as usize] * PROFILER_GRAPH_SCALE;
+                y -= h;
+
+                self.rectangles[i * STAGE_COUNT + *stage as usize] = rectangle(x, y, BAR_WIDTH, h, stage.color());
+            }
+        }
+
+        let budget = 1000.0 / TARGET_FPS * PROFILER_GRAPH_SCALE;
+
+        self.rectangles[PROFILER_HISTORY * STAGE_COUNT] = rectangle(
+            MARGIN,
+            bottom - budget,
+            PROFILER_HISTORY as f32 * BAR_WIDTH,
+            1.0,
+            BUDGET_COLOR
+        );
+
+        self.bars.write(&self.rectangles);
+
+        target.draw(
+            (&self.quad.v_buffer, self.bars.per_instance().map_err(|_| Error::Instancing)?),
+            &self.quad.i_buffer,
+            &self.program,
+            &uniform! {
+                perspective: perspective,
+            },
+            &Default::default()
+        )?;
+
+        Ok(())
+    }
+}
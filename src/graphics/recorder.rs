@@ -0,0 +1,86 @@
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::graphics::capture::CapturedFrame;
+use crate::{RECORDING_DIR, RECORDING_FPS};
+
+// Pipes raw frames into an ffmpeg process that encodes them into an MP4.
+// Every written frame is one frame of video at RECORDING_FPS, no matter how
+// long it took to simulate and draw.
+pub struct Recorder {
+    ffmpeg: Child,
+    input: Option<ChildStdin>,
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    pub frames: usize,
+}
+
+impl Recorder {
+    // Fails if ffmpeg can't be started, it has to be on the PATH
+    pub fn start(width: u32, height: u32) -> Result<Recorder, Box<dyn Error>> {
+        fs::create_dir_all(RECORDING_DIR)?;
+
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let path = PathBuf::from(RECORDING_DIR).join(format!("recording-{}.mp4", time.as_secs()));
+
+        let mut ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .args(["-s", &format!("{}x{}", width, height)])
+            .args(["-r", &RECORDING_FPS.to_string()])
+            .args(["-i", "-"])
+            // H.264 in yuv420p needs even dimensions
+            .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+            .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+            .arg(&path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()?;
+
+        let input = ffmpeg.stdin.take();
+
+        Ok(Recorder {
+            ffmpeg,
+            input,
+            path,
+            width,
+            height,
+            frames: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, frame: &CapturedFrame) -> Result<(), Box<dyn Error>> {
+        if frame.width != self.width || frame.height != self.height {
+            return Err("the window was resized while recording".into());
+        }
+
+        let input = self.input.as_mut().ok_or("ffmpeg has no input")?;
+        input.write_all(&frame.pixels)?;
+
+        self.frames += 1;
+
+        Ok(())
+    }
+
+    pub fn seconds(&self) -> f32 {
+        self.frames as f32 / RECORDING_FPS as f32
+    }
+
+    // Closes the input and waits for ffmpeg to finish the file, returns its path
+    pub fn finish(mut self) -> Result<PathBuf, Box<dyn Error>> {
+        drop(self.input.take());
+
+        let status = self.ffmpeg.wait()?;
+
+        if !status.success() {
+            return Err(format!("ffmpeg exited with {}", status).into());
+        }
+
+        Ok(self.path)
+    }
+}
@@ -0,0 +1,17 @@
+use crate::error::Result;
+use crate::snapshot::RenderSnapshot;
+
+// Draws the flock of a RenderSnapshot with one graphics backend. FlockRenderer does it with glium
+// and WgpuFlockRenderer with wgpu, both take the same packed instances and perspective matrix.
+pub trait Renderer {
+    // What uploads go through, the display for glium and the device with its queue for wgpu
+    type Context;
+
+    fn upload(&mut self, context: &Self::Context, snapshot: &RenderSnapshot) -> Result<()>;
+}
+
+// Targets a renderer draws its last upload into. Glium renderers draw into any surface,
+// the window as well as the framebuffers of the post processing passes.
+pub trait DrawTo<Target>: Renderer {
+    fn draw(&self, target: &mut Target, perspective: [[f32; 4]; 4]) -> Result<()>;
+}
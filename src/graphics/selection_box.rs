@@ -0,0 +1,73 @@
+use glium::{Blend, Display, DrawParameters, Frame, Program, Surface, VertexBuffer};
+
+use crate::error::{Error, Result};
+use crate::graphics::{create_unit_quad, load_program, Mesh};
+
+const FILL_COLOR: [f32; 4] = [0.3, 1.0, 0.5, 0.15];
+const OUTLINE_COLOR: [f32; 4] = [0.3, 1.0, 0.5, 0.8];
+// Width of the outline in screen pixels
+const LINE_WIDTH: f32 = 1.0;
+
+#[derive(Clone, Copy)]
+struct Rectangle {
+    rect: [f32; 4],
+    fill: [f32; 4],
+}
+implement_vertex!(Rectangle, rect, fill);
+
+// Rectangle dragged out with shift and the left mouse button, in screen coordinates
+pub struct SelectionBox {
+    quad: Mesh,
+    program: Program,
+    // Fill and the four edges of the outline
+    rectangles: VertexBuffer<Rectangle>,
+}
+
+impl SelectionBox {
+    pub fn new(display: &Display) -> Result<SelectionBox> {
+        let empty = Rectangle { rect: [0.0; 4], fill: [0.0; 4] };
+
+        Ok(SelectionBox {
+            quad: create_unit_quad(display)?,
+            program: load_program(
+                display,
+                "shaders/overlay_vertex.glsl",
+                "shaders/alpha_fragment.glsl"
+            )?,
+            rectangles: VertexBuffer::dynamic(display, &[empty; 5])?,
+        })
+    }
+
+    // Corners can be given in any order
+    pub fn draw(&self, target: &mut Frame, perspective: [[f32; 4]; 4], a: [f32; 2], b: [f32; 2]) -> Result<()> {
+        let x = a[0].min(b[0]);
+        let y = a[1].min(b[1]);
+        let w = (a[0] - b[0]).abs();
+        let h = (a[1] - b[1]).abs();
+
+        self.rectangles.write(&[
+            Rectangle { rect: [x, y, w, h], fill: FILL_COLOR },
+            Rectangle { rect: [x, y, w, LINE_WIDTH], fill: OUTLINE_COLOR },
+            Rectangle { rect: [x, y + h - LINE_WIDTH, w, LINE_WIDTH], fill: OUTLINE_COLOR },
+            Rectangle { rect: [x, y, LINE_WIDTH, h], fill: OUTLINE_COLOR },
+            Rectangle { rect: [x + w - LINE_WIDTH, y, LINE_WIDTH, h], fill: OUTLINE_COLOR },
+        ]);
+
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target.draw(
+            (&self.quad.v_buffer, self.rectangles.per_instance().map_err(|_| Error::Instancing)?),
+            &self.quad.i_buffer,
+            &self.program,
+            &uniform! {
+                perspective: perspective,
+            },
+            &params
+        )?;
+
+        Ok(())
+    }
+}
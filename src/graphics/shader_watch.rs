@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::graphics::shaders::override_path;
+use crate::SHADER_POLL_INTERVAL;
+
+// Notices when shader overrides change on disk by polling their modification times.
+// Nothing is watched without SHADER_OVERRIDE_DIR, the embedded shaders can't change.
+pub struct ShaderWatcher {
+    paths: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+    // Seconds since the files were last checked
+    since_check: f32,
+}
+
+impl ShaderWatcher {
+    // Takes the paths of the default shaders
+    pub fn new(shaders: &[&str]) -> ShaderWatcher {
+        let paths: Vec<PathBuf> = shaders.iter().filter_map(|shader| override_path(shader)).collect();
+
+        ShaderWatcher {
+            modified: paths.iter().map(|path| modified_time(path)).collect(),
+            paths,
+            since_check: 0.0,
+        }
+    }
+
+    // True once after any of the files changed
+    pub fn changed(&mut self, delta_time: f32) -> bool {
+        self.since_check += delta_time;
+
+        if self.since_check < SHADER_POLL_INTERVAL {
+            return false;
+        }
+
+        self.since_check = 0.0;
+
+        let mut changed = false;
+
+        for (path, modified) in self.paths.iter().zip(self.modified.iter_mut()) {
+            let current = modified_time(path);
+
+            if current != *modified {
+                *modified = current;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,58 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::SHADER_OVERRIDE_DIR;
+
+// Every shader of the app, compiled into the binary so it runs from any working directory
+const EMBEDDED_SHADERS: &[(&str, &str)] = &[
+    ("shaders/alpha_fragment.glsl", include_str!("../../shaders/alpha_fragment.glsl")),
+    ("shaders/background_fragment.glsl", include_str!("../../shaders/background_fragment.glsl")),
+    ("shaders/background_vertex.glsl", include_str!("../../shaders/background_vertex.glsl")),
+    ("shaders/blur_fragment.glsl", include_str!("../../shaders/blur_fragment.glsl")),
+    ("shaders/boid_fragment.glsl", include_str!("../../shaders/boid_fragment.glsl")),
+    ("shaders/bright_fragment.glsl", include_str!("../../shaders/bright_fragment.glsl")),
+    ("shaders/composite_fragment.glsl", include_str!("../../shaders/composite_fragment.glsl")),
+    ("shaders/fade_fragment.glsl", include_str!("../../shaders/fade_fragment.glsl")),
+    ("shaders/flock.wgsl", include_str!("../../shaders/flock.wgsl")),
+    ("shaders/fragment.glsl", include_str!("../../shaders/fragment.glsl")),
+    ("shaders/gpu_field_fragment.glsl", include_str!("../../shaders/gpu_field_fragment.glsl")),
+    ("shaders/gpu_field_vertex.glsl", include_str!("../../shaders/gpu_field_vertex.glsl")),
+    ("shaders/gpu_point_vertex.glsl", include_str!("../../shaders/gpu_point_vertex.glsl")),
+    ("shaders/gpu_render_vertex.glsl", include_str!("../../shaders/gpu_render_vertex.glsl")),
+    ("shaders/gpu_update_fragment.glsl", include_str!("../../shaders/gpu_update_fragment.glsl")),
+    ("shaders/gpu_update_vertex.glsl", include_str!("../../shaders/gpu_update_vertex.glsl")),
+    ("shaders/heatmap_fragment.glsl", include_str!("../../shaders/heatmap_fragment.glsl")),
+    ("shaders/heatmap_vertex.glsl", include_str!("../../shaders/heatmap_vertex.glsl")),
+    ("shaders/line_vertex.glsl", include_str!("../../shaders/line_vertex.glsl")),
+    ("shaders/minimap_vertex.glsl", include_str!("../../shaders/minimap_vertex.glsl")),
+    ("shaders/overlay_vertex.glsl", include_str!("../../shaders/overlay_vertex.glsl")),
+    ("shaders/point_vertex.glsl", include_str!("../../shaders/point_vertex.glsl")),
+    ("shaders/profiler_vertex.glsl", include_str!("../../shaders/profiler_vertex.glsl")),
+    ("shaders/screen_vertex.glsl", include_str!("../../shaders/screen_vertex.glsl")),
+    ("shaders/sprite_fragment.glsl", include_str!("../../shaders/sprite_fragment.glsl")),
+    ("shaders/sprite_vertex.glsl", include_str!("../../shaders/sprite_vertex.glsl")),
+    ("shaders/texture_fragment.glsl", include_str!("../../shaders/texture_fragment.glsl")),
+    ("shaders/vertex.glsl", include_str!("../../shaders/vertex.glsl")),
+];
+
+// File replacing a shader, if SHADER_OVERRIDE_DIR is set. Overrides use the file name of the default.
+pub fn override_path(path: &str) -> Option<PathBuf> {
+    let dir = SHADER_OVERRIDE_DIR?;
+    let name = Path::new(path).file_name()?;
+
+    Some(Path::new(dir).join(name))
+}
+
+// Source of a shader. An existing override file wins over the embedded default,
+// paths that aren't one of the defaults are read from disk as they are.
+pub fn read_shader(path: &str) -> io::Result<String> {
+    if let Some(file) = override_path(path).filter(|file| file.exists()) {
+        return fs::read_to_string(file);
+    }
+
+    match EMBEDDED_SHADERS.iter().find(|(name, _)| *name == path) {
+        Some((_, source)) => Ok(source.to_string()),
+        None => fs::read_to_string(path),
+    }
+}
@@ -0,0 +1,103 @@
+use std::f32::consts::TAU;
+
+use crate::graphics::vertices::Vertex;
+use crate::graphics::create_agent_shape;
+use crate::graphics::obj::load_obj;
+use crate::AGENT_MESH_PATH;
+
+// Built in boid shapes, all pointing along +x and about `size` across
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AgentShape {
+    Triangle,
+    // Triangle with a notch in the back
+    Arrow,
+    // Diamond body with a forked tail
+    Fish,
+    Circle,
+    // Mesh from AGENT_MESH_PATH, the triangle without one
+    Custom,
+}
+
+pub const SHAPES: [AgentShape; 5] = [
+    AgentShape::Triangle,
+    AgentShape::Arrow,
+    AgentShape::Fish,
+    AgentShape::Circle,
+    AgentShape::Custom,
+];
+
+impl AgentShape {
+    pub fn next(self) -> AgentShape {
+        match self {
+            AgentShape::Triangle => AgentShape::Arrow,
+            AgentShape::Arrow => AgentShape::Fish,
+            AgentShape::Fish => AgentShape::Circle,
+            AgentShape::Circle => AgentShape::Custom,
+            AgentShape::Custom => AgentShape::Triangle,
+        }
+    }
+
+    // White mesh the boids of this shape are drawn with, tinted per boid. The custom shape comes
+    // from AGENT_MESH_PATH, or is the built in triangle if there is none or it can't be loaded.
+    pub fn mesh_geometry(self, size: f32) -> (Vec<Vertex>, Vec<u16>) {
+        let color = [1.0, 1.0, 1.0];
+
+        if let (AgentShape::Custom, Some(path)) = (self, AGENT_MESH_PATH) {
+            match load_obj(path, size, color) {
+                Ok(geometry) => return geometry,
+                Err(error) => println!("Could not load agent mesh {}: {}", path, error),
+            }
+        }
+
+        self.geometry(size, color)
+    }
+
+    // Vertices and triangle indices, none for the custom shape which is loaded instead
+    pub fn geometry(self, size: f32, color: [f32; 3]) -> (Vec<Vertex>, Vec<u16>) {
+        let h = size / 2.0;
+        let vertex = |x: f32, y: f32| Vertex { position: [x, y], color, uv: [0.0, 0.0] };
+
+        match self {
+            AgentShape::Triangle | AgentShape::Custom => {
+                let (vertices, indices) = create_agent_shape(size, color);
+                (vertices.to_vec(), indices.to_vec())
+            }
+            AgentShape::Arrow => (
+                vec![
+                    vertex(h, 0.0),
+                    vertex(-h, h),
+                    vertex(-h * 0.4, 0.0),
+                    vertex(-h, -h),
+                ],
+                vec![0, 1, 2, 0, 2, 3],
+            ),
+            AgentShape::Fish => (
+                vec![
+                    vertex(h, 0.0),
+                    vertex(0.0, h * 0.45),
+                    vertex(-h * 0.4, 0.0),
+                    vertex(0.0, -h * 0.45),
+                    vertex(-h, h * 0.5),
+                    vertex(-h, -h * 0.5),
+                ],
+                vec![0, 1, 2, 0, 2, 3, 2, 4, 5],
+            ),
+            AgentShape::Circle => {
+                const SEGMENTS: u16 = 12;
+                let radius = h * 0.7;
+
+                let mut vertices = vec![vertex(0.0, 0.0)];
+                let mut indices = Vec::new();
+
+                for i in 0..SEGMENTS {
+                    let angle = i as f32 / SEGMENTS as f32 * TAU;
+                    vertices.push(vertex(angle.cos() * radius, angle.sin() * radius));
+
+                    indices.extend_from_slice(&[0, i + 1, (i + 1) % SEGMENTS + 1]);
+                }
+
+                (vertices, indices)
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use vecmath::{Vector2, Vector3};
+
+use boids_core::data::{Flap, Forward, Position, Scale};
+
+// The wgpu backend copies vertices and instances into its buffers as plain bytes
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "wgpu", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct Vertex {
+    pub position: Vector2<f32>,
+    pub color: Vector3<f32>,
+    // Texture coordinates, only used by sprites
+    pub uv: Vector2<f32>,
+}
+#[cfg(feature = "glium")]
+implement_vertex!(Vertex, position, color, uv);
+
+// Instance data of one boid, 28 bytes. The heading is stored as normalized
+// signed shorts and the tint as normalized bytes, the GPU unpacks both to floats.
+// The shape is the index of the boid's shape, only boids of the drawn shape show up.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "wgpu", derive(bytemuck::Pod, bytemuck::Zeroable))]
+#[repr(C)]
+pub struct BoidInstance {
+    pub value: Vector2<f32>,
+    pub heading: [i16; 2],
+    pub tint: [u8; 3],
+    pub shape: u8,
+    pub scale: f32,
+    pub flap: f32,
+    // Speed relative to AGENT_SPEED
+    pub speed: f32,
+}
+#[cfg(feature = "glium")]
+implement_vertex!(
+    BoidInstance,
+    value normalize(false),
+    heading normalize(true),
+    tint normalize(true),
+    shape normalize(false),
+    scale normalize(false),
+    flap normalize(false),
+    speed normalize(false)
+);
+
+impl BoidInstance {
+    pub fn pack(
+        position: &Position,
+        forward: &Forward,
+        scale: &Scale,
+        flap: &Flap,
+        speed: f32,
+        color: [f32; 3],
+        shape: u8
+    ) -> BoidInstance {
+        let snorm = |x: f32| (x.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16;
+
+        BoidInstance {
+            value: position.value,
+            heading: [snorm(forward.direction[0]), snorm(forward.direction[1])],
+            tint: pack_color(color),
+            shape,
+            scale: scale.factor,
+            flap: flap.phase,
+            speed,
+        }
+    }
+
+    pub fn recolor(&mut self, color: [f32; 3]) {
+        self.tint = pack_color(color);
+    }
+}
+
+fn pack_color(color: [f32; 3]) -> [u8; 3] {
+    let unorm = |x: f32| (x.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8;
+
+    [unorm(color[0]), unorm(color[1]), unorm(color[2])]
+}
+
+// Boid state of the GPU simulation, also used directly as instance data.
+#[derive(Clone, Copy)]
+pub struct GpuBoid {
+    pub boid_position: Vector2<f32>,
+    pub boid_direction: Vector2<f32>,
+}
+#[cfg(feature = "glium")]
+implement_vertex!(GpuBoid, boid_position, boid_direction);
+
+// One end of a line segment, drawn as a line list
+#[derive(Clone, Copy)]
+pub struct LineVertex {
+    pub position: Vector2<f32>,
+    pub color: [f32; 4],
+}
+#[cfg(feature = "glium")]
+implement_vertex!(LineVertex, position, color);
@@ -0,0 +1,282 @@
+use std::mem;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+
+use crate::error::{Error, Result};
+use crate::graphics::renderer::{DrawTo, Renderer};
+use crate::graphics::shaders::read_shader;
+use crate::graphics::shapes::{AgentShape, SHAPES};
+use crate::graphics::vertices::{BoidInstance, Vertex};
+use crate::snapshot::RenderSnapshot;
+use boids_core::SPECIES_COUNT;
+use crate::{AGENT_SHAPE, FLAP_AMPLITUDE, SOFT_BOIDS, VELOCITY_STRETCH};
+
+pub const FLOCK_SHADER: &str = "shaders/flock.wgsl";
+
+// Device of the wgpu backend and the queue everything is uploaded with
+pub struct Gpu {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+// One frame of the wgpu backend. Passes are recorded into the encoder and draw onto the view,
+// the world is fit into the viewport given as x, y, width and height in pixels.
+pub struct WgpuFrame<'a> {
+    pub queue: &'a wgpu::Queue,
+    pub encoder: &'a mut wgpu::CommandEncoder,
+    pub view: &'a wgpu::TextureView,
+    pub viewport: [f32; 4],
+}
+
+// Uniforms of flock.wgsl, padded to the 16 byte alignment of uniform structs
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+struct Globals {
+    perspective: [[f32; 4]; 4],
+    velocity_stretch: f32,
+    flap_amplitude: f32,
+    agent_radius: f32,
+    drawn_shape: u32,
+    soft: u32,
+    _padding: [u32; 3],
+}
+
+// Mesh of one entry of SHAPES with the uniforms of the pass drawing it
+struct ShapePass {
+    vertices: wgpu::Buffer,
+    indices: wgpu::Buffer,
+    index_count: u32,
+    globals: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+// The flock pass of FlockRenderer for wgpu, one instance of the agent mesh per boid.
+// Trails, sprites and the point LOD are only drawn by the glium backend so far.
+pub struct WgpuFlockRenderer {
+    pipeline: wgpu::RenderPipeline,
+    shapes: Vec<ShapePass>,
+    instances: wgpu::Buffer,
+    // Boids the instance buffer has room for and boids of the last upload
+    capacity: usize,
+    instance_count: u32,
+    // Shape of every species, boids are drawn once per shape in use
+    pub species_shapes: [AgentShape; SPECIES_COUNT],
+    pub soft: bool,
+    pub velocity_stretch: f32,
+    agent_size: f32,
+}
+
+fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Boid instances"),
+        size: (capacity * mem::size_of::<BoidInstance>()) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+impl WgpuFlockRenderer {
+    // `format` is the format of the views drawn into
+    pub fn new(gpu: &Gpu, format: wgpu::TextureFormat, agent_size: f32) -> Result<WgpuFlockRenderer> {
+        let device = &gpu.device;
+
+        let source = read_shader(FLOCK_SHADER)
+            .map_err(|source| Error::Shader { path: FLOCK_SHADER.to_string(), source })?;
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(FLOCK_SHADER),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Flock globals"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Flock"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Flock"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    // The texture coordinates at the end are only used by sprites
+                    wgpu::VertexBufferLayout {
+                        array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x3],
+                    },
+                    wgpu::VertexBufferLayout {
+                        array_stride: mem::size_of::<BoidInstance>() as wgpu::BufferAddress,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &wgpu::vertex_attr_array![
+                            2 => Float32x2,
+                            3 => Snorm16x2,
+                            4 => Uint8x4,
+                            5 => Float32,
+                            6 => Float32,
+                            7 => Float32
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                // Solid boids have an alpha of 1, blending only shows with soft ones
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let shapes = SHAPES.iter().map(|shape| {
+            let (vertices, indices) = shape.mesh_geometry(agent_size);
+
+            let globals = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Flock globals"),
+                size: mem::size_of::<Globals>() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Flock globals"),
+                layout: &bind_group_layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: globals.as_entire_binding(),
+                }],
+            });
+
+            ShapePass {
+                vertices: device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Agent mesh"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: wgpu::BufferUsages::VERTEX,
+                }),
+                indices: device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Agent mesh"),
+                    contents: bytemuck::cast_slice(&indices),
+                    usage: wgpu::BufferUsages::INDEX,
+                }),
+                index_count: indices.len() as u32,
+                globals,
+                bind_group,
+            }
+        }).collect();
+
+        let capacity = 1;
+
+        Ok(WgpuFlockRenderer {
+            pipeline,
+            shapes,
+            instances: create_instance_buffer(device, capacity),
+            capacity,
+            instance_count: 0,
+            species_shapes: [AGENT_SHAPE; SPECIES_COUNT],
+            soft: SOFT_BOIDS,
+            velocity_stretch: VELOCITY_STRETCH,
+            agent_size,
+        })
+    }
+}
+
+impl Renderer for WgpuFlockRenderer {
+    type Context = Gpu;
+
+    fn upload(&mut self, gpu: &Gpu, snapshot: &RenderSnapshot) -> Result<()> {
+        let instances = &snapshot.instances;
+
+        // Grows in steps, so a slowly growing flock doesn't make a new buffer every frame
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.instances = create_instance_buffer(&gpu.device, self.capacity);
+        }
+
+        if !instances.is_empty() {
+            gpu.queue.write_buffer(&self.instances, 0, bytemuck::cast_slice(instances));
+        }
+
+        self.instance_count = instances.len() as u32;
+
+        Ok(())
+    }
+}
+
+impl<'a> DrawTo<WgpuFrame<'a>> for WgpuFlockRenderer {
+    fn draw(&self, frame: &mut WgpuFrame<'a>, perspective: [[f32; 4]; 4]) -> Result<()> {
+        for (i, shape) in self.species_shapes.iter().enumerate() {
+            if self.species_shapes[..i].contains(shape) {
+                continue;
+            }
+
+            let globals = Globals {
+                perspective,
+                velocity_stretch: self.velocity_stretch,
+                flap_amplitude: FLAP_AMPLITUDE,
+                agent_radius: self.agent_size / 2.0,
+                drawn_shape: *shape as u32,
+                soft: self.soft as u32,
+                _padding: [0; 3],
+            };
+
+            frame.queue.write_buffer(&self.shapes[*shape as usize].globals, 0, bytemuck::bytes_of(&globals));
+        }
+
+        let mut pass = frame.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Flock"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: frame.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        let [x, y, w, h] = frame.viewport;
+        pass.set_viewport(x, y, w, h, 0.0, 1.0);
+        pass.set_pipeline(&self.pipeline);
+        pass.set_vertex_buffer(1, self.instances.slice(..));
+
+        for (i, shape) in self.species_shapes.iter().enumerate() {
+            // Drawn already for an earlier species
+            if self.species_shapes[..i].contains(shape) {
+                continue;
+            }
+
+            let shape_pass = &self.shapes[*shape as usize];
+
+            pass.set_bind_group(0, &shape_pass.bind_group, &[]);
+            pass.set_vertex_buffer(0, shape_pass.vertices.slice(..));
+            pass.set_index_buffer(shape_pass.indices.slice(..), wgpu::IndexFormat::Uint16);
+            pass.draw_indexed(0..shape_pass.index_count, 0, 0..self.instance_count);
+        }
+
+        Ok(())
+    }
+}
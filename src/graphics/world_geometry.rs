@@ -0,0 +1,39 @@
+use glium::{Display, Surface};
+
+use crate::error::Result;
+use crate::graphics::lines::LineRenderer;
+use crate::snapshot::RenderSnapshot;
+
+// Everything in the world that isn't a boid, like gusts and obstacles, drawn as outlines under the flock.
+// Kept apart from the boid instances so it can be hidden on its own.
+pub struct WorldGeometry {
+    pub enabled: bool,
+
+    lines: LineRenderer,
+}
+
+impl WorldGeometry {
+    pub fn new(display: &Display) -> Result<WorldGeometry> {
+        Ok(WorldGeometry {
+            enabled: true,
+
+            lines: LineRenderer::new(display)?,
+        })
+    }
+
+    pub fn upload(&mut self, display: &Display, snapshot: &RenderSnapshot) -> Result<()> {
+        self.lines.upload(display, &snapshot.geometry_vertices)
+    }
+
+    pub fn draw<S: Surface>(&self, target: &mut S, perspective: [[f32; 4]; 4]) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        self.lines.draw(target, perspective)
+    }
+
+    pub fn gpu_bytes(&self) -> usize {
+        self.lines.gpu_bytes()
+    }
+}
@@ -0,0 +1,165 @@
+use std::path::Path;
+use std::time::Instant;
+
+use boids_core::profiler::Profiler;
+use boids_core::save::{load_simulation, save_simulation};
+
+use crate::autosave::Autosave;
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::live_control::LiveControl;
+use crate::network::NetworkHost;
+use crate::websocket::WebSocketStream;
+use crate::threads::ThreadSettings;
+use crate::trajectory::TrajectoryRecorder;
+use crate::FIXED_TIMESTEP;
+
+// Steps the simulation without a window as fast as the CPU allows, for scripted runs and benchmarks.
+// Stops after `--steps` steps or runs until interrupted, the profiler logs the time of every stage.
+pub fn run(config: &Config, cli: &Cli) {
+    let threads = ThreadSettings::default();
+    let thread_pool = match threads.build_pool() {
+        Ok(thread_pool) => thread_pool,
+        Err(error) => {
+            println!("Could not start: {}", error);
+            return;
+        }
+    };
+
+    let mut simulation = match cli.start_from() {
+        Some(path) => match load_simulation(Path::new(&path)) {
+            Ok(mut simulation) => {
+                config.load_script(&mut simulation);
+                config.load_plugins(&mut simulation);
+                simulation
+            }
+            Err(error) => {
+                println!("Could not load simulation from {}: {}", path, error);
+                return;
+            }
+        },
+        None => config.create_simulation(),
+    };
+
+    let mut trajectory = match &cli.trajectory {
+        Some(path) => match TrajectoryRecorder::start(Path::new(path), config.trajectory.interval) {
+            Ok(trajectory) => Some(trajectory),
+            Err(error) => {
+                println!("Could not start trajectory {}: {}", path, error);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut autosave = Autosave::new(config.autosave.interval, config.autosave.keep);
+
+    let mut host = match &cli.host {
+        Some(address) => match NetworkHost::start(address) {
+            Ok(host) => {
+                println!("Hosting on {}", address);
+                Some(host)
+            }
+            Err(error) => {
+                println!("Could not host on {}: {}", address, error);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut websocket = match &cli.websocket {
+        Some(address) => match WebSocketStream::start(address) {
+            Ok(websocket) => {
+                println!("Streaming to WebSocket viewers on {}", address);
+                Some(websocket)
+            }
+            Err(error) => {
+                println!("Could not stream on {}: {}", address, error);
+                return;
+            }
+        },
+        None => None,
+    };
+
+    let mut live_control = LiveControl::new();
+
+    if let Some(address) = &cli.osc {
+        match live_control.start_osc(address) {
+            Ok(()) => println!("Listening to OSC on {}", address),
+            Err(error) => {
+                println!("Could not listen to OSC on {}: {}", address, error);
+                return;
+            }
+        }
+    }
+
+    if let Some(port) = &cli.midi {
+        match live_control.start_midi(port, config) {
+            Ok(name) => println!("Listening to MIDI input {}", name),
+            Err(error) => {
+                println!("Could not listen to MIDI: {}", error);
+                return;
+            }
+        }
+    }
+
+    let mut profiler = Profiler::new();
+    profiler.enabled = true;
+
+    println!("Headless simulation with {} boids", simulation.components.len());
+
+    let start = Instant::now();
+    let mut step = 0;
+
+    while Some(step) != cli.steps {
+        let t = Instant::now();
+
+        // Nothing reads the events, every step is a frame of its own
+        simulation.events.next_frame();
+        live_control.apply(&mut simulation);
+        thread_pool.install(|| simulation.update(FIXED_TIMESTEP, &mut profiler));
+        profiler.end_frame(t.elapsed().as_secs_f32());
+
+        if let Some(recorder) = &mut trajectory {
+            if let Err(error) = recorder.record(&simulation, FIXED_TIMESTEP) {
+                println!("Trajectory stopped: {}", error);
+                finish_trajectory(trajectory.take());
+            }
+        }
+
+        autosave.advance(&simulation, FIXED_TIMESTEP);
+
+        if let Some(host) = &mut host {
+            host.advance(&simulation, FIXED_TIMESTEP);
+        }
+
+        if let Some(websocket) = &mut websocket {
+            websocket.advance(&simulation, FIXED_TIMESTEP);
+        }
+
+        step += 1;
+    }
+
+    println!("{} steps in {:.2}s", step, start.elapsed().as_secs_f32());
+
+    finish_trajectory(trajectory);
+
+    if let Some(path) = &cli.save {
+        match save_simulation(&simulation, Path::new(path)) {
+            Ok(()) => println!("Saved simulation to {}", path),
+            Err(error) => println!("Could not save simulation to {}: {}", path, error),
+        }
+    }
+}
+
+fn finish_trajectory(trajectory: Option<TrajectoryRecorder>) {
+    if let Some(trajectory) = trajectory {
+        let rows = trajectory.rows;
+
+        match trajectory.finish() {
+            Ok(path) => println!("Saved {} trajectory rows to {}", rows, path.display()),
+            Err(error) => println!("Could not save trajectory: {}", error),
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::error::Error;
+use std::io;
+use std::net::UdpSocket;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use midir::{MidiInput, MidiInputConnection};
+use rosc::{OscMessage, OscPacket, OscType};
+use serde::Deserialize;
+
+use boids_core::events::{Event, Parameter};
+use boids_core::simulation::Simulation;
+use boids_core::species::InteractionPreset;
+use boids_core::PERCEPTION_RADIUS;
+
+use crate::config::Config;
+use crate::{MIDI_RANGE_FACTOR, PERCEPTION_RADIUS_RANGE};
+
+// Largest OSC packet read, bigger ones are cut off and fail to decode
+const OSC_BUFFER_SIZE: usize = 1536;
+
+// A parameter change from an OSC or MIDI controller or the console, applied between two frames
+#[derive(Clone, Copy, Debug)]
+pub enum Control {
+    Alignment(f32),
+    Cohesion(f32),
+    Separation(f32),
+    Speed(f32),
+    PerceptionRadius(f32),
+    Population(usize),
+    Preset(InteractionPreset),
+    NextPreset,
+}
+
+// MIDI control change numbers of the parameters, a control that isn't mapped is ignored
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct MidiConfig {
+    pub alignment: u8,
+    pub cohesion: u8,
+    pub separation: u8,
+    pub speed: u8,
+    pub perception_radius: u8,
+    pub population: u8,
+}
+
+impl Default for MidiConfig {
+    // The first six sliders of most controllers
+    fn default() -> Self {
+        MidiConfig {
+            alignment: 0,
+            cohesion: 1,
+            separation: 2,
+            speed: 3,
+            perception_radius: 4,
+            population: 5,
+        }
+    }
+}
+
+// Parameters set from outside of the app while it runs.
+//
+// OSC messages over UDP set a parameter to their argument:
+//
+//     /boids/alignment, /boids/cohesion, /boids/separation, /boids/speed, /boids/perception  float
+//     /boids/population  int
+//     /boids/preset  segregated, mixed, predator-prey or mobbing
+//
+// MIDI control changes sweep a parameter from its starting value divided by MIDI_RANGE_FACTOR
+// at 0 to the starting value times MIDI_RANGE_FACTOR at 127, the middle keeps it. Any note
+// cycles the interaction preset. Both are read on threads of their own.
+pub struct LiveControl {
+    sender: Sender<Control>,
+    controls: Receiver<Control>,
+    // Input stops when it is dropped
+    midi: Option<MidiInputConnection<()>>,
+}
+
+impl Default for LiveControl {
+    fn default() -> LiveControl {
+        LiveControl::new()
+    }
+}
+
+impl LiveControl {
+    pub fn new() -> LiveControl {
+        let (sender, controls) = mpsc::channel();
+
+        LiveControl {
+            sender,
+            controls,
+            midi: None,
+        }
+    }
+
+    // `address` is where OSC messages arrive, like 0.0.0.0:9000
+    pub fn start_osc(&mut self, address: &str) -> io::Result<()> {
+        let socket = UdpSocket::bind(address)?;
+        let sender = self.sender.clone();
+
+        thread::Builder::new()
+            .name("osc".to_string())
+            .spawn(move || receive_osc(socket, sender))?;
+
+        Ok(())
+    }
+
+    // Listens to the first MIDI input with `port` in its name
+    pub fn start_midi(&mut self, port: &str, config: &Config) -> Result<String, Box<dyn Error>> {
+        let input = MidiInput::new("boids")?;
+
+        let ports = input.ports();
+        let names: Vec<String> = ports.iter().filter_map(|port| input.port_name(port).ok()).collect();
+
+        let index = names.iter()
+            .position(|name| name.to_lowercase().contains(&port.to_lowercase()))
+            .ok_or_else(|| format!("no MIDI input named {}, found {:?}", port, names))?;
+
+        let mapping = MidiMapping::new(config);
+        let sender = self.sender.clone();
+
+        let connection = input.connect(&ports[index], "boids control", move |_, message, _| {
+            if let Some(control) = mapping.control(message) {
+                sender.send(control).ok();
+            }
+        }, ())?;
+
+        self.midi = Some(connection);
+
+        Ok(names[index].clone())
+    }
+
+    // Applies every control that arrived since the last call
+    pub fn apply(&mut self, simulation: &mut Simulation) {
+        for control in self.controls.try_iter() {
+            println!("{}", control.apply(simulation));
+        }
+    }
+}
+
+impl Control {
+    // Names of the parameters, shared by OSC addresses and console commands
+    pub const NAMES: [&'static str; 7] = [
+        "alignment",
+        "cohesion",
+        "separation",
+        "speed",
+        "perception",
+        "population",
+        "preset",
+    ];
+
+    // Sets the parameter called `name` to `number`, or to `text` for the preset
+    pub fn from_name(name: &str, number: Option<f32>, text: Option<&str>) -> Option<Control> {
+        match name {
+            "alignment" => number.map(Control::Alignment),
+            "cohesion" => number.map(Control::Cohesion),
+            "separation" => number.map(Control::Separation),
+            "speed" => number.map(Control::Speed),
+            "perception" => number.map(Control::PerceptionRadius),
+            "population" => number.map(|count| Control::Population(count.max(0.0) as usize)),
+            "preset" => text.and_then(InteractionPreset::from_name).map(Control::Preset),
+            _ => None,
+        }
+    }
+
+    // Returns the new value to report
+    pub fn apply(self, simulation: &mut Simulation) -> String {
+        match self {
+            Control::Alignment(value) => {
+                simulation.weights.alignment = value.max(0.0);
+                simulation.events.send(Event::ParameterChanged(Parameter::Alignment));
+                format!("Alignment: {:.2}", simulation.weights.alignment)
+            }
+            Control::Cohesion(value) => {
+                simulation.weights.cohesion = value.max(0.0);
+                simulation.events.send(Event::ParameterChanged(Parameter::Cohesion));
+                format!("Cohesion: {:.2}", simulation.weights.cohesion)
+            }
+            Control::Separation(value) => {
+                simulation.weights.separation = value.max(0.0);
+                simulation.events.send(Event::ParameterChanged(Parameter::Separation));
+                format!("Separation: {:.2}", simulation.weights.separation)
+            }
+            Control::Speed(value) => {
+                simulation.speed = value.max(0.0);
+                simulation.events.send(Event::ParameterChanged(Parameter::Speed));
+                format!("Speed: {:.1}", simulation.speed)
+            }
+            Control::PerceptionRadius(value) => {
+                let [min, max] = PERCEPTION_RADIUS_RANGE;
+                simulation.set_perception_radius(value.clamp(min, max));
+                format!("Perception radius: {:.1}", simulation.perception_radius)
+            }
+            Control::Population(count) => {
+                simulation.set_population(count);
+                format!("Population: {}", count)
+            }
+            Control::Preset(preset) => {
+                simulation.set_interaction_preset(preset);
+                format!("Interaction preset: {:?}", simulation.interaction_preset)
+            }
+            Control::NextPreset => {
+                simulation.set_interaction_preset(simulation.interaction_preset.next());
+                format!("Interaction preset: {:?}", simulation.interaction_preset)
+            }
+        }
+    }
+}
+
+fn receive_osc(socket: UdpSocket, sender: Sender<Control>) {
+    let mut buffer = [0; OSC_BUFFER_SIZE];
+
+    loop {
+        let size = match socket.recv(&mut buffer) {
+            Ok(size) => size,
+            Err(error) => {
+                println!("OSC input stopped: {}", error);
+                return;
+            }
+        };
+
+        let packet = match rosc::decoder::decode_udp(&buffer[..size]) {
+            Ok((_, packet)) => packet,
+            Err(error) => {
+                println!("Could not decode OSC packet: {:?}", error);
+                continue;
+            }
+        };
+
+        let mut messages = Vec::new();
+        flatten(packet, &mut messages);
+
+        for message in messages {
+            match osc_control(&message) {
+                Some(control) => {
+                    // The app is gone
+                    if sender.send(control).is_err() {
+                        return;
+                    }
+                }
+                None => println!("Unknown OSC message {} {:?}", message.addr, message.args),
+            }
+        }
+    }
+}
+
+// Bundles are applied as soon as they arrive, their time tags are ignored
+fn flatten(packet: OscPacket, messages: &mut Vec<OscMessage>) {
+    match packet {
+        OscPacket::Message(message) => messages.push(message),
+        OscPacket::Bundle(bundle) => {
+            for packet in bundle.content {
+                flatten(packet, messages);
+            }
+        }
+    }
+}
+
+fn osc_control(message: &OscMessage) -> Option<Control> {
+    let number = match message.args.first()? {
+        OscType::Float(value) => Some(*value),
+        OscType::Double(value) => Some(*value as f32),
+        OscType::Int(value) => Some(*value as f32),
+        OscType::Long(value) => Some(*value as f32),
+        _ => None,
+    };
+
+    let text = match message.args.first()? {
+        OscType::String(text) => Some(text.as_str()),
+        _ => None,
+    };
+
+    Control::from_name(message.addr.strip_prefix("/boids/")?, number, text)
+}
+
+// Turns MIDI messages into controls, with the starting values in the middle of every range
+struct MidiMapping {
+    numbers: MidiConfig,
+    alignment: f32,
+    cohesion: f32,
+    separation: f32,
+    speed: f32,
+    perception_radius: f32,
+    population: f32,
+}
+
+impl MidiMapping {
+    fn new(config: &Config) -> MidiMapping {
+        MidiMapping {
+            numbers: config.midi,
+            alignment: config.flock.alignment,
+            cohesion: config.flock.cohesion,
+            separation: config.flock.separation,
+            speed: config.flock.agent_speed,
+            perception_radius: PERCEPTION_RADIUS,
+            population: config.flock.agent_count as f32,
+        }
+    }
+
+    fn control(&self, message: &[u8]) -> Option<Control> {
+        let (&status, data) = message.split_first()?;
+
+        match status & 0xF0 {
+            // Note on with a velocity, a velocity of 0 is a note off
+            0x90 if data.get(1).copied().unwrap_or(0) > 0 => Some(Control::NextPreset),
+            // Control change
+            0xB0 => {
+                let (number, value) = (*data.first()?, *data.get(1)?);
+                let scale = |middle: f32| middle * MIDI_RANGE_FACTOR.powf((value as f32 - 64.0) / 64.0);
+                let numbers = self.numbers;
+
+                if number == numbers.alignment {
+                    Some(Control::Alignment(scale(self.alignment)))
+                }
+                else if number == numbers.cohesion {
+                    Some(Control::Cohesion(scale(self.cohesion)))
+                }
+                else if number == numbers.separation {
+                    Some(Control::Separation(scale(self.separation)))
+                }
+                else if number == numbers.speed {
+                    Some(Control::Speed(scale(self.speed)))
+                }
+                else if number == numbers.perception_radius {
+                    Some(Control::PerceptionRadius(scale(self.perception_radius)))
+                }
+                else if number == numbers.population {
+                    Some(Control::Population(scale(self.population).round() as usize))
+                }
+                else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+}
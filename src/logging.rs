@@ -0,0 +1,51 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+
+use tracing::level_filters::LevelFilter;
+use tracing_chrome::ChromeLayerBuilder;
+use tracing_flame::FlameLayer;
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+
+use crate::cli::Cli;
+
+// Trace files are only complete once these are dropped, keep them until the program ends
+pub struct TraceGuards {
+    _chrome: Option<tracing_chrome::FlushGuard>,
+    _flame: Option<tracing_flame::FlushGuard<BufWriter<File>>>,
+}
+
+// Log messages down to `--log-level` go to stderr. The spans of every frame phase and system
+// can also be written as a Chrome trace, for chrome://tracing or Perfetto, and as folded
+// stacks, which inferno-flamegraph turns into a flamegraph. Both get the spans at any log level.
+pub fn init(cli: &Cli) -> TraceGuards {
+    let export_level = cli.log_level.max(LevelFilter::INFO);
+
+    let (chrome, chrome_guard) = match &cli.chrome_trace {
+        Some(path) => {
+            let (layer, guard) = ChromeLayerBuilder::new().file(path).build();
+            (Some(layer.with_filter(export_level)), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let (flame, flame_guard) = match cli.flamegraph.as_ref().map(FlameLayer::with_file) {
+        Some(Ok((layer, guard))) => (Some(layer.with_filter(export_level)), Some(guard)),
+        Some(Err(error)) => {
+            println!("Could not write flamegraph: {}", error);
+            (None, None)
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(fmt::layer().with_writer(io::stderr).with_filter(cli.log_level))
+        .with(chrome)
+        .with(flame)
+        .init();
+
+    TraceGuards {
+        _chrome: chrome_guard,
+        _flame: flame_guard,
+    }
+}
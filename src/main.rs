@@ -1,80 +1,291 @@
+// Parts of the app only the glium window uses
+#![cfg_attr(not(feature = "glium"), allow(dead_code))]
+
+#[cfg(feature = "glium")]
 #[macro_use]
 extern crate glium;
 
 mod graphics;
+#[cfg(feature = "glium")]
 mod app;
-mod systems;
-mod data;
+mod governor;
+mod snapshot;
+mod threads;
+mod coloring;
+mod camera;
+mod pacing;
+mod theme;
+#[cfg(feature = "glium")]
+mod comparison;
+mod config;
+mod cli;
+mod error;
+mod headless;
+mod logging;
+mod trajectory;
+mod autosave;
+mod network;
+mod websocket;
+mod live_control;
+mod audio;
+mod sonification;
+mod console;
+mod presets;
+#[cfg(feature = "glium")]
+mod window;
+#[cfg(feature = "wgpu")]
+mod viewer;
 
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
-use app::App;
-use glium::Surface;
-use glium::glutin::event::{Event, WindowEvent};
-use glium::glutin::event_loop::{ControlFlow, EventLoop};
-use graphics::create_display;
+use clap::Parser;
+use cli::Cli;
+use config::Config;
+use boids_core::data::FlockWeights;
+use boids_core::{ALIGNMENT_WEIGHT, COHESION_WEIGHT, SEPARATION_WEIGHT};
+use graphics::WindowMode;
+use graphics::shapes::AgentShape;
 
-const BG: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+// Read at startup unless --config names another file.
+// The constants below are the defaults of whatever the file leaves out.
+pub const CONFIG_PATH: &str = "boids.toml";
 
+// Window size in logical pixels, physical pixels are this times the scale factor of the monitor
 pub const INITIAL_DISPLAY_SIZE: [u32; 2] = [1280, 720];
+// Size of the simulated world, it doesn't change with the window.
+// The world is fit into the window and the rest of the window is letterboxed.
+pub const WORLD_SIZE: [u32; 2] = [1280, 720];
+pub const LETTERBOX_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+// Extra scale of the overlays on top of the monitor's scale factor
+pub const UI_SCALE: f64 = 1.0;
+// Samples per pixel of multisample anti-aliasing, 0 turns it off
+pub const MSAA_SAMPLES: u16 = 4;
+// Windowed, borderless or exclusive fullscreen at startup, Alt+Enter switches at runtime
+pub const WINDOW_MODE: WindowMode = WindowMode::Windowed;
+// Waits for the display refresh before showing a frame, only takes effect at startup
+pub const VSYNC: bool = false;
+// Frames per second the render loop is held to, none runs as fast as possible
+pub const FRAME_RATE_CAP: Option<f32> = Some(60.0);
+
+// Seconds simulated per step, frames take as many steps as fit into their time
+pub const FIXED_TIMESTEP: f32 = 1.0 / 60.0;
+// Frames slower than this many steps simulate less time than they took
+pub const MAX_STEPS_PER_FRAME: u32 = 5;
+// Limits of the time scale, 9 halves and 0 doubles it at runtime
+pub const TIME_SCALE_RANGE: [f32; 2] = [0.1, 10.0];
+pub const TIME_SCALE_FACTOR: f32 = 2.0;
 
 pub const AGENT_COUNT: usize = 5_000;
 pub const AGENT_SIZE: f32 = 7.0;
+// Part of its width a boid folds in at the bottom of a wing beat, 0 turns flapping off
+pub const FLAP_AMPLITUDE: f32 = 0.4;
+
+// Population limits of the frame rate governor
+pub const MIN_AGENT_COUNT: usize = 500;
+pub const MAX_AGENT_COUNT: usize = 50_000;
+pub const TARGET_FPS: f32 = 60.0;
+// Part of the frame budget the governor fills, the rest is left for spikes
+pub const GOVERNOR_HEADROOM: f32 = 0.85;
+// Seconds between two population changes
+pub const GOVERNOR_INTERVAL: f32 = 0.5;
+// Largest relative population change at once
+pub const GOVERNOR_MAX_STEP: f32 = 0.1;
+// Weight of the newest frame time in the moving average
+pub const GOVERNOR_SMOOTHING: f32 = 0.1;
+
+// Height of one millisecond in the profiler graph in pixels
+pub const PROFILER_GRAPH_SCALE: f32 = 4.0;
+// Frames shown by the metric plots and the size of each plot in screen pixels
+pub const PLOT_HISTORY: usize = 200;
+pub const PLOT_SIZE: [f32; 2] = [200.0, 50.0];
+
+// Opacity of the newest end of a trail, it fades out towards the oldest
+pub const TRAIL_ALPHA: f32 = 0.5;
+
+// Boids with this many neighbors get the hottest color in the density color mode
+pub const DENSITY_COLOR_NEIGHBORS: f32 = 20.0;
+
+// Boids are counted into cells this big for the density heatmap
+pub const HEATMAP_CELL_SIZE: f32 = 20.0;
+pub const HEATMAP_BLUR_PASSES: usize = 2;
+// Blurred count per cell shown with the hottest color
+pub const HEATMAP_MAX_DENSITY: f32 = 4.0;
+pub const HEATMAP_ALPHA: f32 = 0.4;
+
+// Part of the way to the background the motion blur layer is faded every frame
+pub const MOTION_BLUR_FADE: f32 = 0.08;
+// Brightness from which on colors glow, from 0 to 1
+pub const BLOOM_THRESHOLD: f32 = 0.6;
+pub const BLOOM_INTENSITY: f32 = 1.2;
+// Each pass blurs once horizontally and once vertically
+pub const BLOOM_BLUR_PASSES: usize = 3;
+
+// Opacity of gust outlines and other world geometry
+pub const GEOMETRY_ALPHA: f32 = 0.5;
+
+// Cells holding this many boids are shaded fully in the grid overlay
+pub const GRID_OVERLAY_MAX_OCCUPANCY: f32 = 30.0;
+// Colors of the world at startup, W cycles through the themes at runtime
+pub const THEME: &str = "Classic";
+
+// Boids this many pixels from the cursor count as hovered
+pub const HOVER_RADIUS: f32 = 15.0;
+// Colors of the selected boid and of its neighbors
+pub const HIGHLIGHT_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+pub const NEIGHBOR_HIGHLIGHT_COLOR: [f32; 3] = [1.0, 0.85, 0.2];
+// Average flow of the boids per grid cell, 7 toggles it
+pub const FLOW_FIELD_COLOR: [f32; 3] = [0.4, 0.9, 1.0];
+pub const FLOW_FIELD_ALPHA: f32 = 0.8;
+// Cells with fewer boids get no arrow
+pub const FLOW_FIELD_MIN_BOIDS: u32 = 3;
+// Arrow length in cells for boids flying at AGENT_SPEED in the same direction
+pub const FLOW_ARROW_SCALE: f32 = 0.8;
+// Color of the boids selected with shift and drag
+pub const GROUP_COLOR: [f32; 3] = [0.3, 1.0, 0.5];
+// Length in pixels of a steering vector of length one in the debug view
+pub const DEBUG_VECTOR_SCALE: f32 = 20.0;
+
+// Width of the minimap in screen pixels, it shows up once the camera zooms in further than MINIMAP_MIN_ZOOM
+pub const MINIMAP_WIDTH: f32 = 200.0;
+pub const MINIMAP_MIN_ZOOM: f32 = 1.05;
+// Brightness one boid adds to the minimap
+pub const MINIMAP_POINT_ALPHA: f32 = 0.35;
+
+// Size of one font pixel of the HUD text in screen pixels
+pub const HUD_TEXT_SCALE: f32 = 2.0;
+// Weight of the newest frame in the HUD averages
+pub const HUD_SMOOTHING: f32 = 0.05;
+// Lines of output the console keeps on screen
+pub const CONSOLE_LINES: usize = 12;
+
+// Directory with shaders replacing the built in ones of the same name, they are hot reloaded.
+// Without one the shaders compiled into the binary are used.
+pub const SHADER_OVERRIDE_DIR: Option<&str> = None;
+// Seconds between two checks of the flock shader overrides for changes
+pub const SHADER_POLL_INTERVAL: f32 = 0.5;
+
+// Ctrl+S saves the simulation here and Ctrl+L loads it back, relative to the working directory
+pub const SAVE_PATH: &str = "boids.save";
+
+// Checkpoints are saved here every AUTOSAVE_INTERVAL seconds of simulated time, the newest
+// AUTOSAVE_KEEP stay. --resume starts from the newest one.
+pub const AUTOSAVE_DIR: &str = "autosaves";
+pub const AUTOSAVE_INTERVAL: f32 = 60.0;
+pub const AUTOSAVE_KEEP: usize = 3;
+
+// Seconds of real time switching to a parameter preset takes, see Presets
+pub const PRESET_TRANSITION: f32 = 2.0;
 
-pub const CELL_SIZE: f32 = 100.0;
+// Every dynamic library in here is loaded as a steering plugin at startup, see boids_core::plugin
+pub const PLUGIN_DIR: &str = "plugins";
 
-pub const ALIGNMENT_WEIGHT: f32 = 0.95;
-pub const COHESION_WEIGHT: f32 = 0.2;
-pub const SEPARATION_WEIGHT: f32 = 8.0;
+// Seconds of simulated time from one snapshot a --host sends its clients to the next,
+// a snapshot only takes about 9 bytes per boid so they can be frequent.
+// Clients that take longer than NETWORK_TIMEOUT for a snapshot are dropped, and clients
+// refuse snapshots bigger than NETWORK_MAX_SNAPSHOT bytes. The boids of a snapshot are
+// deflated while NETWORK_DEFLATE is set, clients read either.
+pub const NETWORK_SYNC_INTERVAL: f32 = 0.1;
+pub const NETWORK_TIMEOUT: Duration = Duration::from_secs(2);
+pub const NETWORK_MAX_SNAPSHOT: u64 = 1 << 30;
+pub const NETWORK_DEFLATE: bool = true;
+// Frames per second of simulated time a --websocket stream sends its viewers
+pub const WEBSOCKET_RATE: f32 = 30.0;
+
+// A MIDI control at 0 divides its parameter by this and at 127 multiplies it, see LiveControl
+pub const MIDI_RANGE_FACTOR: f32 = 4.0;
+
+// Sonification, see Sonification. The chord is rooted at SONIFICATION_PITCH Hz for a flock flying
+// at AGENT_SPEED and a fully milling flock trembles SONIFICATION_TREMOLO times per second. The
+// sound glides to new metrics at SONIFICATION_GLIDE per second and plays at SONIFICATION_VOLUME.
+pub const SONIFICATION_PITCH: f32 = 220.0;
+pub const SONIFICATION_TREMOLO: f32 = 6.0;
+pub const SONIFICATION_GLIDE: f32 = 4.0;
+pub const SONIFICATION_VOLUME: f32 = 0.2;
+
+// Screenshots are saved here, relative to the working directory
+pub const SCREENSHOT_DIR: &str = "screenshots";
+// Recordings are saved here, encoded by ffmpeg at RECORDING_FPS, and trajectories started with Insert
+pub const RECORDING_DIR: &str = "recordings";
+pub const RECORDING_FPS: u32 = 60;
+// Frames from one sample of every boid to the next in a trajectory
+pub const TRAJECTORY_INTERVAL: u64 = 10;
+// Seconds kept for GIF export, captured at GIF_FPS and shrunk to at most GIF_WIDTH pixels wide
+pub const GIF_DURATION: f32 = 10.0;
+pub const GIF_FPS: u32 = 15;
+pub const GIF_WIDTH: u32 = 320;
+
+// Above this many boids they are drawn as points instead of meshes
+pub const LOD_AGENT_COUNT: usize = 20_000;
+pub const LOD_POINT_SIZE: f32 = 2.0;
+
+// Worker threads of the simulation, 0 uses one per core
+pub const WORKER_THREADS: usize = 0;
+// Keeps the render thread on its own core, helps frame pacing on big-little CPUs
+pub const PIN_RENDER_THREAD: bool = false;
+
+// Image drawn behind the world instead of the plain background color
+pub const BACKGROUND_PATH: Option<&str> = None;
+// Color the background image is multiplied with
+pub const BACKGROUND_TINT: [f32; 3] = [1.0, 1.0, 1.0];
+// How much the background follows the camera, 0 keeps it still and 1 moves it with the world
+pub const BACKGROUND_PARALLAX: f32 = 0.3;
+
+// Boids fade out from their center and blend into each other, S toggles it at runtime
+pub const SOFT_BOIDS: bool = false;
+// Boids are drawn this much longer and thinner per unit of speed, 1 being AGENT_SPEED.
+// The digit 1 toggles it at runtime.
+pub const VELOCITY_STRETCH: f32 = 0.3;
+// Shape of every species at startup, Q cycles it at runtime
+pub const AGENT_SHAPE: AgentShape = AgentShape::Triangle;
+// OBJ file with the custom shape of a boid pointing along +x, the built in triangle is used without one
+pub const AGENT_MESH_PATH: Option<&str> = None;
+// Image drawn instead of the triangle of every boid, pointing along +x
+pub const SPRITE_PATH: Option<&str> = None;
+
+// How fast the camera catches up with its target, higher is snappier
+pub const CAMERA_SMOOTHING: f32 = 4.0;
+// Zoom of the camera while it follows a boid
+pub const CAMERA_FOLLOW_ZOOM: f32 = 3.0;
+
+// Agent count of the GPU simulation, much more than the CPU can handle
+pub const GPU_AGENT_COUNT: usize = 100_000;
+
+// Each press of a tuning key scales its parameter by this, see App::tune
+pub const TUNING_FACTOR: f32 = 1.1;
+// Bigger radii make every boid look at many more neighbors
+pub const PERCEPTION_RADIUS_RANGE: [f32; 2] = [10.0, 200.0];
+
+// Weights of the simulation shown next to the main one, 6 toggles the side by side comparison
+pub const COMPARISON_WEIGHTS: FlockWeights = FlockWeights {
+    alignment: ALIGNMENT_WEIGHT,
+    cohesion: COHESION_WEIGHT * 2.0,
+    separation: SEPARATION_WEIGHT,
+};
 
 fn main() {
-    let event_loop = EventLoop::new();
-    let display = create_display(
-        &event_loop,
-        INITIAL_DISPLAY_SIZE[0],
-        INITIAL_DISPLAY_SIZE[1]
-    );
-
-    let mut app = App::new(display);
-
-    // Approx 60 FPS
-    let frame_time = Duration::from_nanos(16_666_667);
-
-    let mut time = Instant::now();
-
-    event_loop.run(move |event, _, control_flow| {
-        let next_frame_time = Instant::now() + frame_time;
-
-        *control_flow = ControlFlow::WaitUntil(next_frame_time);
-
-        match event {
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => {
-                    *control_flow = ControlFlow::Exit;
-                }
-                WindowEvent::Resized(size) => {
-                    app.on_window_resize(&size);
-                }
-                _ => {},
-            }
-            Event::MainEventsCleared => {
-                let new_time = Instant::now();
-                let delta = new_time.duration_since(time).as_secs_f32();
-
-                time = new_time;
-
-                // Logic
-                let t = Instant::now();
-                app.update(delta);
-                println!("Update time: {} ms", t.elapsed().as_millis());
-    
-                // Graphics
-                let mut target = app.display.draw();
-                target.clear_color(BG[0], BG[1], BG[2], BG[3]);
-                app.render(&mut target);
-                target.finish().unwrap();
-            },
-            _ => (),
-        }
-    });
+    let cli = Cli::parse();
+    let trace_guards = logging::init(&cli);
+
+    let mut config = Config::load(&cli.config);
+    cli.apply(&mut config);
+
+    if cli.headless {
+        headless::run(&config, &cli);
+        return;
+    }
+
+    #[cfg(feature = "wgpu")]
+    if cli.wgpu || cfg!(not(feature = "glium")) {
+        viewer::run(&config, &cli, trace_guards);
+    }
+
+    #[cfg(feature = "glium")]
+    window::run(&config, &cli, trace_guards);
+
+    #[cfg(not(feature = "glium"))]
+    {
+        println!("Built without a window, run with --headless or build with the glium feature");
+        drop(trace_guards);
+    }
 }
+
@@ -0,0 +1,614 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::thread;
+
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+
+use boids_core::components::BoidHandle;
+use boids_core::data::{Behavior, BehaviorState, FlockWeights};
+use boids_core::simulation::Simulation;
+use boids_core::SPECIES_COUNT;
+
+use crate::{NETWORK_DEFLATE, NETWORK_MAX_SNAPSHOT, NETWORK_SYNC_INTERVAL, NETWORK_TIMEOUT};
+
+// Snapshots go over TCP as a little endian u64 byte count followed by the state of the living
+// boids, all numbers little endian as well:
+//
+//     u8 NETWORK_VERSION, u8 flags, bit 0 is set when the boids are deflated
+//     u32 boid count, f32 world width, f32 world height
+//     f32 alignment, cohesion, separation, speed and perception radius
+//     u16 x, y of every boid, 65535 is the width or height of the world
+//     i16 heading x, y of every boid, 32767 is 1
+//     u8 species of every boid
+//     u64 id of every boid, the same in every snapshot while the boid lives
+//
+// Save files keep the format of boids_core::save, snapshots only hold what a client needs
+// to follow the host and both ends have to speak the same NETWORK_VERSION.
+const NETWORK_VERSION: u8 = 2;
+const DEFLATED: u8 = 1;
+// Bytes before the boids and bytes of one boid
+const HEADER_SIZE: usize = 34;
+const BOID_SIZE: usize = 17;
+
+// Sends the boids and flocking weights to every connected client every NETWORK_SYNC_INTERVAL
+// seconds of simulated time. Snapshots are written on a thread of their own, when it falls behind
+// the host skips snapshots instead of waiting. A client that can't keep up within NETWORK_TIMEOUT
+// is dropped.
+pub struct NetworkHost {
+    snapshots: SyncSender<Arc<Vec<u8>>>,
+    // Simulated seconds since the last snapshot
+    since: f32,
+}
+
+// Moves the boids of its simulation to those of every snapshot of a host. It keeps simulating
+// between snapshots, so the flock moves smoothly and jumps a little when a snapshot arrives.
+// Changes to the boids and weights made on the client only last until the next snapshot,
+// the rest of its simulation, like obstacles and scripts, is its own.
+pub struct NetworkClient {
+    address: String,
+    // Read and decoded on a thread of their own, an error ends the connection
+    snapshots: Receiver<Result<NetworkState, String>>,
+    // The boid of the client that follows each boid of the host, by id of the host boid
+    followed: HashMap<u64, BoidHandle>,
+}
+
+// One decoded snapshot of a host
+pub struct NetworkState {
+    pub world_size: [f32; 2],
+    weights: FlockWeights,
+    speed: f32,
+    perception_radius: f32,
+    positions: Vec<[f32; 2]>,
+    headings: Vec<[f32; 2]>,
+    species: Vec<u8>,
+    ids: Vec<u64>,
+}
+
+impl NetworkHost {
+    // `address` is where clients connect, like 0.0.0.0:7878
+    pub fn start(address: &str) -> io::Result<NetworkHost> {
+        let listener = TcpListener::bind(address)?;
+        // Only one snapshot waits, the thread always sends the newest it has
+        let (sender, receiver) = mpsc::sync_channel(1);
+
+        thread::Builder::new()
+            .name("network host".to_string())
+            .spawn(move || broadcast(listener, receiver))?;
+
+        Ok(NetworkHost {
+            snapshots: sender,
+            // The first update sends one, clients that connect later get the newest
+            since: NETWORK_SYNC_INTERVAL,
+        })
+    }
+
+    // `dt` is the simulated time since the last call
+    pub fn advance(&mut self, simulation: &Simulation, dt: f32) {
+        self.since += dt;
+
+        if self.since < NETWORK_SYNC_INTERVAL {
+            return;
+        }
+
+        self.since = 0.0;
+
+        let snapshot = match encode(simulation, NETWORK_DEFLATE) {
+            Ok(snapshot) => snapshot,
+            Err(error) => {
+                println!("Could not send simulation: {}", error);
+                return;
+            }
+        };
+
+        match self.snapshots.try_send(Arc::new(snapshot)) {
+            Ok(()) | Err(TrySendError::Full(_)) => {}
+            Err(TrySendError::Disconnected(_)) => println!("Network host stopped"),
+        }
+    }
+}
+
+impl NetworkClient {
+    pub fn connect(address: &str) -> io::Result<NetworkClient> {
+        let stream = TcpStream::connect(address)?;
+        let (sender, receiver) = mpsc::channel();
+
+        thread::Builder::new()
+            .name("network client".to_string())
+            .spawn(move || {
+                if let Err(error) = receive(stream, &sender) {
+                    sender.send(Err(error.to_string())).ok();
+                }
+            })?;
+
+        Ok(NetworkClient {
+            address: address.to_string(),
+            snapshots: receiver,
+            followed: HashMap::new(),
+        })
+    }
+
+    // The newest snapshot received since the last call. Fails once the connection is gone.
+    pub fn latest(&mut self) -> Result<Option<NetworkState>, String> {
+        let mut latest = None;
+
+        loop {
+            match self.snapshots.try_recv() {
+                Ok(Ok(state)) => latest = Some(state),
+                Ok(Err(error)) => return Err(format!("{}: {}", self.address, error)),
+                Err(TryRecvError::Empty) => return Ok(latest),
+                Err(TryRecvError::Disconnected) => return Err(format!("{}: connection closed", self.address)),
+            }
+        }
+    }
+
+    pub fn apply(&mut self, state: &NetworkState, simulation: &mut Simulation) {
+        state.apply(simulation, &mut self.followed);
+    }
+}
+
+impl NetworkState {
+    fn decode(mut snapshot: &[u8]) -> io::Result<NetworkState> {
+        let [version, flags] = read_bytes(&mut snapshot)?;
+
+        if version != NETWORK_VERSION {
+            return Err(invalid(format!("snapshot of version {}, this is version {}", version, NETWORK_VERSION)));
+        }
+
+        let count = u32::from_le_bytes(read_bytes(&mut snapshot)?) as usize;
+
+        let mut header = [0.0; 7];
+
+        for value in &mut header {
+            *value = f32::from_le_bytes(read_bytes(&mut snapshot)?);
+        }
+
+        let [width, height, alignment, cohesion, separation, speed, perception_radius] = header;
+
+        if !(width > 0.0 && height > 0.0) {
+            return Err(invalid(format!("world of {} by {}", width, height)));
+        }
+
+        // Deflated boids could unpack to any size
+        if count as u64 * BOID_SIZE as u64 > NETWORK_MAX_SNAPSHOT {
+            return Err(invalid(format!("snapshot of {} boids is too big", count)));
+        }
+
+        let mut boids = vec![0; count * BOID_SIZE];
+
+        if flags & DEFLATED != 0 {
+            DeflateDecoder::new(snapshot).read_exact(&mut boids)?;
+        }
+        else {
+            snapshot.read_exact(&mut boids)?;
+        }
+
+        let (positions, rest) = boids.split_at(count * 4);
+        let (headings, rest) = rest.split_at(count * 4);
+        let (species, ids) = rest.split_at(count);
+
+        if let Some(id) = species.iter().find(|id| **id as usize >= SPECIES_COUNT) {
+            return Err(invalid(format!("unknown species {}", id)));
+        }
+
+        Ok(NetworkState {
+            world_size: [width, height],
+            weights: FlockWeights { alignment, cohesion, separation },
+            speed,
+            perception_radius,
+            positions: positions.chunks_exact(4)
+                .map(|bytes| {
+                    let x = u16::from_le_bytes([bytes[0], bytes[1]]);
+                    let y = u16::from_le_bytes([bytes[2], bytes[3]]);
+
+                    [x as f32 / u16::MAX as f32 * width, y as f32 / u16::MAX as f32 * height]
+                })
+                .collect(),
+            headings: headings.chunks_exact(4)
+                .map(|bytes| {
+                    let x = i16::from_le_bytes([bytes[0], bytes[1]]);
+                    let y = i16::from_le_bytes([bytes[2], bytes[3]]);
+
+                    [x as f32 / i16::MAX as f32, y as f32 / i16::MAX as f32]
+                })
+                .collect(),
+            species: species.to_vec(),
+            ids: ids.chunks_exact(8)
+                .map(|bytes| {
+                    let mut id = [0; 8];
+                    id.copy_from_slice(bytes);
+
+                    u64::from_le_bytes(id)
+                })
+                .collect(),
+        })
+    }
+
+    // Takes over the boids and flocking weights of the host. Each boid of the host is followed
+    // by the same boid of the client in every snapshot, so it keeps its handle, scale, flap and
+    // trail. Boids are spawned for new boids of the host and despawned once theirs is gone.
+    fn apply(&self, simulation: &mut Simulation, followed: &mut HashMap<u64, BoidHandle>) {
+        let [width, height] = self.world_size;
+
+        if simulation.size() != self.world_size {
+            simulation.world_size = self.world_size;
+            simulation.grid.resize(width, height);
+        }
+
+        simulation.weights = self.weights;
+        simulation.speed = self.speed;
+
+        if simulation.perception_radius != self.perception_radius {
+            simulation.set_perception_radius(self.perception_radius);
+        }
+
+        // Boids spawned on the client and those whose boid of the host is gone
+        let kept: HashSet<BoidHandle> = self.ids.iter()
+            .filter_map(|id| followed.get(id))
+            .filter(|handle| simulation.components.index(**handle).is_some())
+            .copied()
+            .collect();
+        let gone: Vec<BoidHandle> = simulation.components.handles.iter()
+            .filter(|handle| !kept.contains(handle))
+            .copied()
+            .collect();
+
+        simulation.despawn_group(&gone);
+        followed.retain(|_, handle| kept.contains(handle));
+
+        let new: Vec<u64> = self.ids.iter().filter(|id| !followed.contains_key(id)).copied().collect();
+        let first = simulation.components.len();
+        let species = simulation.spawn_species.clone();
+
+        simulation.spawn_in(new.len(), [0.0, 0.0, width, height], &species);
+        followed.extend(new.into_iter().zip(simulation.components.handles[first..].iter().copied()));
+
+        let components = &mut simulation.components;
+
+        for (i, id) in self.ids.iter().enumerate() {
+            let index = match followed.get(id).and_then(|handle| components.index(*handle)) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            components.positions[index].value = self.positions[i];
+            components.directions[index].direction = self.headings[i];
+            components.species[index].id = self.species[i] as usize;
+
+            // Only living boids are sent, one the client saw die lives on at the host
+            if components.behaviors[index].state == BehaviorState::Dead {
+                components.behaviors[index] = Behavior::default();
+            }
+        }
+
+        simulation.neighbor_list.invalidate();
+    }
+}
+
+// Packs the living boids of `simulation` into a snapshot
+fn encode(simulation: &Simulation, deflate: bool) -> io::Result<Vec<u8>> {
+    let components = &simulation.components;
+    let living: Vec<usize> = (0..components.len())
+        .filter(|i| components.behaviors[*i].state != BehaviorState::Dead)
+        .collect();
+
+    let [width, height] = simulation.size();
+    let weights = simulation.weights;
+
+    // Each column on its own deflates better than boid after boid
+    let mut boids = Vec::with_capacity(living.len() * BOID_SIZE);
+
+    for i in &living {
+        let [x, y] = components.positions[*i].value;
+
+        boids.extend_from_slice(&quantize_position(x / width).to_le_bytes());
+        boids.extend_from_slice(&quantize_position(y / height).to_le_bytes());
+    }
+
+    for i in &living {
+        let [dx, dy] = components.directions[*i].direction;
+
+        boids.extend_from_slice(&quantize_heading(dx).to_le_bytes());
+        boids.extend_from_slice(&quantize_heading(dy).to_le_bytes());
+    }
+
+    boids.extend(living.iter().map(|i| components.species[*i].id as u8));
+
+    for i in &living {
+        boids.extend_from_slice(&components.handles[*i].id().to_le_bytes());
+    }
+
+    let mut snapshot = Vec::with_capacity(HEADER_SIZE + boids.len());
+
+    snapshot.push(NETWORK_VERSION);
+    snapshot.push(if deflate { DEFLATED } else { 0 });
+    snapshot.extend_from_slice(&(living.len() as u32).to_le_bytes());
+
+    let header = [width, height, weights.alignment, weights.cohesion, weights.separation, simulation.speed, simulation.perception_radius];
+
+    for value in header {
+        snapshot.extend_from_slice(&value.to_le_bytes());
+    }
+
+    if !deflate {
+        snapshot.extend_from_slice(&boids);
+        return Ok(snapshot);
+    }
+
+    let mut encoder = DeflateEncoder::new(snapshot, Compression::fast());
+    encoder.write_all(&boids)?;
+    encoder.finish()
+}
+
+// `value` goes from 0 to 1 across the world
+fn quantize_position(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
+fn quantize_heading(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn broadcast(listener: TcpListener, snapshots: Receiver<Arc<Vec<u8>>>) {
+    let mut clients: Vec<(String, BufWriter<TcpStream>)> = Vec::new();
+    let mut latest: Option<Arc<Vec<u8>>> = None;
+
+    if let Err(error) = listener.set_nonblocking(true) {
+        println!("Network host stopped: {}", error);
+        return;
+    }
+
+    loop {
+        // Checks for new clients at least this often while no snapshot comes
+        let snapshot = match snapshots.recv_timeout(NETWORK_TIMEOUT) {
+            Ok(snapshot) => Some(snapshot),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        };
+
+        loop {
+            match listener.accept() {
+                Ok((stream, address)) => {
+                    let mut client = match prepare(stream) {
+                        Ok(client) => (address.to_string(), client),
+                        Err(error) => {
+                            println!("Could not accept client {}: {}", address, error);
+                            continue;
+                        }
+                    };
+
+                    println!("Client {} connected", client.0);
+
+                    // Starts out with the newest snapshot instead of waiting for the next one
+                    if let Some(latest) = &latest {
+                        if let Err(error) = send(&mut client.1, latest) {
+                            println!("Client {} disconnected: {}", client.0, error);
+                            continue;
+                        }
+                    }
+
+                    clients.push(client);
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    println!("Could not accept client: {}", error);
+                    break;
+                }
+            }
+        }
+
+        if let Some(snapshot) = snapshot {
+            clients.retain_mut(|(address, client)| match send(client, &snapshot) {
+                Ok(()) => true,
+                Err(error) => {
+                    println!("Client {} disconnected: {}", address, error);
+                    false
+                }
+            });
+
+            latest = Some(snapshot);
+        }
+    }
+}
+
+// Accepted streams can take the non-blocking mode of the listener, writes to clients block
+fn prepare(stream: TcpStream) -> io::Result<BufWriter<TcpStream>> {
+    stream.set_nonblocking(false)?;
+    stream.set_nodelay(true)?;
+    stream.set_write_timeout(Some(NETWORK_TIMEOUT))?;
+
+    Ok(BufWriter::new(stream))
+}
+
+fn send(client: &mut BufWriter<TcpStream>, snapshot: &[u8]) -> io::Result<()> {
+    client.write_all(&(snapshot.len() as u64).to_le_bytes())?;
+    client.write_all(snapshot)?;
+    client.flush()
+}
+
+// Returns once the app is gone or with the error that ended the connection
+fn receive(stream: TcpStream, snapshots: &mpsc::Sender<Result<NetworkState, String>>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut buffer = Vec::new();
+
+    loop {
+        let mut length = [0; 8];
+        reader.read_exact(&mut length)?;
+
+        let length = u64::from_le_bytes(length);
+
+        if length > NETWORK_MAX_SNAPSHOT {
+            return Err(invalid(format!("snapshot of {} bytes is too big", length)));
+        }
+
+        buffer.resize(length as usize, 0);
+        reader.read_exact(&mut buffer)?;
+
+        let state = NetworkState::decode(&buffer)?;
+
+        // The app is gone
+        if snapshots.send(Ok(state)).is_err() {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use boids_core::profiler::Profiler;
+
+    use super::*;
+
+    const WORLD_SIZE: [f32; 2] = [800.0, 600.0];
+
+    fn decode_error(snapshot: &[u8]) -> String {
+        match NetworkState::decode(snapshot) {
+            Ok(_) => panic!("snapshot was accepted"),
+            Err(error) => {
+                assert_eq!(error.kind(), ErrorKind::InvalidData);
+                error.to_string()
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip_keeps_the_boids() {
+        let mut simulation = Simulation::with_seed(200, WORLD_SIZE, 1);
+        simulation.weights.cohesion = 0.25;
+        simulation.speed = 3.5;
+
+        for deflate in [false, true] {
+            let state = NetworkState::decode(&encode(&simulation, deflate).unwrap()).unwrap();
+            let components = &simulation.components;
+
+            assert_eq!(state.world_size, WORLD_SIZE);
+            assert_eq!(state.weights.cohesion, 0.25);
+            assert_eq!(state.speed, 3.5);
+            assert_eq!(state.perception_radius, simulation.perception_radius);
+            assert_eq!(state.positions.len(), components.len());
+
+            for i in 0..components.len() {
+                let [x, y] = components.positions[i].value;
+                let [dx, dy] = components.directions[i].direction;
+
+                assert!((state.positions[i][0] - x).abs() <= WORLD_SIZE[0] / u16::MAX as f32);
+                assert!((state.positions[i][1] - y).abs() <= WORLD_SIZE[1] / u16::MAX as f32);
+                assert!((state.headings[i][0] - dx).abs() <= 1.0 / i16::MAX as f32);
+                assert!((state.headings[i][1] - dy).abs() <= 1.0 / i16::MAX as f32);
+                assert_eq!(state.species[i] as usize, components.species[i].id);
+                assert_eq!(state.ids[i], components.handles[i].id());
+            }
+        }
+    }
+
+    #[test]
+    fn world_edges_stay_inside_the_world() {
+        let mut simulation = Simulation::with_seed(4, WORLD_SIZE, 2);
+        let [width, height] = WORLD_SIZE;
+        let corners = [[0.0, 0.0], [width, height], [width - 0.01, 0.01], [-1.0, height + 1.0]];
+
+        for (i, corner) in corners.iter().enumerate() {
+            simulation.components.positions[i].value = *corner;
+        }
+
+        let state = NetworkState::decode(&encode(&simulation, false).unwrap()).unwrap();
+
+        assert_eq!(state.positions[0], [0.0, 0.0]);
+        assert_eq!(state.positions[1], [width, height]);
+        assert!((state.positions[2][0] - (width - 0.01)).abs() <= width / u16::MAX as f32 / 2.0);
+        assert!((state.positions[2][1] - 0.01).abs() <= height / u16::MAX as f32 / 2.0);
+        // Boids outside the world are clamped to its edges
+        assert_eq!(state.positions[3], [0.0, height]);
+    }
+
+    #[test]
+    fn rejects_a_bad_version() {
+        let mut snapshot = encode(&Simulation::with_seed(10, WORLD_SIZE, 3), true).unwrap();
+        snapshot[0] = NETWORK_VERSION + 1;
+
+        assert!(decode_error(&snapshot).contains("version"));
+    }
+
+    #[test]
+    fn rejects_an_unknown_species() {
+        let mut snapshot = encode(&Simulation::with_seed(10, WORLD_SIZE, 4), false).unwrap();
+        // Species of the last boid, after the positions and headings of all 10
+        snapshot[HEADER_SIZE + 10 * 8 + 9] = SPECIES_COUNT as u8;
+
+        assert!(decode_error(&snapshot).contains("species"));
+    }
+
+    #[test]
+    fn rejects_an_oversized_count() {
+        let mut snapshot = encode(&Simulation::with_seed(10, WORLD_SIZE, 5), true).unwrap();
+        snapshot[2..6].copy_from_slice(&u32::MAX.to_le_bytes());
+
+        assert!(decode_error(&snapshot).contains("too big"));
+    }
+
+    #[test]
+    fn rejects_a_truncated_snapshot() {
+        let snapshot = encode(&Simulation::with_seed(10, WORLD_SIZE, 6), false).unwrap();
+
+        assert!(NetworkState::decode(&snapshot[..snapshot.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn client_boids_follow_the_same_host_boid() {
+        let mut host = Simulation::with_seed(100, WORLD_SIZE, 7);
+        let mut client = Simulation::with_seed(30, WORLD_SIZE, 8);
+        let mut followed = HashMap::new();
+        let mut profiler = Profiler::new();
+
+        NetworkState::decode(&encode(&host, true).unwrap()).unwrap().apply(&mut client, &mut followed);
+
+        assert_eq!(client.components.len(), 100);
+
+        let handles = followed.clone();
+        let scales: HashMap<u64, f32> = handles.iter()
+            .map(|(id, handle)| (*id, client.components.scales[client.components.index(*handle).unwrap()].factor))
+            .collect();
+
+        // The host sorts its boids by cell and loses one, so indices no longer line up
+        for _ in 0..10 {
+            host.update(1.0 / 60.0, &mut profiler);
+        }
+
+        let lost = host.components.handles[0];
+        host.despawn_group(&[lost]);
+
+        NetworkState::decode(&encode(&host, true).unwrap()).unwrap().apply(&mut client, &mut followed);
+
+        let living: Vec<usize> = (0..host.components.len())
+            .filter(|i| host.components.behaviors[*i].state != BehaviorState::Dead)
+            .collect();
+
+        assert_eq!(client.components.len(), living.len());
+        assert!(!followed.contains_key(&lost.id()));
+
+        for i in living {
+            let handle = host.components.handles[i];
+            let follower = followed[&handle.id()];
+            let index = client.components.index(follower).unwrap();
+
+            assert_eq!(follower, handles[&handle.id()]);
+            assert_eq!(client.components.scales[index].factor, scales[&handle.id()]);
+            assert!((client.components.positions[index].value[0] - host.components.positions[i].value[0]).abs() < 0.1);
+        }
+    }
+}
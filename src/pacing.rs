@@ -0,0 +1,141 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{FIXED_TIMESTEP, MAX_STEPS_PER_FRAME, TARGET_FPS, TIME_SCALE_RANGE};
+
+// Sleeps are woken up this early and the rest is spun, thread sleeps often overshoot
+const SPIN_TIME: Duration = Duration::from_micros(1500);
+
+// How often frames are started, on top of vsync if that is on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FrameLimit {
+    Uncapped,
+    // Frames per second
+    Capped(f32),
+}
+
+// Holds frames back to the frame limit
+pub struct FramePacer {
+    pub limit: FrameLimit,
+    // Cap used when switching back from uncapped, the governor target if there was none
+    cap: f32,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new(cap: Option<f32>) -> FramePacer {
+        let limit = match cap {
+            Some(fps) => FrameLimit::Capped(fps),
+            None => FrameLimit::Uncapped,
+        };
+
+        FramePacer {
+            limit,
+            cap: cap.unwrap_or(TARGET_FPS),
+            next_frame: Instant::now(),
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.limit = match self.limit {
+            FrameLimit::Uncapped => FrameLimit::Capped(self.cap),
+            FrameLimit::Capped(_) => FrameLimit::Uncapped,
+        };
+
+        self.next_frame = Instant::now();
+    }
+
+    // Blocks until the next frame may start
+    pub fn wait(&mut self) {
+        let fps = match self.limit {
+            FrameLimit::Uncapped => return,
+            FrameLimit::Capped(fps) => fps,
+        };
+
+        let now = Instant::now();
+
+        if self.next_frame > now + SPIN_TIME {
+            thread::sleep(self.next_frame - now - SPIN_TIME);
+        }
+
+        while Instant::now() < self.next_frame {
+            std::hint::spin_loop();
+        }
+
+        let period = Duration::from_secs_f32(1.0 / fps);
+        self.next_frame += period;
+
+        // After a long frame start over instead of rushing to catch up
+        let now = Instant::now();
+
+        if self.next_frame < now {
+            self.next_frame = now + period;
+        }
+    }
+}
+
+// Turns frame times into steps of FIXED_TIMESTEP, so the simulation runs the same at any frame rate.
+// While paused no time is collected and only requested single steps are taken.
+pub struct SimulationClock {
+    pub paused: bool,
+    // Simulated seconds per real second, within TIME_SCALE_RANGE
+    pub time_scale: f32,
+    // Time not simulated yet, always less than one step
+    accumulator: f32,
+    // Steps requested while paused
+    pending_steps: u32,
+}
+
+impl Default for SimulationClock {
+    fn default() -> Self {
+        SimulationClock {
+            paused: false,
+            time_scale: 1.0,
+            accumulator: 0.0,
+            pending_steps: 0,
+        }
+    }
+}
+
+impl SimulationClock {
+    pub fn scale_time(&mut self, factor: f32) {
+        let [min, max] = TIME_SCALE_RANGE;
+        self.time_scale = (self.time_scale * factor).clamp(min, max);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.accumulator = 0.0;
+        self.pending_steps = 0;
+    }
+
+    // Only has an effect while paused
+    pub fn request_step(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    // Number of steps to simulate for a frame that took `dt` seconds.
+    // Time beyond MAX_STEPS_PER_FRAME is dropped, so a slow simulation can't fall further and further behind.
+    // Fast forward raises the limit along with the time scale.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        if self.paused {
+            return std::mem::take(&mut self.pending_steps);
+        }
+
+        self.accumulator += dt * self.time_scale;
+
+        let steps = (self.accumulator / FIXED_TIMESTEP) as u32;
+        let max_steps = (MAX_STEPS_PER_FRAME as f32 * self.time_scale.max(1.0)).ceil() as u32;
+
+        if steps > max_steps {
+            self.accumulator = 0.0;
+            return max_steps;
+        }
+
+        self.accumulator -= steps as f32 * FIXED_TIMESTEP;
+
+        steps
+    }
+}
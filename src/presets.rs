@@ -0,0 +1,222 @@
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use boids_core::events::{Event, Parameter};
+use boids_core::simulation::Simulation;
+
+use crate::PRESET_TRANSITION;
+
+// Weights, speed and perception radius of the flock under a name, the config file can add more
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ParameterPreset {
+    pub name: String,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub separation: f32,
+    pub speed: f32,
+    pub perception_radius: f32,
+}
+
+// How a saved preset is appended to the config file
+#[derive(Serialize)]
+struct PresetTable {
+    presets: Vec<ParameterPreset>,
+}
+
+impl ParameterPreset {
+    fn new(name: &str, alignment: f32, cohesion: f32, separation: f32, speed: f32, perception_radius: f32) -> ParameterPreset {
+        ParameterPreset {
+            name: name.to_string(),
+            alignment,
+            cohesion,
+            separation,
+            speed,
+            perception_radius,
+        }
+    }
+
+    // The parameters the simulation runs with now
+    pub fn capture(name: &str, simulation: &Simulation) -> ParameterPreset {
+        let weights = simulation.weights;
+
+        ParameterPreset::new(
+            name,
+            weights.alignment,
+            weights.cohesion,
+            weights.separation,
+            simulation.speed,
+            simulation.perception_radius
+        )
+    }
+
+    // Part `t` of the way from `self` to `other`
+    fn lerp(&self, other: &ParameterPreset, t: f32) -> ParameterPreset {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+
+        ParameterPreset::new(
+            &other.name,
+            mix(self.alignment, other.alignment),
+            mix(self.cohesion, other.cohesion),
+            mix(self.separation, other.separation),
+            mix(self.speed, other.speed),
+            mix(self.perception_radius, other.perception_radius)
+        )
+    }
+
+    fn apply(&self, simulation: &mut Simulation) {
+        simulation.weights.alignment = self.alignment;
+        simulation.weights.cohesion = self.cohesion;
+        simulation.weights.separation = self.separation;
+        simulation.speed = self.speed;
+
+        // Changing the radius throws away the neighbor lists, only done when it moves
+        if simulation.perception_radius != self.perception_radius {
+            simulation.set_perception_radius(self.perception_radius);
+        }
+    }
+}
+
+// The presets every run starts with, the config file can replace them by name
+pub fn built_in_presets() -> Vec<ParameterPreset> {
+    vec![
+        // Fast and closely aligned, like a school of fish
+        ParameterPreset::new("tight-school", 2.5, 0.6, 6.0, 60.0, 60.0),
+        // Spread out and lazily aligned
+        ParameterPreset::new("loose-flock", 0.5, 0.1, 12.0, 45.0, 80.0),
+        // Hardly aligned, every boid buzzes around the others like an insect swarm
+        ParameterPreset::new("swarm", 0.05, 1.0, 4.0, 70.0, 40.0),
+        // Strong cohesion over a short range turns the flock into a rotating mill
+        ParameterPreset::new("mill", 1.2, 1.5, 5.0, 55.0, 30.0),
+    ]
+}
+
+struct Transition {
+    from: ParameterPreset,
+    to: ParameterPreset,
+    // Real seconds since it started
+    elapsed: f32,
+}
+
+// Named sets of parameters cycled with a key. Switching glides from the current parameters to the
+// preset over PRESET_TRANSITION seconds of real time, changes made on the way are overridden.
+// Saved presets are appended to the config file, so they are there again on the next start.
+pub struct Presets {
+    presets: Vec<ParameterPreset>,
+    // Preset switched to last
+    current: Option<usize>,
+    transition: Option<Transition>,
+    config_path: String,
+}
+
+impl Presets {
+    // `presets` are added to the built in ones, replacing those with the same name
+    pub fn new(presets: &[ParameterPreset], config_path: &str) -> Presets {
+        let mut all = built_in_presets();
+
+        for preset in presets {
+            add(&mut all, preset.clone());
+        }
+
+        Presets {
+            presets: all,
+            current: None,
+            transition: None,
+            config_path: config_path.to_string(),
+        }
+    }
+
+    pub fn current(&self) -> Option<&str> {
+        self.current.map(|index| self.presets[index].name.as_str())
+    }
+
+    // The preset after the current one, or the first
+    pub fn next(&mut self, simulation: &mut Simulation) -> String {
+        let index = self.current.map_or(0, |index| (index + 1) % self.presets.len());
+        self.start(index, simulation)
+    }
+
+    pub fn select(&mut self, name: &str, simulation: &mut Simulation) -> Result<String, String> {
+        match self.presets.iter().position(|preset| preset.name == name) {
+            Some(index) => Ok(self.start(index, simulation)),
+            None => {
+                let names: Vec<&str> = self.presets.iter().map(|preset| preset.name.as_str()).collect();
+                Err(format!("Unknown preset {}, the presets are {}", name, names.join(", ")))
+            }
+        }
+    }
+
+    fn start(&mut self, index: usize, simulation: &mut Simulation) -> String {
+        for parameter in [Parameter::Alignment, Parameter::Cohesion, Parameter::Separation, Parameter::Speed] {
+            simulation.events.send(Event::ParameterChanged(parameter));
+        }
+
+        self.current = Some(index);
+        self.transition = Some(Transition {
+            from: ParameterPreset::capture("", simulation),
+            to: self.presets[index].clone(),
+            elapsed: 0.0,
+        });
+
+        format!("Parameter preset: {}", self.presets[index].name)
+    }
+
+    // The current parameters become a preset, replacing one with the same name
+    pub fn save(&mut self, name: &str, simulation: &Simulation) -> String {
+        let preset = ParameterPreset::capture(name, simulation);
+
+        self.transition = None;
+        self.current = Some(add(&mut self.presets, preset.clone()));
+
+        match self.append_to_config(preset) {
+            Ok(()) => format!("Saved parameter preset {} to {}", name, self.config_path),
+            Err(error) => format!("Could not save parameter preset {} to {}: {}", name, self.config_path, error),
+        }
+    }
+
+    // Later presets with the same name win when the config is loaded, so the file only grows
+    fn append_to_config(&self, preset: ParameterPreset) -> Result<(), Box<dyn Error>> {
+        let table = toml::to_string(&PresetTable { presets: vec![preset] })?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.config_path)?;
+
+        write!(file, "\n{}", table)?;
+
+        Ok(())
+    }
+
+    // `dt` is the real time since the last call
+    pub fn update(&mut self, simulation: &mut Simulation, dt: f32) {
+        let transition = match &mut self.transition {
+            Some(transition) => transition,
+            None => return,
+        };
+
+        transition.elapsed += dt;
+
+        let t = (transition.elapsed / PRESET_TRANSITION).min(1.0);
+        // Eases in and out
+        let eased = t * t * (3.0 - 2.0 * t);
+
+        transition.from.lerp(&transition.to, eased).apply(simulation);
+
+        if t >= 1.0 {
+            self.transition = None;
+        }
+    }
+}
+
+// Returns the index of the preset
+fn add(presets: &mut Vec<ParameterPreset>, preset: ParameterPreset) -> usize {
+    match presets.iter().position(|other| other.name == preset.name) {
+        Some(index) => {
+            presets[index] = preset;
+            index
+        }
+        None => {
+            presets.push(preset);
+            presets.len() - 1
+        }
+    }
+}
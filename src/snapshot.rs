@@ -0,0 +1,211 @@
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelExtend, ParallelIterator};
+use vecmath::vec2_scale;
+
+use crate::coloring::{boid_color, ColorMode};
+use crate::graphics::shapes::AgentShape;
+use crate::theme::Theme;
+use boids_core::behavior::state_weights;
+use boids_core::components::BoidHandle;
+use boids_core::diagnostics::vec_bytes;
+use boids_core::data::*;
+use crate::graphics::vertices::*;
+use boids_core::grid::Grid;
+use boids_core::simulation::Simulation;
+use boids_core::systems::gust_fade;
+use crate::{FLOW_ARROW_SCALE, FLOW_FIELD_ALPHA, FLOW_FIELD_COLOR, FLOW_FIELD_MIN_BOIDS, GEOMETRY_ALPHA, GROUP_COLOR, HIGHLIGHT_COLOR, NEIGHBOR_HIGHLIGHT_COLOR, TRAIL_ALPHA};
+
+// Packed instance data of the components, taken at the end of an update
+// so the next update can already run while this one is drawn.
+#[derive(Default)]
+pub struct RenderSnapshot {
+    pub instances: Vec<BoidInstance>,
+    // Two vertices per trail segment
+    pub trail_vertices: Vec<LineVertex>,
+    // Perception radius of the highlighted boid
+    pub highlight_vertices: Vec<LineVertex>,
+    // Outlines of gusts, obstacles and walls
+    pub geometry_vertices: Vec<LineVertex>,
+    // Arrows of the flow field, empty while it is disabled
+    pub flow_vertices: Vec<LineVertex>,
+    neighbors: Vec<usize>,
+}
+
+impl RenderSnapshot {
+    pub fn allocated_bytes(&self) -> usize {
+        vec_bytes(&self.instances)
+            + vec_bytes(&self.trail_vertices)
+            + vec_bytes(&self.highlight_vertices)
+            + vec_bytes(&self.geometry_vertices)
+            + vec_bytes(&self.flow_vertices)
+            + vec_bytes(&self.neighbors)
+    }
+
+    // `species_shapes` holds the shape of every species
+    pub fn copy_from(
+        &mut self,
+        simulation: &Simulation,
+        color_mode: ColorMode,
+        theme: &Theme,
+        species_shapes: &[AgentShape]
+    ) {
+        let components = &simulation.components;
+
+        self.instances.clear();
+        self.instances.par_extend(
+            components.positions.par_iter()
+                .zip(&components.directions)
+                .zip(&components.scales)
+                .zip(&components.flaps)
+                .zip(&components.behaviors)
+                .zip(&components.crowding)
+                .zip(&components.species)
+                .map(|((((((position, forward), scale), flap), behavior), crowding), species)| {
+                    let color = simulation.day_cycle.shade(
+                        boid_color(color_mode, theme, forward, behavior, crowding, species)
+                    );
+                    let shape = species_shapes[species.id] as u8;
+                    let speed = state_weights(behavior.state).speed;
+
+                    BoidInstance::pack(position, forward, scale, flap, speed, color, shape)
+                })
+        );
+
+        self.trail_vertices.clear();
+        self.trail_vertices.par_extend(
+            components.trails.par_iter()
+                .zip(&components.species)
+                .filter(|(trail, _)| trail.len > 1)
+                .flat_map_iter(|(trail, species)| {
+                    let color = theme.trail.unwrap_or(theme.species[species.id]);
+                    trail_segments(trail, simulation.day_cycle.shade(color))
+                })
+        );
+
+        self.highlight_vertices.clear();
+
+        let geometry_color = simulation.day_cycle.shade(theme.geometry);
+        let [r, g, b] = geometry_color;
+        let solid_color = [r, g, b, GEOMETRY_ALPHA];
+
+        self.geometry_vertices.clear();
+        self.geometry_vertices.extend(
+            simulation.gusts.iter().flat_map(|gust| gust_outline(gust, geometry_color))
+        );
+        self.geometry_vertices.extend(
+            simulation.obstacles.iter().flat_map(|obstacle| circle_segments(obstacle.center, obstacle.radius, solid_color))
+        );
+        self.geometry_vertices.extend(
+            simulation.walls.iter().flat_map(|wall| [wall.start, wall.end].map(|position| LineVertex { position, color: solid_color }))
+        );
+
+        self.flow_vertices.clear();
+
+        if simulation.flow_field.enabled {
+            self.flow_vertices.extend(flow_arrows(&simulation.flow_field, &simulation.grid, simulation.speed));
+        }
+    }
+
+    // Tints the boids of a selected group. Called after `copy_from` with the same simulation.
+    pub fn highlight_group(&mut self, simulation: &Simulation, group: &[BoidHandle]) {
+        for handle in group {
+            if let Some(index) = simulation.components.index(*handle) {
+                self.instances[index].recolor(GROUP_COLOR);
+            }
+        }
+    }
+
+    // Tints a boid and the boids it currently counts as neighbors and outlines
+    // its perception radius. Called after `copy_from` with the same simulation.
+    pub fn highlight_neighborhood(&mut self, simulation: &Simulation, handle: BoidHandle) {
+        let components = &simulation.components;
+
+        let index = match components.index(handle) {
+            Some(index) if components.behaviors[index].state != BehaviorState::Dead => index,
+            _ => return,
+        };
+
+        simulation.neighbors_of(index, &mut self.neighbors);
+
+        for &neighbor in &self.neighbors {
+            self.instances[neighbor].recolor(NEIGHBOR_HIGHLIGHT_COLOR);
+        }
+
+        self.instances[index].recolor(HIGHLIGHT_COLOR);
+
+        let position = components.positions[index].value;
+        let [r, g, b] = HIGHLIGHT_COLOR;
+        self.highlight_vertices.extend(circle_segments(position, simulation.perception_radius, [r, g, b, 0.6]));
+    }
+}
+
+// Outline of a circle as line segments
+fn circle_segments(center: [f32; 2], radius: f32, color: [f32; 4]) -> impl Iterator<Item = LineVertex> {
+    const SEGMENTS: usize = 64;
+
+    let vertex = move |i: usize| {
+        let angle = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+
+        LineVertex {
+            position: [center[0] + angle.cos() * radius, center[1] + angle.sin() * radius],
+            color,
+        }
+    };
+
+    (1..=SEGMENTS).flat_map(move |i| IntoIterator::into_iter([vertex(i - 1), vertex(i)]))
+}
+
+// Edge of a gust and an arrow from its center along its direction, fading in and out with the gust
+fn gust_outline(gust: &Gust, color: [f32; 3]) -> impl Iterator<Item = LineVertex> {
+    let color = [color[0], color[1], color[2], GEOMETRY_ALPHA * gust_fade(gust)];
+
+    let [x, y] = gust.center;
+    let [dx, dy] = vec2_scale(gust.direction, gust.radius * 0.5);
+    let tip = [x + dx, y + dy];
+
+    // Barbs of the arrow head point back and to either side
+    let barb = |side: f32| [tip[0] - (dx - dy * side) * 0.3, tip[1] - (dy + dx * side) * 0.3];
+
+    let arrow = [gust.center, tip, tip, barb(1.0), tip, barb(-1.0)]
+        .map(|position| LineVertex { position, color });
+
+    circle_segments(gust.center, gust.radius, color).chain(arrow)
+}
+
+// Arrow from the center of every grid cell with at least FLOW_FIELD_MIN_BOIDS boids along
+// their average velocity, FLOW_ARROW_SCALE cells long when it is `speed`
+fn flow_arrows<'a>(flow: &'a FlowField, grid: &'a Grid, speed: f32) -> impl Iterator<Item = LineVertex> + 'a {
+    let size = grid.cell_size;
+    let color = [FLOW_FIELD_COLOR[0], FLOW_FIELD_COLOR[1], FLOW_FIELD_COLOR[2], FLOW_FIELD_ALPHA];
+
+    (0..grid.rows)
+        .flat_map(move |y| (0..grid.columns).map(move |x| (x, y)))
+        .filter_map(move |(x, y)| {
+            let cell = grid.cell_at(x, y);
+
+            // The field is averaged during updates, the grid can have grown since
+            if flow.counts.get(cell).copied().unwrap_or(0) < FLOW_FIELD_MIN_BOIDS {
+                return None;
+            }
+
+            let center = [(x as f32 + 0.5) * size, (y as f32 + 0.5) * size];
+            let [dx, dy] = vec2_scale(flow.velocities[cell], size * FLOW_ARROW_SCALE / speed);
+            let tip = [center[0] + dx, center[1] + dy];
+
+            let barb = |side: f32| [tip[0] - (dx - dy * side) * 0.3, tip[1] - (dy + dx * side) * 0.3];
+
+            Some([center, tip, tip, barb(1.0), tip, barb(-1.0)].map(|position| LineVertex { position, color }))
+        })
+        .flat_map(IntoIterator::into_iter)
+}
+
+// Line segments between consecutive trail points, fading out towards the oldest one
+fn trail_segments(trail: &Trail, tint: [f32; 3]) -> impl Iterator<Item = LineVertex> + '_ {
+    let last = (trail.len - 1) as f32;
+
+    let vertex = move |i: usize| LineVertex {
+        position: trail.point(i),
+        color: [tint[0], tint[1], tint[2], TRAIL_ALPHA * i as f32 / last],
+    };
+
+    (1..trail.len).flat_map(move |i| IntoIterator::into_iter([vertex(i - 1), vertex(i)]))
+}
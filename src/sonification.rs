@@ -0,0 +1,183 @@
+use std::error::Error;
+use std::f32::consts::TAU;
+use std::sync::{Arc, Mutex};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+
+use boids_core::metrics::Metrics;
+use boids_core::AGENT_SPEED;
+
+use crate::{SONIFICATION_GLIDE, SONIFICATION_PITCH, SONIFICATION_TREMOLO, SONIFICATION_VOLUME};
+
+const VOICES: usize = 4;
+// Frequencies of the voices over the lowest one. An ordered flock plays a major chord with the
+// octave, a disordered one a minor second, a tritone and a major seventh.
+const CONSONANT: [f32; VOICES] = [1.0, 5.0 / 4.0, 3.0 / 2.0, 2.0];
+const DISSONANT: [f32; VOICES] = [1.0, 16.0 / 15.0, 45.0 / 32.0, 15.0 / 8.0];
+
+#[derive(Clone, Copy, Default)]
+struct Sound {
+    // Hz and from 0 to 1 of every voice
+    frequencies: [f32; VOICES],
+    amplitudes: [f32; VOICES],
+    // Hz and from 0 to 1
+    tremolo_rate: f32,
+    tremolo_depth: f32,
+}
+
+// Sound played on the default output device, the audio thread reads it
+struct Output {
+    sound: Arc<Mutex<Sound>>,
+    // Playing stops when it is dropped
+    _stream: Stream,
+}
+
+// Plays the state of the flock as a chord on the default output device. The mean speed sets
+// the pitch, one octave either way of SONIFICATION_PITCH, and polarization turns a dissonant
+// chord consonant. Every cluster up to four adds a voice and a milling flock makes the chord
+// tremble. The audio thread glides to every new sound so changes don't click.
+pub struct Sonification {
+    pub enabled: bool,
+
+    // Opened the first time sonification is turned on
+    output: Option<Output>,
+}
+
+impl Default for Sonification {
+    fn default() -> Sonification {
+        Sonification::new()
+    }
+}
+
+impl Sonification {
+    pub fn new() -> Sonification {
+        Sonification {
+            enabled: false,
+            output: None,
+        }
+    }
+
+    // Stays off when there is nothing to play on
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+
+        if self.output.is_none() && self.enabled {
+            match Output::start() {
+                Ok(output) => self.output = Some(output),
+                Err(error) => {
+                    self.enabled = false;
+
+                    println!("Could not play audio: {}", error);
+                    return;
+                }
+            }
+        }
+
+        // Turned off it fades out instead of stopping the stream
+        if !self.enabled {
+            if let Some(output) = &self.output {
+                output.sound.lock().unwrap().amplitudes = [0.0; VOICES];
+            }
+        }
+    }
+
+    pub fn update(&mut self, metrics: &Metrics) {
+        let sound = match &self.output {
+            Some(output) if self.enabled => &output.sound,
+            _ => return,
+        };
+
+        let root = SONIFICATION_PITCH * (metrics.mean_speed / AGENT_SPEED).clamp(0.5, 2.0);
+        let voices = metrics.clusters.min(VOICES);
+
+        let mut next = Sound {
+            tremolo_rate: metrics.milling * SONIFICATION_TREMOLO,
+            tremolo_depth: metrics.milling,
+            ..Default::default()
+        };
+
+        for i in 0..VOICES {
+            let ratio = DISSONANT[i] + (CONSONANT[i] - DISSONANT[i]) * metrics.polarization;
+
+            next.frequencies[i] = root * ratio;
+            next.amplitudes[i] = if i < voices { 1.0 / VOICES as f32 } else { 0.0 };
+        }
+
+        *sound.lock().unwrap() = next;
+    }
+}
+
+impl Output {
+    fn start() -> Result<Output, Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("no audio output device")?;
+
+        let supported = device.default_output_config()?;
+        let config = supported.config();
+        let sound = Arc::new(Mutex::new(Sound::default()));
+
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, sound.clone())?,
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, sound.clone())?,
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, sound.clone())?,
+            format => return Err(format!("unsupported sample format {}", format).into()),
+        };
+
+        stream.play()?;
+
+        Ok(Output { sound, _stream: stream })
+    }
+}
+
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &Device,
+    config: &StreamConfig,
+    sound: Arc<Mutex<Sound>>
+) -> Result<Stream, Box<dyn Error>> {
+    let channels = config.channels as usize;
+    let sample_rate = config.sample_rate.0 as f32;
+    // Share of the way to the new sound covered every sample
+    let glide = (SONIFICATION_GLIDE / sample_rate).min(1.0);
+
+    let mut current = Sound::default();
+    let mut phases = [0.0f32; VOICES];
+    let mut tremolo_phase = 0.0f32;
+
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            // The main thread only holds the lock to write a new sound, when it does the old one plays on
+            let target = sound.try_lock().map_or(current, |sound| *sound);
+
+            for frame in data.chunks_mut(channels) {
+                let mut value = 0.0;
+
+                for (i, phase) in phases.iter_mut().enumerate() {
+                    current.frequencies[i] += (target.frequencies[i] - current.frequencies[i]) * glide;
+                    current.amplitudes[i] += (target.amplitudes[i] - current.amplitudes[i]) * glide;
+
+                    *phase = (*phase + current.frequencies[i] / sample_rate).fract();
+                    value += (*phase * TAU).sin() * current.amplitudes[i];
+                }
+
+                current.tremolo_rate += (target.tremolo_rate - current.tremolo_rate) * glide;
+                current.tremolo_depth += (target.tremolo_depth - current.tremolo_depth) * glide;
+
+                tremolo_phase = (tremolo_phase + current.tremolo_rate / sample_rate).fract();
+                let tremolo = 1.0 - current.tremolo_depth * 0.5 * (1.0 + (tremolo_phase * TAU).sin());
+
+                let sample = T::from_sample(value * tremolo * SONIFICATION_VOLUME);
+
+                for channel in frame.iter_mut() {
+                    *channel = sample;
+                }
+            }
+        },
+        |error| println!("Audio output error: {}", error),
+        None
+    )?;
+
+    Ok(stream)
+}
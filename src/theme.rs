@@ -0,0 +1,77 @@
+use boids_core::SPECIES_COUNT;
+
+// Colors of everything drawn in the world, cycled at runtime
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    pub name: &'static str,
+    pub background: [f32; 4],
+    // Plain color of every species
+    pub species: [[f32; 3]; SPECIES_COUNT],
+    pub dead: [f32; 3],
+    // Trails take the color of their boid's species without one
+    pub trail: Option<[f32; 3]>,
+    // Obstacles, fields and other world geometry
+    pub geometry: [f32; 3],
+}
+
+pub const THEMES: [Theme; 4] = [
+    Theme {
+        name: "Classic",
+        background: [0.1, 0.1, 0.1, 1.0],
+        species: [
+            [1.0, 1.0, 1.0],
+            [0.4, 0.8, 1.0],
+            [1.0, 0.4, 0.3],
+        ],
+        dead: [0.3, 0.3, 0.3],
+        trail: None,
+        geometry: [0.6, 0.6, 0.6],
+    },
+    Theme {
+        name: "Dusk",
+        background: [0.12, 0.08, 0.18, 1.0],
+        species: [
+            [1.0, 0.75, 0.45],
+            [0.95, 0.45, 0.6],
+            [0.55, 0.5, 1.0],
+        ],
+        dead: [0.3, 0.25, 0.35],
+        trail: Some([0.9, 0.6, 0.7]),
+        geometry: [0.7, 0.6, 0.9],
+    },
+    Theme {
+        name: "Ocean",
+        background: [0.02, 0.1, 0.16, 1.0],
+        species: [
+            [0.6, 0.95, 1.0],
+            [0.2, 0.7, 0.8],
+            [1.0, 0.85, 0.4],
+        ],
+        dead: [0.15, 0.3, 0.35],
+        trail: Some([0.3, 0.8, 0.9]),
+        geometry: [0.5, 0.8, 0.8],
+    },
+    // Dark boids on a light background
+    Theme {
+        name: "Paper",
+        background: [0.93, 0.91, 0.86, 1.0],
+        species: [
+            [0.1, 0.1, 0.12],
+            [0.15, 0.3, 0.6],
+            [0.7, 0.15, 0.1],
+        ],
+        dead: [0.65, 0.63, 0.6],
+        trail: None,
+        geometry: [0.4, 0.4, 0.45],
+    },
+];
+
+// Index of the theme with this name, the first theme if there is none
+pub fn find_theme(name: &str) -> usize {
+    THEMES.iter()
+        .position(|theme| theme.name.eq_ignore_ascii_case(name))
+        .unwrap_or_else(|| {
+            println!("Unknown theme {}, using {}", name, THEMES[0].name);
+            0
+        })
+}
@@ -0,0 +1,62 @@
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::error::Result;
+use crate::{PIN_RENDER_THREAD, WORKER_THREADS};
+
+// How the simulation is spread over the CPU.
+// On CPUs with big and little cores the default scheduling can move the render thread
+// around and hurt frame pacing, pinning it and leaving its core to it helps.
+#[derive(Clone, Copy, Debug)]
+pub struct ThreadSettings {
+    // Number of worker threads, 0 leaves it to rayon
+    pub worker_threads: usize,
+    // Keeps the render thread on the first core
+    pub pin_render_thread: bool,
+}
+
+impl Default for ThreadSettings {
+    fn default() -> ThreadSettings {
+        ThreadSettings {
+            worker_threads: WORKER_THREADS,
+            pin_render_thread: PIN_RENDER_THREAD,
+        }
+    }
+}
+
+impl ThreadSettings {
+    // Builds the pool all parallel systems run in.
+    // With a pinned render thread the workers default to the remaining cores.
+    pub fn build_pool(&self) -> Result<ThreadPool> {
+        let mut worker_threads = self.worker_threads;
+
+        if worker_threads == 0 && self.pin_render_thread {
+            worker_threads = num_cores().saturating_sub(1).max(1);
+        }
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(worker_threads)
+            .thread_name(|i| format!("simulation-{}", i))
+            .build()?;
+
+        Ok(pool)
+    }
+
+    // Pins the calling thread if enabled, meant to be called from the render thread
+    pub fn pin_current_thread(&self) {
+        if !self.pin_render_thread {
+            return;
+        }
+
+        let pinned = core_affinity::get_core_ids()
+            .and_then(|cores| cores.first().copied())
+            .is_some_and(core_affinity::set_for_current);
+
+        if !pinned {
+            println!("Could not pin the render thread");
+        }
+    }
+}
+
+fn num_cores() -> usize {
+    core_affinity::get_core_ids().map_or(1, |cores| cores.len())
+}
@@ -0,0 +1,261 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use parquet::data_type::{DoubleType, FloatType, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::schema::parser::parse_message_type;
+use serde::Deserialize;
+
+use boids_core::metrics::MetricsScratch;
+use boids_core::simulation::Simulation;
+
+use crate::RECORDING_DIR;
+
+// Columns of both formats, one row per boid and sample
+const CSV_HEADER: &str = "id,frame,time,x,y,dx,dy";
+// One row per sample in a CSV file next to the trajectory, see Metrics
+const METRICS_HEADER: &str = "frame,time,polarization,milling,nearest_neighbor,clusters,mean_speed";
+const PARQUET_SCHEMA: &str = "
+    message trajectory {
+        REQUIRED INT64 id (UINT_64);
+        REQUIRED INT64 frame;
+        REQUIRED DOUBLE time;
+        REQUIRED FLOAT x;
+        REQUIRED FLOAT y;
+        REQUIRED FLOAT dx;
+        REQUIRED FLOAT dy;
+    }
+";
+// Parquet rows are kept in memory until this many make up a row group
+const PARQUET_ROW_GROUP: usize = 1 << 20;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TrajectoryFormat {
+    Csv,
+    Parquet,
+}
+
+impl TrajectoryFormat {
+    // Parquet for a .parquet file, CSV for anything else
+    pub fn from_path(path: &Path) -> TrajectoryFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("parquet") => TrajectoryFormat::Parquet,
+            _ => TrajectoryFormat::Csv,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            TrajectoryFormat::Csv => "csv",
+            TrajectoryFormat::Parquet => "parquet",
+        }
+    }
+}
+
+// Writes the id, position and heading of every boid every `interval` frames, for analysis and
+// plots outside of the simulation. Ids come from the boid handles and stay with a boid for as
+// long as it lives. Frames and time count from the start of the recording, time in simulated
+// seconds. Dead boids are written as well until they are despawned.
+// The metrics of the flock at every sample go to a .metrics.csv file of the same name.
+pub struct TrajectoryRecorder {
+    writer: Writer,
+    path: PathBuf,
+    metrics: BufWriter<File>,
+    metrics_scratch: MetricsScratch,
+    interval: u64,
+    frame: u64,
+    time: f64,
+    pub rows: usize,
+}
+
+enum Writer {
+    Csv(BufWriter<File>),
+    Parquet(ParquetWriter),
+}
+
+struct ParquetWriter {
+    file: SerializedFileWriter<File>,
+    ids: Vec<i64>,
+    frames: Vec<i64>,
+    times: Vec<f64>,
+    xs: Vec<f32>,
+    ys: Vec<f32>,
+    dxs: Vec<f32>,
+    dys: Vec<f32>,
+}
+
+impl TrajectoryRecorder {
+    // The format is picked by the extension of the path, see TrajectoryFormat::from_path
+    pub fn start(path: &Path, interval: u64) -> Result<TrajectoryRecorder, Box<dyn Error>> {
+        if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = File::create(path)?;
+
+        let writer = match TrajectoryFormat::from_path(path) {
+            TrajectoryFormat::Csv => {
+                let mut file = BufWriter::new(file);
+                writeln!(file, "{}", CSV_HEADER)?;
+
+                Writer::Csv(file)
+            }
+            TrajectoryFormat::Parquet => Writer::Parquet(ParquetWriter::new(file)?),
+        };
+
+        let mut metrics = BufWriter::new(File::create(path.with_extension("metrics.csv"))?);
+        writeln!(metrics, "{}", METRICS_HEADER)?;
+
+        Ok(TrajectoryRecorder {
+            writer,
+            path: path.to_path_buf(),
+            metrics,
+            metrics_scratch: MetricsScratch::new(),
+            interval: interval.max(1),
+            frame: 0,
+            time: 0.0,
+            rows: 0,
+        })
+    }
+
+    // Starts a new file named after the current time in RECORDING_DIR
+    pub fn start_in_recordings(format: TrajectoryFormat, interval: u64) -> Result<TrajectoryRecorder, Box<dyn Error>> {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)?;
+        let name = format!("trajectory-{}.{}", time.as_secs(), format.extension());
+
+        TrajectoryRecorder::start(&PathBuf::from(RECORDING_DIR).join(name), interval)
+    }
+
+    // Called once per frame, after the simulation stepped `dt` seconds
+    pub fn record(&mut self, simulation: &Simulation, dt: f32) -> Result<(), Box<dyn Error>> {
+        if self.frame.is_multiple_of(self.interval) {
+            let components = &simulation.components;
+            let boids = components.handles.iter()
+                .zip(&components.positions)
+                .zip(&components.directions);
+
+            match &mut self.writer {
+                Writer::Csv(file) => {
+                    for ((handle, position), forward) in boids {
+                        let [x, y] = position.value;
+                        let [dx, dy] = forward.direction;
+
+                        writeln!(file, "{},{},{},{},{},{},{}", handle.id(), self.frame, self.time, x, y, dx, dy)?;
+                    }
+                }
+                Writer::Parquet(parquet) => {
+                    for ((handle, position), forward) in boids {
+                        parquet.ids.push(handle.id() as i64);
+                        parquet.frames.push(self.frame as i64);
+                        parquet.times.push(self.time);
+                        parquet.xs.push(position.value[0]);
+                        parquet.ys.push(position.value[1]);
+                        parquet.dxs.push(forward.direction[0]);
+                        parquet.dys.push(forward.direction[1]);
+                    }
+
+                    if parquet.ids.len() >= PARQUET_ROW_GROUP {
+                        parquet.write_row_group()?;
+                    }
+                }
+            }
+
+            self.rows += components.len();
+
+            let metrics = self.metrics_scratch.measure(simulation);
+
+            writeln!(
+                self.metrics,
+                "{},{},{},{},{},{},{}",
+                self.frame,
+                self.time,
+                metrics.polarization,
+                metrics.milling,
+                metrics.nearest_neighbor,
+                metrics.clusters,
+                metrics.mean_speed
+            )?;
+        }
+
+        self.frame += 1;
+        self.time += dt as f64;
+
+        Ok(())
+    }
+
+    // Until then the file may be incomplete, a Parquet file can't be read at all
+    pub fn finish(mut self) -> Result<PathBuf, Box<dyn Error>> {
+        self.metrics.flush()?;
+
+        match self.writer {
+            Writer::Csv(mut file) => file.flush()?,
+            Writer::Parquet(mut parquet) => {
+                parquet.write_row_group()?;
+                parquet.file.close()?;
+            }
+        }
+
+        Ok(self.path)
+    }
+}
+
+impl ParquetWriter {
+    fn new(file: File) -> Result<ParquetWriter, Box<dyn Error>> {
+        let schema = Arc::new(parse_message_type(PARQUET_SCHEMA)?);
+        let properties = Arc::new(WriterProperties::builder().build());
+
+        Ok(ParquetWriter {
+            file: SerializedFileWriter::new(file, schema, properties)?,
+            ids: Vec::new(),
+            frames: Vec::new(),
+            times: Vec::new(),
+            xs: Vec::new(),
+            ys: Vec::new(),
+            dxs: Vec::new(),
+            dys: Vec::new(),
+        })
+    }
+
+    // Writes the buffered rows as one row group, in the column order of PARQUET_SCHEMA
+    fn write_row_group(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut row_group = self.file.next_row_group()?;
+        let mut index = 0;
+
+        while let Some(mut column) = row_group.next_column()? {
+            match index {
+                0 => column.typed::<Int64Type>().write_batch(&self.ids, None, None)?,
+                1 => column.typed::<Int64Type>().write_batch(&self.frames, None, None)?,
+                2 => column.typed::<DoubleType>().write_batch(&self.times, None, None)?,
+                3 => column.typed::<FloatType>().write_batch(&self.xs, None, None)?,
+                4 => column.typed::<FloatType>().write_batch(&self.ys, None, None)?,
+                5 => column.typed::<FloatType>().write_batch(&self.dxs, None, None)?,
+                _ => column.typed::<FloatType>().write_batch(&self.dys, None, None)?,
+            };
+
+            column.close()?;
+            index += 1;
+        }
+
+        row_group.close()?;
+
+        self.ids.clear();
+        self.frames.clear();
+        self.times.clear();
+        self.xs.clear();
+        self.ys.clear();
+        self.dxs.clear();
+        self.dys.clear();
+
+        Ok(())
+    }
+}
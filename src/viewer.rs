@@ -0,0 +1,256 @@
+use std::path::Path;
+use std::process;
+use std::time::Instant;
+
+use boids_core::profiler::Profiler;
+use boids_core::save::load_simulation;
+use boids_core::simulation::Simulation;
+use tracing::{info_span, warn};
+use winit::dpi::LogicalSize;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
+
+use crate::cli::Cli;
+use crate::coloring::ColorMode;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::graphics::perspective;
+use crate::graphics::renderer::{DrawTo, Renderer};
+use crate::graphics::wgpu_flock::{Gpu, WgpuFlockRenderer, WgpuFrame};
+use crate::logging::TraceGuards;
+use crate::pacing::{FramePacer, SimulationClock};
+use crate::snapshot::RenderSnapshot;
+use crate::theme::{find_theme, THEMES};
+use crate::threads::ThreadSettings;
+use crate::FIXED_TIMESTEP;
+
+// Window drawn with wgpu instead of glium, with --wgpu or in builds without the glium feature.
+// It only steps the simulation and draws the flock, none of the overlays and controls of App exist here.
+struct Viewer {
+    window: Window,
+    surface: wgpu::Surface,
+    surface_config: wgpu::SurfaceConfiguration,
+    gpu: Gpu,
+    flock_renderer: WgpuFlockRenderer,
+    simulation: Simulation,
+    snapshot: RenderSnapshot,
+    // Index into THEMES
+    theme: usize,
+}
+
+impl Viewer {
+    fn new(event_loop: &EventLoop<()>, config: &Config, simulation: Simulation) -> Result<Viewer> {
+        let window = WindowBuilder::new()
+            .with_inner_size(LogicalSize {
+                width: config.window.size[0],
+                height: config.window.size[1]
+            })
+            .with_title("Boids")
+            .build(event_loop)?;
+
+        let instance = wgpu::Instance::default();
+        // The window lives in the viewer as long as the surface does
+        let surface = unsafe { instance.create_surface(&window) }?;
+
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: Some(&surface),
+            force_fallback_adapter: false,
+        })).ok_or(Error::Adapter)?;
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: None,
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            },
+            None
+        ))?;
+
+        let capabilities = surface.get_capabilities(&adapter);
+        let size = window.inner_size();
+
+        let surface_config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: capabilities.formats[0],
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: if config.window.vsync { wgpu::PresentMode::AutoVsync } else { wgpu::PresentMode::AutoNoVsync },
+            alpha_mode: capabilities.alpha_modes[0],
+            view_formats: Vec::new(),
+        };
+        surface.configure(&device, &surface_config);
+
+        let gpu = Gpu { device, queue };
+        let flock_renderer = WgpuFlockRenderer::new(&gpu, surface_config.format, config.flock.agent_size)?;
+
+        Ok(Viewer {
+            window,
+            surface,
+            surface_config,
+            gpu,
+            flock_renderer,
+            simulation,
+            snapshot: RenderSnapshot::default(),
+            theme: find_theme(&config.colors.theme),
+        })
+    }
+
+    fn resize(&mut self) {
+        let size = self.window.inner_size();
+
+        self.surface_config.width = size.width.max(1);
+        self.surface_config.height = size.height.max(1);
+        self.surface.configure(&self.gpu.device, &self.surface_config);
+    }
+
+    // Part of the window the world is fit into, the rest only shows the background
+    fn viewport(&self) -> [f32; 4] {
+        let [world_w, world_h] = self.simulation.size();
+        let [w, h] = [self.surface_config.width as f32, self.surface_config.height as f32];
+        let scale = (w / world_w).min(h / world_h);
+
+        let [viewport_w, viewport_h] = [world_w * scale, world_h * scale];
+
+        [(w - viewport_w) / 2.0, (h - viewport_h) / 2.0, viewport_w, viewport_h]
+    }
+
+    fn draw(&mut self) {
+        let theme = &THEMES[self.theme];
+        let species_shapes = self.flock_renderer.species_shapes;
+
+        self.snapshot.copy_from(&self.simulation, ColorMode::Plain, theme, &species_shapes);
+        if let Err(error) = self.flock_renderer.upload(&self.gpu, &self.snapshot) {
+            warn!("Skipped the upload: {}", error);
+        }
+
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            // The window changed under the surface, the next frame draws again
+            Err(wgpu::SurfaceError::Lost) | Err(wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(&self.gpu.device, &self.surface_config);
+                return;
+            }
+            Err(error) => {
+                println!("Could not draw a frame: {}", error);
+                return;
+            }
+        };
+
+        let view = output.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self.gpu.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        let [r, g, b, a] = self.simulation.day_cycle.shade_rgba(theme.background);
+
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Background"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r: r as f64, g: g as f64, b: b as f64, a: a as f64 }),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        let [world_w, world_h] = self.simulation.size();
+        let viewport = self.viewport();
+
+        let drawn = self.flock_renderer.draw(
+            &mut WgpuFrame {
+                queue: &self.gpu.queue,
+                encoder: &mut encoder,
+                view: &view,
+                viewport,
+            },
+            perspective(world_w as u32, world_h as u32)
+        );
+
+        if let Err(error) = drawn {
+            warn!("Skipped the flock pass: {}", error);
+        }
+
+        self.gpu.queue.submit(Some(encoder.finish()));
+        output.present();
+    }
+}
+
+// Opens the wgpu window and runs its event loop, which never returns
+pub fn run(config: &Config, cli: &Cli, trace_guards: TraceGuards) -> ! {
+    let simulation = match cli.start_from() {
+        Some(path) => match load_simulation(Path::new(&path)) {
+            Ok(mut simulation) => {
+                config.load_script(&mut simulation);
+                config.load_plugins(&mut simulation);
+                simulation
+            }
+            Err(error) => {
+                println!("Could not load simulation from {}: {}", path, error);
+                process::exit(1);
+            }
+        },
+        None => config.create_simulation(),
+    };
+
+    let event_loop = EventLoop::new();
+    let viewer = ThreadSettings::default().build_pool()
+        .and_then(|thread_pool| Ok((thread_pool, Viewer::new(&event_loop, config, simulation)?)));
+
+    let (thread_pool, mut viewer) = match viewer {
+        Ok(viewer) => viewer,
+        Err(error) => {
+            println!("Could not start: {}", error);
+            process::exit(1);
+        }
+    };
+
+    let mut pacer = FramePacer::new(config.window.frame_rate_cap());
+    let mut clock = SimulationClock::default();
+    let mut profiler = Profiler::new();
+    let mut time = Instant::now();
+    // The event loop never returns, the trace files are finished when it is destroyed
+    let mut trace_guards = Some(trace_guards);
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+
+        match event {
+            Event::WindowEvent { event, .. } => match event {
+                WindowEvent::CloseRequested => {
+                    *control_flow = ControlFlow::Exit;
+                }
+                WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                    viewer.resize();
+                }
+                _ => {}
+            }
+            Event::MainEventsCleared => {
+                pacer.wait();
+
+                let new_time = Instant::now();
+                let delta = new_time.duration_since(time).as_secs_f32();
+
+                time = new_time;
+
+                let _span = info_span!("frame").entered();
+
+                let simulation = &mut viewer.simulation;
+
+                for _ in 0..clock.advance(delta) {
+                    // Nothing reads the events, every step is a frame of its own
+                    simulation.events.next_frame();
+                    thread_pool.install(|| simulation.update(FIXED_TIMESTEP, &mut profiler));
+                }
+
+                viewer.draw();
+            }
+            Event::LoopDestroyed => {
+                trace_guards.take();
+            }
+            _ => {}
+        }
+    })
+}
@@ -0,0 +1,144 @@
+use std::io::{self, ErrorKind};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::thread;
+
+use tungstenite::{Message, WebSocket};
+
+use boids_core::data::BehaviorState;
+use boids_core::simulation::Simulation;
+
+use crate::{NETWORK_TIMEOUT, WEBSOCKET_RATE};
+
+// Streams the living boids to every WebSocket client WEBSOCKET_RATE times per second of simulated
+// time, for viewers and tools outside of the app, examples/websocket.html is one. Every frame is a
+// binary message, all numbers little endian:
+//
+//     u32 boid count, f32 world width, f32 world height
+//     f32 x, y, dx, dy of every boid
+//     u8 species of every boid
+//
+// so a browser reads it with a Float32Array and a Uint8Array without copying. Clients only
+// receive, when one falls behind by more than NETWORK_TIMEOUT it is dropped and the others go on.
+pub struct WebSocketStream {
+    frames: SyncSender<Arc<Vec<u8>>>,
+    // Simulated seconds since the last frame
+    since: f32,
+}
+
+impl WebSocketStream {
+    // `address` is where clients connect, like 0.0.0.0:9001
+    pub fn start(address: &str) -> io::Result<WebSocketStream> {
+        let listener = TcpListener::bind(address)?;
+        let (sender, receiver) = mpsc::sync_channel(1);
+
+        thread::Builder::new()
+            .name("websocket".to_string())
+            .spawn(move || broadcast(listener, receiver))?;
+
+        Ok(WebSocketStream {
+            frames: sender,
+            since: 0.0,
+        })
+    }
+
+    // `dt` is the simulated time since the last call
+    pub fn advance(&mut self, simulation: &Simulation, dt: f32) {
+        self.since += dt;
+
+        if self.since < 1.0 / WEBSOCKET_RATE {
+            return;
+        }
+
+        self.since = 0.0;
+
+        // Frames the thread hasn't sent yet are skipped
+        match self.frames.try_send(Arc::new(pack(simulation))) {
+            Ok(()) | Err(TrySendError::Full(_)) => {}
+            Err(TrySendError::Disconnected(_)) => println!("WebSocket stream stopped"),
+        }
+    }
+}
+
+fn pack(simulation: &Simulation) -> Vec<u8> {
+    let components = &simulation.components;
+    let living: Vec<usize> = (0..components.len())
+        .filter(|i| components.behaviors[*i].state != BehaviorState::Dead)
+        .collect();
+
+    let [width, height] = simulation.size();
+    let mut frame = Vec::with_capacity(12 + living.len() * 17);
+
+    frame.extend_from_slice(&(living.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&width.to_le_bytes());
+    frame.extend_from_slice(&height.to_le_bytes());
+
+    for i in &living {
+        let [x, y] = components.positions[*i].value;
+        let [dx, dy] = components.directions[*i].direction;
+
+        for value in [x, y, dx, dy] {
+            frame.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    frame.extend(living.iter().map(|i| components.species[*i].id as u8));
+
+    frame
+}
+
+fn broadcast(listener: TcpListener, frames: Receiver<Arc<Vec<u8>>>) {
+    let mut clients: Vec<(String, WebSocket<TcpStream>)> = Vec::new();
+
+    if let Err(error) = listener.set_nonblocking(true) {
+        println!("WebSocket stream stopped: {}", error);
+        return;
+    }
+
+    loop {
+        // Checks for new clients at least this often while no frame comes
+        let frame = match frames.recv_timeout(NETWORK_TIMEOUT) {
+            Ok(frame) => Some(frame),
+            Err(mpsc::RecvTimeoutError::Timeout) => None,
+            Err(mpsc::RecvTimeoutError::Disconnected) => return,
+        };
+
+        loop {
+            match listener.accept() {
+                Ok((stream, address)) => match handshake(stream) {
+                    Ok(client) => {
+                        println!("WebSocket client {} connected", address);
+                        clients.push((address.to_string(), client));
+                    }
+                    Err(error) => println!("Could not accept WebSocket client {}: {}", address, error),
+                },
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    println!("Could not accept WebSocket client: {}", error);
+                    break;
+                }
+            }
+        }
+
+        if let Some(frame) = frame {
+            clients.retain_mut(|(address, client)| match client.send(Message::Binary(frame.to_vec())) {
+                Ok(()) => true,
+                Err(error) => {
+                    println!("WebSocket client {} disconnected: {}", address, error);
+                    false
+                }
+            });
+        }
+    }
+}
+
+// A client that doesn't finish the handshake within NETWORK_TIMEOUT is turned away
+fn handshake(stream: TcpStream) -> Result<WebSocket<TcpStream>, String> {
+    stream.set_nonblocking(false).map_err(|error| error.to_string())?;
+    stream.set_nodelay(true).map_err(|error| error.to_string())?;
+    stream.set_read_timeout(Some(NETWORK_TIMEOUT)).map_err(|error| error.to_string())?;
+    stream.set_write_timeout(Some(NETWORK_TIMEOUT)).map_err(|error| error.to_string())?;
+
+    tungstenite::accept(stream).map_err(|error| error.to_string())
+}
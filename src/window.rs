@@ -0,0 +1,166 @@
+use std::process;
+use std::time::Instant;
+
+use glium::{Surface, SwapBuffersError};
+use glium::glutin::event::{Event, WindowEvent};
+use glium::glutin::event_loop::{ControlFlow, EventLoop};
+use glium::glutin::platform::run_return::EventLoopExtRunReturn;
+use tracing::info_span;
+
+use crate::app::App;
+use crate::cli::Cli;
+use crate::config::Config;
+use crate::graphics::{create_display, rebuild_display};
+use crate::logging::TraceGuards;
+use crate::pacing::FramePacer;
+use crate::threads::ThreadSettings;
+use crate::SAVE_PATH;
+
+// Opens the glium window with the whole app and runs its event loop until the window is closed.
+// Vsync is fixed when a context is created, so to switch it the loop is left, the display is
+// created again and the loop goes on.
+pub fn run(config: &Config, cli: &Cli, trace_guards: TraceGuards) -> ! {
+    let mut event_loop = EventLoop::new();
+    let display = create_display(
+        &event_loop,
+        config.window.size[0],
+        config.window.size[1],
+        config.window.msaa_samples,
+        config.window.vsync
+    );
+
+    let pacer = FramePacer::new(config.window.frame_rate_cap());
+
+    let app = display.and_then(|display| App::new(display, ThreadSettings::default(), pacer, config));
+
+    let mut app = match app {
+        Ok(app) => app,
+        Err(error) => {
+            println!("Could not start: {}", error);
+            process::exit(1);
+        }
+    };
+
+    if let Some(path) = cli.start_from() {
+        println!("{}", app.load(&path));
+    }
+
+    app.start_connections(cli, config);
+
+    let mut time = Instant::now();
+    let mut vsync = config.window.vsync;
+    let mut closed = false;
+
+    loop {
+        event_loop.run_return(|event, _, control_flow| {
+            handle_event(&mut app, event, control_flow, &mut time, &mut closed);
+
+            if app.vsync != vsync {
+                *control_flow = ControlFlow::Exit;
+            }
+        });
+
+        if closed || app.vsync == vsync {
+            break;
+        }
+
+        match rebuild_display(&app.display, &event_loop, config.window.msaa_samples, app.vsync) {
+            Ok(()) => {
+                vsync = app.vsync;
+                app.on_display_rebuilt();
+            }
+            Err(error) => {
+                println!("Could not switch vsync: {}", error);
+                app.vsync = vsync;
+            }
+        }
+
+        // Creating the window took long enough to throw off the simulation clock
+        time = Instant::now();
+    }
+
+    app.stop_trajectory();
+    // Finishes the trace files
+    drop(trace_guards);
+
+    process::exit(0);
+}
+
+// Handles one event of the loop, `closed` is set once the app should quit
+fn handle_event(app: &mut App, event: Event<()>, control_flow: &mut ControlFlow, time: &mut Instant, closed: &mut bool) {
+    // Frames are paced by the app, vsync or both
+    *control_flow = ControlFlow::Poll;
+
+    match event {
+        Event::WindowEvent { event, .. } => match event {
+            WindowEvent::CloseRequested => {
+                *control_flow = ControlFlow::Exit;
+                *closed = true;
+            }
+            WindowEvent::Resized(size) => {
+                app.on_window_resize(&size);
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, new_inner_size } => {
+                app.on_scale_factor_changed(scale_factor, new_inner_size);
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                app.modifiers = modifiers;
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                app.on_keyboard(&input);
+            }
+            WindowEvent::ReceivedCharacter(character) => {
+                app.on_character(character);
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                app.on_cursor_moved([position.x as f32, position.y as f32]);
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                app.on_mouse_input(button, state);
+            }
+            WindowEvent::CursorLeft { .. } => {
+                app.cursor = None;
+            }
+            _ => {},
+        }
+        Event::MainEventsCleared => {
+            app.pacer.wait();
+
+            let new_time = Instant::now();
+            let delta = new_time.duration_since(*time).as_secs_f32();
+
+            *time = new_time;
+
+            let t = Instant::now();
+            let _span = info_span!("frame").entered();
+
+            let mut target = app.display.draw();
+            let [r, g, b, a] = app.background_color();
+            target.clear_color(r, g, b, a);
+            app.frame(delta, &mut target);
+
+            let present = info_span!("present").entered();
+            let finished = target.finish();
+            present.exit();
+
+            match finished {
+                Ok(()) => {}
+                // Everything on the GPU is gone, the simulation is saved so a restart can pick it up
+                Err(SwapBuffersError::ContextLost) => {
+                    println!("Lost the graphics context");
+                    println!("{}", app.save(SAVE_PATH));
+                    println!("Start again with --load {} to continue", SAVE_PATH);
+
+                    *control_flow = ControlFlow::Exit;
+                    *closed = true;
+                    return;
+                }
+                // The frame was already shown, nothing is lost
+                Err(SwapBuffersError::AlreadySwapped) => {}
+            }
+
+            app.on_frame_finished(delta, t.elapsed().as_secs_f32());
+        },
+        _ => (),
+    }
+}